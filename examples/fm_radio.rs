@@ -151,7 +151,9 @@ fn main() {
     let mut convert3 = Convert3Node::new();
     let mut dec2: DecimateNode<f32> = DecimateNode::new(5);
     let mut dec3: DecimateNode<f32> = DecimateNode::new(4);
-    let mut audio: audio::AudioNode<f32> = audio::AudioNode::new(1, 44100, 0.1);
+    // The audio comes out at 1.14 MHz / 25, and the audio node resamples it
+    // to whatever rate the sound card runs at.
+    let mut audio: audio::AudioNode<f32> = audio::AudioNode::new(1, 45600, 0.1);
 
     connect_nodes!(sdr, output, convert, input);
     connect_nodes!(convert, output, filt1, input);
@@ -15,7 +15,7 @@ struct ParsedFields<'a> {
     send_fields: Vec<&'a syn::Field>,
 }
 
-#[proc_macro_derive(Node, attributes(aggregate, pass_by_ref))]
+#[proc_macro_derive(Node, attributes(aggregate, non_blocking, pass_by_ref))]
 /// Creates a node derived from an input structure with a constructor and
 /// implements the Node trait.
 ///
@@ -37,6 +37,22 @@ struct ParsedFields<'a> {
 /// present on the structure, the return type must be an Option. Otherwise, it
 /// can be anything.
 ///
+/// If #[non_blocking] is specified on the structure, the node waits until
+/// any one of its connected receivers has data instead of blocking on each
+/// receiver in turn. The run() function then takes an Option for each
+/// receiver, which is None if that receiver had no data ready, isn't
+/// connected, or has been disconnected. The node keeps running as long as any
+/// of its connected receivers can still deliver data, and once all of them
+/// are disconnected and drained, call() returns NodeError::DataEnd. A node
+/// with no connected receivers yields to other threads between runs rather
+/// than waiting on an input. This is useful for nodes fed by
+/// inputs running at different rates, such as an occasional control input
+/// alongside a stream of samples, and is usually combined with #[aggregate].
+///
+/// Otherwise, if the sender feeding any receiver is dropped, such as when a
+/// finite source finishes, call() returns NodeError::DataEnd so that start()
+/// exits its loop instead of panicking.
+///
 /// Fields that are receivers must be of type NodeReceiver<T>. Fields that are
 /// senders must be of type NodeSender<T>.
 ///
@@ -58,6 +74,7 @@ pub fn node_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let attributes = &input.attrs;
     let mut aggregate = false;
     let mut pass_by_ref = false;
+    let mut non_blocking = false;
     for attr in attributes {
         match attr.parse_meta() {
             Ok(syn::Meta::Word(ref id)) if *id == "aggregate" => {
@@ -66,6 +83,9 @@ pub fn node_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             Ok(syn::Meta::Word(ref id)) if *id == "pass_by_ref" => {
                 pass_by_ref = true
             }
+            Ok(syn::Meta::Word(ref id)) if *id == "non_blocking" => {
+                non_blocking = true
+            }
             Ok(_) => continue,
             Err(_) => {
                 let err = quote! {
@@ -126,7 +146,11 @@ pub fn node_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let recv_block_idents = &recv_idents;
     let recv_block_fields = &recv_idents;
 
-    let run_func = if pass_by_ref {
+    let run_func = if pass_by_ref && non_blocking {
+        quote! {
+            let res = self.run(#(#recv_block_idents.as_ref()),*)?;
+        }
+    } else if pass_by_ref {
         quote! {
             let res = self.run(#(&#recv_block_idents),*)?;
         }
@@ -162,13 +186,23 @@ pub fn node_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
-    let is_connected = quote! {
-        fn is_connected(&self) -> bool {
+    // Receivers are optional for non-blocking nodes, so only the senders
+    // need to be hooked up for them to be considered connected.
+    let recv_connected = if non_blocking {
+        quote! {}
+    } else {
+        quote! {
             #(
                 if self.#recv_block_fields.is_none() {
                     return false;
                 }
             )*
+        }
+    };
+
+    let is_connected = quote! {
+        fn is_connected(&self) -> bool {
+            #recv_connected
             #(
                 if self.#send_idents1.is_empty() {
                     return false;
@@ -196,14 +230,86 @@ pub fn node_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
-    let call = quote! {
-        fn call(&mut self) -> Result<(), NodeError> {
+    let recv_func = if non_blocking {
+        // Each receiver is polled in turn.  A disconnected receiver is
+        // treated like one with no data, and the node only waits on the
+        // receivers that can still deliver something, so one input finishing
+        // doesn't stop the others.
+        let live_idents: Vec<syn::Ident> = recv_idents
+            .iter()
+            .map(|x| {
+                syn::Ident::new(
+                    &format!("__live_{}", x),
+                    proc_macro2::Span::call_site(),
+                )
+            })
+            .collect();
+        let live_idents1 = &live_idents;
+        let live_idents2 = &live_idents;
+        let live_idents3 = &live_idents;
+        quote! {
+            let (#(#recv_block_idents,)*) = loop {
+                let mut __any = false;
+                let mut __n_connected = 0;
+                let mut __n_live = 0;
+                #(
+                    let mut #live_idents1 = false;
+                    let #recv_block_idents = match self.#recv_block_fields {
+                        Some(ref r) => {
+                            __n_connected += 1;
+                            match r.try_recv() {
+                                Ok(val) => {
+                                    __any = true;
+                                    Some(val)
+                                }
+                                Err(channel::TryRecvError::Empty) => {
+                                    #live_idents3 = true;
+                                    __n_live += 1;
+                                    None
+                                }
+                                Err(channel::TryRecvError::Disconnected) => {
+                                    None
+                                }
+                            }
+                        }
+                        None => None,
+                    };
+                )*
+                if __any {
+                    break (#(#recv_block_idents,)*);
+                }
+                if __n_connected == 0 {
+                    thread::yield_now();
+                    break (#(#recv_block_idents,)*);
+                }
+                if __n_live == 0 {
+                    return Err(NodeError::DataEnd);
+                }
+                let mut __select = channel::Select::new();
+                #(
+                    if #live_idents2 {
+                        if let Some(ref r) = self.#recv_block_fields {
+                            __select.recv(r);
+                        }
+                    }
+                )*
+                __select.ready();
+            };
+        }
+    } else {
+        quote! {
             #(
                 let #recv_block_idents = match self.#recv_block_fields {
                     Some(ref r) => r.recv().or(Err(NodeError::DataEnd))?,
                     None => return Err(NodeError::PermanentError),
                 };
             )*
+        }
+    };
+
+    let call = quote! {
+        fn call(&mut self) -> Result<(), NodeError> {
+            #recv_func
             #run_func
             #send_func
             Ok(())
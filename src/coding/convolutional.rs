@@ -0,0 +1,300 @@
+//! Convolutional coding with Viterbi decoding.
+//!
+//! The codes here are rate 1/n feedforward convolutional codes, described by
+//! their constraint length `K` and a generator polynomial for each of their
+//! `n` outputs, given in the usual octal form.  The most significant bit of
+//! each polynomial taps the newest input bit.  The popular K = 7 code with
+//! polynomials 133 and 171 used by 802.11, DVB and CCSDS is
+//! `ConvolutionalCode::new(7, vec![0o133, 0o171])`.
+//!
+//! Each block of bits is encoded separately and terminated with `K - 1` zero
+//! tail bits, which returns the encoder to the all zero state so that the
+//! decoder knows where the trellis ends.  Bits are carried one per byte, as
+//! 0 or 1.  The decoder makes hard decisions, and also accepts `ERASURE` in
+//! place of any coded bit, such as those deleted by puncturing, which counts
+//! as a match for either bit.
+use crate::prelude::*;
+
+/// Marks a coded bit whose value is unknown.
+pub const ERASURE: u8 = 0xFF;
+
+/// Implementation of a convolutional encoder and Viterbi decoder.
+///
+/// This can be used on its own to encode and decode single blocks, and backs
+/// the convolutional coding nodes.
+pub struct ConvolutionalCode {
+    constraint_len: usize,
+    polys: Vec<u32>,
+    outputs: Vec<Vec<u8>>,
+}
+
+impl ConvolutionalCode {
+    /// Creates a new convolutional code.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint_len` - Constraint length `K` of the code, from 2 to 16.
+    /// * `polys` - Generator polynomial of each output, with at most `K`
+    ///   bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::convolutional::ConvolutionalCode;
+    ///
+    /// // The rate 1/2, K = 7 industry standard code.
+    /// let code = ConvolutionalCode::new(7, vec![0o133, 0o171]);
+    /// ```
+    pub fn new(constraint_len: usize, polys: Vec<u32>) -> ConvolutionalCode {
+        assert!(
+            (2..=16).contains(&constraint_len),
+            "constraint length must be on the interval [2, 16]"
+        );
+        assert!(!polys.is_empty(), "there must be at least one polynomial");
+        assert!(
+            polys.iter().all(|&p| p > 0 && p < 1 << constraint_len),
+            "polynomials must be nonzero with at most K bits"
+        );
+        // The output bits for every value of the shift register, newest bit
+        // in the most significant position.
+        let outputs = (0..1u32 << constraint_len)
+            .map(|reg| {
+                polys
+                    .iter()
+                    .map(|p| ((reg & p).count_ones() & 1) as u8)
+                    .collect()
+            })
+            .collect();
+        ConvolutionalCode {
+            constraint_len,
+            polys,
+            outputs,
+        }
+    }
+
+    /// Returns the number of coded bits produced for each input bit.
+    pub fn n(&self) -> usize {
+        self.polys.len()
+    }
+
+    /// Returns the constraint length of the code.
+    pub fn constraint_len(&self) -> usize {
+        self.constraint_len
+    }
+
+    /// Encodes a block of bits, followed by the tail bits that terminate it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - Bits to encode, each 0 or 1.
+    pub fn encode(&self, bits: &[u8]) -> Vec<u8> {
+        let k = self.constraint_len;
+        let tail = vec![0; k - 1];
+        let mut state = 0u32;
+        let mut coded = Vec::with_capacity((bits.len() + k - 1) * self.n());
+        for &bit in bits.iter().chain(&tail) {
+            let reg = (u32::from(bit & 1) << (k - 1)) | state;
+            coded.extend_from_slice(&self.outputs[reg as usize]);
+            state = reg >> 1;
+        }
+        coded
+    }
+
+    /// Decodes a terminated block of coded bits with the Viterbi algorithm.
+    /// Returns `None` if the block isn't a whole number of steps or is too
+    /// short to hold the tail.
+    ///
+    /// # Arguments
+    ///
+    /// * `coded` - Coded bits, each 0, 1 or `ERASURE`.
+    pub fn decode(&self, coded: &[u8]) -> Option<Vec<u8>> {
+        let k = self.constraint_len;
+        let n = self.n();
+        if !coded.chunks_exact(n).remainder().is_empty()
+            || coded.len() / n < k - 1
+        {
+            return None;
+        }
+        let n_states = 1 << (k - 1);
+        let mut metrics = vec![u32::MAX; n_states];
+        metrics[0] = 0;
+        let mut history: Vec<Vec<u16>> = Vec::with_capacity(coded.len() / n);
+
+        // Add, compare and select over every value of the shift register,
+        // which is the old state along with the new input bit.
+        for symbols in coded.chunks(n) {
+            let mut next = vec![u32::MAX; n_states];
+            let mut prev = vec![0u16; n_states];
+            for (reg, expected) in self.outputs.iter().enumerate() {
+                let state = reg & (n_states - 1);
+                if metrics[state] == u32::MAX {
+                    continue;
+                }
+                let cost = symbols
+                    .iter()
+                    .zip(expected)
+                    .filter(|&(&s, &e)| s != ERASURE && s != e)
+                    .count() as u32;
+                let metric = metrics[state] + cost;
+                if metric < next[reg >> 1] {
+                    next[reg >> 1] = metric;
+                    prev[reg >> 1] = state as u16;
+                }
+            }
+            metrics = next;
+            history.push(prev);
+        }
+
+        // Trace back from the all zero state the tail leaves behind.
+        let mut state = 0;
+        let mut bits: Vec<u8> = history
+            .iter()
+            .rev()
+            .map(|prev| {
+                let bit = (state >> (k - 2)) as u8 & 1;
+                state = prev[state] as usize;
+                bit
+            })
+            .collect();
+        bits.reverse();
+        bits.truncate(bits.len() - (k - 1));
+        Some(bits)
+    }
+}
+
+/// A node that convolutionally encodes blocks of bits.
+///
+/// Each input is encoded as a separate block and terminated with `K - 1`
+/// zero tail bits, so each output holds `n * (len + K - 1)` coded bits.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::convolutional::ConvolutionalEncodeNode;
+///
+/// let node = ConvolutionalEncodeNode::new(7, vec![0o133, 0o171]);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ConvolutionalEncodeNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    code: ConvolutionalCode,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl ConvolutionalEncodeNode {
+    /// Constructs a new `ConvolutionalEncodeNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint_len` - Constraint length `K` of the code, from 2 to 16.
+    /// * `polys` - Generator polynomial of each output, with at most `K`
+    ///   bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::convolutional::ConvolutionalEncodeNode;
+    ///
+    /// // A rate 1/3, K = 3 code.
+    /// let node = ConvolutionalEncodeNode::new(3, vec![0o7, 0o7, 0o5]);
+    /// ```
+    pub fn new(
+        constraint_len: usize,
+        polys: Vec<u32>,
+    ) -> ConvolutionalEncodeNode {
+        ConvolutionalEncodeNode {
+            code: ConvolutionalCode::new(constraint_len, polys),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ConvolutionalEncodeNode`.  Produces the coded block.
+    pub fn run(&mut self, bits: &[u8]) -> Result<Vec<u8>, NodeError> {
+        Ok(self.code.encode(bits))
+    }
+}
+
+/// A node that decodes convolutionally coded blocks with the Viterbi
+/// algorithm.
+///
+/// Each input must be a whole terminated block as produced by
+/// `ConvolutionalEncodeNode`, possibly with erasures in place of some of the
+/// coded bits, and produces the decoded bits without the tail.  Any other
+/// input length is a `NodeError::DataError`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::convolutional::ViterbiDecodeNode;
+///
+/// let node = ViterbiDecodeNode::new(7, vec![0o133, 0o171]);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ViterbiDecodeNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    code: ConvolutionalCode,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl ViterbiDecodeNode {
+    /// Constructs a new `ViterbiDecodeNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint_len` - Constraint length `K` of the code, from 2 to 16.
+    /// * `polys` - Generator polynomial of each output, with at most `K`
+    ///   bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::convolutional::ViterbiDecodeNode;
+    ///
+    /// let node = ViterbiDecodeNode::new(3, vec![0o7, 0o5]);
+    /// ```
+    pub fn new(constraint_len: usize, polys: Vec<u32>) -> ViterbiDecodeNode {
+        ViterbiDecodeNode {
+            code: ConvolutionalCode::new(constraint_len, polys),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ViterbiDecodeNode`.  Produces the decoded bits.
+    pub fn run(&mut self, coded: &[u8]) -> Result<Vec<u8>, NodeError> {
+        self.code.decode(coded).ok_or(NodeError::DataError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::convolutional::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Checks the encoder against a known output of the K = 3 code, and that
+    // a block with scattered errors decodes cleanly.
+    fn test_convolutional() {
+        let code = ConvolutionalCode::new(3, vec![0o7, 0o5]);
+        assert_eq!(
+            code.encode(&[1, 0, 1, 1]),
+            vec![1, 1, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1]
+        );
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bits: Vec<u8> = (0..500).map(|_| rng.gen_range(0, 2)).collect();
+        let mut encoder = ConvolutionalEncodeNode::new(7, vec![0o133, 0o171]);
+        let mut decoder = ViterbiDecodeNode::new(7, vec![0o133, 0o171]);
+        let mut coded = encoder.run(&bits).unwrap();
+        assert_eq!(coded.len(), 2 * (500 + 6));
+        for ix in (0..coded.len()).step_by(50) {
+            coded[ix] ^= 1;
+        }
+        assert_eq!(decoder.run(&coded).unwrap(), bits);
+        assert!(decoder.run(&coded[..11]).is_err());
+    }
+}
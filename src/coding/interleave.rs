@@ -0,0 +1,240 @@
+//! Convolutional interleaving to spread bursts of errors.
+//!
+//! A convolutional (Forney) interleaver has `B` branches, and a commutator
+//! feeds successive symbols to successive branches, wrapping around after
+//! the last one.  Branch `i` delays its symbols by `i * M` places in that
+//! branch, where `M` is the increment, and a second commutator reads the
+//! branches out in step.  The deinterleaver does the reverse, with branch
+//! `i` delaying by `(B - 1 - i) * M`, so every symbol sees the same total
+//! delay of `B * (B - 1) * M` symbols.
+//!
+//! Neighbouring symbols on the channel come from different branches, so
+//! after deinterleaving a burst of up to `B` errors lands at least
+//! `B * M - 1` symbols apart, which a code correcting scattered errors can
+//! deal with.  For the same spreading, the delay and memory are half those
+//! of a block interleaver, which is why DVB and other broadcast standards
+//! use it between their Reed-Solomon and convolutional codes.
+use crate::prelude::*;
+
+use std::collections::VecDeque;
+
+// Branches of delay lines fed in turn by a commutator.
+struct Branches<T> {
+    lines: Vec<VecDeque<T>>,
+    pos: usize,
+}
+
+impl<T> Branches<T>
+where
+    T: Clone,
+{
+    fn new<I>(delays: I) -> Self
+    where
+        I: Iterator<Item = usize>,
+        T: Default,
+    {
+        Branches {
+            lines: delays.map(|d| vec![T::default(); d].into()).collect(),
+            pos: 0,
+        }
+    }
+
+    // Pushes a symbol into the current branch and takes the one leaving it.
+    fn push(&mut self, symbol: &T) -> T {
+        let n_branches = self.lines.len();
+        let line = &mut self.lines[self.pos];
+        self.pos = (self.pos + 1) % n_branches;
+        match line.pop_front() {
+            Some(out) => {
+                line.push_back(symbol.clone());
+                out
+            }
+            None => symbol.clone(),
+        }
+    }
+}
+
+/// A node that interleaves a stream of symbols with a convolutional
+/// interleaver.
+///
+/// The symbols may be bits, bytes or soft decisions.  The branches start
+/// out filled with `T::default()`, which the first symbols out are made of,
+/// and the commutator position carries across batches, so the stream may be
+/// batched arbitrarily.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::interleave::ConvolutionalInterleaverNode;
+///
+/// // The DVB interleaver, with 12 branches in steps of 17 bytes.
+/// let node: ConvolutionalInterleaverNode<u8> =
+///     ConvolutionalInterleaverNode::new(12, 17);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ConvolutionalInterleaverNode<T>
+where
+    T: Clone + Send + Default,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    branches: Branches<T>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> ConvolutionalInterleaverNode<T>
+where
+    T: Clone + Send + Default,
+{
+    /// Constructs a new `ConvolutionalInterleaverNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_branches` - Number of branches, which is the longest burst
+    ///   that's spread out.
+    /// * `increment` - Step in delay from one branch to the next, in
+    ///   symbols of that branch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::interleave::ConvolutionalInterleaverNode;
+    ///
+    /// let node: ConvolutionalInterleaverNode<f32> =
+    ///     ConvolutionalInterleaverNode::new(4, 2);
+    /// ```
+    pub fn new(n_branches: usize, increment: usize) -> Self {
+        assert!(n_branches > 0, "there must be at least one branch");
+        ConvolutionalInterleaverNode {
+            branches: Branches::new((0..n_branches).map(|i| i * increment)),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ConvolutionalInterleaverNode<T>`.  Produces the
+    /// interleaved symbols.
+    pub fn run(&mut self, symbols: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(symbols.iter().map(|x| self.branches.push(x)).collect())
+    }
+}
+
+/// A node that undoes a convolutional interleaver.
+///
+/// This must be built with the same number of branches and increment as
+/// the `ConvolutionalInterleaverNode`, and see the interleaved symbols from
+/// the first one on, so that the commutators line up.  The output is the
+/// original stream delayed by `delay()` symbols, which start out as
+/// `T::default()`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::interleave::ConvolutionalDeinterleaverNode;
+///
+/// let node: ConvolutionalDeinterleaverNode<u8> =
+///     ConvolutionalDeinterleaverNode::new(12, 17);
+/// assert_eq!(node.delay(), 2244);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ConvolutionalDeinterleaverNode<T>
+where
+    T: Clone + Send + Default,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    branches: Branches<T>,
+    delay: usize,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> ConvolutionalDeinterleaverNode<T>
+where
+    T: Clone + Send + Default,
+{
+    /// Constructs a new `ConvolutionalDeinterleaverNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_branches` - Number of branches of the interleaver.
+    /// * `increment` - Step in delay from one branch to the next of the
+    ///   interleaver.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::interleave::ConvolutionalDeinterleaverNode;
+    ///
+    /// let node: ConvolutionalDeinterleaverNode<f32> =
+    ///     ConvolutionalDeinterleaverNode::new(4, 2);
+    /// ```
+    pub fn new(n_branches: usize, increment: usize) -> Self {
+        assert!(n_branches > 0, "there must be at least one branch");
+        ConvolutionalDeinterleaverNode {
+            branches: Branches::new(
+                (0..n_branches).map(|i| (n_branches - 1 - i) * increment),
+            ),
+            delay: n_branches * (n_branches - 1) * increment,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the delay in symbols through the interleaver and
+    /// deinterleaver together.
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    /// Runs the `ConvolutionalDeinterleaverNode<T>`.  Produces the
+    /// deinterleaved symbols.
+    pub fn run(&mut self, symbols: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(symbols.iter().map(|x| self.branches.push(x)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::interleave::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Interleaves and deinterleaves random bytes in uneven batches, checking
+    // that they come back after the end to end delay, and that a burst of
+    // errors on the channel comes out spread apart.
+    fn test_convolutional_interleave() {
+        let (n_branches, increment) = (12, 17);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let data: Vec<u8> = (0..20000).map(|_| rng.gen()).collect();
+
+        let mut interleaver =
+            ConvolutionalInterleaverNode::new(n_branches, increment);
+        let mut deinterleaver =
+            ConvolutionalDeinterleaverNode::new(n_branches, increment);
+        let delay = deinterleaver.delay();
+        let mut sent = vec![];
+        for chunk in data.chunks(997) {
+            sent.extend(interleaver.run(chunk).unwrap());
+        }
+        assert_ne!(sent[delay..], data[..data.len() - delay]);
+
+        // Wipe out a burst as long as the number of branches.
+        let burst = 10000..10000 + n_branches;
+        for x in &mut sent[burst] {
+            *x = !*x;
+        }
+        let mut received = vec![];
+        for chunk in sent.chunks(1234) {
+            received.extend(deinterleaver.run(chunk).unwrap());
+        }
+        assert!(received[..delay].iter().all(|&x| x == 0));
+        let errors: Vec<usize> = (delay..received.len())
+            .filter(|&n| received[n] != data[n - delay])
+            .collect();
+        assert_eq!(errors.len(), n_branches);
+        assert!(errors
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] >= n_branches * increment - 1));
+    }
+}
@@ -0,0 +1,7 @@
+//! Nodes for forward error correction coding.
+
+pub mod convolutional;
+pub mod interleave;
+pub mod puncture;
+pub mod reed_solomon;
+pub mod symbol_whiten;
@@ -0,0 +1,249 @@
+//! Puncturing of coded bits to raise the code rate.
+//!
+//! A puncturing matrix has a row for each output of the code and a column for
+//! each input bit of the puncturing period.  A 1 keeps the coded bit and a 0
+//! deletes it.  The coded bits are taken to be interleaved as the encoder in
+//! `coding::convolutional` produces them, every output for one input bit
+//! before moving on to the next, and the matrix is applied to each period in
+//! turn.
+use crate::coding::convolutional::ERASURE;
+use crate::prelude::*;
+
+/// Returns the puncturing matrix for rate 2/3 from a rate 1/2 code, as used
+/// with the K = 7 code by 802.11.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::puncture::{rate_2_3, PunctureNode};
+///
+/// let node = PunctureNode::new(rate_2_3());
+/// ```
+pub fn rate_2_3() -> Vec<Vec<u8>> {
+    vec![vec![1, 1], vec![1, 0]]
+}
+
+/// Returns the puncturing matrix for rate 3/4 from a rate 1/2 code, as used
+/// with the K = 7 code by 802.11.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::puncture::{rate_3_4, PunctureNode};
+///
+/// let node = PunctureNode::new(rate_3_4());
+/// ```
+pub fn rate_3_4() -> Vec<Vec<u8>> {
+    vec![vec![1, 1, 0], vec![1, 0, 1]]
+}
+
+/// Flattens a puncturing matrix into a mask over one period of coded bits in
+/// the order they're sent.
+fn pattern_mask(matrix: &[Vec<u8>]) -> Vec<bool> {
+    assert!(!matrix.is_empty(), "puncturing matrix must not be empty");
+    let period = matrix[0].len();
+    assert!(
+        period > 0 && matrix.iter().all(|row| row.len() == period),
+        "puncturing matrix rows must all be the same nonzero length"
+    );
+    let mask: Vec<bool> = (0..period)
+        .flat_map(|col| matrix.iter().map(move |row| row[col] != 0))
+        .collect();
+    assert!(
+        mask.iter().any(|&keep| keep),
+        "puncturing matrix must keep at least one bit"
+    );
+    mask
+}
+
+/// A node that punctures a stream of coded bits.
+///
+/// The bits marked with a 0 in the puncturing matrix are deleted.  The
+/// position in the pattern carries over from one batch to the next, so the
+/// stream may be batched arbitrarily; to puncture each block the same way,
+/// every block should be a whole number of periods long.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::puncture::PunctureNode;
+///
+/// // Rate 5/6 from a rate 1/2 code.
+/// let node = PunctureNode::new(vec![
+///     vec![1, 1, 0, 1, 0],
+///     vec![1, 0, 1, 0, 1],
+/// ]);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct PunctureNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    mask: Vec<bool>,
+    pos: usize,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl PunctureNode {
+    /// Constructs a new `PunctureNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` - Puncturing matrix, with a row for each output of the
+    ///   code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::puncture::PunctureNode;
+    ///
+    /// let node = PunctureNode::new(vec![vec![1, 1], vec![1, 0]]);
+    /// ```
+    pub fn new(matrix: Vec<Vec<u8>>) -> PunctureNode {
+        PunctureNode {
+            mask: pattern_mask(&matrix),
+            pos: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `PunctureNode`.  Produces the coded bits that are kept.
+    pub fn run(&mut self, coded: &[u8]) -> Result<Vec<u8>, NodeError> {
+        let mut output = Vec::with_capacity(coded.len());
+        for &bit in coded {
+            if self.mask[self.pos] {
+                output.push(bit);
+            }
+            self.pos = (self.pos + 1) % self.mask.len();
+        }
+        Ok(output)
+    }
+}
+
+/// A node that restores punctured bits as erasures.
+///
+/// This undoes `PunctureNode` with the same puncturing matrix, putting an
+/// `ERASURE` back in the place of each deleted bit so that the Viterbi
+/// decoder sees the full rate code and ignores the bits it never received.
+/// Erasures that follow the last received bit of a batch are emitted with
+/// it, so a block that's a whole number of periods long comes back whole.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::puncture::{rate_3_4, DepunctureNode};
+///
+/// let node = DepunctureNode::new(rate_3_4());
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DepunctureNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    mask: Vec<bool>,
+    pos: usize,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl DepunctureNode {
+    /// Constructs a new `DepunctureNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` - Puncturing matrix, with a row for each output of the
+    ///   code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::puncture::DepunctureNode;
+    ///
+    /// let node = DepunctureNode::new(vec![vec![1, 1], vec![1, 0]]);
+    /// ```
+    pub fn new(matrix: Vec<Vec<u8>>) -> DepunctureNode {
+        DepunctureNode {
+            mask: pattern_mask(&matrix),
+            pos: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `DepunctureNode`.  Produces the coded bits with erasures in
+    /// the punctured positions.
+    pub fn run(&mut self, punctured: &[u8]) -> Result<Vec<u8>, NodeError> {
+        let mut output = Vec::with_capacity(2 * punctured.len());
+        let mut bits = punctured.iter();
+        loop {
+            if self.mask[self.pos] {
+                match bits.next() {
+                    Some(&bit) => output.push(bit),
+                    None => break,
+                }
+            } else {
+                output.push(ERASURE);
+            }
+            self.pos = (self.pos + 1) % self.mask.len();
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::convolutional::*;
+    use crate::coding::puncture::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Checks where the bits go for the rate 3/4 pattern, batched across the
+    // end of a period.
+    fn test_puncture_pattern() {
+        let mut puncture = PunctureNode::new(rate_3_4());
+        let mut depuncture = DepunctureNode::new(rate_3_4());
+        let coded: Vec<u8> = (0..12).collect();
+
+        let mut punctured = puncture.run(&coded[..5]).unwrap();
+        punctured.extend(puncture.run(&coded[5..]).unwrap());
+        assert_eq!(punctured, vec![0, 1, 2, 5, 6, 7, 8, 11]);
+
+        let e = ERASURE;
+        let mut restored = depuncture.run(&punctured[..3]).unwrap();
+        assert_eq!(restored, vec![0, 1, 2, e, e]);
+        restored.extend(depuncture.run(&punctured[3..]).unwrap());
+        assert_eq!(restored, vec![0, 1, 2, e, e, 5, 6, 7, 8, e, e, 11]);
+    }
+
+    #[test]
+    // Encodes random blocks with the K = 7 code, punctures them to rate 2/3
+    // and 3/4, sends them over a binary symmetric channel, and checks that
+    // the Viterbi decoder still recovers every block.
+    fn test_puncture_round_trip() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let polys = vec![0o133, 0o171];
+        let mut encoder = ConvolutionalEncodeNode::new(7, polys.clone());
+        let mut decoder = ViterbiDecodeNode::new(7, polys);
+
+        for matrix in [rate_2_3(), rate_3_4()].iter() {
+            let mut puncture = PunctureNode::new(matrix.clone());
+            let mut depuncture = DepunctureNode::new(matrix.clone());
+            // With the tail, 306 bits fills a whole number of periods of
+            // either pattern.
+            for _ in 0..20 {
+                let bits: Vec<u8> =
+                    (0..300).map(|_| rng.gen_range(0, 2)).collect();
+                let coded = encoder.run(&bits).unwrap();
+                let mut sent = puncture.run(&coded).unwrap();
+                assert!(sent.len() < coded.len());
+                for bit in sent.iter_mut() {
+                    if rng.gen_bool(0.005) {
+                        *bit ^= 1;
+                    }
+                }
+                let received = depuncture.run(&sent).unwrap();
+                assert_eq!(received.len(), coded.len());
+                assert_eq!(decoder.run(&received).unwrap(), bits);
+            }
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! Reed-Solomon block coding over GF(256).
+//!
+//! The code here is a systematic RS(n, k) code over GF(256), built from the
+//! primitive polynomial x^8 + x^4 + x^3 + x^2 + 1 (0x11D) with the roots of
+//! the generator polynomial starting at alpha^0.  Each codeword holds the `k`
+//! message bytes followed by `n - k` parity bytes, and up to `(n - k) / 2`
+//! corrupted bytes anywhere in the codeword can be corrected.  Values of `n`
+//! less than 255 give a shortened code, which behaves as if the missing
+//! leading bytes were all zero.
+//!
+//! Decoding uses the usual chain of syndrome calculation, Berlekamp-Massey to
+//! find the error locator polynomial, a Chien search for the error positions
+//! and Forney's algorithm for the error values.
+use crate::prelude::*;
+
+/// Primitive polynomial used to build the field.
+const PRIM_POLY: u16 = 0x11D;
+
+/// Implementation of a Reed-Solomon encoder and decoder.
+///
+/// This does all of the finite field arithmetic for the Reed-Solomon nodes
+/// and can be used on its own to encode and decode single blocks.
+pub struct ReedSolomon {
+    n: usize,
+    k: usize,
+    exp: [u8; 512],
+    log: [u8; 256],
+    generator: Vec<u8>,
+}
+
+impl ReedSolomon {
+    /// Creates a new RS(n, k) code.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of bytes in each codeword, at most 255.
+    /// * `k` - Number of message bytes in each codeword, less than `n`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::reed_solomon::ReedSolomon;
+    ///
+    /// // The code used by CCSDS and DVB, correcting 16 bytes per block.
+    /// let rs = ReedSolomon::new(255, 223);
+    /// ```
+    pub fn new(n: usize, k: usize) -> ReedSolomon {
+        assert!(n <= 255, "codeword length must be at most 255");
+        assert!(k > 0 && k < n, "message length must be on (0, n)");
+
+        let mut exp = [0; 512];
+        let mut log = [0; 256];
+        let mut x: u16 = 1;
+        for (i, e) in exp.iter_mut().enumerate().take(255) {
+            *e = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= PRIM_POLY;
+            }
+        }
+        // Repeat the table so products of logs don't need a modulo.
+        for i in 255..512 {
+            exp[i] = exp[i - 255];
+        }
+
+        let mut rs = ReedSolomon {
+            n,
+            k,
+            exp,
+            log,
+            generator: vec![1],
+        };
+
+        // g(x) = (x - a^0)(x - a^1)...(x - a^(n - k - 1)), highest degree
+        // coefficient first.
+        for i in 0..n - k {
+            let root = rs.exp[i];
+            let mut next = rs.generator.clone();
+            next.push(0);
+            for (j, g) in rs.generator.iter().enumerate() {
+                next[j + 1] ^= rs.mul(*g, root);
+            }
+            rs.generator = next;
+        }
+        rs
+    }
+
+    /// Returns the number of bytes in each codeword.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the number of message bytes in each codeword.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp
+                [self.log[a as usize] as usize + self.log[b as usize] as usize]
+        }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        assert!(b != 0, "division by zero in GF(256)");
+        if a == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + 255
+                - self.log[b as usize] as usize]
+        }
+    }
+
+    /// Returns alpha raised to the given power.
+    fn pow_alpha(&self, power: usize) -> u8 {
+        self.exp[power % 255]
+    }
+
+    /// Evaluates a polynomial with its lowest degree coefficient first.
+    fn eval_low_first(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, c| self.mul(acc, x) ^ c)
+    }
+
+    /// Encodes a block of `k` message bytes into a codeword of `n` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The `k` message bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::reed_solomon::ReedSolomon;
+    ///
+    /// let rs = ReedSolomon::new(15, 11);
+    /// let codeword = rs.encode(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    /// assert_eq!(codeword.len(), 15);
+    /// assert_eq!(&codeword[..11], &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    /// ```
+    pub fn encode(&self, message: &[u8]) -> Vec<u8> {
+        assert_eq!(message.len(), self.k, "message must be k bytes long");
+        // Long division of message * x^(n - k) by the generator, keeping the
+        // remainder as the parity bytes.
+        let mut remainder = vec![0; self.n - self.k];
+        for byte in message {
+            let coef = byte ^ remainder[0];
+            remainder.remove(0);
+            remainder.push(0);
+            if coef != 0 {
+                for (r, g) in remainder.iter_mut().zip(&self.generator[1..]) {
+                    *r ^= self.mul(*g, coef);
+                }
+            }
+        }
+        let mut codeword = message.to_vec();
+        codeword.extend(remainder);
+        codeword
+    }
+
+    /// Computes the syndromes of a codeword, all of which are zero for a
+    /// valid codeword.
+    fn syndromes(&self, codeword: &[u8]) -> Vec<u8> {
+        (0..self.n - self.k)
+            .map(|i| {
+                let x = self.pow_alpha(i);
+                codeword.iter().fold(0, |acc, c| self.mul(acc, x) ^ c)
+            })
+            .collect()
+    }
+
+    /// Corrects a codeword in place.
+    ///
+    /// Returns the number of bytes corrected, or `None` if the codeword has
+    /// more errors than the code can correct.  In that case the codeword is
+    /// left as it was.
+    ///
+    /// # Arguments
+    ///
+    /// * `codeword` - The `n` byte codeword to correct.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::reed_solomon::ReedSolomon;
+    ///
+    /// let rs = ReedSolomon::new(15, 11);
+    /// let mut codeword = rs.encode(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    /// codeword[3] ^= 0x55;
+    /// codeword[12] = 0;
+    /// assert_eq!(rs.decode(&mut codeword), Some(2));
+    /// assert_eq!(&codeword[..11], &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    /// ```
+    pub fn decode(&self, codeword: &mut [u8]) -> Option<usize> {
+        assert_eq!(codeword.len(), self.n, "codeword must be n bytes long");
+        let synd = self.syndromes(codeword);
+        if synd.iter().all(|s| *s == 0) {
+            return Some(0);
+        }
+
+        // Berlekamp-Massey, with polynomials lowest degree first.
+        let mut locator = vec![1];
+        let mut prev = vec![1];
+        let mut n_errors = 0;
+        let mut shift = 1;
+        let mut prev_disc = 1;
+        for i in 0..synd.len() {
+            let disc = (1..=n_errors).fold(synd[i], |acc, j| {
+                acc ^ self.mul(*locator.get(j).unwrap_or(&0), synd[i - j])
+            });
+            if disc == 0 {
+                shift += 1;
+                continue;
+            }
+            let scale = self.div(disc, prev_disc);
+            let mut next = locator.clone();
+            next.resize(next.len().max(prev.len() + shift), 0);
+            for (j, p) in prev.iter().enumerate() {
+                next[j + shift] ^= self.mul(scale, *p);
+            }
+            if 2 * n_errors <= i {
+                prev = locator;
+                n_errors = i + 1 - n_errors;
+                prev_disc = disc;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+            locator = next;
+        }
+        if 2 * n_errors > synd.len() {
+            return None;
+        }
+
+        // Chien search for the roots, which are the inverses of the error
+        // locations.  Byte ix of the codeword is the coefficient of degree
+        // n - 1 - ix.
+        let positions: Vec<usize> = (0..self.n)
+            .filter(|degree| {
+                let inv = self.pow_alpha(255 - degree % 255);
+                self.eval_low_first(&locator, inv) == 0
+            })
+            .collect();
+        if positions.len() != n_errors {
+            return None;
+        }
+
+        // Forney's algorithm for the error values.  The error evaluator is
+        // S(x) * L(x) mod x^(n - k), and the derivative of the locator only
+        // keeps its odd degree terms.
+        let mut evaluator = vec![0; synd.len()];
+        for (i, s) in synd.iter().enumerate() {
+            for (j, l) in locator.iter().enumerate() {
+                if i + j < evaluator.len() {
+                    evaluator[i + j] ^= self.mul(*s, *l);
+                }
+            }
+        }
+        let derivative: Vec<u8> = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(j, l)| if j % 2 == 1 { *l } else { 0 })
+            .collect();
+
+        let mut corrected = codeword.to_vec();
+        for degree in &positions {
+            let x = self.pow_alpha(*degree);
+            let inv = self.pow_alpha(255 - degree % 255);
+            let num = self.mul(x, self.eval_low_first(&evaluator, inv));
+            let den = self.eval_low_first(&derivative, inv);
+            if den == 0 {
+                return None;
+            }
+            corrected[self.n - 1 - degree] ^= self.div(num, den);
+        }
+
+        if self.syndromes(&corrected).iter().any(|s| *s != 0) {
+            return None;
+        }
+        codeword.copy_from_slice(&corrected);
+        Some(n_errors)
+    }
+}
+
+/// A node that Reed-Solomon encodes blocks of bytes.
+///
+/// The input must be a whole number of `k` byte messages, each of which is
+/// encoded into an `n` byte codeword.  The codewords are concatenated in the
+/// output.  Any other input length is a `NodeError::DataError`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::reed_solomon::ReedSolomonEncodeNode;
+///
+/// let node = ReedSolomonEncodeNode::new(255, 239);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ReedSolomonEncodeNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    rs: ReedSolomon,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl ReedSolomonEncodeNode {
+    /// Constructs a new `ReedSolomonEncodeNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of bytes in each codeword, at most 255.
+    /// * `k` - Number of message bytes in each codeword, less than `n`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::reed_solomon::ReedSolomonEncodeNode;
+    ///
+    /// // A shortened code correcting 4 bytes in each 64 byte block.
+    /// let node = ReedSolomonEncodeNode::new(64, 56);
+    /// ```
+    pub fn new(n: usize, k: usize) -> ReedSolomonEncodeNode {
+        ReedSolomonEncodeNode {
+            rs: ReedSolomon::new(n, k),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ReedSolomonEncodeNode`.  Produces the encoded codewords.
+    pub fn run(&mut self, data: &[u8]) -> Result<Vec<u8>, NodeError> {
+        if !data.chunks_exact(self.rs.k()).remainder().is_empty() {
+            return Err(NodeError::DataError);
+        }
+        Ok(data
+            .chunks(self.rs.k())
+            .flat_map(|msg| self.rs.encode(msg))
+            .collect())
+    }
+}
+
+/// A node that decodes Reed-Solomon codewords.
+///
+/// The input must be a whole number of `n` byte codewords.  For each codeword
+/// the output holds the corrected `k` message bytes, or `None` if the
+/// codeword had too many errors to correct, so that failed blocks can be
+/// flagged downstream rather than passed on as good data.  Any other input
+/// length is a `NodeError::DataError`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::reed_solomon::ReedSolomonDecodeNode;
+///
+/// let node = ReedSolomonDecodeNode::new(255, 239);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ReedSolomonDecodeNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    rs: ReedSolomon,
+    pub output: NodeSender<Vec<Option<Vec<u8>>>>,
+}
+
+impl ReedSolomonDecodeNode {
+    /// Constructs a new `ReedSolomonDecodeNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of bytes in each codeword, at most 255.
+    /// * `k` - Number of message bytes in each codeword, less than `n`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::reed_solomon::ReedSolomonDecodeNode;
+    ///
+    /// let node = ReedSolomonDecodeNode::new(64, 56);
+    /// ```
+    pub fn new(n: usize, k: usize) -> ReedSolomonDecodeNode {
+        ReedSolomonDecodeNode {
+            rs: ReedSolomon::new(n, k),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ReedSolomonDecodeNode`.  Produces the decoded message for
+    /// each codeword.
+    pub fn run(
+        &mut self,
+        data: &[u8],
+    ) -> Result<Vec<Option<Vec<u8>>>, NodeError> {
+        if !data.chunks_exact(self.rs.n()).remainder().is_empty() {
+            return Err(NodeError::DataError);
+        }
+        Ok(data
+            .chunks(self.rs.n())
+            .map(|block| {
+                let mut block = block.to_vec();
+                self.rs.decode(&mut block)?;
+                block.truncate(self.rs.k());
+                Some(block)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::reed_solomon::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use rand::seq::sample_indices;
+
+    fn random_bytes(rng: &mut SmallRng, len: usize) -> Vec<u8> {
+        (0..len).map(|_| rng.gen()).collect()
+    }
+
+    // Corrupts `n_errors` distinct bytes of each codeword in a stream.
+    fn corrupt(rng: &mut SmallRng, data: &mut [u8], n: usize, n_errors: usize) {
+        for block in data.chunks_mut(n) {
+            for ix in sample_indices(rng, n, n_errors) {
+                block[ix] ^= rng.gen_range(1, 256) as u8;
+            }
+        }
+    }
+
+    #[test]
+    // Corrupts the maximum correctable number of bytes in each block and
+    // checks that every block decodes perfectly.
+    fn test_reed_solomon_correctable() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for &(n, k) in &[(255, 223), (64, 56), (15, 11)] {
+            let t = (n - k) / 2;
+            let message = random_bytes(&mut rng, k * 10);
+            let mut encoder = ReedSolomonEncodeNode::new(n, k);
+            let mut decoder = ReedSolomonDecodeNode::new(n, k);
+
+            let mut coded = encoder.run(&message).unwrap();
+            assert_eq!(coded.len(), n * 10);
+            corrupt(&mut rng, &mut coded, n, t);
+
+            let decoded = decoder.run(&coded).unwrap();
+            assert_eq!(decoded.len(), 10);
+            for (block, truth) in decoded.iter().zip(message.chunks(k)) {
+                assert_eq!(block.as_ref().unwrap().as_slice(), truth);
+            }
+        }
+    }
+
+    #[test]
+    // Corrupts more bytes than the code can correct and checks that the
+    // blocks are flagged as failures.
+    fn test_reed_solomon_uncorrectable() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let (n, k) = (255, 223);
+        let message = random_bytes(&mut rng, k * 20);
+        let mut encoder = ReedSolomonEncodeNode::new(n, k);
+        let mut decoder = ReedSolomonDecodeNode::new(n, k);
+
+        let mut coded = encoder.run(&message).unwrap();
+        corrupt(&mut rng, &mut coded, n, 17);
+        let decoded = decoder.run(&coded).unwrap();
+        assert!(decoded.iter().all(|block| block.is_none()));
+
+        assert!(encoder.run(&message[..100]).is_err());
+        assert!(decoder.run(&coded[..100]).is_err());
+    }
+}
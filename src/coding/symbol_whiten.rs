@@ -0,0 +1,240 @@
+//! Whitening of modulated symbols with a pseudo-random phase sequence.
+//!
+//! Long runs of the same symbol, such as idle fill or a repeated header, put
+//! strong lines in the spectrum of a signal and starve timing recovery of the
+//! transitions it needs.  Rotating every symbol by a pseudo-random multiple of
+//! `2 * PI / 2^bits` spreads that energy evenly over the band while leaving
+//! the magnitude of each symbol alone, and since the receiver knows the
+//! sequence it can undo the rotation exactly.  With two bits per symbol the
+//! rotations are multiples of 90 degrees, which map square QAM and QPSK
+//! constellations back onto themselves.
+//!
+//! The sequence comes from a `PrnGen` LFSR, and the whitening and dewhitening
+//! nodes must be built with the same polynomial, initial state and number of
+//! bits per symbol, and be started on the same symbol.
+use crate::prelude::*;
+use crate::prns::PrnGen;
+
+use num::{Complex, PrimInt};
+use std::f64::consts::PI;
+
+/// Pseudo-random unit-magnitude sequence shared by both nodes.
+struct WhitenSequence<T> {
+    prngen: PrnGen<T>,
+    bits: usize,
+    rotations: Vec<Complex<f64>>,
+}
+
+impl<T: PrimInt> WhitenSequence<T> {
+    fn new(poly_mask: T, state: T, bits: usize) -> WhitenSequence<T> {
+        assert!(
+            (1..=8).contains(&bits),
+            "bits per symbol must be between 1 and 8"
+        );
+        let n = 1 << bits;
+        let rotations = (0..n)
+            .map(|m| Complex::from_polar(1.0, 2.0 * PI * m as f64 / n as f64))
+            .collect();
+        WhitenSequence {
+            prngen: PrnGen::new(poly_mask, state),
+            bits,
+            rotations,
+        }
+    }
+
+    // Returns the rotation for the next symbol.
+    fn next(&mut self) -> Complex<f64> {
+        let mut ix = 0;
+        for _ in 0..self.bits {
+            ix = (ix << 1) | usize::from(self.prngen.next_byte());
+        }
+        self.rotations[ix]
+    }
+}
+
+/// A node that whitens modulated symbols with a pseudo-random phase
+/// sequence.
+///
+/// Every symbol is multiplied by the next value of the sequence.  The symbols
+/// are undone by a `SymbolDewhitenNode` with the same parameters.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::symbol_whiten::SymbolWhitenNode;
+///
+/// // PRBS16 with rotations by multiples of 90 degrees.
+/// let node = SymbolWhitenNode::new(0xB400_u16, 0xFFFF, 2);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SymbolWhitenNode<T>
+where
+    T: PrimInt + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    sequence: WhitenSequence<T>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl<T> SymbolWhitenNode<T>
+where
+    T: PrimInt + Send,
+{
+    /// Constructs a new `SymbolWhitenNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `poly_mask` - Polynomial bit mask of the LFSR, as for `PrnGen`.
+    /// * `state` - Initial state of the LFSR, which must be nonzero.
+    /// * `bits` - Number of sequence bits used for each symbol, between 1 and
+    ///   8, giving rotations by multiples of `2 * PI / 2^bits`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::symbol_whiten::SymbolWhitenNode;
+    ///
+    /// // PRBS8 with rotations of 0 or 180 degrees, as for BPSK.
+    /// let node = SymbolWhitenNode::new(0xB8_u8, 0x01, 1);
+    /// ```
+    pub fn new(poly_mask: T, state: T, bits: usize) -> Self {
+        SymbolWhitenNode {
+            sequence: WhitenSequence::new(poly_mask, state, bits),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `SymbolWhitenNode<T>`.  Produces the whitened symbols.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(symbols.iter().map(|s| s * self.sequence.next()).collect())
+    }
+}
+
+/// A node that undoes the whitening of a `SymbolWhitenNode`.
+///
+/// Every symbol is multiplied by the conjugate of the next value of the
+/// sequence, which must line up with the sequence used to whiten it.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::coding::symbol_whiten::SymbolDewhitenNode;
+///
+/// let node = SymbolDewhitenNode::new(0xB400_u16, 0xFFFF, 2);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SymbolDewhitenNode<T>
+where
+    T: PrimInt + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    sequence: WhitenSequence<T>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl<T> SymbolDewhitenNode<T>
+where
+    T: PrimInt + Send,
+{
+    /// Constructs a new `SymbolDewhitenNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `poly_mask` - Polynomial bit mask of the LFSR, as for `PrnGen`.
+    /// * `state` - Initial state of the LFSR, which must be nonzero.
+    /// * `bits` - Number of sequence bits used for each symbol, between 1 and
+    ///   8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::coding::symbol_whiten::SymbolDewhitenNode;
+    ///
+    /// let node = SymbolDewhitenNode::new(0xB8_u8, 0x01, 1);
+    /// ```
+    pub fn new(poly_mask: T, state: T, bits: usize) -> Self {
+        SymbolDewhitenNode {
+            sequence: WhitenSequence::new(poly_mask, state, bits),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `SymbolDewhitenNode<T>`.  Produces the original symbols.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(symbols
+            .iter()
+            .map(|s| s * self.sequence.next().conj())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::symbol_whiten::*;
+    use crate::fft::BatchFFT;
+    use rustfft::FFTplanner;
+
+    // Spectral flatness of the averaged periodogram, the ratio of its
+    // geometric mean to its arithmetic mean.  One for white noise, and near
+    // zero for a spectrum made of lines.
+    fn flatness(x: &[Complex<f64>]) -> f64 {
+        let n = 64;
+        let mut planner = FFTplanner::new(false);
+        let mut fft = BatchFFT::new(planner.plan_fft(n), n);
+        let mut psd = vec![0.0; n];
+        for frame in x.chunks_exact(n) {
+            for (p, y) in psd.iter_mut().zip(fft.run_fft(frame)) {
+                *p += y.norm_sqr();
+            }
+        }
+        let log_mean =
+            psd.iter().map(|p| p.max(1e-12).ln()).sum::<f64>() / n as f64;
+        let mean = psd.iter().sum::<f64>() / n as f64;
+        log_mean.exp() / mean
+    }
+
+    #[test]
+    // Whitens a QPSK stream stuck on a short repeating pattern, checks that
+    // its spectrum becomes flat, and that dewhitening in different batches
+    // gives back the original symbols.
+    fn test_symbol_whiten() {
+        let pattern = [
+            Complex::new(1.0, 1.0),
+            Complex::new(1.0, 1.0),
+            Complex::new(-1.0, 1.0),
+            Complex::new(1.0, -1.0),
+        ];
+        let symbols: Vec<Complex<f64>> =
+            pattern.iter().cycle().take(64 * 200).cloned().collect();
+
+        let mut whiten = SymbolWhitenNode::new(0xB400_u16, 0xACE1, 2);
+        let mut dewhiten = SymbolDewhitenNode::new(0xB400_u16, 0xACE1, 2);
+        let mut whitened = vec![];
+        for chunk in symbols.chunks(100) {
+            whitened.extend(whiten.run(chunk).unwrap());
+        }
+        let mut recovered = vec![];
+        for chunk in whitened.chunks(33) {
+            recovered.extend(dewhiten.run(chunk).unwrap());
+        }
+
+        for ((x, w), y) in symbols.iter().zip(&whitened).zip(&recovered) {
+            assert!((w.norm() - x.norm()).abs() < 1e-12);
+            // 90 degree rotations keep QPSK on the constellation.
+            assert!((w.re.abs() - 1.0).abs() < 1e-12);
+            assert!((y - x).norm() < 1e-12);
+        }
+        assert!(flatness(&symbols) < 0.1);
+        assert!(flatness(&whitened) > 0.9);
+    }
+}
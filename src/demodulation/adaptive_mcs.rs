@@ -0,0 +1,253 @@
+//! Link adaptation from a running measurement of EVM and SNR.
+use crate::prelude::*;
+
+use num::Complex;
+use std::collections::VecDeque;
+
+/// A node that recommends a modulation and coding scheme from the measured
+/// SNR of received symbols.
+///
+/// Each received symbol is compared against the nearest point of the
+/// reference constellation, and the error vector magnitude is measured over
+/// the most recent `window` symbols.  The SNR is estimated from it as
+/// `-20 * log10(EVM)`, and the recommended MCS index is the number of
+/// thresholds the SNR is above, so with thresholds of `[6.0, 12.0, 18.0]` an
+/// SNR of 14 dB recommends MCS 2.  Once the window has filled, every batch
+/// produces the current recommendation.
+///
+/// The symbols should be equalized and scaled to the power of the reference
+/// constellation, which is QPSK with unit power unless set otherwise.  Since
+/// the errors are measured against decisions, the estimate reads high at low
+/// SNR where decisions start going wrong, which matters little as long as
+/// the lowest threshold is above that point.  A hysteresis band keeps a link
+/// near a threshold from switching back and forth; the recommendation only
+/// moves up once the SNR clears a threshold by half the band, and only moves
+/// down once it falls half the band below it.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::adaptive_mcs::AdaptiveMcsNode;
+///
+/// // Step up through three schemes at 6, 12 and 18 dB.
+/// let node = AdaptiveMcsNode::new(vec![6.0, 12.0, 18.0], 1000)
+///     .with_hysteresis(1.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct AdaptiveMcsNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    thresholds: Vec<f64>,
+    window: usize,
+    hysteresis: f64,
+    constellation: Vec<Complex<f64>>,
+    errors: VecDeque<(f64, f64)>,
+    error_sum: f64,
+    power_sum: f64,
+    mcs: usize,
+    pub output: NodeSender<usize>,
+}
+
+impl AdaptiveMcsNode {
+    /// Constructs a new `AdaptiveMcsNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - SNR in dB needed for each MCS above the lowest, in
+    ///   ascending order.
+    /// * `window` - Number of symbols the EVM is measured over.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::adaptive_mcs::AdaptiveMcsNode;
+    ///
+    /// let node = AdaptiveMcsNode::new(vec![10.0], 500);
+    /// ```
+    pub fn new(thresholds: Vec<f64>, window: usize) -> AdaptiveMcsNode {
+        assert!(
+            thresholds.windows(2).all(|t| t[0] < t[1]),
+            "thresholds must be in ascending order"
+        );
+        assert!(window > 0, "window must be nonzero");
+        let a = 0.5_f64.sqrt();
+        AdaptiveMcsNode {
+            thresholds,
+            window,
+            hysteresis: 0.0,
+            constellation: vec![
+                Complex::new(a, a),
+                Complex::new(-a, a),
+                Complex::new(-a, -a),
+                Complex::new(a, -a),
+            ],
+            errors: VecDeque::with_capacity(window),
+            error_sum: 0.0,
+            power_sum: 0.0,
+            mcs: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the total width in dB of the hysteresis band around each
+    /// threshold.
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        assert!(hysteresis >= 0.0, "hysteresis must not be negative");
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Sets the reference constellation the symbols are compared against.
+    pub fn with_constellation(mut self, points: Vec<Complex<f64>>) -> Self {
+        assert!(!points.is_empty(), "constellation must not be empty");
+        self.constellation = points;
+        self
+    }
+
+    /// Returns the RMS error vector magnitude over the window, as a fraction
+    /// of the RMS reference symbol, or `None` before any symbols arrive.
+    pub fn evm(&self) -> Option<f64> {
+        if self.errors.is_empty() {
+            None
+        } else {
+            Some((self.error_sum.max(0.0) / self.power_sum).sqrt())
+        }
+    }
+
+    /// Returns the estimated SNR in dB, or `None` before any symbols arrive.
+    pub fn snr(&self) -> Option<f64> {
+        self.evm().map(|evm| -20.0 * evm.log10())
+    }
+
+    /// Returns the current recommended MCS index.
+    pub fn mcs(&self) -> usize {
+        self.mcs
+    }
+
+    // Moves the recommendation given the current SNR.
+    fn update_mcs(&mut self, snr: f64) {
+        let above = |offset: f64| {
+            self.thresholds
+                .iter()
+                .filter(|&&t| snr > t + offset)
+                .count()
+        };
+        let half = self.hysteresis / 2.0;
+        let target = above(0.0);
+        if target > self.mcs {
+            self.mcs = above(half).max(self.mcs);
+        } else if target < self.mcs {
+            self.mcs = above(-half).min(self.mcs);
+        }
+    }
+
+    /// Runs the `AdaptiveMcsNode`.  Produces the recommended MCS index once
+    /// the window has filled.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<f64>],
+    ) -> Result<Option<usize>, NodeError> {
+        for s in symbols {
+            let nearest = self
+                .constellation
+                .iter()
+                .min_by(|a, b| {
+                    (s - *a)
+                        .norm_sqr()
+                        .partial_cmp(&(s - *b).norm_sqr())
+                        .unwrap()
+                })
+                .unwrap();
+            let entry = ((s - nearest).norm_sqr(), nearest.norm_sqr());
+            self.error_sum += entry.0;
+            self.power_sum += entry.1;
+            self.errors.push_back(entry);
+            if self.errors.len() > self.window {
+                let (error, power) = self.errors.pop_front().unwrap();
+                self.error_sum -= error;
+                self.power_sum -= power;
+            }
+        }
+        if self.errors.len() < self.window {
+            return Ok(None);
+        }
+        let snr = self.snr().unwrap();
+        self.update_mcs(snr);
+        Ok(Some(self.mcs))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::adaptive_mcs::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    // Unit power QPSK symbols with noise at an SNR in dB.
+    fn noisy_qpsk(
+        rng: &mut SmallRng,
+        snr: f64,
+        len: usize,
+    ) -> Vec<Complex<f64>> {
+        let a = 0.5_f64.sqrt();
+        let sigma = (0.5 * 10.0_f64.powf(-snr / 10.0)).sqrt();
+        let dist = Normal::new(0.0, sigma);
+        (0..len)
+            .map(|_| {
+                let s = Complex::new(
+                    if rng.gen_bool(0.5) { a } else { -a },
+                    if rng.gen_bool(0.5) { a } else { -a },
+                );
+                s + Complex::new(rng.sample(dist), rng.sample(dist))
+            })
+            .collect()
+    }
+
+    #[test]
+    // Raises the SNR in steps between the thresholds and checks that the
+    // estimate tracks it and the recommendation steps up with it.
+    fn test_adaptive_mcs() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut node = AdaptiveMcsNode::new(vec![6.0, 12.0, 18.0], 2000);
+        assert_eq!(node.run(&noisy_qpsk(&mut rng, 3.0, 1000)).unwrap(), None);
+
+        for &(snr, mcs) in &[(3.0, 0), (9.0, 1), (15.0, 2), (21.0, 3)] {
+            let mut last = None;
+            for _ in 0..4 {
+                last = node.run(&noisy_qpsk(&mut rng, snr, 1000)).unwrap();
+            }
+            assert_eq!(last, Some(mcs));
+            if snr > 6.0 {
+                assert!((node.snr().unwrap() - snr).abs() < 0.5);
+            }
+        }
+    }
+
+    #[test]
+    // Holds the SNR just below a threshold after crossing it and checks that
+    // the hysteresis keeps the recommendation from dropping.
+    fn test_adaptive_mcs_hysteresis() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut node =
+            AdaptiveMcsNode::new(vec![12.0], 2000).with_hysteresis(3.0);
+        for _ in 0..4 {
+            node.run(&noisy_qpsk(&mut rng, 12.5, 1000)).unwrap();
+        }
+        assert_eq!(node.mcs(), 0);
+        for _ in 0..4 {
+            node.run(&noisy_qpsk(&mut rng, 14.5, 1000)).unwrap();
+        }
+        assert_eq!(node.mcs(), 1);
+        for _ in 0..4 {
+            node.run(&noisy_qpsk(&mut rng, 11.0, 1000)).unwrap();
+        }
+        assert_eq!(node.mcs(), 1);
+        for _ in 0..4 {
+            node.run(&noisy_qpsk(&mut rng, 9.0, 1000)).unwrap();
+        }
+        assert_eq!(node.mcs(), 0);
+    }
+}
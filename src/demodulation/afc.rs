@@ -0,0 +1,163 @@
+//! Automatic frequency control to track a drifting carrier.
+use crate::demodulation::frequency_estimator::frequency_offset_estimate;
+use crate::demodulation::nco::Nco;
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that removes a slowly drifting carrier frequency offset with an
+/// automatic frequency control (AFC) loop.
+///
+/// Each batch is mixed down by an internal NCO running at the current
+/// correction, and the residual offset left in the corrected batch is
+/// measured with `frequency_offset_estimate`.  A fraction of the residual,
+/// set by the loop gain, is then added to the correction for the next batch,
+/// so the loop integrates successive estimates into a correction that
+/// follows the carrier as it drifts, where a one-off correction at the start
+/// of a capture would leave an offset that grows with time.
+///
+/// A small gain averages the estimates over many batches, which lowers the
+/// jitter of the correction, but follows drift more slowly: a carrier
+/// drifting by `d` radians per sample per batch is tracked with a lag of
+/// about `d / gain`.  A gain of one makes each correction the previous one
+/// plus the latest estimate, with no averaging at all.  The NCO phase is
+/// continuous across batches, so the corrections don't add phase jumps.
+///
+/// Like `frequency_offset_estimate`, the loop expects oversampled samples
+/// that haven't been matched filtered, and the offset can't be more than pi
+/// radians per sample, the most the estimator can measure.  The output is
+/// the corrected batch.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::afc::AfcNode;
+///
+/// let node = AfcNode::new(0.1);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct AfcNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    gain: f64,
+    freq: f64,
+    nco: Nco,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl AfcNode {
+    /// Constructs a new `AfcNode`, starting with no correction.
+    ///
+    /// # Arguments
+    ///
+    /// * `gain` - Loop gain, the fraction of each residual estimate added to
+    ///   the correction.  Must be on the interval (0, 1].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::afc::AfcNode;
+    ///
+    /// // Heavy averaging for a stable oscillator.
+    /// let node = AfcNode::new(0.01);
+    /// ```
+    pub fn new(gain: f64) -> AfcNode {
+        assert!(gain > 0.0 && gain <= 1.0, "loop gain must be in (0, 1]");
+        AfcNode {
+            gain,
+            freq: 0.0,
+            nco: Nco::new(0.0, 0.0),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the current correction, the estimated carrier offset, in
+    /// radians per sample.
+    pub fn frequency(&self) -> f64 {
+        self.freq
+    }
+
+    /// Runs the `AfcNode`.  Produces the corrected batch and updates the
+    /// correction from it.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        let corrected: Vec<Complex<f64>> = samples
+            .iter()
+            .map(|x| x * self.nco.push(-self.freq))
+            .collect();
+        if corrected.len() > 1 {
+            self.freq += self.gain * frequency_offset_estimate(&corrected);
+        }
+        Ok(corrected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::afc::*;
+    use crate::filter::fir;
+    use crate::util::math::rrc_taps;
+    use num::Zero;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Sends QPSK on a carrier whose offset ramps steadily over a long
+    // capture, and checks that the AFC keeps the residual offset small the
+    // whole way, while a correction fixed at the start falls further and
+    // further behind.
+    fn test_afc_drift() {
+        let sam_per_sym = 4;
+        let batch = 1000;
+        let n_batches = 400;
+        let len = batch * n_batches;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut upsampled = vec![Complex::zero(); len];
+        for x in upsampled.iter_mut().step_by(sam_per_sym) {
+            let k = rng.gen_range(0, 4) as f64;
+            *x = Complex::new(0.0, PI / 4.0 + PI * k / 2.0).exp();
+        }
+        let taps: Vec<Complex<f64>> =
+            rrc_taps(16, sam_per_sym as f64, 0.75).unwrap();
+        let mut state = vec![Complex::zero(); 16];
+        let shaped = fir::batch_fir(&upsampled, &taps, &mut state);
+
+        // The offset ramps from 0.05 to 0.15 radians per sample.
+        let offset = |n: usize| 0.05 + 0.1 * n as f64 / len as f64;
+        let noise = Normal::new(0.0, 0.05);
+        let mut phase = 0.0;
+        let signal: Vec<Complex<f64>> = shaped
+            .iter()
+            .enumerate()
+            .map(|(n, x)| {
+                phase += offset(n);
+                x * Complex::new(0.0, phase).exp()
+                    + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect();
+
+        let fixed = frequency_offset_estimate(&signal[..batch]);
+        let mut node = AfcNode::new(0.1);
+        let mut residuals = vec![];
+        for (k, chunk) in signal.chunks(batch).enumerate() {
+            node.run(chunk).unwrap();
+            // Give the loop time to pull in from the initial offset.
+            if k >= 50 {
+                residuals
+                    .push((node.frequency() - offset((k + 1) * batch)).abs());
+            }
+        }
+        let mean = residuals.iter().sum::<f64>() / residuals.len() as f64;
+        assert!(residuals.iter().all(|&r| r < 0.02));
+        assert!(mean < 0.006);
+
+        // A correction estimated once from the first batch ends up off by
+        // nearly the whole drift.
+        assert!((fixed - offset(len)).abs() > 0.08);
+    }
+}
@@ -0,0 +1,238 @@
+//! Frequency stability measurement with the Allan deviation.
+use crate::prelude::*;
+
+use std::collections::VecDeque;
+
+/// Computes the overlapping Allan deviation of a series of fractional
+/// frequency estimates.
+///
+/// The estimates are integrated into a phase series `x`, and the Allan
+/// variance at an averaging interval of `m` estimates is
+/// `sum((x[i + 2m] - 2 * x[i + m] + x[i])^2) / (2 * (m * tau0)^2 * (N - 2m))`
+/// over every starting point `i`, where `tau0` is the interval between
+/// estimates and `N` is the number of phase points.  Using every starting
+/// point rather than only disjoint intervals gives a much better estimate
+/// from the same data.  Returns `None` if `m` is zero or there are fewer
+/// than `2 * m` estimates.
+///
+/// # Arguments
+///
+/// * `freq` - Fractional frequency estimates, evenly spaced in time.
+/// * `m` - Averaging interval in number of estimates.
+/// * `sample_rate` - Rate of the estimates in Hz.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::allan_dev::overlapping_adev;
+///
+/// // A constant frequency offset is perfectly stable.
+/// let freq = vec![1e-6; 100];
+/// assert!(overlapping_adev(&freq, 10, 1.0).unwrap() < 1e-15);
+/// ```
+pub fn overlapping_adev(
+    freq: &[f64],
+    m: usize,
+    sample_rate: f64,
+) -> Option<f64> {
+    if m == 0 || freq.len() < 2 * m {
+        return None;
+    }
+    let tau0 = 1.0 / sample_rate;
+    let mut phase = Vec::with_capacity(freq.len() + 1);
+    phase.push(0.0);
+    for y in freq {
+        let last = phase[phase.len() - 1];
+        phase.push(last + y * tau0);
+    }
+    Some(adev_from_phase(&phase, m, tau0))
+}
+
+// Overlapping Allan deviation from a phase series with at least 2m + 1
+// points.
+fn adev_from_phase(phase: &[f64], m: usize, tau0: f64) -> f64 {
+    let n_terms = phase.len() - 2 * m;
+    let sum: f64 = (0..n_terms)
+        .map(|i| {
+            let d = phase[i + 2 * m] - 2.0 * phase[i + m] + phase[i];
+            d * d
+        })
+        .sum();
+    let tau = m as f64 * tau0;
+    (sum / (2.0 * tau * tau * n_terms as f64)).sqrt()
+}
+
+/// A node that measures the frequency stability of a carrier.
+///
+/// The input is a stream of fractional frequency estimates, such as the
+/// output of a frequency estimator or carrier tracking loop divided by the
+/// carrier frequency, at a fixed rate.  The node keeps a running sum of the
+/// squared second differences of the phase at each of the configured
+/// averaging intervals, so that after each batch it emits the overlapping
+/// Allan deviation over everything received so far, as in
+/// `overlapping_adev`, while only holding on to the last `2 * m + 1` phase
+/// points for the longest interval `m`.
+/// This is the standard measure of oscillator stability: white frequency
+/// noise falls off as `tau^(-1/2)`, while flicker and random walk noise
+/// level off or grow at longer intervals.
+///
+/// Each averaging interval is rounded to a whole number of estimates.  The
+/// output is a `(tau, adev)` pair for each interval that has enough data so
+/// far, in the order the intervals were given.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::allan_dev::AllanDevNode;
+///
+/// // Estimates at 10 Hz, measured over 0.1 s to 100 s.
+/// let node = AllanDevNode::new(vec![0.1, 1.0, 10.0, 100.0], 10.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct AllanDevNode {
+    pub input: NodeReceiver<Vec<f64>>,
+    intervals: Vec<usize>,
+    tau0: f64,
+    phase: VecDeque<f64>,
+    history: usize,
+    sums: Vec<f64>,
+    counts: Vec<usize>,
+    pub output: NodeSender<Vec<(f64, f64)>>,
+}
+
+impl AllanDevNode {
+    /// Constructs a new `AllanDevNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `taus` - Averaging intervals in seconds.  Each must be at least the
+    ///   interval between estimates.
+    /// * `sample_rate` - Rate of the frequency estimates in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::allan_dev::AllanDevNode;
+    ///
+    /// let node = AllanDevNode::new(vec![1.0, 2.0, 4.0, 8.0], 1.0);
+    /// ```
+    pub fn new(taus: Vec<f64>, sample_rate: f64) -> AllanDevNode {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let intervals: Vec<usize> = taus
+            .iter()
+            .map(|tau| {
+                let m = (tau * sample_rate).round();
+                assert!(
+                    m >= 1.0,
+                    "averaging intervals must be at least one estimate long"
+                );
+                m as usize
+            })
+            .collect();
+        let history = 2 * intervals.iter().max().unwrap_or(&0) + 1;
+        let mut phase = VecDeque::with_capacity(history);
+        phase.push_back(0.0);
+        AllanDevNode {
+            sums: vec![0.0; intervals.len()],
+            counts: vec![0; intervals.len()],
+            intervals,
+            tau0: 1.0 / sample_rate,
+            phase,
+            history,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `AllanDevNode`.  Produces the Allan deviation at each
+    /// averaging interval with enough data.
+    pub fn run(&mut self, freq: &[f64]) -> Result<Vec<(f64, f64)>, NodeError> {
+        for y in freq {
+            if self.phase.len() == self.history {
+                self.phase.pop_front();
+            }
+            let last = self.phase[self.phase.len() - 1];
+            self.phase.push_back(last + y * self.tau0);
+
+            let n = self.phase.len() - 1;
+            for (k, &m) in self.intervals.iter().enumerate() {
+                if n >= 2 * m {
+                    let d = self.phase[n] - 2.0 * self.phase[n - m]
+                        + self.phase[n - 2 * m];
+                    self.sums[k] += d * d;
+                    self.counts[k] += 1;
+                }
+            }
+        }
+        Ok(self
+            .intervals
+            .iter()
+            .zip(self.sums.iter().zip(&self.counts))
+            .filter(|(_, (_, &count))| count > 0)
+            .map(|(&m, (sum, &count))| {
+                let tau = m as f64 * self.tau0;
+                (tau, (sum / (2.0 * tau * tau * count as f64)).sqrt())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::allan_dev::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Feeds white frequency noise and checks that the Allan deviation
+    // matches sigma / sqrt(tau) and falls off with a slope of -1/2.
+    fn test_allan_dev() {
+        let sample_rate = 10.0;
+        let sigma = 1e-9;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, sigma);
+        let freq: Vec<f64> = (0..100_000).map(|_| rng.sample(noise)).collect();
+
+        let taus = vec![0.1, 0.4, 1.6, 6.4, 25.6];
+        let mut node = AllanDevNode::new(taus.clone(), sample_rate);
+        let mut adev = vec![];
+        for chunk in freq.chunks(10_000) {
+            adev = node.run(chunk).unwrap();
+        }
+        assert_eq!(adev.len(), taus.len());
+
+        let tau0 = 1.0 / sample_rate;
+        for ((tau, dev), expected_tau) in adev.iter().zip(&taus) {
+            assert!((tau - expected_tau).abs() < 1e-9);
+            let expected = sigma * (tau0 / tau).sqrt();
+            assert!((dev / expected - 1.0).abs() < 0.1);
+            let direct = overlapping_adev(
+                &freq,
+                (tau / tau0).round() as usize,
+                sample_rate,
+            );
+            assert!((direct.unwrap() / dev - 1.0).abs() < 1e-9);
+        }
+
+        // Least squares fit of the slope on a log-log scale.
+        let pts: Vec<(f64, f64)> =
+            adev.iter().map(|(t, d)| (t.ln(), d.ln())).collect();
+        let n = pts.len() as f64;
+        let mean_x = pts.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = pts.iter().map(|p| p.1).sum::<f64>() / n;
+        let slope = pts
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>()
+            / pts.iter().map(|(x, _)| (x - mean_x).powi(2)).sum::<f64>();
+        assert!((slope + 0.5).abs() < 0.05);
+
+        // Intervals longer than half the data aren't reported.
+        let mut node = AllanDevNode::new(vec![1.0, 10.0], 1.0);
+        assert_eq!(node.run(&freq[..5]).unwrap().len(), 1);
+        assert_eq!(node.run(&[]).unwrap().len(), 1);
+        assert!(overlapping_adev(&freq[..5], 3, 1.0).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! Slicing bursts of energy out of a stream of samples.
+use crate::prelude::*;
+
+use num::{Complex, Float, NumCast};
+use std::collections::VecDeque;
+
+/// A node that cuts bursts out of a stream based on their energy.
+///
+/// The power of the incoming samples is smoothed with a leaky integrator,
+/// `p += alpha * (|x|^2 - p)`.  A burst starts when the smoothed power rises
+/// above the on threshold, and ends once it has fallen below the off
+/// threshold and stayed there for `post_pad` more samples; rising above the
+/// on threshold again in that time continues the same burst.  The `pre_pad`
+/// samples before the start are included as well, which covers the lag of
+/// the smoothing and leaves some margin for the processing of each burst.
+/// Having the off threshold lower than the on threshold keeps noise from
+/// chopping a burst into pieces.
+///
+/// Each complete burst is emitted as its own `Vec`.  The input may be batched
+/// arbitrarily; if a single batch completes more than one burst, they're
+/// emitted together, and a burst still in progress is held until it ends.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::burst_segment::BurstSegmenterNode;
+///
+/// let node: BurstSegmenterNode<f32> =
+///     BurstSegmenterNode::new(0.1, 0.03, 64, 64);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct BurstSegmenterNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    on_threshold: f64,
+    off_threshold: f64,
+    pre_pad: usize,
+    post_pad: usize,
+    alpha: f64,
+    power: f64,
+    history: VecDeque<Complex<T>>,
+    burst: Option<Vec<Complex<T>>>,
+    quiet: usize,
+    pub output: NodeSender<Vec<Vec<Complex<T>>>>,
+}
+
+impl<T> BurstSegmenterNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `BurstSegmenterNode<T>`, with a smoothing factor of
+    /// 0.1.
+    ///
+    /// # Arguments
+    ///
+    /// * `on_threshold` - Smoothed power above which a burst starts.
+    /// * `off_threshold` - Smoothed power below which a burst ends.  Must
+    ///   not be greater than `on_threshold`.
+    /// * `pre_pad` - Number of samples before the start to include.
+    /// * `post_pad` - Number of samples after the end to include.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::burst_segment::BurstSegmenterNode;
+    ///
+    /// let node: BurstSegmenterNode<f64> =
+    ///     BurstSegmenterNode::new(1e-3, 1e-4, 16, 100);
+    /// ```
+    pub fn new(
+        on_threshold: f64,
+        off_threshold: f64,
+        pre_pad: usize,
+        post_pad: usize,
+    ) -> Self {
+        assert!(
+            off_threshold <= on_threshold,
+            "off threshold must not be greater than on threshold"
+        );
+        BurstSegmenterNode {
+            on_threshold,
+            off_threshold,
+            pre_pad,
+            post_pad,
+            alpha: 0.1,
+            power: 0.0,
+            history: VecDeque::with_capacity(pre_pad + 1),
+            burst: None,
+            quiet: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the smoothing factor of the power estimate, on the interval
+    /// (0.0, 1.0].  Smaller values ride out short dips in power within a
+    /// burst but respond more slowly to its start and end.
+    pub fn with_smoothing(mut self, alpha: f64) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "smoothing factor must be on the interval (0.0, 1.0]"
+        );
+        self.alpha = alpha;
+        self
+    }
+
+    /// Runs the `BurstSegmenterNode<T>`.  Produces the bursts completed by
+    /// the batch of samples.
+    #[allow(clippy::type_complexity)]
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<Vec<Complex<T>>>>, NodeError> {
+        let mut bursts = vec![];
+        for &x in samples {
+            let p: f64 = NumCast::from(x.norm_sqr()).unwrap();
+            self.power += self.alpha * (p - self.power);
+
+            match self.burst {
+                None => {
+                    self.history.push_back(x);
+                    if self.power > self.on_threshold {
+                        self.burst = Some(self.history.drain(..).collect());
+                        self.quiet = 0;
+                    } else if self.history.len() > self.pre_pad {
+                        self.history.pop_front();
+                    }
+                }
+                Some(ref mut burst) => {
+                    burst.push(x);
+                    if self.power > self.on_threshold {
+                        self.quiet = 0;
+                    } else if self.power < self.off_threshold || self.quiet > 0
+                    {
+                        self.quiet += 1;
+                    }
+                    if self.quiet > self.post_pad {
+                        bursts.push(self.burst.take().unwrap());
+                    }
+                }
+            }
+        }
+        if bursts.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(bursts))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::burst_segment::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Puts two tone bursts in low level noise and checks that each comes out
+    // whole, with a little noise on either side.
+    fn test_burst_segment() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.01);
+        let bursts = [(1000, 1500), (3000, 3800)];
+        let signal: Vec<Complex<f64>> = (0..5000)
+            .map(|n| {
+                let on = bursts.iter().any(|&(s, e)| n >= s && n < e);
+                let x = if on {
+                    Complex::new(0.0, 0.1 * n as f64).exp()
+                } else {
+                    Complex::new(0.0, 0.0)
+                };
+                x + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect();
+
+        let pre_pad = 20;
+        let post_pad = 20;
+        let mut node = BurstSegmenterNode::new(0.25, 0.1, pre_pad, post_pad)
+            .with_smoothing(0.2);
+        let mut output = vec![];
+        for chunk in signal.chunks(300) {
+            if let Some(b) = node.run(chunk).unwrap() {
+                output.extend(b);
+            }
+        }
+
+        assert_eq!(output.len(), 2);
+        for (burst, &(start, end)) in output.iter().zip(&bursts) {
+            let strong = burst.iter().filter(|x| x.norm() > 0.5).count();
+            assert_eq!(strong, end - start);
+            assert!(burst[0].norm() < 0.1);
+            assert!(burst[burst.len() - 1].norm() < 0.1);
+            // Both pads, plus the lag of the smoothing at the end.
+            assert!(burst.len() > end - start + post_pad);
+            assert!(burst.len() <= end - start + pre_pad + post_pad + 15);
+        }
+    }
+}
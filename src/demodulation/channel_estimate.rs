@@ -0,0 +1,235 @@
+//! Least-squares estimation of a channel impulse response from a known
+//! training sequence.
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+
+/// A node that estimates the impulse response of a channel from a received
+/// training sequence.
+///
+/// Each input frame must start with the received copy of the training
+/// sequence.  The channel is modeled as an FIR filter of `n_taps` taps,
+///
+/// `y[n] = sum_k h[k] * x[n - k]`
+///
+/// and the taps are the least-squares fit to the received samples.  Only the
+/// samples from `n_taps - 1` on are used, since the earlier ones also depend
+/// on whatever was sent before the training sequence.  The fit is found by
+/// solving the normal equations, which are set up and factored once from
+/// the training sequence since they don't depend on the received samples.
+///
+/// The training sequence should be at least a few times longer than the
+/// number of taps and have a flat spectrum, such as a PN sequence or a
+/// Zadoff-Chu sequence, so that the fit is well conditioned.  Each frame
+/// produces the estimated taps, which can be used to initialize an equalizer
+/// or as the channel input of an `MrcCombineNode`.  Frames shorter than the
+/// training sequence are dropped without producing an estimate.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::channel_estimate::ChannelEstimateNode;
+/// use num::Complex;
+///
+/// let training: Vec<Complex<f64>> = (0..64)
+///     .map(|n| Complex::new(0.0, 0.05 * (n * n) as f64).exp())
+///     .collect();
+/// let node = ChannelEstimateNode::new(training, 5);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct ChannelEstimateNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    training: Vec<Complex<f64>>,
+    n_taps: usize,
+    lu: Vec<Vec<Complex<f64>>>,
+    perm: Vec<usize>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl ChannelEstimateNode {
+    /// Constructs a new `ChannelEstimateNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `training` - The known training sequence at the start of each
+    ///   frame.
+    /// * `n_taps` - Number of channel taps to estimate, no more than half of
+    ///   the length of the training sequence.
+    ///
+    /// Panics if the training sequence doesn't determine the taps, as a
+    /// constant sequence doesn't for example.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::channel_estimate::ChannelEstimateNode;
+    /// use comms_rs::util::math::zadoff_chu;
+    ///
+    /// let node = ChannelEstimateNode::new(zadoff_chu(25, 63).unwrap(), 8);
+    /// ```
+    pub fn new(
+        training: Vec<Complex<f64>>,
+        n_taps: usize,
+    ) -> ChannelEstimateNode {
+        assert!(n_taps > 0, "number of taps must be nonzero");
+        assert!(
+            2 * n_taps <= training.len(),
+            "training sequence must be at least twice the number of taps"
+        );
+
+        // X^H X for the rows of the convolution matrix that lie entirely
+        // within the training sequence, which is then factored in place.
+        let mut lu: Vec<Vec<Complex<f64>>> = (0..n_taps)
+            .map(|i| {
+                (0..n_taps)
+                    .map(|j| {
+                        (n_taps - 1..training.len())
+                            .map(|n| training[n - i].conj() * training[n - j])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        let perm = lu_factor(&mut lu);
+        assert!(
+            perm.is_some(),
+            "training sequence must determine the channel taps"
+        );
+        ChannelEstimateNode {
+            training,
+            n_taps,
+            lu,
+            perm: perm.unwrap(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ChannelEstimateNode`.  Produces the estimated channel taps,
+    /// or nothing if the frame is shorter than the training sequence.
+    pub fn run(
+        &mut self,
+        frame: &[Complex<f64>],
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        let len = self.training.len();
+        if frame.len() < len {
+            return Ok(None);
+        }
+        let rhs: Vec<Complex<f64>> = (0..self.n_taps)
+            .map(|i| {
+                (self.n_taps - 1..len)
+                    .map(|n| self.training[n - i].conj() * frame[n])
+                    .sum()
+            })
+            .collect();
+        Ok(Some(lu_solve(&self.lu, &self.perm, &rhs)))
+    }
+}
+
+// Factors a square matrix in place into lower and upper triangular parts by
+// Gaussian elimination with partial pivoting, or returns None if it's
+// singular.  The multipliers of the lower part are stored below the
+// diagonal, and the returned permutation gives the original row of each row
+// of the factors.
+fn lu_factor(a: &mut [Vec<Complex<f64>>]) -> Option<Vec<usize>> {
+    let n = a.len();
+    let mut perm: Vec<usize> = (0..n).collect();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].norm().total_cmp(&a[j][col].norm()))?;
+        if a[pivot][col].norm() < f64::EPSILON {
+            return None;
+        }
+        a.swap(col, pivot);
+        perm.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let (upper, lower) = a.split_at_mut(row);
+            for (x, p) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *x -= p * factor;
+            }
+            a[row][col] = factor;
+        }
+    }
+    Some(perm)
+}
+
+// Solves a square system of linear equations from the factors given by
+// lu_factor.
+fn lu_solve(
+    lu: &[Vec<Complex<f64>>],
+    perm: &[usize],
+    rhs: &[Complex<f64>],
+) -> Vec<Complex<f64>> {
+    let n = rhs.len();
+    let mut y: Vec<Complex<f64>> = Vec::with_capacity(n);
+    for row in 0..n {
+        let sum: Complex<f64> = (0..row).map(|k| lu[row][k] * y[k]).sum();
+        y.push(rhs[perm[row]] - sum);
+    }
+    let mut x = vec![Complex::zero(); n];
+    for row in (0..n).rev() {
+        let sum: Complex<f64> = (row + 1..n).map(|k| lu[row][k] * x[k]).sum();
+        x[row] = (y[row] - sum) / lu[row][row];
+    }
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::channel_estimate::*;
+    use crate::filter::fir::batch_fir;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Sends a random QPSK training sequence through a known two tap channel,
+    // with data after it, and checks the estimated taps with and without
+    // noise.
+    fn test_channel_estimate() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut qpsk = |len: usize| -> Vec<Complex<f64>> {
+            (0..len)
+                .map(|_| {
+                    let re = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                    let im = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                    Complex::new(re, im) * 0.5_f64.sqrt()
+                })
+                .collect()
+        };
+        let training = qpsk(128);
+        let mut frame_tx = training.clone();
+        frame_tx.extend(qpsk(200));
+
+        let channel = vec![Complex::new(0.9, 0.1), Complex::new(0.4, -0.3)];
+        let mut state = vec![Complex::zero(); channel.len()];
+        let received = batch_fir(&frame_tx, &channel, &mut state);
+
+        let mut node = ChannelEstimateNode::new(training, 4);
+        let taps = node.run(&received).unwrap().unwrap();
+        assert_eq!(taps.len(), 4);
+        for (k, t) in taps.iter().enumerate() {
+            let expected = channel.get(k).cloned().unwrap_or_else(Zero::zero);
+            assert!((t - expected).norm() < 1e-9);
+        }
+
+        // Noise 20 dB down still gives a close estimate.
+        let dist = Normal::new(0.0, 0.05_f64.sqrt() / 10.0_f64.sqrt());
+        let noisy: Vec<Complex<f64>> = received
+            .iter()
+            .map(|y| y + Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect();
+        let taps = node.run(&noisy).unwrap().unwrap();
+        for (k, t) in taps.iter().enumerate() {
+            let expected = channel.get(k).cloned().unwrap_or_else(Zero::zero);
+            assert!((t - expected).norm() < 0.05);
+        }
+
+        // A short frame is dropped, and the next one is still estimated.
+        assert_eq!(node.run(&received[..127]).unwrap(), None);
+        assert_eq!(node.run(&received).unwrap().unwrap().len(), 4);
+    }
+}
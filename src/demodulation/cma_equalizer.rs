@@ -0,0 +1,166 @@
+//! Blind equalization with the constant modulus algorithm.
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+use std::collections::VecDeque;
+
+/// A node that equalizes a signal using the constant modulus algorithm.
+///
+/// The constant modulus algorithm (CMA) adapts a linear FIR equalizer to
+/// drive the magnitude of its output toward a constant, without needing a
+/// training sequence.  It's a natural fit for PSK signals, where every
+/// symbol has the same magnitude, and still works reasonably well for QAM.
+/// For each output sample `y` the taps `w` are updated as
+///
+/// `w -= mu * y * (|y|^2 - R) * conj(x)`
+///
+/// where `x` is the input history in the equalizer and `R` is the target
+/// modulus squared.  The taps start out as a single spike in the center of
+/// the filter.
+///
+/// The input should be one sample per symbol, and already matched filtered
+/// and timing corrected.  CMA is insensitive to carrier phase, so the output
+/// is left with whatever phase rotation the input had.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::cma_equalizer::CmaEqualizerNode;
+///
+/// // An 11 tap equalizer for unit magnitude PSK symbols.
+/// let node = CmaEqualizerNode::new(11, 1e-3, 1.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct CmaEqualizerNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    taps: Vec<Complex<f64>>,
+    step: f64,
+    modulus: f64,
+    history: VecDeque<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl CmaEqualizerNode {
+    /// Constructs a new `CmaEqualizerNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_taps` - Number of taps in the equalizer.
+    /// * `step` - Adaptation step size.  Larger steps converge faster but
+    ///   leave more residual noise in the taps.
+    /// * `modulus` - Target magnitude of the output symbols.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::cma_equalizer::CmaEqualizerNode;
+    ///
+    /// let node = CmaEqualizerNode::new(7, 5e-4, 2.0_f64.sqrt());
+    /// ```
+    pub fn new(n_taps: usize, step: f64, modulus: f64) -> CmaEqualizerNode {
+        assert!(n_taps > 0, "number of taps must be nonzero");
+        let mut taps = vec![Complex::zero(); n_taps];
+        taps[n_taps / 2] = Complex::new(1.0, 0.0);
+        CmaEqualizerNode {
+            taps,
+            step,
+            modulus: modulus * modulus,
+            history: vec![Complex::zero(); n_taps].into(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the current equalizer taps.
+    pub fn taps(&self) -> &[Complex<f64>] {
+        &self.taps
+    }
+
+    /// Equalizes a single sample and adapts the taps.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - Next input sample.
+    pub fn equalize(&mut self, sample: Complex<f64>) -> Complex<f64> {
+        self.history.pop_back();
+        self.history.push_front(sample);
+        let y: Complex<f64> = self
+            .taps
+            .iter()
+            .zip(self.history.iter())
+            .map(|(w, x)| w * x)
+            .sum();
+        let err = y * (y.norm_sqr() - self.modulus);
+        for (w, x) in self.taps.iter_mut().zip(self.history.iter()) {
+            *w -= err * x.conj() * self.step;
+        }
+        y
+    }
+
+    /// Runs the `CmaEqualizerNode`.  Produces the equalized batch of
+    /// samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(samples.iter().map(|x| self.equalize(*x)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::cma_equalizer::*;
+    use crate::util::channel_node::MultipathChannelNode;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    // Mean squared deviation of the magnitude squared from one.
+    fn dispersion(samples: &[Complex<f64>]) -> f64 {
+        samples
+            .iter()
+            .map(|x| (x.norm_sqr() - 1.0).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64
+    }
+
+    #[test]
+    // Passes 8-PSK through a multipath channel and checks that the CMA
+    // equalizer pulls the constellation back toward the unit circle.
+    fn test_cma_equalizer() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.01);
+        let symbols: Vec<Complex<f64>> = (0..20000)
+            .map(|_| {
+                let k = rng.gen_range(0, 8) as f64;
+                Complex::new(0.0, 2.0 * PI * k / 8.0).exp()
+            })
+            .collect();
+        let paths = vec![
+            (0, Complex::new(1.0, 0.0)),
+            (1, Complex::new(0.35, 0.2)),
+            (2, Complex::new(-0.1, 0.15)),
+        ];
+        let mut channel = MultipathChannelNode::new(paths, None);
+        let received: Vec<Complex<f64>> = channel
+            .run(&symbols)
+            .unwrap()
+            .iter()
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+
+        let mut node = CmaEqualizerNode::new(11, 2e-3, 1.0);
+        let mut output = vec![];
+        for chunk in received.chunks(500) {
+            output.extend(node.run(chunk).unwrap());
+        }
+
+        let before = dispersion(&received[..2000]);
+        let start = dispersion(&output[..500]);
+        let end = dispersion(&output[18000..]);
+        assert!(end < before / 20.0);
+        assert!(end < start / 10.0);
+    }
+}
@@ -0,0 +1,247 @@
+//! Carrier frequency offset correction for OFDM from the cyclic prefix.
+use crate::prelude::*;
+
+use num::Complex;
+use std::f64::consts::PI;
+
+/// Estimates the fractional carrier frequency offset of an OFDM symbol.
+///
+/// The cyclic prefix is a copy of the last `cp_len` samples of the symbol,
+/// so with a frequency offset of `f` cycles per sample each sample of the
+/// prefix and its copy `fft_size` samples later differ in phase by
+/// `2 * pi * f * fft_size`.  The phase of their correlation gives the
+/// offset, which is unambiguous up to half a subcarrier spacing,
+/// `1 / (2 * fft_size)` cycles per sample, in either direction.
+///
+/// # Arguments
+///
+/// * `symbol` - Samples of a single OFDM symbol, starting with the cyclic
+///   prefix.
+/// * `fft_size` - Number of samples in the symbol after the prefix.
+/// * `cp_len` - Number of samples in the cyclic prefix.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::cp_cfo::cp_cfo_estimate;
+/// use num::Complex;
+///
+/// let symbol = vec![Complex::new(1.0, 0.0); 80];
+/// assert_eq!(cp_cfo_estimate(&symbol, 64, 16), 0.0);
+/// ```
+pub fn cp_cfo_estimate(
+    symbol: &[Complex<f64>],
+    fft_size: usize,
+    cp_len: usize,
+) -> f64 {
+    let corr: Complex<f64> = symbol[..cp_len]
+        .iter()
+        .zip(&symbol[fft_size..fft_size + cp_len])
+        .map(|(x, y)| x.conj() * y)
+        .sum();
+    corr.arg() / (2.0 * PI * fft_size as f64)
+}
+
+/// A node that corrects the carrier frequency offset of an OFDM signal.
+///
+/// The input is a stream of OFDM symbols, each a cyclic prefix of `cp_len`
+/// samples followed by `fft_size` samples, which must already be aligned so
+/// that the stream starts on a symbol boundary.  The fractional frequency
+/// offset of each symbol is estimated with `cp_cfo_estimate`, and the symbol
+/// is derotated by it.  The derotation phase is carried continuously from one
+/// symbol to the next, so the output has no phase jumps between symbols.
+///
+/// Removing the frequency offset restores the orthogonality of the
+/// subcarriers, which would otherwise leak into each other after the FFT.
+/// Only the fractional part of the offset, within half a subcarrier spacing,
+/// can be seen from the cyclic prefix; any integer number of subcarriers of
+/// offset has to be found after the FFT.
+///
+/// The output is the corrected symbols, prefixes included.  The input may be
+/// batched arbitrarily; each run emits every symbol the batch completes.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::cp_cfo::CpCfoNode;
+///
+/// // 802.11a/g style symbols.
+/// let node = CpCfoNode::new(64, 16);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct CpCfoNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    fft_size: usize,
+    cp_len: usize,
+    buffer: Vec<Complex<f64>>,
+    estimate: f64,
+    phase: f64,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl CpCfoNode {
+    /// Constructs a new `CpCfoNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_size` - Number of samples in each symbol after the prefix.
+    /// * `cp_len` - Number of samples in the cyclic prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::cp_cfo::CpCfoNode;
+    ///
+    /// let node = CpCfoNode::new(2048, 144);
+    /// ```
+    pub fn new(fft_size: usize, cp_len: usize) -> CpCfoNode {
+        assert!(fft_size > 0, "FFT size must be nonzero");
+        assert!(
+            cp_len > 0 && cp_len <= fft_size,
+            "cyclic prefix length must be on the interval [1, fft_size]"
+        );
+        CpCfoNode {
+            fft_size,
+            cp_len,
+            buffer: vec![],
+            estimate: 0.0,
+            phase: 0.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the frequency offset estimate of the last symbol in cycles
+    /// per sample.
+    pub fn estimate(&self) -> f64 {
+        self.estimate
+    }
+
+    /// Runs the `CpCfoNode`.  Produces the corrected symbols completed by the
+    /// batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        self.buffer.extend_from_slice(samples);
+        let sym_len = self.fft_size + self.cp_len;
+        let n_syms = self.buffer.len() / sym_len;
+        if n_syms == 0 {
+            return Ok(None);
+        }
+
+        let mut output = Vec::with_capacity(n_syms * sym_len);
+        for symbol in self.buffer.chunks_exact(sym_len) {
+            self.estimate = cp_cfo_estimate(symbol, self.fft_size, self.cp_len);
+            let dphase = -2.0 * PI * self.estimate;
+            for x in symbol {
+                output.push(x * Complex::new(0.0, self.phase).exp());
+                self.phase += dphase;
+            }
+            self.phase %= 2.0 * PI;
+        }
+        self.buffer.drain(..n_syms * sym_len);
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::cp_cfo::*;
+    use crate::fft::BatchFFT;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use rustfft::FFTplanner;
+
+    // Removes the prefix from each symbol and takes the FFT.
+    fn demodulate(
+        signal: &[Complex<f64>],
+        fft_size: usize,
+        cp_len: usize,
+    ) -> Vec<Vec<Complex<f64>>> {
+        let mut planner = FFTplanner::new(false);
+        let mut fft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        signal
+            .chunks_exact(fft_size + cp_len)
+            .map(|sym| fft.run_fft(&sym[cp_len..]))
+            .collect()
+    }
+
+    // Error vector magnitude in dB of each symbol after removing its common
+    // phase rotation, which is left alone by the correction.
+    fn evm(rx: &[Complex<f64>], tx: &[Complex<f64>]) -> f64 {
+        let rotation: Complex<f64> =
+            rx.iter().zip(tx).map(|(y, x)| y * x.conj()).sum();
+        let rotation = rotation / rotation.norm();
+        let err: f64 = rx
+            .iter()
+            .zip(tx)
+            .map(|(y, x)| (y - x * rotation).norm_sqr())
+            .sum();
+        let power: f64 = tx.iter().map(|x| x.norm_sqr()).sum();
+        10.0 * (err / power).log10()
+    }
+
+    #[test]
+    // Builds QPSK OFDM symbols, applies a fractional frequency offset, and
+    // checks the estimate and that the subcarriers come back clean.
+    fn test_cp_cfo() {
+        let fft_size = 64;
+        let cp_len = 16;
+        let n_syms = 20;
+        let cfo = 0.3 / fft_size as f64;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.001);
+
+        let mut planner = FFTplanner::new(true);
+        let mut ifft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        let mut tx_data = vec![];
+        let mut signal = vec![];
+        for _ in 0..n_syms {
+            let data: Vec<Complex<f64>> = (0..fft_size)
+                .map(|_| {
+                    Complex::new(
+                        if rng.gen() { 1.0 } else { -1.0 },
+                        if rng.gen() { 1.0 } else { -1.0 },
+                    )
+                })
+                .collect();
+            let time: Vec<Complex<f64>> = ifft
+                .run_fft(&data)
+                .iter()
+                .map(|x| x / fft_size as f64)
+                .collect();
+            signal.extend_from_slice(&time[fft_size - cp_len..]);
+            signal.extend(time);
+            tx_data.push(data);
+        }
+        let received: Vec<Complex<f64>> = signal
+            .iter()
+            .enumerate()
+            .map(|(n, x)| {
+                let rot = Complex::new(0.0, 2.0 * PI * cfo * n as f64).exp();
+                x * rot + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect();
+
+        let mut node = CpCfoNode::new(fft_size, cp_len);
+        let mut corrected = vec![];
+        for chunk in received.chunks(100) {
+            if let Some(syms) = node.run(chunk).unwrap() {
+                corrected.extend(syms);
+            }
+        }
+        assert_eq!(corrected.len(), received.len());
+        assert!((node.estimate() - cfo).abs() < 0.01 * cfo);
+
+        let before = demodulate(&received, fft_size, cp_len);
+        let after = demodulate(&corrected, fft_size, cp_len);
+        for ((b, a), tx) in before.iter().zip(&after).zip(&tx_data) {
+            assert!(evm(b, tx) > -20.0);
+            assert!(evm(a, tx) < -35.0);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Cross-correlation of two live sample streams.
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+
+/// A node that measures the cross-correlation between two sample streams.
+///
+/// The node takes in a reference stream and a received stream, buffers both,
+/// and for each window of `window_len` samples computes
+///
+/// `r[l] = sum_n received[n + l] * conj(reference[n])`
+///
+/// for every lag `l` in `-max_lag..=max_lag`.  If the received stream is a
+/// copy of the reference delayed by `d` samples, the magnitude of `r` peaks at
+/// lag `d`.  This is useful for channel sounding or measuring the delay
+/// through a system, and unlike a preamble correlator both inputs are live
+/// streams.
+///
+/// Each window produces `2 * max_lag + 1` values ordered from lag `-max_lag`
+/// to lag `max_lag`.  The windows don't overlap.  If a single set of inputs
+/// completes more than one window, their results are concatenated.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::cross_corr::CrossCorrNode;
+///
+/// // Search +/- 64 samples of delay over windows of 4096 samples.
+/// let node = CrossCorrNode::new(64, 4096);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct CrossCorrNode {
+    pub reference: NodeReceiver<Vec<Complex<f64>>>,
+    pub received: NodeReceiver<Vec<Complex<f64>>>,
+    max_lag: usize,
+    window_len: usize,
+    ref_buf: Vec<Complex<f64>>,
+    rx_buf: Vec<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl CrossCorrNode {
+    /// Constructs a new `CrossCorrNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_lag` - Largest lag in samples, in either direction, to compute
+    ///   the correlation for.
+    /// * `window_len` - Number of reference samples in each correlation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::cross_corr::CrossCorrNode;
+    ///
+    /// let node = CrossCorrNode::new(10, 1000);
+    /// ```
+    pub fn new(max_lag: usize, window_len: usize) -> CrossCorrNode {
+        assert!(window_len > 0, "window length must be nonzero");
+        // Pad the front of the buffers so that the first window can look
+        // back by max_lag samples.
+        CrossCorrNode {
+            max_lag,
+            window_len,
+            ref_buf: vec![Complex::zero(); max_lag],
+            rx_buf: vec![Complex::zero(); max_lag],
+            reference: Default::default(),
+            received: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Computes the cross-correlation over the first window of the buffers.
+    fn correlate(&self) -> Vec<Complex<f64>> {
+        let reference =
+            &self.ref_buf[self.max_lag..self.max_lag + self.window_len];
+        (0..=2 * self.max_lag)
+            .map(|offset| {
+                self.rx_buf[offset..offset + self.window_len]
+                    .iter()
+                    .zip(reference)
+                    .map(|(y, x)| y * x.conj())
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Runs the `CrossCorrNode`.  Produces the correlation for each window
+    /// that both streams have supplied enough samples to complete.
+    pub fn run(
+        &mut self,
+        reference: &[Complex<f64>],
+        received: &[Complex<f64>],
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        self.ref_buf.extend_from_slice(reference);
+        self.rx_buf.extend_from_slice(received);
+
+        let needed = self.window_len + 2 * self.max_lag;
+        let mut output = vec![];
+        while self.ref_buf.len() >= needed && self.rx_buf.len() >= needed {
+            output.extend(self.correlate());
+            self.ref_buf.drain(..self.window_len);
+            self.rx_buf.drain(..self.window_len);
+        }
+        if output.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(output))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::cross_corr::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Feeds in noise and a delayed, rotated copy of it and checks that the
+    // correlation peaks at the delay in every window.
+    fn test_cross_corr() {
+        let delay = 13;
+        let max_lag = 20;
+        let window_len = 500;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 1.0);
+        let reference: Vec<Complex<f64>> = (0..5000)
+            .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect();
+        let rotation = Complex::from_polar(0.5, 1.0);
+        let received: Vec<Complex<f64>> = vec![Complex::zero(); delay]
+            .into_iter()
+            .chain(reference.iter().map(|x| x * rotation))
+            .take(reference.len())
+            .collect();
+
+        let mut node = CrossCorrNode::new(max_lag, window_len);
+        let mut output = vec![];
+        for (x, y) in reference.chunks(300).zip(received.chunks(300)) {
+            if let Some(corr) = node.run(x, y).unwrap() {
+                output.extend(corr);
+            }
+        }
+
+        let n_lags = 2 * max_lag + 1;
+        assert_eq!(output.len() % n_lags, 0);
+        assert!(output.len() / n_lags >= 8);
+        for corr in output.chunks(n_lags) {
+            let peak = corr
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.norm().partial_cmp(&b.1.norm()).unwrap())
+                .unwrap()
+                .0;
+            assert_eq!(peak, max_lag + delay);
+            let phase = corr[peak].arg();
+            assert!((phase - 1.0).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Estimation of the cyclic autocorrelation of a signal.
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// A node that estimates the cyclic autocorrelation function of a signal.
+///
+/// The cyclic autocorrelation at cyclic frequency `alpha` and lag `tau` is
+///
+/// `R(alpha, tau) = mean(x[n] * conj(x[n - tau]) * exp(-j 2 pi alpha n))`
+///
+/// which is the Fourier coefficient at `alpha` of the lag product
+/// `x[n] * conj(x[n - tau])`.  For a stationary signal it's zero at every
+/// cyclic frequency except zero, where it's the ordinary autocorrelation.
+/// Modulated signals are cyclostationary instead, with features at cyclic
+/// frequencies set by their symbol rate, and which features appear, and at
+/// which lags, depends on the modulation.  This makes the cyclic
+/// autocorrelation a robust basis for blind symbol rate estimation and
+/// modulation recognition, since noise, being stationary, contributes
+/// nothing to it away from zero.
+///
+/// The function is evaluated on the given cyclic frequencies and lags, and
+/// the estimate is averaged over every sample seen so far, so the features
+/// stand out further from the estimation noise the longer the node runs.
+/// Each batch produces the current estimate, indexed first by cyclic
+/// frequency and then by lag in the order given.  Cyclic frequencies are in
+/// cycles per sample, or in Hz if a sample rate is given.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::cyclic_autocorr::CyclicAutocorrNode;
+///
+/// // Look for symbol rates of 100 and 125 kHz at lags of up to 2 samples.
+/// let node = CyclicAutocorrNode::new(vec![100e3, 125e3], vec![0, 1, 2])
+///     .with_sample_rate(1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct CyclicAutocorrNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    alphas: Vec<f64>,
+    lags: Vec<usize>,
+    sample_rate: Option<f64>,
+    max_lag: usize,
+    history: VecDeque<Complex<f64>>,
+    sums: Vec<Vec<Complex<f64>>>,
+    counts: Vec<u64>,
+    n: u64,
+    pub output: NodeSender<Vec<Vec<Complex<f64>>>>,
+}
+
+impl CyclicAutocorrNode {
+    /// Constructs a new `CyclicAutocorrNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `alphas` - Cyclic frequencies to evaluate at.
+    /// * `lags` - Lags in samples to evaluate at.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::cyclic_autocorr::CyclicAutocorrNode;
+    ///
+    /// // The symbol rate features of a signal at 4 samples per symbol.
+    /// let node = CyclicAutocorrNode::new(vec![0.25, 0.5], vec![0, 2]);
+    /// ```
+    pub fn new(alphas: Vec<f64>, lags: Vec<usize>) -> CyclicAutocorrNode {
+        assert!(!alphas.is_empty(), "there must be a cyclic frequency");
+        assert!(!lags.is_empty(), "there must be a lag");
+        let max_lag = *lags.iter().max().unwrap();
+        CyclicAutocorrNode {
+            sums: vec![vec![Complex::zero(); lags.len()]; alphas.len()],
+            counts: vec![0; lags.len()],
+            alphas,
+            lags,
+            sample_rate: None,
+            max_lag,
+            history: VecDeque::with_capacity(max_lag),
+            n: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the input in Hz, so that the cyclic
+    /// frequencies are in Hz.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Returns the current estimate, indexed by cyclic frequency and then by
+    /// lag.
+    pub fn estimate(&self) -> Vec<Vec<Complex<f64>>> {
+        self.sums
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&self.counts)
+                    .map(|(s, &c)| if c > 0 { s / c as f64 } else { *s })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Runs the `CyclicAutocorrNode`.  Produces the estimate from every
+    /// sample seen so far.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Vec<Complex<f64>>>, NodeError> {
+        let scale = 1.0 / self.sample_rate.unwrap_or(1.0);
+        for &x in samples {
+            let rotations: Vec<Complex<f64>> = self
+                .alphas
+                .iter()
+                .map(|a| {
+                    let cycles = (a * scale * self.n as f64).fract();
+                    Complex::from_polar(1.0, -2.0 * PI * cycles)
+                })
+                .collect();
+            for (j, &lag) in self.lags.iter().enumerate() {
+                let product = if lag == 0 {
+                    Complex::new(x.norm_sqr(), 0.0)
+                } else if let Some(past) = self.history.get(lag - 1) {
+                    x * past.conj()
+                } else {
+                    continue;
+                };
+                self.counts[j] += 1;
+                for (row, r) in self.sums.iter_mut().zip(&rotations) {
+                    row[j] += product * r;
+                }
+            }
+            if self.max_lag > 0 {
+                if self.history.len() == self.max_lag {
+                    self.history.pop_back();
+                }
+                self.history.push_front(x);
+            }
+            self.n += 1;
+        }
+        Ok(self.estimate())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::cyclic_autocorr::*;
+    use crate::filter::fir_node::BatchFirNode;
+    use crate::util::math::rrc_taps;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Scans the cyclic frequencies of a root raised cosine shaped QPSK
+    // signal in noise and checks that the strongest feature is at the
+    // symbol rate, well clear of the rest.
+    fn test_cyclic_autocorr_symbol_rate() {
+        let sam_per_sym = 4;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.1);
+
+        let mut upsampled = vec![];
+        for _ in 0..5000 {
+            upsampled.push(Complex::new(
+                if rng.gen() { 1.0 } else { -1.0 },
+                if rng.gen() { 1.0 } else { -1.0 },
+            ));
+            upsampled.extend(vec![Complex::new(0.0, 0.0); sam_per_sym - 1]);
+        }
+        let taps = rrc_taps(41, sam_per_sym as f64, 0.5).unwrap();
+        let mut fir = BatchFirNode::new(taps, None);
+        let signal: Vec<Complex<f64>> = fir
+            .run(&upsampled)
+            .unwrap()
+            .iter()
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+
+        let alphas: Vec<f64> = (1..50).map(|k| k as f64 * 0.01).collect();
+        let mut node = CyclicAutocorrNode::new(alphas.clone(), vec![0, 1, 2]);
+        let mut caf = vec![];
+        for chunk in signal.chunks(1000) {
+            caf = node.run(chunk).unwrap();
+        }
+
+        // Strength of the features at each cyclic frequency, over all lags.
+        let strength: Vec<f64> = caf
+            .iter()
+            .map(|row| row.iter().map(|r| r.norm()).fold(0.0, f64::max))
+            .collect();
+        let (peak, &peak_strength) = strength
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap();
+        assert!((alphas[peak] - 0.25).abs() < 1e-9);
+        let mut others: Vec<f64> = strength.clone();
+        others.remove(peak);
+        let highest_other = others.iter().cloned().fold(0.0, f64::max);
+        assert!(peak_strength > 5.0 * highest_other);
+    }
+}
@@ -0,0 +1,259 @@
+//! Decision feedback equalization adapted with LMS.
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+use std::collections::VecDeque;
+
+/// A node that equalizes a signal with a decision feedback equalizer.
+///
+/// A decision feedback equalizer (DFE) pairs a linear feedforward filter on
+/// the received samples with a feedback filter on the symbols already
+/// decided.  The feedforward filter only has to deal with the intersymbol
+/// interference from symbols that haven't been decided yet, while the
+/// interference that earlier symbols leave on the current one is subtracted
+/// out by the feedback filter.  Since the feedback works on clean decisions
+/// rather than on noisy samples, a DFE can cancel the interference of a
+/// channel with a deep spectral null without the noise enhancement that a
+/// linear equalizer suffers trying to invert the null.
+///
+/// Both filters are adapted with the least mean squares (LMS) algorithm.
+/// For each output `y` with decision `d`, the nearest point of the
+/// constellation, the taps are updated as
+///
+/// `wf += mu * (d - y) * conj(x)`
+///
+/// `wb += mu * (d - y) * conj(b)`
+///
+/// where `x` is the input history in the feedforward filter and `b` is the
+/// history of past decisions.  The feedforward taps start out as a single
+/// spike in the center of the filter and the feedback taps at zero, so the
+/// output lags the input by `n_forward / 2` symbols.
+///
+/// Adapting on its own decisions only works once the eye is at least partly
+/// open, so for a severe channel the equalizer should first be trained on a
+/// known preamble, given with `with_training`.  The training symbols are
+/// used in place of the decisions for the first outputs once the lag has
+/// passed.  The input should be one sample per symbol, matched filtered and
+/// timing corrected, and the output is the equalized soft symbols.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::dfe::DfeNode;
+/// use comms_rs::modulation::digital::psk_table;
+///
+/// // A QPSK equalizer with 7 feedforward and 4 feedback taps.
+/// let node = DfeNode::new(7, 4, 5e-3, psk_table(2));
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DfeNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    forward: Vec<Complex<f64>>,
+    feedback: Vec<Complex<f64>>,
+    step: f64,
+    table: Vec<Complex<f64>>,
+    training: Vec<Complex<f64>>,
+    history: VecDeque<Complex<f64>>,
+    decisions: VecDeque<Complex<f64>>,
+    count: usize,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl DfeNode {
+    /// Constructs a new `DfeNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_forward` - Number of taps in the feedforward filter.
+    /// * `n_feedback` - Number of taps in the feedback filter.  Zero gives a
+    ///   linear equalizer.
+    /// * `step` - Adaptation step size.  Larger steps converge faster but
+    ///   leave more residual noise in the taps.
+    /// * `table` - Constellation points to make decisions against.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::dfe::DfeNode;
+    /// use num::Complex;
+    ///
+    /// let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+    /// let node = DfeNode::new(11, 3, 1e-3, bpsk);
+    /// ```
+    pub fn new(
+        n_forward: usize,
+        n_feedback: usize,
+        step: f64,
+        table: Vec<Complex<f64>>,
+    ) -> DfeNode {
+        assert!(n_forward > 0, "number of feedforward taps must be nonzero");
+        assert!(!table.is_empty(), "constellation must not be empty");
+        let mut forward = vec![Complex::zero(); n_forward];
+        forward[n_forward / 2] = Complex::new(1.0, 0.0);
+        DfeNode {
+            forward,
+            feedback: vec![Complex::zero(); n_feedback],
+            step,
+            table,
+            training: vec![],
+            history: vec![Complex::zero(); n_forward].into(),
+            decisions: vec![Complex::zero(); n_feedback].into(),
+            count: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets a preamble of known symbols to train the equalizer on before it
+    /// switches over to its own decisions.
+    pub fn with_training(mut self, training: Vec<Complex<f64>>) -> Self {
+        self.training = training;
+        self
+    }
+
+    /// Returns the number of symbols the output lags the input by.
+    pub fn delay(&self) -> usize {
+        self.forward.len() / 2
+    }
+
+    /// Returns the current feedforward taps.
+    pub fn forward_taps(&self) -> &[Complex<f64>] {
+        &self.forward
+    }
+
+    /// Returns the current feedback taps.
+    pub fn feedback_taps(&self) -> &[Complex<f64>] {
+        &self.feedback
+    }
+
+    // Nearest point of the constellation to a sample.
+    fn decide(&self, y: Complex<f64>) -> Complex<f64> {
+        *self
+            .table
+            .iter()
+            .min_by(|a, b| {
+                (y - *a)
+                    .norm_sqr()
+                    .partial_cmp(&(y - *b).norm_sqr())
+                    .unwrap()
+            })
+            .unwrap()
+    }
+
+    /// Equalizes a single sample and adapts the taps.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - Next input sample.
+    pub fn equalize(&mut self, sample: Complex<f64>) -> Complex<f64> {
+        self.history.pop_back();
+        self.history.push_front(sample);
+        let y: Complex<f64> = self
+            .forward
+            .iter()
+            .zip(self.history.iter())
+            .chain(self.feedback.iter().zip(self.decisions.iter()))
+            .map(|(w, x)| w * x)
+            .sum();
+
+        // Nothing has reached the center of the feedforward filter yet, so
+        // there's nothing to adapt to.
+        let delay = self.delay();
+        self.count += 1;
+        if self.count <= delay {
+            return y;
+        }
+
+        let decision = match self.training.get(self.count - 1 - delay) {
+            Some(symbol) => *symbol,
+            None => self.decide(y),
+        };
+        let err = (decision - y) * self.step;
+        for (w, x) in self.forward.iter_mut().zip(self.history.iter()) {
+            *w += err * x.conj();
+        }
+        for (w, d) in self.feedback.iter_mut().zip(self.decisions.iter()) {
+            *w += err * d.conj();
+        }
+        if !self.decisions.is_empty() {
+            self.decisions.pop_back();
+            self.decisions.push_front(decision);
+        }
+        y
+    }
+
+    /// Runs the `DfeNode`.  Produces the equalized batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(samples.iter().map(|x| self.equalize(*x)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::dfe::*;
+    use crate::modulation::digital::psk_table;
+    use crate::util::channel_node::MultipathChannelNode;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    // Counts the symbol errors of an equalizer over the second half of the
+    // received samples.
+    fn symbol_errors(
+        mut node: DfeNode,
+        received: &[Complex<f64>],
+        symbols: &[Complex<f64>],
+    ) -> usize {
+        let delay = node.delay();
+        let output = node.run(received).unwrap();
+        (received.len() / 2..received.len())
+            .filter(|&n| node.decide(output[n]) != symbols[n - delay])
+            .count()
+    }
+
+    #[test]
+    // Passes QPSK through a channel with a spectral null and checks that
+    // the DFE makes far fewer symbol errors than a linear equalizer with the
+    // same number of taps.
+    fn test_dfe_spectral_null() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let table = psk_table(2);
+        let symbols: Vec<Complex<f64>> =
+            (0..20000).map(|_| table[rng.gen_range(0, 4)]).collect();
+
+        // This channel has a null at half the symbol rate.  The first sample
+        // is dropped so that the output lines up with the main path.
+        let paths = vec![
+            (0, Complex::new(0.407, 0.0)),
+            (1, Complex::new(0.815, 0.0)),
+            (2, Complex::new(0.407, 0.0)),
+        ];
+        let mut channel = MultipathChannelNode::new(paths, None);
+        let noise = Normal::new(0.0, 0.1);
+        let mut received: Vec<Complex<f64>> = channel
+            .run(&symbols)
+            .unwrap()
+            .iter()
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+        received.remove(0);
+
+        let training = symbols[..500].to_vec();
+        let dfe = DfeNode::new(7, 4, 5e-3, table.clone())
+            .with_training(training.clone());
+        let linear =
+            DfeNode::new(11, 0, 5e-3, table.clone()).with_training(training);
+        let dfe_errors = symbol_errors(dfe, &received, &symbols);
+        let linear_errors = symbol_errors(linear, &received, &symbols);
+        assert!(
+            dfe_errors * 5 < linear_errors,
+            "DFE {} linear {}",
+            dfe_errors,
+            linear_errors
+        );
+    }
+}
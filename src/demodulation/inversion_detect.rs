@@ -0,0 +1,169 @@
+//! Detection and correction of spectral inversion.
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that detects whether a burst is spectrally inverted.
+///
+/// Some receive chains swap I and Q, or mix with the local oscillator above
+/// the signal instead of below it, which mirrors the spectrum and conjugates
+/// the baseband signal.  This node looks for a known preamble in each burst,
+/// correlating against both the preamble and its conjugate, and flags the
+/// burst as inverted when the conjugate matches better.  The preamble has to
+/// look different when conjugated, such as a chirp or a sequence of complex
+/// symbols; a purely real preamble like BPSK can't tell the two apart.
+///
+/// The output is the flag along with the burst.  By default the burst passes
+/// through untouched, and with `with_correction` an inverted burst is
+/// conjugated back to normal.  A burst too short to hold the preamble passes
+/// through untouched with a flag of `None`, since it can't be checked.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::inversion_detect::SpectrumInversionDetectNode;
+/// use num::Complex;
+///
+/// let preamble = vec![Complex::new(1.0, 0.0), Complex::new(0.0, 1.0)];
+/// let node = SpectrumInversionDetectNode::new(preamble).with_correction();
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SpectrumInversionDetectNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    preamble: Vec<Complex<f64>>,
+    correct: bool,
+    pub output: NodeSender<(Option<bool>, Vec<Complex<f64>>)>,
+}
+
+impl SpectrumInversionDetectNode {
+    /// Constructs a new `SpectrumInversionDetectNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `preamble` - The known preamble, as transmitted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::inversion_detect::SpectrumInversionDetectNode;
+    /// use num::Complex;
+    ///
+    /// let preamble: Vec<Complex<f64>> = (0..64)
+    ///     .map(|n| Complex::new(0.0, 0.01 * (n * n) as f64).exp())
+    ///     .collect();
+    /// let node = SpectrumInversionDetectNode::new(preamble);
+    /// ```
+    pub fn new(preamble: Vec<Complex<f64>>) -> SpectrumInversionDetectNode {
+        assert!(!preamble.is_empty(), "preamble must not be empty");
+        SpectrumInversionDetectNode {
+            preamble,
+            correct: false,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Conjugates bursts that are detected as inverted.
+    pub fn with_correction(mut self) -> Self {
+        self.correct = true;
+        self
+    }
+
+    // Largest correlation magnitude of the preamble, or its conjugate,
+    // against the burst.
+    fn peak(&self, burst: &[Complex<f64>], conjugate: bool) -> f64 {
+        burst
+            .windows(self.preamble.len())
+            .map(|window| {
+                self.preamble
+                    .iter()
+                    .zip(window)
+                    .map(|(p, x)| if conjugate { p * x } else { p.conj() * x })
+                    .sum::<Complex<f64>>()
+                    .norm()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Returns true if the burst is inverted, or `None` if it's too short to
+    /// hold the preamble.
+    ///
+    /// # Arguments
+    ///
+    /// * `burst` - Burst of received samples holding the preamble.
+    pub fn is_inverted(&self, burst: &[Complex<f64>]) -> Option<bool> {
+        if burst.len() < self.preamble.len() {
+            return None;
+        }
+        Some(self.peak(burst, true) > self.peak(burst, false))
+    }
+
+    /// Runs the `SpectrumInversionDetectNode`.  Produces the inversion flag
+    /// and the burst, corrected if enabled.  The flag is `None` if the burst
+    /// is shorter than the preamble.
+    pub fn run(
+        &mut self,
+        burst: &[Complex<f64>],
+    ) -> Result<(Option<bool>, Vec<Complex<f64>>), NodeError> {
+        let inverted = self.is_inverted(burst);
+        if inverted == Some(true) && self.correct {
+            Ok((inverted, burst.iter().map(|x| x.conj()).collect()))
+        } else {
+            Ok((inverted, burst.to_vec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::inversion_detect::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Embeds a QPSK preamble in a noisy burst, and checks that the burst is
+    // flagged correctly as sent and when conjugated, and that the inverted
+    // one is corrected.
+    fn test_inversion_detect() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.3);
+        let preamble: Vec<Complex<f64>> = (0..32)
+            .map(|_| {
+                Complex::from_polar(
+                    1.0,
+                    PI / 4.0 * (2 * rng.gen_range(0, 4) + 1) as f64,
+                )
+            })
+            .collect();
+        let mut burst: Vec<Complex<f64>> = (0..300)
+            .map(|_| Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+        let rotation = Complex::from_polar(0.8, 2.0);
+        for (x, p) in burst[100..].iter_mut().zip(&preamble) {
+            *x += p * rotation;
+        }
+        let inverted: Vec<Complex<f64>> =
+            burst.iter().map(|x| x.conj()).collect();
+
+        let mut node = SpectrumInversionDetectNode::new(preamble.clone());
+        assert_eq!(node.run(&burst).unwrap(), (Some(false), burst.clone()));
+        assert_eq!(
+            node.run(&inverted).unwrap(),
+            (Some(true), inverted.clone())
+        );
+
+        let mut node =
+            SpectrumInversionDetectNode::new(preamble).with_correction();
+        assert_eq!(node.run(&burst).unwrap(), (Some(false), burst.clone()));
+        assert_eq!(node.run(&inverted).unwrap(), (Some(true), burst.clone()));
+
+        // A short burst passes through unchecked, and the node carries on
+        // with the next one.
+        let short = inverted[..31].to_vec();
+        assert_eq!(node.run(&short).unwrap(), (None, short.clone()));
+        assert_eq!(node.run(&inverted).unwrap(), (Some(true), burst.clone()));
+    }
+}
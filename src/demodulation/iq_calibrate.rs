@@ -0,0 +1,252 @@
+//! Calibration of I/Q DC offset and imbalance from a loopback tone.
+use crate::prelude::*;
+
+use num::Complex;
+use std::f64::consts::PI;
+
+/// A node that calibrates out the DC offset and I/Q imbalance of a receiver.
+///
+/// The imbalance is modeled with the I branch as the reference and the Q
+/// branch off by a gain `g` and a phase `phi`, plus a DC offset on each:
+///
+/// `I' = I + dc.re`
+/// `Q' = g * (Q * cos(phi) - I * sin(phi)) + dc.im`
+///
+/// which puts an image of every signal at the mirror frequency along with a
+/// spur at DC.  The first samples the node receives must be a known loopback
+/// tone, which makes the imbalance easy to measure: the correlations of the
+/// tone with the expected frequency and its mirror give the strengths of the
+/// signal and its image, whose ratio depends only on `g` and `phi`, and the
+/// DC offset is what's left over on average.  Once the calibration duration
+/// has passed the correction is fixed, and every sample after that has the
+/// DC offset removed and the imbalance inverted.
+///
+/// The samples used for calibration aren't passed on, so the first output is
+/// the corrected batch following them.  The tone should be well away from DC
+/// and the calibration long enough to hold many cycles of it.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::iq_calibrate::CalibrationNode;
+///
+/// // Calibrate on a tone at 1/16 of the sample rate for 8192 samples.
+/// let node = CalibrationNode::new(0.0625, 8192);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct CalibrationNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    tone_freq: f64,
+    duration: usize,
+    captured: Vec<Complex<f64>>,
+    calibrated: bool,
+    dc: Complex<f64>,
+    gain: f64,
+    phase: f64,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl CalibrationNode {
+    /// Constructs a new `CalibrationNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tone_freq` - Frequency of the loopback tone in cycles per sample,
+    ///   on the interval (-0.5, 0.5) and not zero.
+    /// * `duration` - Number of samples of the tone to calibrate on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::iq_calibrate::CalibrationNode;
+    ///
+    /// let node = CalibrationNode::new(-0.1, 4096);
+    /// ```
+    pub fn new(tone_freq: f64, duration: usize) -> CalibrationNode {
+        assert!(
+            tone_freq != 0.0 && tone_freq.abs() < 0.5,
+            "tone frequency must be nonzero and on (-0.5, 0.5)"
+        );
+        assert!(duration > 0, "calibration duration must be nonzero");
+        CalibrationNode {
+            tone_freq,
+            duration,
+            captured: Vec::with_capacity(duration),
+            calibrated: false,
+            dc: Complex::new(0.0, 0.0),
+            gain: 1.0,
+            phase: 0.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns true once the calibration is complete.
+    pub fn is_calibrated(&self) -> bool {
+        self.calibrated
+    }
+
+    /// Returns the estimated DC offset.
+    pub fn dc_offset(&self) -> Complex<f64> {
+        self.dc
+    }
+
+    /// Returns the estimated gain of the Q branch relative to the I branch.
+    pub fn gain_imbalance(&self) -> f64 {
+        self.gain
+    }
+
+    /// Returns the estimated phase error of the Q branch in radians.
+    pub fn phase_imbalance(&self) -> f64 {
+        self.phase
+    }
+
+    // Measures the correction from the captured tone.
+    fn calibrate(&mut self) {
+        let n = self.captured.len() as f64;
+        let tone: Vec<Complex<f64>> = (0..self.captured.len())
+            .map(|k| {
+                Complex::new(0.0, 2.0 * PI * self.tone_freq * k as f64).exp()
+            })
+            .collect();
+
+        // Strength of the tone and of its image, with the DC offset refined
+        // by removing them both from the average.
+        let mut dc: Complex<f64> =
+            self.captured.iter().sum::<Complex<f64>>() / n;
+        let mut signal = Complex::new(0.0, 0.0);
+        let mut image = Complex::new(0.0, 0.0);
+        for _ in 0..2 {
+            signal = self
+                .captured
+                .iter()
+                .zip(&tone)
+                .map(|(y, t)| (y - dc) * t.conj())
+                .sum::<Complex<f64>>()
+                / n;
+            image = self
+                .captured
+                .iter()
+                .zip(&tone)
+                .map(|(y, t)| (y - dc) * t)
+                .sum::<Complex<f64>>()
+                / n;
+            dc = self
+                .captured
+                .iter()
+                .zip(&tone)
+                .map(|(y, t)| y - signal * t - image * t.conj())
+                .sum::<Complex<f64>>()
+                / n;
+        }
+
+        // The received tone is mu * x + nu * conj(x), with
+        // mu = (1 + g * exp(-j * phi)) / 2 and nu = (1 - g * exp(j * phi)) / 2,
+        // so k = nu / conj(mu) = (1 - z) / (1 + z) for z = g * exp(j * phi).
+        let k = image / signal.conj();
+        let z = (Complex::new(1.0, 0.0) - k) / (Complex::new(1.0, 0.0) + k);
+        self.dc = dc;
+        self.gain = z.norm();
+        self.phase = z.arg();
+        self.calibrated = true;
+        self.captured = vec![];
+    }
+
+    // Removes the DC offset and inverts the imbalance of one sample.
+    fn correct(&self, y: Complex<f64>) -> Complex<f64> {
+        let y = y - self.dc;
+        let i = y.re;
+        let q = (y.im / self.gain + i * self.phase.sin()) / self.phase.cos();
+        Complex::new(i, q)
+    }
+
+    /// Runs the `CalibrationNode`.  Produces the corrected samples once the
+    /// calibration is complete.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        let mut samples = samples;
+        if !self.calibrated {
+            let needed = self.duration - self.captured.len();
+            let take = needed.min(samples.len());
+            self.captured.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.captured.len() < self.duration {
+                return Ok(None);
+            }
+            self.calibrate();
+            if samples.is_empty() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(samples.iter().map(|&y| self.correct(y)).collect()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::iq_calibrate::*;
+
+    // Applies DC offset and I/Q imbalance to a signal.
+    fn impair(
+        x: &[Complex<f64>],
+        dc: Complex<f64>,
+        gain: f64,
+        phase: f64,
+    ) -> Vec<Complex<f64>> {
+        x.iter()
+            .map(|x| {
+                let q = gain * (x.im * phase.cos() - x.re * phase.sin());
+                Complex::new(x.re, q) + dc
+            })
+            .collect()
+    }
+
+    fn tone(freq: f64, amp: f64, len: usize) -> Vec<Complex<f64>> {
+        (0..len)
+            .map(|n| Complex::from_polar(amp, 2.0 * PI * freq * n as f64 + 0.3))
+            .collect()
+    }
+
+    // Level in dB of the component of a signal at a frequency.
+    fn level(y: &[Complex<f64>], freq: f64) -> f64 {
+        let c: Complex<f64> = y
+            .iter()
+            .enumerate()
+            .map(|(n, y)| {
+                y * Complex::new(0.0, -2.0 * PI * freq * n as f64).exp()
+            })
+            .sum();
+        20.0 * (c.norm() / y.len() as f64).log10()
+    }
+
+    #[test]
+    // Calibrates on a tone through a receiver with DC offset and imbalance,
+    // then checks the estimates and that the image and DC spurs of a second
+    // tone are suppressed.
+    fn test_iq_calibrate() {
+        let dc = Complex::new(0.05, -0.03);
+        let (gain, phase) = (1.1, 5.0_f64.to_radians());
+        let mut node = CalibrationNode::new(0.05, 4000);
+
+        let cal = impair(&tone(0.05, 0.5, 4000), dc, gain, phase);
+        for chunk in cal.chunks(1500) {
+            assert_eq!(node.run(chunk).unwrap(), None);
+        }
+        assert!(node.is_calibrated());
+        assert!((node.dc_offset() - dc).norm() < 1e-6);
+        assert!((node.gain_imbalance() - gain).abs() < 1e-6);
+        assert!((node.phase_imbalance() - phase).abs() < 1e-6);
+
+        let freq = 0.12;
+        let rx = impair(&tone(freq, 0.8, 2000), dc, gain, phase);
+        assert!(level(&rx, -freq) - level(&rx, freq) > -30.0);
+        let out = node.run(&rx).unwrap().unwrap();
+        assert_eq!(out.len(), rx.len());
+        assert!(level(&out, -freq) - level(&out, freq) < -80.0);
+        assert!(level(&out, 0.0) - level(&out, freq) < -80.0);
+    }
+}
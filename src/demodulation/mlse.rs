@@ -0,0 +1,296 @@
+//! Maximum likelihood sequence estimation with the Viterbi algorithm.
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that detects symbols sent over a channel with intersymbol
+/// interference by maximum likelihood sequence estimation (MLSE).
+///
+/// The channel is modeled as an FIR filter of a few taps,
+///
+/// `r[n] = sum_k h[k] * s[n - k] + noise`
+///
+/// so each received sample depends on the current symbol and the `L` before
+/// it, where `L` is one less than the number of taps.  Those `L` symbols make
+/// up the state of a trellis with `M^L` states for a constellation of `M`
+/// points, and the Viterbi algorithm finds the path through it, and with it
+/// the sequence of symbols, whose noiseless channel output is closest to what
+/// was received.  With Gaussian noise that's the most likely sequence sent,
+/// so unlike an equalizer, which has to trade residual interference against
+/// noise enhancement, MLSE makes use of all of the energy the channel spreads
+/// over several samples.  The price is that the work grows exponentially
+/// with the channel memory, so it's only practical for short channels and
+/// small constellations.
+///
+/// Each batch is a block that the trellis is run over from start to end, and
+/// the decisions for the whole block come from tracing back from the best
+/// state at the end of it.  The last symbols of one block are the known
+/// starting state for the next, while the first block starts from any
+/// state.  Decisions near the end of a block can't make use of the samples
+/// that follow, so blocks should be much longer than the channel.
+///
+/// The input should be one sample per symbol, timing corrected so that the
+/// first tap lines up with the current symbol, and the output is the
+/// decided constellation points.  New channel estimates, from a
+/// `ChannelEstimateNode` for example, arrive on the `channel` control input
+/// and apply from the batch they arrive with.  They must have the same
+/// number of taps as the channel the node was built with; estimates of any
+/// other length are ignored and the previous channel kept.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::mlse::MlseNode;
+/// use comms_rs::modulation::digital::psk_table;
+/// use num::Complex;
+///
+/// // QPSK over a three tap channel, for 16 states.
+/// let channel = vec![
+///     Complex::new(1.0, 0.0),
+///     Complex::new(0.5, 0.2),
+///     Complex::new(0.1, -0.1),
+/// ];
+/// let node = MlseNode::new(channel, psk_table(2));
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct MlseNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    pub channel: NodeReceiver<Vec<Complex<f64>>>,
+    table: Vec<Complex<f64>>,
+    n_taps: usize,
+    n_states: usize,
+    expected: Vec<Vec<Complex<f64>>>,
+    state: Option<usize>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl MlseNode {
+    /// Constructs a new `MlseNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Taps of the channel, starting with the one applied to
+    ///   the current symbol.
+    /// * `table` - Constellation points that may have been sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::mlse::MlseNode;
+    /// use num::Complex;
+    ///
+    /// let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+    /// let channel = vec![Complex::new(1.0, 0.0), Complex::new(0.8, 0.0)];
+    /// let node = MlseNode::new(channel, bpsk);
+    /// ```
+    pub fn new(
+        channel: Vec<Complex<f64>>,
+        table: Vec<Complex<f64>>,
+    ) -> MlseNode {
+        assert!(!channel.is_empty(), "channel must have at least one tap");
+        assert!(!table.is_empty(), "constellation must not be empty");
+        let n_states = table.len().pow(channel.len() as u32 - 1);
+        let mut node = MlseNode {
+            table,
+            n_taps: channel.len(),
+            n_states,
+            expected: vec![],
+            state: None,
+            input: Default::default(),
+            channel: Default::default(),
+            output: Default::default(),
+        };
+        node.set_channel(&channel).unwrap();
+        node
+    }
+
+    /// Sets the channel taps used for the following samples.  Gives a
+    /// `NodeError::DataError` if the number of taps has changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Taps of the channel, starting with the one applied to
+    ///   the current symbol.
+    pub fn set_channel(
+        &mut self,
+        channel: &[Complex<f64>],
+    ) -> Result<(), NodeError> {
+        if channel.len() != self.n_taps {
+            return Err(NodeError::DataError);
+        }
+
+        // The noiseless channel output for each state and new symbol.  The
+        // state holds the index of the previous symbol in its lowest base M
+        // digit, the one before that in the next digit, and so on.
+        let m = self.table.len();
+        self.expected = (0..self.n_states)
+            .map(|state| {
+                let past: Complex<f64> = channel[1..]
+                    .iter()
+                    .scan(state, |digits, h| {
+                        let point = self.table[*digits % m];
+                        *digits /= m;
+                        Some(h * point)
+                    })
+                    .sum();
+                self.table.iter().map(|s| channel[0] * s + past).collect()
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Finds the most likely symbols sent for a block of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Block of received samples.
+    pub fn detect(&mut self, samples: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        if samples.is_empty() {
+            return vec![];
+        }
+        let m = self.table.len();
+        let mut metrics = match self.state {
+            Some(state) => {
+                let mut metrics = vec![f64::INFINITY; self.n_states];
+                metrics[state] = 0.0;
+                metrics
+            }
+            None => vec![0.0; self.n_states],
+        };
+
+        // For every sample, the state each state was reached from and the
+        // symbol that was sent to get there.
+        let mut survivors = vec![vec![(0, 0); self.n_states]; samples.len()];
+        for (r, survivor) in samples.iter().zip(survivors.iter_mut()) {
+            let mut next = vec![f64::INFINITY; self.n_states];
+            for (prev, metric) in metrics.iter().enumerate() {
+                if metric.is_infinite() {
+                    continue;
+                }
+                for (symbol, expected) in self.expected[prev].iter().enumerate()
+                {
+                    let state = (prev * m + symbol) % self.n_states;
+                    let candidate = metric + (r - expected).norm_sqr();
+                    if candidate < next[state] {
+                        next[state] = candidate;
+                        survivor[state] = (prev, symbol);
+                    }
+                }
+            }
+            metrics = next;
+        }
+
+        let mut state = metrics
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0;
+        self.state = Some(state);
+        let mut decisions = vec![self.table[0]; samples.len()];
+        for (decision, survivor) in decisions.iter_mut().zip(&survivors).rev() {
+            let (prev, symbol) = survivor[state];
+            *decision = self.table[symbol];
+            state = prev;
+        }
+        decisions
+    }
+
+    /// Runs the `MlseNode`.  Updates the channel if valid new taps have
+    /// arrived, then produces the decided symbols if a batch was received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<f64>>>,
+        channel: Option<Vec<Complex<f64>>>,
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        if let Some(channel) = channel {
+            // Keep the current trellis if the taps don't fit.
+            let _ = self.set_channel(&channel);
+        }
+        Ok(input.map(|samples| self.detect(&samples)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::dfe::DfeNode;
+    use crate::demodulation::mlse::*;
+    use crate::modulation::digital::psk_table;
+    use crate::util::channel_node::MultipathChannelNode;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    // Nearest point of a constellation.
+    fn decide(table: &[Complex<f64>], y: Complex<f64>) -> Complex<f64> {
+        *table
+            .iter()
+            .min_by(|a, b| {
+                (y - *a)
+                    .norm_sqr()
+                    .partial_cmp(&(y - *b).norm_sqr())
+                    .unwrap()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    // Sends QPSK over a two tap channel with a deep null and checks that
+    // MLSE makes far fewer symbol errors than a trained linear equalizer at
+    // the same SNR.
+    fn test_mlse_two_tap() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let table = psk_table(2);
+        let symbols: Vec<Complex<f64>> =
+            (0..20000).map(|_| table[rng.gen_range(0, 4)]).collect();
+        let taps = vec![Complex::new(1.0, 0.0), Complex::new(0.0, 0.9)];
+        let paths = taps.iter().cloned().enumerate().collect();
+        let mut channel = MultipathChannelNode::new(paths, None);
+        let noise = Normal::new(0.0, 0.25);
+        let received: Vec<Complex<f64>> = channel
+            .run(&symbols)
+            .unwrap()
+            .iter()
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+        let half = symbols.len() / 2;
+
+        let mut node = MlseNode::new(taps.clone(), table.clone());
+        let mut decisions = vec![];
+        for block in received.chunks(500) {
+            decisions.extend(node.detect(block));
+        }
+        let mlse_errors = (half..symbols.len())
+            .filter(|&n| decisions[n] != symbols[n])
+            .count();
+
+        let mut linear = DfeNode::new(15, 0, 2e-3, table.clone())
+            .with_training(symbols[..2000].to_vec());
+        let delay = linear.delay();
+        let output = linear.run(&received).unwrap();
+        let linear_errors = (half..symbols.len())
+            .filter(|&n| decide(&table, output[n]) != symbols[n - delay])
+            .count();
+
+        assert!(
+            mlse_errors * 5 < linear_errors,
+            "MLSE {} linear {}",
+            mlse_errors,
+            linear_errors
+        );
+
+        // The channel can be swapped for one of the same length only, and
+        // taps of another length leave the trellis as it was.
+        assert_eq!(node.run(None, Some(taps.clone())).unwrap(), None);
+        assert!(node.set_channel(&[Complex::new(1.0, 0.0)]).is_err());
+        let mut reference = MlseNode::new(taps, table.clone());
+        let block = received[..500].to_vec();
+        let bad = Some(vec![Complex::new(1.0, 0.0)]);
+        assert_eq!(
+            node.run(Some(block.clone()), bad).unwrap().unwrap(),
+            reference.detect(&block)
+        );
+    }
+}
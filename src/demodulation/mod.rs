@@ -1,5 +1,31 @@
 //! Nodes for demodulating signals.
+pub mod adaptive_mcs;
+pub mod afc;
+pub mod allan_dev;
+pub mod burst_segment;
+pub mod channel_estimate;
+pub mod cma_equalizer;
+pub mod cp_cfo;
+pub mod cross_corr;
+pub mod cyclic_autocorr;
+pub mod dfe;
+pub mod farrow_filter;
 pub mod frequency_estimator;
+pub mod inversion_detect;
+pub mod iq_calibrate;
+pub mod mlse;
+pub mod mod_classify;
+pub mod moments;
+pub mod mrc_combine;
 pub mod nco;
+pub mod normalize_power;
+pub mod packet_start;
 pub mod phase_estimator;
+pub mod ranging;
+pub mod sfo_correct;
+pub mod slicer;
+pub mod symbol_downsample;
+pub mod symbol_rate;
+pub mod symbol_sync;
+pub mod timing_correct;
 pub mod timing_estimator;
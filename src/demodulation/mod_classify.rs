@@ -0,0 +1,270 @@
+//! Blind recognition of a symbol constellation from its statistics.
+use crate::prelude::*;
+
+use crate::demodulation::moments::{moments, Moment};
+use num::Complex;
+
+/// The constellations a `ModClassifyNode` can tell apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constellation {
+    Bpsk,
+    Qpsk,
+    Psk8,
+    Qam16,
+}
+
+impl Constellation {
+    /// Returns the normalized fourth order cumulants `(|C40|, C42)` of the
+    /// constellation with equally likely symbols.
+    pub fn cumulants(self) -> (f64, f64) {
+        match self {
+            Constellation::Bpsk => (2.0, -2.0),
+            Constellation::Qpsk => (1.0, -1.0),
+            Constellation::Psk8 => (0.0, -1.0),
+            Constellation::Qam16 => (0.68, -0.68),
+        }
+    }
+}
+
+/// Estimates the normalized fourth order cumulants `(|C40|, C42)` of a set of
+/// symbols, or returns `None` if they're all zero.
+///
+/// The mean of the symbols is removed first, and the cumulants are then
+/// computed with `moments` and normalized by the square of the symbol power,
+/// which makes them independent of the scale of the symbols.  The magnitude
+/// of `C40` is taken so that they're independent of any carrier phase offset
+/// as well.  The fourth order cumulants of Gaussian noise are zero, so noise
+/// only pulls the estimates toward zero through the normalization.
+///
+/// # Arguments
+///
+/// * `symbols` - Symbols to estimate the cumulants of.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::mod_classify::fourth_order_cumulants;
+/// use num::Complex;
+///
+/// let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+/// let (c40, c42) = fourth_order_cumulants(&bpsk).unwrap();
+/// assert!((c40 - 2.0).abs() < 1e-12);
+/// assert!((c42 + 2.0).abs() < 1e-12);
+/// ```
+pub fn fourth_order_cumulants(symbols: &[Complex<f64>]) -> Option<(f64, f64)> {
+    if symbols.is_empty() {
+        return None;
+    }
+    let n = symbols.len() as f64;
+    let mean: Complex<f64> = symbols.iter().sum::<Complex<f64>>() / n;
+    let centred: Vec<Complex<f64>> = symbols.iter().map(|s| s - mean).collect();
+    let stats = moments(&centred, &[Moment::M2, Moment::C40, Moment::C42]);
+    let (m21, c40, c42) = (stats[0].re, stats[1], stats[2].re);
+    if m21 == 0.0 {
+        return None;
+    }
+    Some((c40.norm() / (m21 * m21), c42 / (m21 * m21)))
+}
+
+/// A node that recognizes the constellation of a batch of recovered symbols.
+///
+/// The normalized fourth order cumulants of each batch are estimated with
+/// `fourth_order_cumulants`, and the batch is classified as the candidate
+/// whose theoretical cumulants are closest.  These statistics are a standard
+/// feature for blind modulation recognition, since they differ between the
+/// common constellations while ignoring scale, phase offset and, apart from
+/// a gradual shrinking toward zero, noise.
+///
+/// The symbols should be at one sample per symbol with the timing and
+/// frequency corrected, though a fixed phase offset is fine.  The estimates
+/// need a fair number of symbols to settle, so batches of at least several
+/// hundred symbols are recommended.  If the symbols arrive in smaller
+/// batches, `with_batch_size` gathers them until there are enough.  Each
+/// batch produces its classification, or a `NodeError::DataError` if it
+/// holds no signal.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::mod_classify::{Constellation, ModClassifyNode};
+///
+/// // Tell apart the phase shift keyed constellations only.
+/// let node = ModClassifyNode::new(vec![
+///     Constellation::Bpsk,
+///     Constellation::Qpsk,
+///     Constellation::Psk8,
+/// ]);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct ModClassifyNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    candidates: Vec<Constellation>,
+    batch_size: usize,
+    pending: Vec<Complex<f64>>,
+    pub output: NodeSender<Constellation>,
+}
+
+impl ModClassifyNode {
+    /// Constructs a new `ModClassifyNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - The constellations to choose between.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::mod_classify::{Constellation, ModClassifyNode};
+    ///
+    /// let node =
+    ///     ModClassifyNode::new(vec![Constellation::Qpsk, Constellation::Qam16]);
+    /// ```
+    pub fn new(candidates: Vec<Constellation>) -> ModClassifyNode {
+        assert!(
+            !candidates.is_empty(),
+            "there must be at least one candidate"
+        );
+        ModClassifyNode {
+            candidates,
+            batch_size: 1,
+            pending: vec![],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the fewest symbols classified together.  Incoming symbols are
+    /// gathered until there are at least this many, and then classified as
+    /// one batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_size` - Fewest symbols per classification, nonzero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::mod_classify::{Constellation, ModClassifyNode};
+    ///
+    /// let node = ModClassifyNode::new(vec![Constellation::Qpsk])
+    ///     .with_batch_size(500);
+    /// ```
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be nonzero");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Runs the `ModClassifyNode`.  Produces the most likely constellation
+    /// once enough symbols have been gathered.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<f64>],
+    ) -> Result<Option<Constellation>, NodeError> {
+        self.pending.extend_from_slice(symbols);
+        if self.pending.len() < self.batch_size {
+            return Ok(None);
+        }
+        let batch: Vec<Complex<f64>> = self.pending.drain(..).collect();
+        let (c40, c42) =
+            fourth_order_cumulants(&batch).ok_or(NodeError::DataError)?;
+        let distance = |c: &Constellation| {
+            let (t40, t42) = c.cumulants();
+            (c40 - t40).powi(2) + (c42 - t42).powi(2)
+        };
+        Ok(self
+            .candidates
+            .iter()
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::mod_classify::*;
+    use num::Zero;
+    use rand::distributions::{Normal, Uniform};
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    // Random unit power symbols from a constellation, rotated by a phase
+    // offset, with noise at an SNR in dB.
+    fn symbols(
+        rng: &mut SmallRng,
+        constellation: Constellation,
+        snr: f64,
+        len: usize,
+    ) -> Vec<Complex<f64>> {
+        let points: Vec<Complex<f64>> = match constellation {
+            Constellation::Bpsk => {
+                vec![Complex::new(1.0, 0.0), -Complex::new(1.0, 0.0)]
+            }
+            Constellation::Qpsk => (0..4)
+                .map(|k| {
+                    Complex::new(0.0, PI / 4.0 + PI * k as f64 / 2.0).exp()
+                })
+                .collect(),
+            Constellation::Psk8 => (0..8)
+                .map(|k| Complex::new(0.0, PI * k as f64 / 4.0).exp())
+                .collect(),
+            Constellation::Qam16 => (0..16)
+                .map(|k| {
+                    let level = |i| (2 * i - 3) as f64 / 10.0_f64.sqrt();
+                    Complex::new(level(k % 4), level(k / 4))
+                })
+                .collect(),
+        };
+        let rotation = Complex::new(0.0, rng.gen_range(0.0, 2.0 * PI)).exp();
+        let pick = Uniform::new(0, points.len());
+        let noise = Normal::new(0.0, (0.5 * 10.0_f64.powf(-snr / 10.0)).sqrt());
+        (0..len)
+            .map(|_| {
+                points[rng.sample(pick)] * rotation
+                    + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect()
+    }
+
+    #[test]
+    // Classifies batches of each constellation at an SNR of 15 dB and checks
+    // that nearly all of them are recognized.
+    fn test_mod_classify() {
+        let all = vec![
+            Constellation::Bpsk,
+            Constellation::Qpsk,
+            Constellation::Psk8,
+            Constellation::Qam16,
+        ];
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut node = ModClassifyNode::new(all.clone());
+        for &truth in &all {
+            let correct = (0..50)
+                .filter(|_| {
+                    let batch = symbols(&mut rng, truth, 15.0, 1000);
+                    node.run(&batch).unwrap() == Some(truth)
+                })
+                .count();
+            assert!(correct >= 45, "{:?}: {} of 50", truth, correct);
+        }
+
+        // Only the candidates given are considered.
+        let mut node = ModClassifyNode::new(vec![Constellation::Qpsk]);
+        let batch = symbols(&mut rng, Constellation::Bpsk, 15.0, 1000);
+        assert_eq!(node.run(&batch).unwrap(), Some(Constellation::Qpsk));
+        assert!(node.run(&[Complex::zero(); 10]).is_err());
+
+        // Small batches are gathered up to the batch size before they're
+        // classified.
+        let mut node = ModClassifyNode::new(all).with_batch_size(1000);
+        let batch = symbols(&mut rng, Constellation::Psk8, 15.0, 1200);
+        let results: Vec<Option<Constellation>> = batch
+            .chunks(300)
+            .map(|chunk| node.run(chunk).unwrap())
+            .collect();
+        assert_eq!(results, vec![None, None, None, Some(Constellation::Psk8)]);
+    }
+}
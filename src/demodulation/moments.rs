@@ -0,0 +1,219 @@
+//! Higher order moments and cumulants of a signal.
+use crate::prelude::*;
+use crate::util::math::cast_complex;
+
+use num::{Complex, Float, Zero};
+use std::collections::VecDeque;
+
+/// The statistics a `MomentsNode` can compute.
+///
+/// The signal is taken to be zero mean, so the cumulants follow from the
+/// moments `M_pq = E[x^(p - q) * conj(x)^q]` as below.  The moments are
+/// raw: they're taken about zero without removing the mean of the samples,
+/// and aren't normalized by the signal power.  Centre the samples first if
+/// they may carry a DC offset, as `fourth_order_cumulants` does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Moment {
+    /// The power, `M21 = E[|x|^2]`.
+    M2,
+    /// The fourth order moment `M42 = E[|x|^4]`.
+    M4,
+    /// The second order cumulant `C20 = E[x^2]`, zero for any constellation
+    /// with more than two phases.
+    C20,
+    /// The fourth order cumulant `C40 = M40 - 3 * M20^2`, which is complex
+    /// and rotates with four times any carrier phase offset.
+    C40,
+    /// The fourth order cumulant `C42 = M42 - |M20|^2 - 2 * M21^2`, which is
+    /// real.
+    C42,
+}
+
+/// Computes moments and cumulants of a set of samples, in the order asked
+/// for.  Real valued statistics are returned with a zero imaginary part.
+///
+/// # Arguments
+///
+/// * `samples` - Samples to compute the statistics of.
+/// * `moments` - Which statistics to compute.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::moments::{moments, Moment};
+/// use num::Complex;
+///
+/// let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+/// let stats = moments(&bpsk, &[Moment::M2, Moment::C42]);
+/// assert_eq!(stats, vec![Complex::new(1.0, 0.0), Complex::new(-2.0, 0.0)]);
+/// ```
+pub fn moments(
+    samples: &[Complex<f64>],
+    moments: &[Moment],
+) -> Vec<Complex<f64>> {
+    let n = samples.len().max(1) as f64;
+    let mut m20 = Complex::zero();
+    let mut m21 = 0.0;
+    let mut m40 = Complex::zero();
+    let mut m42 = 0.0;
+    for x in samples {
+        let x2 = x * x;
+        m20 += x2;
+        m21 += x.norm_sqr();
+        m40 += x2 * x2;
+        m42 += x.norm_sqr() * x.norm_sqr();
+    }
+    let (m20, m21, m40, m42) = (m20 / n, m21 / n, m40 / n, m42 / n);
+    moments
+        .iter()
+        .map(|moment| match moment {
+            Moment::M2 => Complex::new(m21, 0.0),
+            Moment::M4 => Complex::new(m42, 0.0),
+            Moment::C20 => m20,
+            Moment::C40 => m40 - m20 * m20 * 3.0,
+            Moment::C42 => {
+                Complex::new(m42 - m20.norm_sqr() - 2.0 * m21 * m21, 0.0)
+            }
+        })
+        .collect()
+}
+
+/// A node that computes moments and cumulants of a signal over a sliding
+/// window.
+///
+/// Second and fourth order statistics are the raw material of a number of
+/// blind estimators.  The ratio of `M4` to the square of `M2` gives the
+/// M2M4 SNR estimate, and the cumulants normalized by the square of `M2`
+/// tell the common constellations apart, as `ModClassifyNode` does, while
+/// being blind to Gaussian noise, whose fourth order cumulants are zero.
+/// This node computes whichever of them are wanted, so that several
+/// estimators can share one pass over the signal.
+///
+/// The statistics are computed over the most recent `window` samples, which
+/// may span batches.  Once the window has filled, each batch produces the
+/// statistics in the order they were asked for, as in `moments`.  The input
+/// is usually symbols at one sample per symbol, timing corrected.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::moments::{Moment, MomentsNode};
+///
+/// // The statistics for an M2M4 SNR estimate over 1000 symbols.
+/// let node: MomentsNode<f32> =
+///     MomentsNode::new(1000, vec![Moment::M2, Moment::M4]);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct MomentsNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    window: usize,
+    moments: Vec<Moment>,
+    history: VecDeque<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl<T> MomentsNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `MomentsNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Number of samples the statistics are computed over.
+    /// * `moments` - Which statistics to compute.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::moments::{Moment, MomentsNode};
+    ///
+    /// let node: MomentsNode<f64> = MomentsNode::new(
+    ///     4096,
+    ///     vec![Moment::M2, Moment::C20, Moment::C40, Moment::C42],
+    /// );
+    /// ```
+    pub fn new(window: usize, moments: Vec<Moment>) -> Self {
+        assert!(window > 0, "window must be nonzero");
+        assert!(!moments.is_empty(), "there must be a statistic to compute");
+        MomentsNode {
+            window,
+            moments,
+            history: VecDeque::with_capacity(window),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `MomentsNode<T>`.  Produces the statistics over the latest
+    /// window once it has filled.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        let skip = samples.len().saturating_sub(self.window);
+        for x in &samples[skip..] {
+            if self.history.len() == self.window {
+                self.history.pop_front();
+            }
+            self.history
+                .push_back(cast_complex(x).ok_or(NodeError::DataError)?);
+        }
+        if self.history.len() < self.window {
+            return Ok(None);
+        }
+        let (a, b) = self.history.as_slices();
+        Ok(Some(moments(&[a, b].concat(), &self.moments)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::moments::*;
+    use crate::modulation::digital::{psk_table, qam_table};
+    use rand::distributions::Uniform;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Computes the statistics of random QPSK and 16-QAM symbols at unit
+    // power and checks them against the theoretical values.
+    fn test_moments_constellations() {
+        let all = vec![
+            Moment::M2,
+            Moment::M4,
+            Moment::C20,
+            Moment::C40,
+            Moment::C42,
+        ];
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        // QPSK on the axes has |x| = 1 everywhere and x^4 = 1, and 16-QAM
+        // has M4 of 1.32, with C40 and C42 both -0.68.
+        let cases = vec![
+            (psk_table(2), vec![1.0, 1.0, 0.0, 1.0, -1.0]),
+            (qam_table(4), vec![1.0, 1.32, 0.0, -0.68, -0.68]),
+        ];
+        for (table, expected) in cases {
+            let pick = Uniform::new(0, table.len());
+            let symbols: Vec<Complex<f64>> =
+                (0..30000).map(|_| table[rng.sample(pick)]).collect();
+            let mut node = MomentsNode::new(20000, all.clone());
+            let outputs: Vec<Option<Vec<Complex<f64>>>> = symbols
+                .chunks(1000)
+                .map(|chunk| node.run(chunk).unwrap())
+                .collect();
+            // Nothing comes out until the window has filled.
+            assert_eq!(outputs.iter().filter(|x| x.is_none()).count(), 19);
+            let stats = outputs[29].clone().unwrap();
+            for (stat, want) in stats.iter().zip(&expected) {
+                assert!((stat - Complex::new(*want, 0.0)).norm() < 0.03);
+            }
+        }
+    }
+}
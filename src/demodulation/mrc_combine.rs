@@ -0,0 +1,204 @@
+//! Maximal-ratio combining of diversity branches.
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that combines multiple antenna branches with maximal-ratio
+/// combining.
+///
+/// The input carries the samples of every branch interleaved, as multichannel
+/// radios deliver them: the first sample of each branch in order, then the
+/// second sample of each branch, and so on.  Each branch `i` sees the same
+/// signal through its own channel gain `h[i]`, and the branches are combined
+/// as
+///
+/// `y = sum(conj(h[i]) * x[i]) / sum(|h[i]|^2)`
+///
+/// which lines up their phases and weights each branch by its strength.  With
+/// equal noise power on every branch this maximizes the output SNR, which is
+/// the sum of the branch SNRs, and the normalization leaves the signal at its
+/// transmitted scale.  If the noise power differs between branches, each
+/// estimate should be divided by the noise power of its branch.
+///
+/// The channel estimates arrive on the `channel` control input, one per
+/// branch, and replace the old ones starting with the batch they arrive with.
+/// Until the first estimates arrive every branch has unit gain, which makes
+/// this equal gain combining.  The node is non-blocking, so estimates don't
+/// need to come with every batch.  Estimates for the wrong number of
+/// branches are ignored and the previous ones kept.  A batch needn't hold a
+/// whole number of samples per branch; any partial set of branch samples at
+/// the end is held until the rest of it arrives with the next batch.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::mrc_combine::MrcCombineNode;
+///
+/// // Two receive antennas.
+/// let node = MrcCombineNode::new(2);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct MrcCombineNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    pub channel: NodeReceiver<Vec<Complex<f64>>>,
+    n_branches: usize,
+    weights: Vec<Complex<f64>>,
+    partial: Vec<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl MrcCombineNode {
+    /// Constructs a new `MrcCombineNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_branches` - Number of branches interleaved in the input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::mrc_combine::MrcCombineNode;
+    ///
+    /// let node = MrcCombineNode::new(4);
+    /// ```
+    pub fn new(n_branches: usize) -> MrcCombineNode {
+        assert!(n_branches > 0, "number of branches must be nonzero");
+        let mut node = MrcCombineNode {
+            n_branches,
+            weights: vec![],
+            partial: vec![],
+            input: Default::default(),
+            channel: Default::default(),
+            output: Default::default(),
+        };
+        node.set_channel(&vec![Complex::new(1.0, 0.0); n_branches])
+            .unwrap();
+        node
+    }
+
+    /// Sets the channel estimates of each branch, used for the following
+    /// samples.  Gives a `NodeError::DataError` if there isn't one estimate
+    /// per branch.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Complex gain of each branch.
+    pub fn set_channel(
+        &mut self,
+        channel: &[Complex<f64>],
+    ) -> Result<(), NodeError> {
+        if channel.len() != self.n_branches {
+            return Err(NodeError::DataError);
+        }
+        let total: f64 = channel.iter().map(|h| h.norm_sqr()).sum();
+        self.weights = if total > 0.0 {
+            channel.iter().map(|h| h.conj() / total).collect()
+        } else {
+            vec![Complex::new(0.0, 0.0); self.n_branches]
+        };
+        Ok(())
+    }
+
+    /// Runs the `MrcCombineNode`.  Updates the channel estimates if valid
+    /// new ones have arrived, then produces the combined samples if a batch
+    /// was received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<f64>>>,
+        channel: Option<Vec<Complex<f64>>>,
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        if let Some(channel) = channel {
+            // Keep the previous weights if the estimates don't fit.
+            let _ = self.set_channel(&channel);
+        }
+        let samples = match input {
+            Some(samples) => samples,
+            None => return Ok(None),
+        };
+        self.partial.extend(samples);
+        let n_whole = self.partial.len() / self.n_branches * self.n_branches;
+        let combined = self.partial[..n_whole]
+            .chunks_exact(self.n_branches)
+            .map(|branches| {
+                branches.iter().zip(&self.weights).map(|(x, w)| x * w).sum()
+            })
+            .collect();
+        self.partial.drain(..n_whole);
+        Ok(Some(combined))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::mrc_combine::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    // SNR in dB of a received signal against the transmitted one.
+    fn snr(rx: &[Complex<f64>], tx: &[Complex<f64>]) -> f64 {
+        let err: f64 = rx.iter().zip(tx).map(|(y, x)| (y - x).norm_sqr()).sum();
+        let power: f64 = tx.iter().map(|x| x.norm_sqr()).sum();
+        10.0 * (power / err).log10()
+    }
+
+    #[test]
+    // Sends QPSK over two branches with different gains and phases and equal
+    // noise, and checks that the combined SNR beats the better branch by
+    // the expected amount.
+    fn test_mrc_combine() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.1);
+        let tx: Vec<Complex<f64>> = (0..20000)
+            .map(|_| {
+                Complex::new(
+                    if rng.gen() { 1.0 } else { -1.0 },
+                    if rng.gen() { 1.0 } else { -1.0 },
+                )
+            })
+            .collect();
+        let channel = vec![
+            Complex::from_polar(1.0, 0.7),
+            Complex::from_polar(0.6, -2.1),
+        ];
+        let mut interleaved = vec![];
+        let mut branches = [vec![], vec![]];
+        for x in &tx {
+            for (h, branch) in channel.iter().zip(branches.iter_mut()) {
+                let y =
+                    x * h + Complex::new(rng.sample(noise), rng.sample(noise));
+                interleaved.push(y);
+                // Each branch on its own, corrected by its channel.
+                branch.push(y / h);
+            }
+        }
+
+        // Batches that split the branches of a sample are fine.
+        let mut node = MrcCombineNode::new(2);
+        let mut combined = vec![];
+        for (ix, chunk) in interleaved.chunks(999).enumerate() {
+            let estimates = if ix == 0 { Some(channel.clone()) } else { None };
+            combined.extend(
+                node.run(Some(chunk.to_vec()), estimates).unwrap().unwrap(),
+            );
+        }
+        assert_eq!(combined.len(), tx.len());
+
+        // The combined SNR is the sum of the branch SNRs.
+        let best = snr(&branches[0], &tx);
+        let gain = 10.0 * (1.0 + 0.6_f64.powi(2)).log10();
+        assert!(snr(&branches[1], &tx) < best);
+        assert!((snr(&combined, &tx) - best - gain).abs() < 0.2);
+
+        assert_eq!(node.run(None, None).unwrap(), None);
+
+        // Estimates for the wrong number of branches are ignored, and the
+        // node carries on with the ones it had.
+        assert!(node.set_channel(&[channel[0]]).is_err());
+        let bad = Some(vec![channel[0]]);
+        let out = node.run(Some(interleaved[..4].to_vec()), bad).unwrap();
+        assert_eq!(out.unwrap(), combined[..2].to_vec());
+    }
+}
@@ -2,6 +2,7 @@
 //! for sample by sample operation with and without a specified initial phase.
 
 use crate::prelude::*;
+use std::collections::VecDeque;
 use std::f64::consts::PI;
 
 extern crate num; // 0.2.0
@@ -132,3 +133,300 @@ impl NcoNode {
         Ok(self.nco.push(input))
     }
 }
+
+/// A node that turns the NCO into a modulated signal generator.
+///
+/// The node generates a complex carrier at a base frequency, with optional
+/// control inputs for amplitude modulation and frequency modulation.  Each
+/// active control input supplies one value per output sample: the AM input
+/// sets the amplitude of the carrier directly, and the FM input is an offset
+/// in Hz from the base frequency.  When both inputs are active, a sample is
+/// only produced once a value has arrived on each.  Values arriving on an
+/// inactive input are dropped.  With no active inputs the node free-runs as
+/// a plain tone generator.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::nco::NcoSourceNode;
+///
+/// // A 1 kHz carrier at 48 kHz, frequency modulated by the FM input.
+/// let node = NcoSourceNode::new(1000.0, 48000.0, false, true);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct NcoSourceNode {
+    pub am: NodeReceiver<f64>,
+    pub fm: NodeReceiver<f64>,
+    nco: Nco,
+    sample_rate: f64,
+    am_active: bool,
+    fm_active: bool,
+    am_queue: VecDeque<f64>,
+    fm_queue: VecDeque<f64>,
+    pub output: NodeSender<Complex<f64>>,
+}
+
+impl NcoSourceNode {
+    /// Constructs a new `NcoSourceNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - Base frequency of the carrier in Hz.
+    /// * `sample_rate` - Sample rate in Hz.
+    /// * `am_active` - Whether to wait for and apply the AM control input.
+    /// * `fm_active` - Whether to wait for and apply the FM control input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::nco::NcoSourceNode;
+    ///
+    /// // An unmodulated 10 kHz tone.
+    /// let node = NcoSourceNode::new(10_000.0, 1e6, false, false);
+    /// ```
+    pub fn new(
+        freq: f64,
+        sample_rate: f64,
+        am_active: bool,
+        fm_active: bool,
+    ) -> Self {
+        NcoSourceNode {
+            nco: Nco::new(0.0, 2.0 * PI * freq / sample_rate),
+            sample_rate,
+            am_active,
+            fm_active,
+            am_queue: VecDeque::new(),
+            fm_queue: VecDeque::new(),
+            am: Default::default(),
+            fm: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `NcoSourceNode`.  Produces the next carrier sample once a
+    /// value is available on every active control input.
+    pub fn run(
+        &mut self,
+        am: Option<f64>,
+        fm: Option<f64>,
+    ) -> Result<Option<Complex<f64>>, NodeError> {
+        // Values on an inactive input are never used, so don't queue them.
+        if let (Some(am), true) = (am, self.am_active) {
+            self.am_queue.push_back(am);
+        }
+        if let (Some(fm), true) = (fm, self.fm_active) {
+            self.fm_queue.push_back(fm);
+        }
+        if (self.am_active && self.am_queue.is_empty())
+            || (self.fm_active && self.fm_queue.is_empty())
+        {
+            return Ok(None);
+        }
+
+        let amplitude = if self.am_active {
+            self.am_queue.pop_front().unwrap()
+        } else {
+            1.0
+        };
+        let offset = if self.fm_active {
+            self.fm_queue.pop_front().unwrap()
+        } else {
+            0.0
+        };
+        let perr = 2.0 * PI * offset / self.sample_rate;
+        Ok(Some(self.nco.push(perr) * amplitude))
+    }
+}
+
+impl SampleRate for NcoSourceNode {
+    fn sample_rate(&self) -> Option<f64> {
+        Some(self.sample_rate)
+    }
+}
+
+/// A node that runs an NCO and exposes its phase accumulator.
+///
+/// Each step produces the complex exponential `exp(j * phase)` along with
+/// the phase itself, wrapped to the interval (-PI, PI], and then advances the
+/// phase by `2 * PI * freq / sample_rate`.  Algorithms such as PLLs and phase
+/// modulators often need the raw phase as well as the oscillator output, and
+/// this avoids having to recover it with an `arg()` call.
+///
+/// The frequency can be changed through the `freq` control input, in Hz.
+/// With the control input left unconnected the node free-runs at the
+/// frequency given at construction.  With it connected, the node steps once
+/// for each frequency received, so it should be driven at the sample rate,
+/// such as by the loop filter of a PLL.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::nco::NcoPhaseNode;
+///
+/// let node = NcoPhaseNode::new(1000.0, 48000.0);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+pub struct NcoPhaseNode {
+    pub freq: NodeReceiver<f64>,
+    phase: f64,
+    dphase: f64,
+    sample_rate: f64,
+    pub output: NodeSender<(Complex<f64>, f64)>,
+}
+
+impl NcoPhaseNode {
+    /// Constructs a new `NcoPhaseNode` with an initial phase of zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - Initial frequency of the oscillator in Hz.
+    /// * `sample_rate` - Sample rate in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::nco::NcoPhaseNode;
+    ///
+    /// // A negative frequency rotates clockwise.
+    /// let node = NcoPhaseNode::new(-2500.0, 1e6);
+    /// ```
+    pub fn new(freq: f64, sample_rate: f64) -> NcoPhaseNode {
+        NcoPhaseNode {
+            phase: 0.0,
+            dphase: 2.0 * PI * freq / sample_rate,
+            sample_rate,
+            freq: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Changes the frequency of the oscillator, taking effect from the next
+    /// step.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - New frequency of the oscillator in Hz.
+    pub fn set_frequency(&mut self, freq: f64) {
+        self.dphase = 2.0 * PI * freq / self.sample_rate;
+    }
+
+    /// Produces the current output and phase of the oscillator and advances
+    /// it by one sample.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::nco::NcoPhaseNode;
+    /// use std::f64::consts::PI;
+    ///
+    /// let mut node = NcoPhaseNode::new(1.0, 4.0);
+    /// assert_eq!(node.step().1, 0.0);
+    /// assert!((node.step().1 - PI / 2.0).abs() < 1e-12);
+    /// ```
+    pub fn step(&mut self) -> (Complex<f64>, f64) {
+        let phase = self.phase;
+        self.phase += self.dphase;
+        self.phase -= 2.0 * PI * ((self.phase - PI) / (2.0 * PI)).ceil();
+        (Complex::new(0.0, phase).exp(), phase)
+    }
+
+    /// Runs the `NcoPhaseNode`.  Applies any new frequency, then produces
+    /// the next output and phase.
+    pub fn run(
+        &mut self,
+        freq: Option<f64>,
+    ) -> Result<(Complex<f64>, f64), NodeError> {
+        if let Some(freq) = freq {
+            self.set_frequency(freq);
+        }
+        Ok(self.step())
+    }
+}
+
+impl SampleRate for NcoPhaseNode {
+    fn sample_rate(&self) -> Option<f64> {
+        Some(self.sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::nco::*;
+    use crate::modulation::analog::FM;
+
+    #[test]
+    // Frequency modulates the carrier with a sinusoid and checks that an FM
+    // discriminator gets the sinusoid back.
+    fn test_nco_source_fm() {
+        let fs = 48000.0;
+        let fc = 1000.0;
+        let dev = 2000.0;
+        let control: Vec<f64> = (0..4800)
+            .map(|n| dev * (2.0 * PI * 50.0 * n as f64 / fs).sin())
+            .collect();
+
+        let mut node = NcoSourceNode::new(fc, fs, false, true);
+        let signal: Vec<Complex<f64>> = control
+            .iter()
+            .map(|fm| node.run(None, Some(*fm)).unwrap().unwrap())
+            .collect();
+        for samp in &signal {
+            assert!((samp.norm() - 1.0).abs() < 1e-12);
+        }
+
+        let mut fm = FM::default();
+        let demod = fm.demod(&signal);
+        for (freq, truth) in demod.iter().zip(control.iter()).skip(1) {
+            let recovered = freq * fs / (2.0 * PI) - fc;
+            assert!((recovered - truth).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    // With both inputs active, nothing comes out until both have supplied a
+    // value.
+    fn test_nco_source_am_fm() {
+        let mut node = NcoSourceNode::new(0.0, 1.0, true, true);
+        assert_eq!(node.run(Some(0.5), None).unwrap(), None);
+        assert_eq!(node.run(Some(2.0), None).unwrap(), None);
+        let first = node.run(None, Some(0.25)).unwrap().unwrap();
+        assert!((first - Complex::new(0.0, 0.5)).norm() < 1e-12);
+        let second = node.run(None, Some(0.25)).unwrap().unwrap();
+        assert!((second - Complex::new(-2.0, 0.0)).norm() < 1e-12);
+    }
+
+    #[test]
+    // Runs the oscillator through several frequency changes and checks the
+    // phase stays wrapped and matches the output.
+    fn test_nco_phase() {
+        let fs = 1000.0;
+        let mut node = NcoPhaseNode::new(123.0, fs);
+        let mut unwrapped = 0.0;
+        let mut freq = 123.0;
+        for n in 0..5000 {
+            let update = match n {
+                1000 => Some(-377.0),
+                2500 => Some(499.9),
+                4000 => Some(0.0),
+                _ => None,
+            };
+            if let Some(f) = update {
+                freq = f;
+            }
+            let (out, phase) = node.run(update).unwrap();
+            assert!(phase > -PI && phase <= PI);
+            assert!((out - Complex::new(0.0, phase).exp()).norm() < 1e-12);
+            let diff = Complex::new(0.0, phase - unwrapped).exp();
+            assert!((diff - 1.0).norm() < 1e-9);
+            unwrapped += 2.0 * PI * freq / fs;
+        }
+
+        // Exactly half a cycle per sample lands on PI rather than -PI.
+        let mut node = NcoPhaseNode::new(0.5, 1.0);
+        node.step();
+        assert_eq!(node.step().1, PI);
+    }
+}
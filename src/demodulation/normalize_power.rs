@@ -0,0 +1,175 @@
+//! Scaling received symbols to a known average power.
+use crate::prelude::*;
+
+use num::{Complex, Float, NumCast};
+
+/// How `NormalizePowerNode` estimates the power of its input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalizeMode {
+    /// Each block is scaled by its own average power, so every output block
+    /// has exactly the target power.
+    PerBlock,
+    /// The power is tracked across blocks with a leaky integrator of the
+    /// given rate on the interval (0.0, 1.0], which rides out blocks too
+    /// short to give a good estimate on their own.
+    Adaptive(f64),
+}
+
+/// A node that scales symbols to a target average power.
+///
+/// Received symbols come in at whatever level the front end and any gain
+/// control left them, but EVM measurements, soft demappers and fixed
+/// decision thresholds all assume a known constellation power.  This node
+/// measures the average power `|x|^2` of the symbols and scales them so that
+/// it matches the target, either block by block or with a running estimate,
+/// as selected by the mode.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::normalize_power::{NormalizeMode, NormalizePowerNode};
+///
+/// let node: NormalizePowerNode<f64> =
+///     NormalizePowerNode::new(1.0, NormalizeMode::PerBlock);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct NormalizePowerNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    target: f64,
+    mode: NormalizeMode,
+    power: Option<f64>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> NormalizePowerNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `NormalizePowerNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Average power of the output symbols.
+    /// * `mode` - How the power of the input is estimated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::normalize_power::{NormalizeMode, NormalizePowerNode};
+    ///
+    /// // Unit power with a slowly adapting estimate.
+    /// let node: NormalizePowerNode<f32> =
+    ///     NormalizePowerNode::new(1.0, NormalizeMode::Adaptive(0.01));
+    /// ```
+    pub fn new(target: f64, mode: NormalizeMode) -> Self {
+        assert!(target > 0.0, "target power must be positive");
+        if let NormalizeMode::Adaptive(rate) = mode {
+            assert!(
+                rate > 0.0 && rate <= 1.0,
+                "adaptation rate must be on the interval (0.0, 1.0]"
+            );
+        }
+        NormalizePowerNode {
+            target,
+            mode,
+            power: None,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the current estimate of the input power, if there is one.
+    pub fn power(&self) -> Option<f64> {
+        self.power
+    }
+
+    /// Runs the `NormalizePowerNode<T>`.  Produces the block of symbols
+    /// scaled to the target power.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        if symbols.is_empty() {
+            return Ok(vec![]);
+        }
+        let block: f64 = symbols
+            .iter()
+            .map(|x| x.norm_sqr().to_f64().unwrap())
+            .sum::<f64>()
+            / symbols.len() as f64;
+        let power = match (self.mode, self.power) {
+            (NormalizeMode::Adaptive(rate), Some(power)) => {
+                power + rate * (block - power)
+            }
+            _ => block,
+        };
+        self.power = Some(power);
+        if power <= 0.0 {
+            return Ok(symbols.to_vec());
+        }
+        let scale: T = NumCast::from((self.target / power).sqrt()).unwrap();
+        Ok(symbols.iter().map(|x| x * scale).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::normalize_power::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    fn avg_power(symbols: &[Complex<f64>]) -> f64 {
+        symbols.iter().map(|x| x.norm_sqr()).sum::<f64>() / symbols.len() as f64
+    }
+
+    // Random 16-QAM symbols at an arbitrary scale.
+    fn qam16(rng: &mut SmallRng, len: usize, scale: f64) -> Vec<Complex<f64>> {
+        let levels = [-3.0, -1.0, 1.0, 3.0];
+        (0..len)
+            .map(|_| {
+                Complex::new(
+                    levels[rng.gen_range(0, 4)],
+                    levels[rng.gen_range(0, 4)],
+                ) * scale
+            })
+            .collect()
+    }
+
+    #[test]
+    // Checks that each block comes out at exactly the target power in per
+    // block mode.
+    fn test_normalize_per_block() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut node = NormalizePowerNode::new(2.0, NormalizeMode::PerBlock);
+        for &scale in &[0.01, 1.0, 37.0] {
+            let symbols = qam16(&mut rng, 500, scale);
+            let out = node.run(&symbols).unwrap();
+            assert!((avg_power(&out) - 2.0).abs() < 1e-9);
+            // The shape of the constellation is left alone.
+            let ratio = out[0] / symbols[0];
+            for (y, x) in out.iter().zip(&symbols) {
+                assert!((y - x * ratio).norm() < 1e-9);
+            }
+        }
+        assert!(node.run(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    // Checks that the adaptive mode converges to the target power over
+    // blocks too short to measure on their own.
+    fn test_normalize_adaptive() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut node =
+            NormalizePowerNode::new(1.0, NormalizeMode::Adaptive(0.05));
+        let mut out = vec![];
+        for _ in 0..400 {
+            out.extend(node.run(&qam16(&mut rng, 8, 5.0)).unwrap());
+        }
+        assert!((node.power().unwrap() - 250.0).abs() < 25.0);
+        assert!((avg_power(&out[1600..]) - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,187 @@
+//! Coarse time synchronization by detecting the leading edge of a packet.
+use crate::prelude::*;
+
+use num::{Complex, Float, NumCast};
+
+/// A node that finds where packets begin from a rise in energy above the
+/// noise floor.
+///
+/// The noise floor is the average power of the samples, tracked with a leaky
+/// integrator while no packet is present.  A packet starts when the power of
+/// `sustain` consecutive samples is more than `threshold` dB above the noise
+/// floor, and its start is taken as the first of those samples.  Requiring
+/// the rise to be sustained keeps noise spikes from triggering a detection.
+/// The packet is over once the power has stayed below the threshold for
+/// `sustain` samples, after which the node watches for the next one.
+///
+/// This is a lightweight alternative to correlating against a preamble when
+/// the signal is well above the noise, and can be used to narrow down where
+/// a more precise synchronizer needs to look.  Each batch produces the
+/// indices of the packet starts found in it, counting from the first sample
+/// the node received.  No detections are made until the noise floor has
+/// been averaged over the time constant of the integrator, so the stream
+/// should start with some noise ahead of the first packet.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::packet_start::PacketStartNode;
+///
+/// // Detect packets at least 10 dB above the noise that hold up for 16
+/// // samples.
+/// let node: PacketStartNode<f32> = PacketStartNode::new(10.0, 16);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct PacketStartNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    threshold: f64,
+    sustain: usize,
+    alpha: f64,
+    noise: f64,
+    count: u64,
+    run_length: usize,
+    in_packet: bool,
+    pub output: NodeSender<Vec<u64>>,
+}
+
+impl<T> PacketStartNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `PacketStartNode<T>`, with a noise floor smoothing
+    /// factor of 0.01.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Power in dB above the noise floor that counts as a
+    ///   packet.
+    /// * `sustain` - Number of consecutive samples that must be above the
+    ///   threshold to start a packet, or below it to end one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::packet_start::PacketStartNode;
+    ///
+    /// let node: PacketStartNode<f64> = PacketStartNode::new(6.0, 32);
+    /// ```
+    pub fn new(threshold: f64, sustain: usize) -> Self {
+        assert!(sustain > 0, "sustain count must be nonzero");
+        PacketStartNode {
+            threshold: 10.0_f64.powf(threshold / 10.0),
+            sustain,
+            alpha: 0.01,
+            noise: 0.0,
+            count: 0,
+            run_length: 0,
+            in_packet: false,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the smoothing factor of the noise floor estimate, on the
+    /// interval (0.0, 1.0].  Smaller values give a steadier estimate but
+    /// take longer to settle.
+    pub fn with_smoothing(mut self, alpha: f64) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "smoothing factor must be on the interval (0.0, 1.0]"
+        );
+        self.alpha = alpha;
+        self
+    }
+
+    /// Returns the current noise floor estimate as a power.
+    pub fn noise_floor(&self) -> f64 {
+        self.noise
+    }
+
+    /// Runs the `PacketStartNode<T>`.  Produces the indices of the packet
+    /// starts found in the batch, if there are any.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<u64>>, NodeError> {
+        let settle = (1.0 / self.alpha).ceil() as u64;
+        let mut starts = vec![];
+        for x in samples {
+            let p: f64 = NumCast::from(x.norm_sqr()).unwrap();
+            let index = self.count;
+            self.count += 1;
+
+            if self.in_packet {
+                if p > self.threshold * self.noise {
+                    self.run_length = 0;
+                } else {
+                    self.run_length += 1;
+                    if self.run_length == self.sustain {
+                        self.in_packet = false;
+                        self.run_length = 0;
+                    }
+                }
+            } else if index >= settle && p > self.threshold * self.noise {
+                self.run_length += 1;
+                if self.run_length == self.sustain {
+                    starts.push(index + 1 - self.sustain as u64);
+                    self.in_packet = true;
+                    self.run_length = 0;
+                }
+            } else {
+                // Averages the first samples evenly so the estimate settles
+                // quickly, then switches over to the leaky integrator.
+                self.run_length = 0;
+                let alpha = self.alpha.max(1.0 / (index + 1) as f64);
+                self.noise += alpha * (p - self.noise);
+            }
+        }
+        if starts.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(starts))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::packet_start::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Sends noise followed by bursts of QPSK 20 dB above it, batched across
+    // the burst edges, and checks that each reported start is at the onset
+    // of a burst.
+    fn test_packet_start() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 0.5_f64.sqrt());
+        let mut stream: Vec<Complex<f64>> = (0..6000)
+            .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect();
+        let onsets = [2000, 4500];
+        for &onset in &onsets {
+            for x in &mut stream[onset..onset + 1000] {
+                let re = if rng.gen_bool(0.5) { 7.0 } else { -7.0 };
+                let im = if rng.gen_bool(0.5) { 7.0 } else { -7.0 };
+                *x += Complex::new(re, im);
+            }
+        }
+
+        let mut node = PacketStartNode::new(10.0, 16);
+        let mut starts = vec![];
+        for chunk in stream.chunks(333) {
+            if let Some(found) = node.run(chunk).unwrap() {
+                starts.extend(found);
+            }
+        }
+        assert_eq!(starts, vec![2000, 4500]);
+        assert!((node.noise_floor() - 1.0).abs() < 0.3);
+    }
+}
@@ -0,0 +1,155 @@
+//! Time of arrival estimation for ranging.
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that estimates the time of arrival of a known waveform.
+///
+/// Each input is a capture of received samples, starting at the time the
+/// reference waveform was transmitted.  The capture is correlated against
+/// the reference, which is the matched filter for it, and the position of
+/// the largest correlation magnitude is refined to a fraction of a sample by
+/// fitting a parabola through the peak and its two neighbors.  The output is
+/// the time of arrival of the start of the waveform in samples, which is the
+/// round trip delay of an echo in radar or lidar style ranging.
+///
+/// Wideband waveforms such as chirps give a narrow correlation peak and so
+/// the best accuracy.  A capture shorter than the reference is dropped
+/// without producing an estimate.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::ranging::RangingNode;
+/// use num::Complex;
+///
+/// let reference: Vec<Complex<f64>> = (0..128)
+///     .map(|n| Complex::new(0.0, 0.001 * (n * n) as f64).exp())
+///     .collect();
+/// let node = RangingNode::new(reference);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct RangingNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    reference: Vec<Complex<f64>>,
+    pub output: NodeSender<f64>,
+}
+
+impl RangingNode {
+    /// Constructs a new `RangingNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The transmitted waveform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::ranging::RangingNode;
+    /// use num::Complex;
+    ///
+    /// let node = RangingNode::new(vec![Complex::new(1.0, 0.0); 13]);
+    /// ```
+    pub fn new(reference: Vec<Complex<f64>>) -> RangingNode {
+        assert!(!reference.is_empty(), "reference must not be empty");
+        RangingNode {
+            reference,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Estimates the time of arrival of the reference in a capture, in
+    /// samples.  Returns `None` if the capture is shorter than the
+    /// reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture` - Received samples.
+    pub fn time_of_arrival(&self, capture: &[Complex<f64>]) -> Option<f64> {
+        let n_lags = (capture.len() + 1).checked_sub(self.reference.len())?;
+        if n_lags == 0 {
+            return None;
+        }
+        let corr: Vec<f64> = (0..n_lags)
+            .map(|lag| {
+                self.reference
+                    .iter()
+                    .zip(&capture[lag..])
+                    .map(|(r, x)| r.conj() * x)
+                    .sum::<Complex<f64>>()
+                    .norm()
+            })
+            .collect();
+        let (peak, _) =
+            corr.iter()
+                .enumerate()
+                .fold(
+                    (0, f64::MIN),
+                    |best, (ix, &c)| if c > best.1 { (ix, c) } else { best },
+                );
+
+        // Parabolic interpolation around the peak for a fractional estimate.
+        let mut frac = 0.0;
+        if peak > 0 && peak + 1 < corr.len() {
+            let (a, b, c) = (corr[peak - 1], corr[peak], corr[peak + 1]);
+            let denom = a - 2.0 * b + c;
+            if denom.abs() > 0.0 {
+                frac = 0.5 * (a - c) / denom;
+            }
+        }
+        Some(peak as f64 + frac)
+    }
+
+    /// Runs the `RangingNode`.  Produces the time of arrival in samples, or
+    /// nothing if the capture is shorter than the reference.
+    pub fn run(
+        &mut self,
+        capture: &[Complex<f64>],
+    ) -> Result<Option<f64>, NodeError> {
+        Ok(self.time_of_arrival(capture))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::ranging::*;
+    use crate::util::channel_node::ChannelImpairmentNode;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Delays a chirp by fractional amounts and checks that the estimated
+    // time of arrival lands within a small fraction of a sample.
+    fn test_ranging() {
+        // Sweep from -0.2 to 0.2 cycles per sample.
+        let len = 256;
+        let rate = 0.4 / len as f64;
+        let reference: Vec<Complex<f64>> = (0..len)
+            .map(|n| {
+                let n = n as f64;
+                let cycles = -0.2 * n + 0.5 * rate * n * n;
+                Complex::new(0.0, 2.0 * PI * cycles).exp()
+            })
+            .collect();
+        let mut capture = reference.clone();
+        capture.extend(vec![Complex::new(0.0, 0.0); 200]);
+
+        let mut node = RangingNode::new(reference);
+        for &delay in &[37.0, 37.25, 80.5, 123.8] {
+            let mut channel = ChannelImpairmentNode::new(0.0, delay, 1.0, 0.0);
+            let echo = channel.run(&capture).unwrap();
+            let toa = node.run(&echo).unwrap().unwrap();
+            assert!((toa - delay).abs() < 0.1);
+        }
+
+        // A short capture is skipped, and the next valid one is still
+        // measured.
+        assert_eq!(node.run(&capture[..100]).unwrap(), None);
+        let mut channel = ChannelImpairmentNode::new(0.0, 50.0, 1.0, 0.0);
+        let echo = channel.run(&capture).unwrap();
+        let toa = node.run(&echo).unwrap().unwrap();
+        assert!((toa - 50.0).abs() < 0.1);
+    }
+}
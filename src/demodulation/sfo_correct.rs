@@ -0,0 +1,283 @@
+//! Estimation and correction of sample clock frequency offset (SFO).
+//!
+//! A mismatch between the transmitter and receiver sample clocks shows up as
+//! a slow drift in symbol timing over the course of a burst.  Timing recovery
+//! can track this for short bursts, but over long bursts the drift will
+//! eventually exceed what it can handle, so it's better to remove the clock
+//! offset up front.
+
+use crate::demodulation::farrow_filter::FarrowResampler;
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that estimates and removes a sample clock offset from a burst.
+///
+/// The burst is expected to contain a known preamble repeated every `period`
+/// samples, as is common with pilot or midamble based framing.  Each
+/// occurrence of the preamble is located by cross-correlation, refined to a
+/// fractional sample position, and a line is fit through the observed
+/// positions.  The slope of that line is the ratio between the receive and
+/// transmit sample clocks, which is then removed by resampling the burst
+/// with a Farrow resampler.
+///
+/// If fewer than two occurrences of the preamble fit in the burst, the burst
+/// is passed through unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::sfo_correct::SfoCorrectNode;
+/// use num::Complex;
+///
+/// let preamble = vec![Complex::new(1.0, 0.0); 32];
+/// let node = SfoCorrectNode::new(preamble, 1024);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SfoCorrectNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    preamble: Vec<Complex<f64>>,
+    preamble_energy: f64,
+    period: usize,
+    ratio: f64,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl SfoCorrectNode {
+    /// Constructs a new `SfoCorrectNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `preamble` - The known preamble samples.
+    /// * `period` - Nominal number of samples between the start of
+    ///   consecutive preambles, at the transmitter's sample rate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::sfo_correct::SfoCorrectNode;
+    /// use num::Complex;
+    ///
+    /// let preamble = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+    /// let node = SfoCorrectNode::new(preamble, 256);
+    /// ```
+    pub fn new(preamble: Vec<Complex<f64>>, period: usize) -> SfoCorrectNode {
+        assert!(
+            preamble.len() <= period,
+            "preamble must fit within a single period"
+        );
+        let preamble_energy = preamble.iter().map(|x| x.norm_sqr()).sum();
+        SfoCorrectNode {
+            preamble,
+            preamble_energy,
+            period,
+            ratio: 1.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the clock ratio estimated from the most recent burst.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    // Normalized correlation of the preamble against the burst at `ix`.
+    fn correlate(&self, burst: &[Complex<f64>], ix: usize) -> f64 {
+        let window = &burst[ix..ix + self.preamble.len()];
+        let energy: f64 = window.iter().map(|x| x.norm_sqr()).sum();
+        if energy == 0.0 {
+            return 0.0;
+        }
+        let corr: Complex<f64> = self
+            .preamble
+            .iter()
+            .zip(window.iter())
+            .map(|(p, x)| p.conj() * x)
+            .sum();
+        corr.norm_sqr() / (energy * self.preamble_energy)
+    }
+
+    // Finds the fractional position of the correlation peak in the range
+    // [start, end).
+    fn find_peak(
+        &self,
+        burst: &[Complex<f64>],
+        start: usize,
+        end: usize,
+    ) -> Option<f64> {
+        let end = end.min((burst.len() + 1).checked_sub(self.preamble.len())?);
+        if start >= end {
+            return None;
+        }
+        let corr: Vec<f64> =
+            (start..end).map(|ix| self.correlate(burst, ix)).collect();
+        let (peak, _) =
+            corr.iter()
+                .enumerate()
+                .fold(
+                    (0, f64::MIN),
+                    |best, (ix, &c)| if c > best.1 { (ix, c) } else { best },
+                );
+
+        // Parabolic interpolation around the peak for a fractional estimate.
+        let mut frac = 0.0;
+        if peak > 0 && peak + 1 < corr.len() {
+            let (a, b, c) = (corr[peak - 1], corr[peak], corr[peak + 1]);
+            let denom = a - 2.0 * b + c;
+            if denom.abs() > 0.0 {
+                frac = 0.5 * (a - c) / denom;
+            }
+        }
+        Some((start + peak) as f64 + frac)
+    }
+
+    /// Estimates the ratio of the receive to transmit sample clock period
+    /// from a burst.  Returns `None` if fewer than two preambles could be
+    /// found.
+    ///
+    /// # Arguments
+    ///
+    /// * `burst` - Burst of received samples.
+    pub fn estimate(&self, burst: &[Complex<f64>]) -> Option<f64> {
+        let search = (self.period / 8).max(1);
+        let mut peaks = vec![];
+        let mut expected = None;
+        loop {
+            let (start, end) = match expected {
+                None => (0, self.period),
+                Some(e) => (e - search.min(e), e + search + 1),
+            };
+            let peak = match self.find_peak(burst, start, end) {
+                Some(peak) => peak,
+                None => break,
+            };
+            peaks.push(peak);
+            expected = Some(peak.round() as usize + self.period);
+        }
+        if peaks.len() < 2 {
+            return None;
+        }
+
+        // Least squares fit of observed position against nominal position.
+        let n = peaks.len() as f64;
+        let nominal: Vec<f64> =
+            (0..peaks.len()).map(|m| (m * self.period) as f64).collect();
+        let mean_x = nominal.iter().sum::<f64>() / n;
+        let mean_y = peaks.iter().sum::<f64>() / n;
+        let (num, den) = nominal.iter().zip(peaks.iter()).fold(
+            (0.0, 0.0),
+            |(num, den), (x, y)| {
+                (
+                    num + (x - mean_x) * (y - mean_y),
+                    den + (x - mean_x).powi(2),
+                )
+            },
+        );
+        Some(num / den)
+    }
+
+    /// Runs the `SfoCorrectNode`.  Produces the burst resampled to remove
+    /// the estimated sample clock offset.
+    pub fn run(
+        &mut self,
+        burst: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        match self.estimate(burst) {
+            Some(ratio) => {
+                self.ratio = ratio;
+                Ok(FarrowResampler::new(ratio).resample(burst))
+            }
+            None => {
+                self.ratio = 1.0;
+                Ok(burst.to_vec())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::farrow_filter::FarrowResampler;
+    use crate::demodulation::sfo_correct::*;
+    use crate::filter::fir::batch_fir;
+    use crate::util::math::gaussian_taps;
+    use num::Zero;
+    use rand::distributions::Uniform;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    fn shape(symbols: &[Complex<f64>], sps: usize) -> Vec<Complex<f64>> {
+        let taps: Vec<Complex<f64>> =
+            gaussian_taps(4 * sps as u32, sps as f64, 4.0).unwrap();
+        let mut upsampled = vec![Complex::zero(); symbols.len() * sps];
+        for (ix, sym) in symbols.iter().enumerate() {
+            upsampled[ix * sps] = *sym;
+        }
+        let mut state = vec![Complex::zero(); taps.len()];
+        batch_fir(&upsampled, &taps, &mut state)
+    }
+
+    // Largest error between two signals over a range of samples.
+    fn max_error(
+        a: &[Complex<f64>],
+        b: &[Complex<f64>],
+        range: std::ops::Range<usize>,
+    ) -> f64 {
+        range.map(|n| (a[n] - b[n]).norm()).fold(0.0, f64::max)
+    }
+
+    #[test]
+    // Injects a 200 ppm clock error into a long burst and checks that the
+    // corrected output lines up with the transmitted signal at both the
+    // start and the end of the burst.
+    fn test_sfo_correct() {
+        let sps = 8;
+        let n_pre = 32;
+        let period_sym = 128;
+        let n_sym = 40 * period_sym;
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bit = Uniform::new(0, 2);
+        let mut random_symbol =
+            || Complex::new(2.0 * rng.sample(bit) as f64 - 1.0, 0.0);
+        let pre_symbols: Vec<Complex<f64>> =
+            (0..n_pre).map(|_| random_symbol()).collect();
+        let symbols: Vec<Complex<f64>> = (0..n_sym)
+            .map(|ix| {
+                if ix % period_sym < n_pre {
+                    pre_symbols[ix % period_sym]
+                } else {
+                    random_symbol()
+                }
+            })
+            .collect();
+        let tx = shape(&symbols, sps);
+        let preamble = shape(&pre_symbols, sps);
+
+        let eps = 200e-6;
+        let rx = FarrowResampler::new(1.0 + eps).resample(&tx);
+
+        // Without correction the timing has drifted by several samples by
+        // the end of the burst.
+        let end = rx.len() - 2000..rx.len() - 100;
+        assert!(max_error(&rx, &tx, end.clone()) > 0.1);
+
+        let mut node = SfoCorrectNode::new(preamble, period_sym * sps);
+        let corrected = node.run(&rx).unwrap();
+        assert!((node.ratio() - 1.0 / (1.0 + eps)).abs() < 5e-6);
+        assert!(max_error(&corrected, &tx, 100..2000) < 0.02);
+        assert!(max_error(&corrected, &tx, end) < 0.02);
+    }
+
+    #[test]
+    // A burst too short to hold two preambles is passed through untouched.
+    fn test_sfo_short_burst() {
+        let preamble = vec![Complex::new(1.0, 0.0); 4];
+        let mut node = SfoCorrectNode::new(preamble, 64);
+        let burst = vec![Complex::new(0.5, 0.0); 40];
+        assert_eq!(node.run(&burst).unwrap(), burst);
+        assert_eq!(node.ratio(), 1.0);
+    }
+}
@@ -0,0 +1,147 @@
+//! Hard decision slicing of soft real valued samples into bits.
+use crate::prelude::*;
+
+use num::Float;
+
+/// A node that slices soft real samples into hard bits.
+///
+/// Samples above the threshold become a 1 and samples below it become a 0.
+/// With a nonzero hysteresis band, the output only changes once a sample
+/// crosses to the far side of the band, i.e. above `threshold + band / 2` to
+/// go to 1 and below `threshold - band / 2` to go to 0.  This keeps noise
+/// near the decision boundary from causing chatter in the output.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::slicer::SlicerNode;
+///
+/// let node = SlicerNode::new(0.0_f64, 0.2);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SlicerNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    threshold: T,
+    hysteresis: T,
+    state: Option<u8>,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl<T> SlicerNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `SlicerNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Decision threshold, typically 0.0 for NRZ signals.
+    /// * `hysteresis` - Total width of the hysteresis band centered on the
+    ///   threshold.  Zero gives a plain threshold.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::slicer::SlicerNode;
+    ///
+    /// // A plain threshold at 0.5 for on-off keyed signals.
+    /// let node = SlicerNode::new(0.5_f32, 0.0);
+    /// ```
+    pub fn new(threshold: T, hysteresis: T) -> Self {
+        assert!(
+            hysteresis >= T::zero(),
+            "hysteresis band must not be negative"
+        );
+        SlicerNode {
+            threshold,
+            hysteresis,
+            state: None,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Slices a single sample, updating the internal state.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - Soft sample to make a decision on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::slicer::SlicerNode;
+    ///
+    /// let mut node = SlicerNode::new(0.0, 1.0);
+    /// assert_eq!(node.slice(0.7), 1);
+    /// // Inside the hysteresis band, so the output doesn't change.
+    /// assert_eq!(node.slice(-0.3), 1);
+    /// assert_eq!(node.slice(-0.7), 0);
+    /// ```
+    pub fn slice(&mut self, sample: T) -> u8 {
+        let half = self.hysteresis / T::from(2.0).unwrap();
+        let bit = match self.state {
+            Some(1) if sample < self.threshold - half => 0,
+            Some(0) if sample > self.threshold + half => 1,
+            Some(bit) => bit,
+            None => (sample > self.threshold) as u8,
+        };
+        self.state = Some(bit);
+        bit
+    }
+
+    /// Runs the `SlicerNode<T>`.  Produces a bit for each input sample.
+    pub fn run(&mut self, samples: &[T]) -> Result<Vec<u8>, NodeError> {
+        Ok(samples.iter().map(|x| self.slice(*x)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::slicer::*;
+    use rand::distributions::{Normal, Uniform};
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    fn transitions(bits: &[u8]) -> usize {
+        bits.windows(2).filter(|w| w[0] != w[1]).count()
+    }
+
+    #[test]
+    // Slices a noisy, oversampled two level signal with and without
+    // hysteresis.
+    fn test_slicer() {
+        let sps = 20;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bit_dist = Uniform::new(0, 2);
+        let noise = Normal::new(0.0, 0.3);
+        let bits: Vec<u8> = (0..500).map(|_| rng.sample(bit_dist)).collect();
+        let signal: Vec<f64> = bits
+            .iter()
+            .flat_map(|b| vec![2.0 * f64::from(*b) - 1.0; sps])
+            .map(|x| x + rng.sample(noise))
+            .collect();
+
+        let mut plain = SlicerNode::new(0.0, 0.0);
+        let mut hyst = SlicerNode::new(0.0, 1.2);
+        let plain_out = plain.run(&signal).unwrap();
+        let mut hyst_out = vec![];
+        for chunk in signal.chunks(77) {
+            hyst_out.extend(hyst.run(chunk).unwrap());
+        }
+
+        // Decisions at the end of each bit period should be correct.
+        for (ix, bit) in bits.iter().enumerate() {
+            assert_eq!(hyst_out[ix * sps + sps - 1], *bit);
+        }
+
+        let truth = transitions(&bits);
+        assert!(transitions(&plain_out) > truth);
+        assert!(transitions(&hyst_out) < transitions(&plain_out));
+        assert!(transitions(&hyst_out) <= truth + truth / 20);
+    }
+}
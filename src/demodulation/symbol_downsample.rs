@@ -0,0 +1,162 @@
+//! Picking out one sample per symbol at the estimated symbol instants.
+use crate::prelude::*;
+
+use crate::demodulation::farrow_filter::FarrowResampler;
+use num::Complex;
+
+/// A node that downsamples an oversampled signal to one sample per symbol.
+///
+/// The node takes in matched filter output at `sam_per_sym` samples per
+/// symbol, along with a timing estimate on a second input, and produces the
+/// samples at the symbol instants `k * sam_per_sym + tau`.  Since the
+/// estimate is generally fractional, the samples are interpolated with a
+/// cubic Farrow filter.  The timing estimate is in samples, as produced by
+/// `TimingEstimatorNode`, and only needs to arrive when it changes; until
+/// the first one arrives a timing offset of zero is used.
+///
+/// When a new estimate arrives, the sampling point is moved by the
+/// difference from the previous estimate, wrapped to within half a symbol.
+/// This keeps an estimate that wraps around from one side of the symbol to
+/// the other from dropping or repeating a symbol.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::symbol_downsample::SymbolDownsampleNode;
+///
+/// let node = SymbolDownsampleNode::new(4.0);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct SymbolDownsampleNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    pub timing: NodeReceiver<f64>,
+    sam_per_sym: f64,
+    tau: f64,
+    resampler: FarrowResampler,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl SymbolDownsampleNode {
+    /// Constructs a new `SymbolDownsampleNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sam_per_sym` - Samples per symbol of the input signal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::symbol_downsample::SymbolDownsampleNode;
+    ///
+    /// // Non-integer oversampling is fine too.
+    /// let node = SymbolDownsampleNode::new(3.2);
+    /// ```
+    pub fn new(sam_per_sym: f64) -> SymbolDownsampleNode {
+        SymbolDownsampleNode {
+            sam_per_sym,
+            tau: 0.0,
+            resampler: FarrowResampler::new(sam_per_sym),
+            input: Default::default(),
+            timing: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Updates the timing estimate, moving the sampling point to match.
+    ///
+    /// # Arguments
+    ///
+    /// * `tau` - Timing estimate in samples.
+    pub fn set_timing(&mut self, tau: f64) {
+        let sps = self.sam_per_sym;
+        let delta = tau - self.tau;
+        let delta = delta - sps * (delta / sps).round();
+        self.resampler.advance(delta);
+        self.tau = tau;
+    }
+
+    /// Runs the `SymbolDownsampleNode`.  Applies any new timing estimate,
+    /// then produces the symbols for the batch of samples if one was
+    /// received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<f64>>>,
+        timing: Option<f64>,
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        if let Some(tau) = timing {
+            self.set_timing(tau);
+        }
+        Ok(input.map(|samples| self.resampler.resample(&samples)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::symbol_downsample::*;
+    use crate::filter::fir::batch_fir;
+    use crate::util::channel_node::ChannelImpairmentNode;
+    use crate::util::math::rrc_taps;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Shapes QPSK symbols with an RRC filter, delays them by a fractional
+    // number of samples, matched filters them, and checks that sampling at
+    // the known timing offset gets the symbols back.
+    fn test_symbol_downsample() {
+        let sps = 4;
+        let n_taps = 8 * sps as u32 + 1;
+        let delay = 1.3;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let symbols: Vec<Complex<f64>> = (0..2000)
+            .map(|_| {
+                Complex::new(
+                    if rng.gen() { 1.0 } else { -1.0 },
+                    if rng.gen() { 1.0 } else { -1.0 },
+                )
+            })
+            .collect();
+
+        let taps: Vec<Complex<f64>> =
+            rrc_taps(n_taps, sps as f64, 0.35).unwrap();
+        let gain: f64 = taps.iter().map(|t| t.norm_sqr()).sum();
+        let upsampled: Vec<Complex<f64>> = symbols
+            .iter()
+            .flat_map(|s| {
+                let mut v = vec![Complex::new(0.0, 0.0); sps];
+                v[0] = *s;
+                v
+            })
+            .collect();
+        let mut state = vec![Complex::new(0.0, 0.0); n_taps as usize];
+        let shaped = batch_fir(&upsampled, &taps, &mut state);
+        let mut channel = ChannelImpairmentNode::new(0.0, delay, 0.0, 0.0);
+        let delayed = channel.run(&shaped).unwrap();
+        let mut state = vec![Complex::new(0.0, 0.0); n_taps as usize];
+        let matched: Vec<Complex<f64>> = batch_fir(&delayed, &taps, &mut state)
+            .iter()
+            .map(|x| x / gain)
+            .collect();
+
+        // Each RRC filter delays by half its length, so symbol k lands at
+        // k * sps + (n_taps - 1) + delay.
+        let total = (n_taps - 1) as f64 + delay;
+        let lag = (total / sps as f64).floor() as usize;
+        let tau = total - (lag * sps) as f64;
+
+        let mut node = SymbolDownsampleNode::new(sps as f64);
+        assert!(node.run(None, Some(tau)).unwrap().is_none());
+        let mut output = vec![];
+        for chunk in matched.chunks(250) {
+            output
+                .extend(node.run(Some(chunk.to_vec()), None).unwrap().unwrap());
+        }
+
+        assert!(output.len() > 1900);
+        for (y, x) in output.iter().skip(lag).zip(symbols.iter()).skip(10) {
+            assert!((y - x).norm() < 0.1);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Blind estimation of the symbol rate of a signal.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+
+/// A node that estimates the symbol rate of an unknown signal.
+///
+/// Linearly modulated signals are cyclostationary: their statistics repeat
+/// once per symbol.  Squaring the magnitude of the signal turns that into a
+/// spectral line at the symbol rate, even though the spectrum of the signal
+/// itself shows no sign of it.  This node takes the squared magnitude of
+/// each frame of `fft_size` samples, removes its mean, applies a Hann window
+/// and averages the magnitude of its spectrum across frames.  The strongest
+/// bin between DC and the Nyquist frequency, refined by parabolic
+/// interpolation, is taken as the symbol rate.
+///
+/// The line is strongest for signals with a large pulse shaping excess
+/// bandwidth, and vanishes for constant envelope signals like unfiltered PSK
+/// or FSK.  The input may be batched arbitrarily; each batch that completes at
+/// least one frame produces an updated estimate in Hz, from every frame seen
+/// so far.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::symbol_rate::SymbolRateEstimateNode;
+///
+/// let node = SymbolRateEstimateNode::new(4096, 1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct SymbolRateEstimateNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    sample_rate: f64,
+    window: Vec<f64>,
+    batch_fft: BatchFFT,
+    buffer: Vec<Complex<f64>>,
+    spectrum: Vec<f64>,
+    pub output: NodeSender<f64>,
+}
+
+impl SymbolRateEstimateNode {
+    /// Constructs a new `SymbolRateEstimateNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_size` - Number of samples in each frame.  The estimate is
+    ///   resolved to `sample_rate / fft_size` before interpolation.
+    /// * `sample_rate` - Sample rate of the input in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::symbol_rate::SymbolRateEstimateNode;
+    ///
+    /// let node = SymbolRateEstimateNode::new(1024, 48000.0);
+    /// ```
+    pub fn new(fft_size: usize, sample_rate: f64) -> SymbolRateEstimateNode {
+        assert!(fft_size >= 8, "FFT size must be at least 8");
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let mut planner = FFTplanner::new(false);
+        SymbolRateEstimateNode {
+            sample_rate,
+            window: Window::Hann.coefficients(fft_size),
+            batch_fft: BatchFFT::new(planner.plan_fft(fft_size), fft_size),
+            buffer: vec![],
+            spectrum: vec![0.0; fft_size / 2],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    // Adds the spectrum of the squared magnitude of one frame.
+    fn accumulate(&mut self, frame: &[Complex<f64>]) {
+        let power: Vec<f64> = frame.iter().map(|x| x.norm_sqr()).collect();
+        let mean = power.iter().sum::<f64>() / power.len() as f64;
+        let windowed: Vec<Complex<f64>> = power
+            .iter()
+            .zip(&self.window)
+            .map(|(p, w)| Complex::new((p - mean) * w, 0.0))
+            .collect();
+        let spectrum = self.batch_fft.run_fft(&windowed);
+        for (acc, x) in self.spectrum.iter_mut().zip(&spectrum) {
+            *acc += x.norm();
+        }
+    }
+
+    // Frequency of the strongest line, skipping the bins next to DC that
+    // the window leaks into.
+    fn estimate(&self) -> f64 {
+        let s = &self.spectrum;
+        let (peak, _) = s.iter().enumerate().skip(2).fold(
+            (2, f64::MIN),
+            |best, (ix, &p)| if p > best.1 { (ix, p) } else { best },
+        );
+        let mut frac = 0.0;
+        if peak + 1 < s.len() {
+            let (a, b, c) = (s[peak - 1], s[peak], s[peak + 1]);
+            let denom = a - 2.0 * b + c;
+            if denom.abs() > 0.0 {
+                frac = 0.5 * (a - c) / denom;
+            }
+        }
+        (peak as f64 + frac) * self.sample_rate / self.window.len() as f64
+    }
+
+    /// Runs the `SymbolRateEstimateNode`.  Produces the symbol rate estimate
+    /// in Hz once a batch completes a frame.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Option<f64>, NodeError> {
+        self.buffer.extend_from_slice(samples);
+        let fft_size = self.window.len();
+        let n_frames = self.buffer.len() / fft_size;
+        if n_frames == 0 {
+            return Ok(None);
+        }
+        let frames: Vec<Complex<f64>> =
+            self.buffer.drain(..n_frames * fft_size).collect();
+        for frame in frames.chunks_exact(fft_size) {
+            self.accumulate(frame);
+        }
+        Ok(Some(self.estimate()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::symbol_rate::*;
+    use crate::filter::fir_node::BatchFirNode;
+    use crate::util::math::rrc_taps;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Builds a root raised cosine shaped QPSK signal in noise at a known
+    // symbol rate, and checks that the estimate lands within a bin of it.
+    fn test_symbol_rate() {
+        let sample_rate = 1e6;
+        let sam_per_sym = 5;
+        let symbol_rate = sample_rate / sam_per_sym as f64;
+        let fft_size = 1024;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, 0.05);
+
+        let mut upsampled = vec![];
+        for _ in 0..4000 {
+            upsampled.push(Complex::new(
+                if rng.gen() { 1.0 } else { -1.0 },
+                if rng.gen() { 1.0 } else { -1.0 },
+            ));
+            upsampled.extend(vec![Complex::new(0.0, 0.0); sam_per_sym - 1]);
+        }
+        let taps = rrc_taps(61, sam_per_sym as f64, 0.35).unwrap();
+        let mut fir = BatchFirNode::new(taps, None);
+        let signal: Vec<Complex<f64>> = fir
+            .run(&upsampled)
+            .unwrap()
+            .iter()
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+
+        let mut node = SymbolRateEstimateNode::new(fft_size, sample_rate);
+        assert_eq!(node.run(&signal[..1000]).unwrap(), None);
+        let mut estimate = 0.0;
+        for chunk in signal[1000..].chunks(3000) {
+            if let Some(e) = node.run(chunk).unwrap() {
+                estimate = e;
+            }
+        }
+        let bin = sample_rate / fft_size as f64;
+        assert!((estimate - symbol_rate).abs() < bin);
+    }
+}
@@ -0,0 +1,362 @@
+//! Joint timing and carrier recovery for a complete symbol synchronizer.
+use crate::demodulation::farrow_filter::cubic_interpolate;
+use crate::filter::fir::batch_fir;
+use crate::prelude::*;
+use crate::util::math::{rrc_taps, sinc};
+
+use num::{Complex, Zero};
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+/// Number of symbols the automatic gain control averages the power over.
+const AGC_SYMBOLS: f64 = 100.0;
+
+/// Width of the matched filter in symbols.
+const FILTER_SPAN: f64 = 8.0;
+
+// Proportional and integral gains of a second order loop, critically damped
+// at a damping factor of 1/sqrt(2), with the noise bandwidth given as a
+// fraction of the update rate and the detector gain as the slope of the
+// error at lock.
+fn loop_gains(bandwidth: f64, detector_gain: f64) -> (f64, f64) {
+    let zeta = FRAC_1_SQRT_2;
+    let theta = bandwidth / (zeta + 0.25 / zeta);
+    let denom = (1.0 + 2.0 * zeta * theta + theta * theta) * detector_gain;
+    (4.0 * zeta * theta / denom, 4.0 * theta * theta / denom)
+}
+
+// Raised cosine pulse with unit peak, at a time in symbols.
+fn raised_cosine(t: f64, rolloff: f64) -> f64 {
+    let denom = 1.0 - (2.0 * rolloff * t).powi(2);
+    if denom.abs() < 1e-9 {
+        PI / 4.0 * sinc(0.5 / rolloff)
+    } else {
+        sinc(t) * (PI * rolloff * t).cos() / denom
+    }
+}
+
+// Slope at lock of the mean Gardner error for random unit power symbols, per
+// sample of timing offset, found from the raised cosine pulse the matched
+// filter leaves.
+fn gardner_gain(sam_per_sym: f64, rolloff: f64) -> f64 {
+    let mean_error = |tau: f64| -> f64 {
+        (-20..=20)
+            .map(|n| {
+                let t = n as f64 + tau / sam_per_sym;
+                (raised_cosine(t, rolloff) - raised_cosine(t - 1.0, rolloff))
+                    * raised_cosine(t - 0.5, rolloff)
+            })
+            .sum()
+    };
+    let step = 1e-3;
+    (mean_error(step) - mean_error(-step)) / (2.0 * step)
+}
+
+/// A node that recovers QPSK symbols from an oversampled, root raised cosine
+/// shaped signal.
+///
+/// Getting from raw samples to symbols takes a matched filter, a symbol
+/// timing loop and a carrier loop, which depend on each other for their
+/// gains, their ordering and the rates they run at.  This node wires them up
+/// in one place:
+///
+/// * The samples pass through a root raised cosine matched filter spanning
+///   eight symbols, then an automatic gain control averaging over about 100
+///   symbols, which scales the symbols to unit power so that the loop gains
+///   hold whatever the input level.
+/// * A Gardner timing error detector, which needs two samples per symbol
+///   and works regardless of the carrier phase, drives a second order loop
+///   that steps a cubic Farrow interpolator from one symbol to the next.
+///   The loop tracks both a fixed timing offset and an offset between the
+///   transmit and receive symbol clocks.
+/// * A Costas loop for QPSK derotates each symbol, taking the phase error
+///   from the nearest constellation point, and its second order loop tracks
+///   out a carrier frequency offset as well as the phase.
+///
+/// Both loops are critically damped, with their noise bandwidths given as a
+/// fraction of the symbol rate.  Wider loops pull in larger offsets and
+/// lock sooner, while narrower ones leave less jitter on the symbols, and
+/// around 1% for the timing loop and 2% for the carrier loop suits most
+/// links.  The carrier loop only pulls in frequency offsets up to a fraction
+/// of its bandwidth, so a larger offset should be brought in first with a
+/// coarse correction, such as from `AfcNode`.
+///
+/// The output symbols are at one per symbol with unit power, on the points
+/// `(+/-1 +/-j) / sqrt(2)` once the loops have locked.  As with any Costas
+/// loop, the carrier phase can settle on any of the four rotations of the
+/// constellation, which a preamble or differential coding has to resolve.
+/// The state of the filter and both loops carries across batches, so the
+/// samples may be batched arbitrarily.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::symbol_sync::SymbolSynchronizerNode;
+///
+/// // Four samples per symbol, a rolloff of 0.35, and loop bandwidths of 1%
+/// // and 2% of the symbol rate.
+/// let node = SymbolSynchronizerNode::new(4.0, 0.35, 0.01, 0.02);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SymbolSynchronizerNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    sam_per_sym: f64,
+    taps: Vec<Complex<f64>>,
+    state: Vec<Complex<f64>>,
+    target_power: f64,
+    power: f64,
+    timing_gains: (f64, f64),
+    carrier_gains: (f64, f64),
+    history: Vec<Complex<f64>>,
+    time: f64,
+    prev: Complex<f64>,
+    clock_offset: f64,
+    phase: f64,
+    freq: f64,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl SymbolSynchronizerNode {
+    /// Constructs a new `SymbolSynchronizerNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sam_per_sym` - Nominal samples per symbol of the input, at least
+    ///   two.
+    /// * `rolloff` - Rolloff of the root raised cosine pulse shape, on the
+    ///   interval (0.0, 1.0].
+    /// * `timing_bw` - Noise bandwidth of the timing loop as a fraction of
+    ///   the symbol rate.
+    /// * `carrier_bw` - Noise bandwidth of the carrier loop as a fraction of
+    ///   the symbol rate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::symbol_sync::SymbolSynchronizerNode;
+    ///
+    /// let node = SymbolSynchronizerNode::new(2.0, 0.5, 0.005, 0.01);
+    /// ```
+    pub fn new(
+        sam_per_sym: f64,
+        rolloff: f64,
+        timing_bw: f64,
+        carrier_bw: f64,
+    ) -> SymbolSynchronizerNode {
+        assert!(sam_per_sym >= 2.0, "need at least two samples per symbol");
+        assert!(
+            rolloff > 0.0 && rolloff <= 1.0,
+            "rolloff must be on the interval (0.0, 1.0]"
+        );
+        assert!(
+            timing_bw > 0.0 && timing_bw < 0.5,
+            "timing loop bandwidth must be between zero and half"
+        );
+        assert!(
+            carrier_bw > 0.0 && carrier_bw < 0.5,
+            "carrier loop bandwidth must be between zero and half"
+        );
+        let n_taps = (FILTER_SPAN * sam_per_sym).round() as u32 | 1;
+        let taps = rrc_taps(n_taps, sam_per_sym, rolloff).unwrap();
+
+        // The matched filter output is a raised cosine pulse train, whose
+        // power is below that of the symbols by a quarter of the rolloff.
+        let half = (sam_per_sym / 2.0).ceil();
+        SymbolSynchronizerNode {
+            sam_per_sym,
+            taps,
+            state: vec![Complex::zero(); n_taps as usize],
+            target_power: 1.0 - rolloff / 4.0,
+            power: 0.0,
+            timing_gains: loop_gains(
+                timing_bw,
+                gardner_gain(sam_per_sym, rolloff),
+            ),
+            carrier_gains: loop_gains(carrier_bw, 1.0),
+            history: vec![Complex::zero(); half as usize + 1],
+            time: sam_per_sym / 2.0 + 1.0,
+            prev: Complex::zero(),
+            clock_offset: 0.0,
+            phase: 0.0,
+            freq: 0.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the samples per symbol the timing loop is tracking, which
+    /// differs from the nominal rate by any offset between the clocks.
+    pub fn sam_per_sym(&self) -> f64 {
+        self.sam_per_sym - self.clock_offset
+    }
+
+    /// Returns the carrier frequency offset the carrier loop is tracking, in
+    /// cycles per sample.
+    pub fn frequency(&self) -> f64 {
+        self.freq / (2.0 * PI * self.sam_per_sym())
+    }
+
+    // Interpolates the filtered samples at a fractional index.
+    fn interpolate(&self, time: f64) -> Complex<f64> {
+        let ix = time.floor() as usize;
+        let x = [
+            self.history[ix - 1],
+            self.history[ix],
+            self.history[ix + 1],
+            self.history[ix + 2],
+        ];
+        cubic_interpolate(&x, time - ix as f64)
+    }
+
+    /// Runs the `SymbolSynchronizerNode`.  Produces the symbols recovered
+    /// from the batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        if samples
+            .iter()
+            .any(|x| !x.re.is_finite() || !x.im.is_finite())
+        {
+            return Err(NodeError::DataError);
+        }
+        let filtered = batch_fir(samples, &self.taps, &mut self.state);
+
+        // Start the gain control from the power of the first batch with any
+        // signal in it, so it doesn't have to ramp up from nothing.
+        if self.power == 0.0 && !filtered.is_empty() {
+            self.power = filtered.iter().map(|x| x.norm_sqr()).sum::<f64>()
+                / filtered.len() as f64;
+        }
+        let alpha = 1.0 / (AGC_SYMBOLS * self.sam_per_sym);
+        for x in filtered {
+            if self.power > 0.0 {
+                self.power += alpha * (x.norm_sqr() - self.power);
+                self.history
+                    .push(x * (self.target_power / self.power).sqrt());
+            } else {
+                self.history.push(x);
+            }
+        }
+
+        let sps = self.sam_per_sym;
+        let (kp_t, ki_t) = self.timing_gains;
+        let (kp_c, ki_c) = self.carrier_gains;
+        let mut symbols = vec![];
+        while (self.time.floor() as usize) + 2 < self.history.len() {
+            // Gardner timing error, positive when sampling late.
+            let y = self.interpolate(self.time);
+            let mid = self.interpolate(self.time - sps / 2.0);
+            let error = ((y - self.prev) * mid.conj()).re;
+            self.prev = y;
+            self.clock_offset += ki_t * error;
+            let step = sps - kp_t * error - self.clock_offset;
+            self.time += step.clamp(sps / 2.0, 1.5 * sps);
+
+            // Costas error against the nearest QPSK point.
+            let z = y * Complex::from_polar(1.0, -self.phase);
+            let decision =
+                Complex::new(z.re.signum(), z.im.signum()) * FRAC_1_SQRT_2;
+            let error = (z * decision.conj()).im;
+            self.freq += ki_c * error;
+            self.phase =
+                (self.phase + kp_c * error + self.freq).rem_euclid(2.0 * PI);
+            symbols.push(z);
+        }
+
+        // Keep enough history to interpolate half a symbol back.
+        let drop = (self.time - sps / 2.0 - 1.0).floor().max(0.0) as usize;
+        let drop = drop.min(self.history.len());
+        self.history.drain(..drop);
+        self.time -= drop as f64;
+        Ok(symbols)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::farrow_filter::FarrowResampler;
+    use crate::demodulation::symbol_sync::*;
+    use crate::util::channel_node::ChannelImpairmentNode;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Shapes random QPSK symbols with an RRC filter, runs them through a
+    // channel with a symbol clock offset, a fractional timing offset, a
+    // carrier frequency and phase offset and noise, and checks that once the
+    // loops have locked every symbol comes out on the one sent, up to the
+    // rotation the Costas loop settles on.
+    fn test_symbol_synchronizer() {
+        let sps = 4;
+        let cfo = 0.002;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let a = FRAC_1_SQRT_2;
+        let symbols: Vec<Complex<f64>> = (0..20000)
+            .map(|_| {
+                Complex::new(
+                    if rng.gen() { a } else { -a },
+                    if rng.gen() { a } else { -a },
+                )
+            })
+            .collect();
+
+        let taps: Vec<Complex<f64>> =
+            rrc_taps(8 * sps as u32 + 1, sps as f64, 0.35).unwrap();
+        let mut upsampled = vec![Complex::zero(); symbols.len() * sps];
+        for (i, s) in symbols.iter().enumerate() {
+            upsampled[i * sps] = *s * 0.3;
+        }
+        let mut state = vec![Complex::zero(); taps.len()];
+        let shaped = batch_fir(&upsampled, &taps, &mut state);
+        let mut clock = FarrowResampler::new(1.0 + 1e-4);
+        let mut channel =
+            ChannelImpairmentNode::new(cfo, 1.6, 0.7, 0.002).with_seed(0);
+
+        let mut node =
+            SymbolSynchronizerNode::new(sps as f64, 0.35, 0.01, 0.02);
+        let mut output = vec![];
+        for chunk in shaped.chunks(1000) {
+            let received = channel.impair(&clock.resample(chunk));
+            output.extend(node.run(&received).unwrap());
+        }
+        // The estimates wander with the noise in the loops, the symbol clock
+        // more so since the timing loop is the narrower of the two.
+        assert!(output.len() > 19980);
+        assert!((node.frequency() - cfo).abs() < 5e-5);
+        assert!((node.sam_per_sym() - 4.0 / (1.0 + 1e-4)).abs() < 2e-3);
+
+        // Line the output up with the symbols sent, after the filter delays
+        // and whichever rotation the carrier loop locked to, over the second
+        // half of the run.
+        let settled = 10000;
+        let rotations = [
+            Complex::new(1.0, 0.0),
+            Complex::new(0.0, 1.0),
+            Complex::new(-1.0, 0.0),
+            Complex::new(0.0, -1.0),
+        ];
+        let mut best = (0, rotations[0], f64::INFINITY);
+        for lag in 0..20 {
+            for rot in &rotations {
+                let err: f64 = (settled..settled + 100)
+                    .map(|i| (output[i] * rot - symbols[i - lag]).norm_sqr())
+                    .sum();
+                if err < best.2 {
+                    best = (lag, *rot, err);
+                }
+            }
+        }
+        let (lag, rot, _) = best;
+        let mut error_sum = 0.0;
+        for i in settled..output.len() {
+            let y = output[i] * rot;
+            let x = symbols[i - lag];
+            assert_eq!(y.re > 0.0, x.re > 0.0);
+            assert_eq!(y.im > 0.0, x.im > 0.0);
+            error_sum += (y - x).norm_sqr();
+        }
+        let evm = (error_sum / (output.len() - settled) as f64).sqrt();
+        assert!(evm < 0.2, "EVM {}", evm);
+    }
+}
@@ -0,0 +1,200 @@
+//! Correcting the symbol timing of a stream block by block.
+use crate::prelude::*;
+
+use crate::demodulation::farrow_filter::cubic_interpolate;
+use num::{Complex, Zero};
+
+/// A node that shifts an oversampled signal so that its symbol instants land
+/// on whole multiples of the samples per symbol.
+///
+/// Each block of samples arrives along with a timing estimate for it, in
+/// samples, as produced by `TimingEstimatorNode` from the same block.  With
+/// the symbol instants at `k * sam_per_sym + tau`, output sample `n` is the
+/// input interpolated at `n + tau` with a cubic Farrow filter, so the output
+/// is still at `sam_per_sym` samples per symbol but with the symbols at
+/// `k * sam_per_sym`.  This lines the signal up for any later node that
+/// expects symbol aligned samples, such as a fixed decimation.
+///
+/// The sampling point is carried from one block to the next, and a new
+/// estimate only moves it by the difference from the previous one, wrapped
+/// to within half a symbol.  The output is therefore continuous across
+/// blocks, and an estimate that wraps from one side of the symbol to the
+/// other doesn't drop or repeat a symbol.  Enough input history is kept to
+/// move the sampling point back by half a symbol, and the input before the
+/// first sample is taken to be zero.  The interpolator needs two samples
+/// past the one being produced, so the output trails the input by a couple
+/// of samples, and a block's length may differ from the input block's by
+/// however far the timing moved.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::demodulation::timing_correct::TimingCorrectNode;
+///
+/// let node = TimingCorrectNode::new(4);
+/// ```
+#[derive(Node)]
+pub struct TimingCorrectNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    pub timing: NodeReceiver<f64>,
+    sam_per_sym: f64,
+    margin: usize,
+    tau: f64,
+    time: f64,
+    history: Vec<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl TimingCorrectNode {
+    /// Constructs a new `TimingCorrectNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sam_per_sym` - Samples per symbol of the input signal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::demodulation::timing_correct::TimingCorrectNode;
+    ///
+    /// let node = TimingCorrectNode::new(10);
+    /// ```
+    pub fn new(sam_per_sym: u32) -> TimingCorrectNode {
+        assert!(sam_per_sym > 0, "samples per symbol must be nonzero");
+        // History needed behind the sampling point to step back by half a
+        // symbol and still have the sample before it for the interpolator.
+        let margin = (sam_per_sym as usize).div_ceil(2) + 1;
+        TimingCorrectNode {
+            sam_per_sym: sam_per_sym as f64,
+            margin,
+            tau: 0.0,
+            time: margin as f64,
+            history: vec![Complex::zero(); margin],
+            input: Default::default(),
+            timing: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the timing offset currently being corrected, in samples.
+    /// This follows the estimates without wrapping, so it can drift past a
+    /// whole symbol when the symbol clock is off.
+    pub fn timing(&self) -> f64 {
+        self.tau
+    }
+
+    /// Runs the `TimingCorrectNode`.  Moves the sampling point to the new
+    /// timing estimate and produces the resampled block.
+    pub fn run(
+        &mut self,
+        input: Vec<Complex<f64>>,
+        timing: f64,
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        if !timing.is_finite() {
+            return Err(NodeError::DataError);
+        }
+        let sps = self.sam_per_sym;
+        let delta = timing - self.tau;
+        let delta = delta - sps * (delta / sps).round();
+        self.tau += delta;
+        self.time = (self.time + delta).max(1.0);
+
+        self.history.extend(input);
+        let mut output = vec![];
+        loop {
+            let ix = self.time.floor() as usize;
+            if ix + 2 >= self.history.len() {
+                break;
+            }
+            let x = [
+                self.history[ix - 1],
+                self.history[ix],
+                self.history[ix + 1],
+                self.history[ix + 2],
+            ];
+            output.push(cubic_interpolate(&x, self.time - ix as f64));
+            self.time += 1.0;
+        }
+
+        // Keep enough history to step back by half a symbol next time.
+        let keep = self.margin as f64;
+        let drop = (self.time - keep).floor().max(0.0) as usize;
+        let drop = drop.min(self.history.len());
+        self.history.drain(..drop);
+        self.time -= drop as f64;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demodulation::timing_correct::*;
+    use crate::demodulation::timing_estimator::TimingEstimatorNode;
+    use crate::filter::fir::batch_fir;
+    use crate::util::channel_node::ChannelImpairmentNode;
+    use crate::util::math::rrc_taps;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Shapes QPSK symbols with an RRC filter, delays them by a fractional
+    // number of samples and matched filters them, then corrects the timing
+    // block by block from the estimator and checks that every symbol of
+    // every block lands back on a multiple of the samples per symbol.
+    fn test_timing_correct() {
+        let sps = 4;
+        let n_taps = 8 * sps + 1;
+        let delay = 1.3;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let symbols: Vec<Complex<f64>> = (0..5000)
+            .map(|_| {
+                Complex::new(
+                    if rng.gen() { 1.0 } else { -1.0 },
+                    if rng.gen() { 1.0 } else { -1.0 },
+                )
+            })
+            .collect();
+
+        let taps: Vec<Complex<f64>> =
+            rrc_taps(n_taps, sps as f64, 0.35).unwrap();
+        let gain: f64 = taps.iter().map(|t| t.norm_sqr()).sum();
+        let upsampled: Vec<Complex<f64>> = symbols
+            .iter()
+            .flat_map(|s| {
+                let mut v = vec![Complex::zero(); sps as usize];
+                v[0] = *s;
+                v
+            })
+            .collect();
+        let mut state = vec![Complex::zero(); n_taps as usize];
+        let shaped = batch_fir(&upsampled, &taps, &mut state);
+        let mut channel = ChannelImpairmentNode::new(0.0, delay, 0.0, 0.0);
+        let delayed = channel.run(&shaped).unwrap();
+        let mut state = vec![Complex::zero(); n_taps as usize];
+        let matched: Vec<Complex<f64>> = batch_fir(&delayed, &taps, &mut state)
+            .iter()
+            .map(|x| x / gain)
+            .collect();
+
+        let mut estimator = TimingEstimatorNode::new(sps, 5, 0.35).unwrap();
+        let mut node = TimingCorrectNode::new(sps);
+        let mut output = vec![];
+        for block in matched.chunks(400) {
+            let tau = estimator.run(block).unwrap();
+            assert!((tau - delay).abs() < 0.1);
+            output.extend(node.run(block.to_vec(), tau).unwrap());
+        }
+        assert!((node.timing() - delay).abs() < 0.1);
+        assert!(output.len() > matched.len() - 4);
+
+        // Each RRC filter delays by half its length, a whole number of
+        // symbols.
+        let lag = n_taps as usize - 1;
+        let aligned: Vec<_> =
+            output[lag..].iter().step_by(sps as usize).collect();
+        assert!(aligned.len() > 4900);
+        for (y, x) in aligned.iter().zip(&symbols) {
+            assert!((*y - x).norm() < 0.1);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Measurement of adjacent channel power ratio.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+
+/// A node that measures the adjacent channel power ratio of a signal.
+///
+/// Each input frame is windowed with a Hann window and transformed, and the
+/// power spectra of `n_averages` frames are averaged together.  The power in
+/// a band is the sum of the bins whose center frequency lies within it, and
+/// the ACPR of each adjacent band is its power relative to the power of the
+/// main channel, in dB.  This is the usual check that a transmitter isn't
+/// splattering into its neighbors, as happens when an amplifier is driven
+/// into compression.
+///
+/// Bands are given as `(low, high)` frequencies in Hz, relative to the
+/// center of the input, and the bins from `low` up to but not including
+/// `high` are counted, so that bands which share an edge never share a bin.
+/// The bin at the Nyquist frequency counts as -fs/2.  Leakage of the window
+/// limits how far below the main channel an adjacent band can be measured,
+/// to about 100 dB once the band is more than a few bins from the main
+/// channel, and the spectral resolution is the sample rate divided by the
+/// frame length, so frames should be long enough to put plenty of bins in
+/// each band.
+///
+/// One set of ratios, in the same order as the adjacent bands, is produced
+/// for every `n_averages` frames, and the average then starts over.  All of
+/// the frames must be the same length, set by the first frame, or a
+/// `NodeError::DataError` is produced.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::acpr_node::AcprNode;
+///
+/// // A 100 kHz channel with its lower and upper neighbors, 150 kHz away.
+/// let node = AcprNode::new(
+///     (-50e3, 50e3),
+///     vec![(-200e3, -100e3), (100e3, 200e3)],
+///     1e6,
+/// )
+/// .with_averages(32);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct AcprNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    main: (f64, f64),
+    adjacent: Vec<(f64, f64)>,
+    sample_rate: f64,
+    n_averages: usize,
+    window: Vec<f64>,
+    batch_fft: Option<BatchFFT>,
+    accum: Vec<f64>,
+    count: usize,
+    pub output: NodeSender<Vec<f64>>,
+}
+
+impl AcprNode {
+    /// Constructs a new `AcprNode` that reports on every frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `main` - Edges of the main channel in Hz.
+    /// * `adjacent` - Edges of each adjacent band in Hz.
+    /// * `sample_rate` - Sample rate of the input in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::acpr_node::AcprNode;
+    ///
+    /// let node = AcprNode::new((-5e3, 5e3), vec![(7.5e3, 17.5e3)], 48e3);
+    /// ```
+    pub fn new(
+        main: (f64, f64),
+        adjacent: Vec<(f64, f64)>,
+        sample_rate: f64,
+    ) -> AcprNode {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        assert!(!adjacent.is_empty(), "there must be an adjacent band");
+        for &(low, high) in adjacent.iter().chain(Some(&main)) {
+            assert!(low < high, "band edges must be in increasing order");
+            assert!(
+                low >= -sample_rate / 2.0 && high <= sample_rate / 2.0,
+                "bands must lie within the sampled bandwidth"
+            );
+        }
+        AcprNode {
+            main,
+            adjacent,
+            sample_rate,
+            n_averages: 1,
+            window: vec![],
+            batch_fft: None,
+            accum: vec![],
+            count: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the number of frames whose spectra are averaged for each
+    /// measurement.
+    pub fn with_averages(mut self, n_averages: usize) -> Self {
+        assert!(n_averages > 0, "number of averages must be nonzero");
+        self.n_averages = n_averages;
+        self
+    }
+
+    // Total power of the bins within a band of the averaged spectrum, from
+    // the low edge up to but not including the high edge.
+    fn band_power(&self, (low, high): (f64, f64)) -> f64 {
+        let n = self.accum.len();
+        let bin_width = self.sample_rate / n as f64;
+        let first = (low / bin_width).ceil() as isize;
+        let end = (high / bin_width).ceil() as isize;
+        (first..end)
+            .map(|k| self.accum[(k + n as isize) as usize % n])
+            .sum()
+    }
+
+    /// Runs the `AcprNode`.  Produces the ACPR of each adjacent band in dB
+    /// once enough frames have been averaged.
+    pub fn run(
+        &mut self,
+        frame: &[Complex<f64>],
+    ) -> Result<Option<Vec<f64>>, NodeError> {
+        if self.batch_fft.is_none() {
+            if frame.is_empty() {
+                return Err(NodeError::DataError);
+            }
+            let mut planner = FFTplanner::new(false);
+            self.batch_fft =
+                Some(BatchFFT::new(planner.plan_fft(frame.len()), frame.len()));
+            self.window = Window::Hann.coefficients(frame.len());
+            self.accum = vec![0.0; frame.len()];
+        }
+        if frame.len() != self.window.len() {
+            return Err(NodeError::DataError);
+        }
+
+        let windowed: Vec<Complex<f64>> =
+            frame.iter().zip(&self.window).map(|(x, w)| x * w).collect();
+        let spectrum = self.batch_fft.as_mut().unwrap().run_fft(&windowed);
+        for (a, x) in self.accum.iter_mut().zip(&spectrum) {
+            *a += x.norm_sqr();
+        }
+        self.count += 1;
+        if self.count < self.n_averages {
+            return Ok(None);
+        }
+
+        let main = self.band_power(self.main).max(f64::MIN_POSITIVE);
+        let ratios = self
+            .adjacent
+            .iter()
+            .map(|&band| {
+                let ratio = self.band_power(band) / main;
+                10.0 * ratio.max(f64::MIN_POSITIVE).log10()
+            })
+            .collect();
+        for a in self.accum.iter_mut() {
+            *a = 0.0;
+        }
+        self.count = 0;
+        Ok(Some(ratios))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::acpr_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Builds a signal that fills a main channel and two adjacent bands at
+    // known power levels, and checks the reported ACPR against the ratio of
+    // the powers.
+    fn test_acpr() {
+        let sample_rate = 1e6;
+        let fft_size = 1000;
+        let n_averages = 200;
+
+        // Random phase tones every 3 kHz between the band edges stand in for
+        // band limited noise with a known power.  They sit on bin centers and
+        // far enough apart that their leakage through the window doesn't
+        // overlap.
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 1e-4);
+        let mut tones = vec![];
+        for &(low, high, power) in &[
+            (-39e3, 39e3, 1.0),
+            (-180e3, -120e3, 1e-3),
+            (120e3, 180e3, 1e-5),
+        ] {
+            let freqs: Vec<f64> = (0..)
+                .map(|k| low + 3e3 * k as f64)
+                .take_while(|&f| f <= high)
+                .collect();
+            let amp = (power / freqs.len() as f64).sqrt();
+            for f in freqs {
+                tones.push((f, amp, rng.gen_range(0.0, 2.0 * PI)));
+            }
+        }
+        let signal: Vec<Complex<f64>> = (0..fft_size * n_averages)
+            .map(|n| {
+                let t = n as f64 / sample_rate;
+                tones
+                    .iter()
+                    .map(|&(f, a, p)| {
+                        Complex::from_polar(a, 2.0 * PI * f * t + p)
+                    })
+                    .sum::<Complex<f64>>()
+                    + Complex::new(rng.sample(dist), rng.sample(dist))
+            })
+            .collect();
+
+        let mut node = AcprNode::new(
+            (-50e3, 50e3),
+            vec![(-200e3, -100e3), (100e3, 200e3)],
+            sample_rate,
+        )
+        .with_averages(n_averages);
+        let mut acpr = None;
+        for frame in signal.chunks_exact(fft_size) {
+            if let Some(a) = node.run(frame).unwrap() {
+                acpr = Some(a);
+            }
+        }
+        let acpr = acpr.unwrap();
+        assert_eq!(acpr.len(), 2);
+        assert!((acpr[0] + 30.0).abs() < 0.1);
+        assert!((acpr[1] + 50.0).abs() < 0.1);
+
+        assert!(node.run(&signal[..100]).is_err());
+
+        // Each bin, including the one at the Nyquist frequency, belongs to
+        // exactly one of two bands that meet, and the whole band holds every
+        // bin once.
+        for &n in &[fft_size, fft_size + 1] {
+            node.accum = vec![1.0; n];
+            let half = sample_rate / 2.0;
+            assert_eq!(node.band_power((-half, half)), n as f64);
+            let split =
+                node.band_power((-half, 0.0)) + node.band_power((0.0, half));
+            assert_eq!(split, n as f64);
+        }
+    }
+}
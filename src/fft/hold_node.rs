@@ -0,0 +1,221 @@
+//! Nodes for holding the extremes of a spectrum across frames.
+//!
+//! These are the classic max hold and min hold traces of a spectrum analyzer.
+//! They take in frames of FFT magnitudes, or powers in dB, and keep the
+//! extreme value seen in each bin.  With a nonzero decay, the held value
+//! relaxes toward the current frame by that fraction of the difference each
+//! frame, so old peaks fade out instead of being held forever.
+use crate::prelude::*;
+
+/// Updates the held spectrum with a new frame.  `better` returns true when
+/// its first argument should replace the held value outright.
+fn update_hold(
+    held: &mut Vec<f64>,
+    frame: &[f64],
+    decay: f64,
+    better: fn(f64, f64) -> bool,
+) -> Result<(), NodeError> {
+    if held.is_empty() {
+        held.extend_from_slice(frame);
+        return Ok(());
+    }
+    if held.len() != frame.len() {
+        return Err(NodeError::DataError);
+    }
+    for (h, x) in held.iter_mut().zip(frame) {
+        if better(*x, *h) {
+            *h = *x;
+        } else {
+            *h += decay * (x - *h);
+        }
+    }
+    Ok(())
+}
+
+/// A node that holds the maximum of each bin of a spectrum across frames.
+///
+/// Each run emits the accumulated max hold spectrum, which is invaluable for
+/// catching intermittent signals that only show up in the odd frame.  All
+/// frames must be the same length as the first one received, or the node
+/// returns a `NodeError::DataError`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::hold_node::MaxHoldNode;
+///
+/// // Let peaks fade out by 1% of the way to the current frame each frame.
+/// let node = MaxHoldNode::new(0.01);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct MaxHoldNode {
+    pub input: NodeReceiver<Vec<f64>>,
+    decay: f64,
+    held: Vec<f64>,
+    pub output: NodeSender<Vec<f64>>,
+}
+
+impl MaxHoldNode {
+    /// Constructs a new `MaxHoldNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay` - Fraction of the way the held value moves toward the
+    ///   current frame each frame, on the interval [0.0, 1.0].  Zero holds
+    ///   peaks forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::hold_node::MaxHoldNode;
+    ///
+    /// // Infinite hold.
+    /// let node = MaxHoldNode::new(0.0);
+    /// ```
+    pub fn new(decay: f64) -> MaxHoldNode {
+        assert!(
+            (0.0..=1.0).contains(&decay),
+            "decay must be on the interval [0.0, 1.0]"
+        );
+        MaxHoldNode {
+            decay,
+            held: vec![],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Clears the held spectrum, so the next frame starts it over.
+    pub fn reset(&mut self) {
+        self.held.clear();
+    }
+
+    /// Runs the `MaxHoldNode`.  Produces the max hold spectrum including the
+    /// frame.
+    pub fn run(&mut self, frame: &[f64]) -> Result<Vec<f64>, NodeError> {
+        update_hold(&mut self.held, frame, self.decay, |x, h| x > h)?;
+        Ok(self.held.clone())
+    }
+}
+
+/// A node that holds the minimum of each bin of a spectrum across frames.
+///
+/// Each run emits the accumulated min hold spectrum, which shows the floor
+/// under intermittent signals.  All frames must be the same length as the
+/// first one received, or the node returns a `NodeError::DataError`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::hold_node::MinHoldNode;
+///
+/// let node = MinHoldNode::new(0.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct MinHoldNode {
+    pub input: NodeReceiver<Vec<f64>>,
+    decay: f64,
+    held: Vec<f64>,
+    pub output: NodeSender<Vec<f64>>,
+}
+
+impl MinHoldNode {
+    /// Constructs a new `MinHoldNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay` - Fraction of the way the held value moves toward the
+    ///   current frame each frame, on the interval [0.0, 1.0].  Zero holds
+    ///   the minimum forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::hold_node::MinHoldNode;
+    ///
+    /// let node = MinHoldNode::new(0.05);
+    /// ```
+    pub fn new(decay: f64) -> MinHoldNode {
+        assert!(
+            (0.0..=1.0).contains(&decay),
+            "decay must be on the interval [0.0, 1.0]"
+        );
+        MinHoldNode {
+            decay,
+            held: vec![],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Clears the held spectrum, so the next frame starts it over.
+    pub fn reset(&mut self) {
+        self.held.clear();
+    }
+
+    /// Runs the `MinHoldNode`.  Produces the min hold spectrum including the
+    /// frame.
+    pub fn run(&mut self, frame: &[f64]) -> Result<Vec<f64>, NodeError> {
+        update_hold(&mut self.held, frame, self.decay, |x, h| x < h)?;
+        Ok(self.held.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::hold_node::*;
+
+    #[test]
+    // Feeds a flat noise floor with a tone in a single frame, and checks that
+    // max hold keeps the tone while min hold ignores it.
+    fn test_hold_transient() {
+        let floor = vec![1.0; 64];
+        let mut tone = floor.clone();
+        tone[20] = 100.0;
+
+        let mut max_hold = MaxHoldNode::new(0.0);
+        let mut min_hold = MinHoldNode::new(0.0);
+        for ix in 0..50 {
+            let frame = if ix == 10 { &tone } else { &floor };
+            let max = max_hold.run(frame).unwrap();
+            let min = min_hold.run(frame).unwrap();
+            assert_eq!(min, floor);
+            if ix < 10 {
+                assert_eq!(max, floor);
+            } else {
+                assert_eq!(max, tone);
+            }
+        }
+
+        assert!(max_hold.run(&floor[1..]).is_err());
+        max_hold.reset();
+        assert_eq!(max_hold.run(&floor[1..]).unwrap(), floor[1..].to_vec());
+    }
+
+    #[test]
+    // Checks that with a decay the held peak fades back toward the floor.
+    fn test_hold_decay() {
+        let floor = vec![0.0; 8];
+        let mut tone = floor.clone();
+        tone[3] = 1.0;
+
+        let mut max_hold = MaxHoldNode::new(0.1);
+        max_hold.run(&tone).unwrap();
+        let mut peak = 1.0;
+        for _ in 0..20 {
+            peak *= 0.9;
+            let max = max_hold.run(&floor).unwrap();
+            assert!((max[3] - peak).abs() < 1e-12);
+        }
+
+        // A new peak replaces the faded one immediately.
+        assert_eq!(max_hold.run(&tone).unwrap(), tone);
+
+        let mut min_hold = MinHoldNode::new(0.5);
+        min_hold.run(&floor).unwrap();
+        let min = min_hold.run(&tone).unwrap();
+        assert_eq!(min[3], 0.5);
+    }
+}
@@ -0,0 +1,230 @@
+//! Detection of spectral lines such as pilots, clock leakage and carriers.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+
+/// A node that finds narrow, persistent peaks in the spectrum of a signal.
+///
+/// Each input frame is windowed with a Hann window and transformed, and the
+/// power spectra of `n_averages` frames are averaged together.  Averaging
+/// shrinks the fluctuation of the noise in each bin, while a steady tone keeps
+/// its power in the same few bins, so lines far too weak to see in a single
+/// frame stand out once enough frames have gone by.  The noise floor is taken
+/// as the median of the averaged spectrum, which a handful of lines can't
+/// drag up, and every local maximum more than `threshold` dB above it is
+/// reported as a line.
+///
+/// Each line is reported as its frequency, refined between bins with a
+/// parabola through the peak and its neighbors in dB, and its power in dB
+/// relative to a full scale complex exponential, summed over the main lobe of
+/// the window with the noise in those bins removed.  The frequency is in
+/// cycles per sample on the interval [-0.5, 0.5), or in Hz if a sample rate
+/// is given.  One list of lines, strongest first, is produced for every
+/// `n_averages` frames, and the average then starts over.
+///
+/// All of the frames must be the same length, set by the first frame, or a
+/// `NodeError::DataError` is produced.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::line_detect_node::SpectralLineDetectNode;
+///
+/// // Report lines 10 dB above the floor after averaging 64 frames.
+/// let node = SpectralLineDetectNode::new(64, 10.0).with_sample_rate(1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct SpectralLineDetectNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    n_averages: usize,
+    threshold: f64,
+    sample_rate: Option<f64>,
+    window: Vec<f64>,
+    batch_fft: Option<BatchFFT>,
+    accum: Vec<f64>,
+    count: usize,
+    pub output: NodeSender<Vec<(f64, f64)>>,
+}
+
+impl SpectralLineDetectNode {
+    /// Constructs a new `SpectralLineDetectNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_averages` - Number of frames averaged for each detection.
+    /// * `threshold` - Height in dB above the noise floor for a peak to be
+    ///   reported as a line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::line_detect_node::SpectralLineDetectNode;
+    ///
+    /// let node = SpectralLineDetectNode::new(100, 6.0);
+    /// ```
+    pub fn new(n_averages: usize, threshold: f64) -> SpectralLineDetectNode {
+        assert!(n_averages > 0, "number of averages must be nonzero");
+        SpectralLineDetectNode {
+            n_averages,
+            threshold,
+            sample_rate: None,
+            window: vec![],
+            batch_fft: None,
+            accum: vec![],
+            count: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the input in Hz, so that the frequencies of
+    /// the lines are reported in Hz.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    // Finds the lines in the averaged power spectrum.
+    fn detect(&self) -> Vec<(f64, f64)> {
+        let n = self.accum.len();
+        let spectrum: Vec<f64> =
+            self.accum.iter().map(|p| p / self.count as f64).collect();
+        let mut sorted = spectrum.clone();
+        sorted.sort_by(f64::total_cmp);
+        let floor = sorted[n / 2].max(f64::MIN_POSITIVE);
+        let limit = floor * 10.0_f64.powf(self.threshold / 10.0);
+
+        // Power of a full scale tone summed over the whole spectrum.
+        let full_scale =
+            n as f64 * self.window.iter().map(|w| w * w).sum::<f64>();
+        let db = |p: f64| 10.0 * p.max(f64::MIN_POSITIVE).log10();
+        let bin = |k: isize| spectrum[(k + n as isize) as usize % n];
+
+        let mut lines = vec![];
+        for k in 0..n as isize {
+            let p = bin(k);
+            if p <= limit || p < bin(k - 1) || p <= bin(k + 1) {
+                continue;
+            }
+            let (below, peak, above) = (db(bin(k - 1)), db(p), db(bin(k + 1)));
+            let denom = below - 2.0 * peak + above;
+            let delta = if denom < 0.0 {
+                0.5 * (below - above) / denom
+            } else {
+                0.0
+            };
+            let mut freq = (k as f64 + delta) / n as f64;
+            if freq >= 0.5 {
+                freq -= 1.0;
+            }
+            if let Some(fs) = self.sample_rate {
+                freq *= fs;
+            }
+
+            // The Hann window's main lobe spans two bins either side.
+            let lobe: f64 = (-2..=2).map(|m| bin(k + m) - floor).sum();
+            lines.push((freq, db(lobe / full_scale)));
+        }
+        lines.sort_by(|a, b| b.1.total_cmp(&a.1));
+        lines
+    }
+
+    /// Runs the `SpectralLineDetectNode`.  Produces the lines found once
+    /// enough frames have been averaged.
+    pub fn run(
+        &mut self,
+        frame: &[Complex<f64>],
+    ) -> Result<Option<Vec<(f64, f64)>>, NodeError> {
+        if self.batch_fft.is_none() {
+            if frame.len() < 5 {
+                return Err(NodeError::DataError);
+            }
+            let mut planner = FFTplanner::new(false);
+            self.batch_fft =
+                Some(BatchFFT::new(planner.plan_fft(frame.len()), frame.len()));
+            self.window = Window::Hann.coefficients(frame.len());
+            self.accum = vec![0.0; frame.len()];
+        }
+        if frame.len() != self.window.len() {
+            return Err(NodeError::DataError);
+        }
+
+        let windowed: Vec<Complex<f64>> =
+            frame.iter().zip(&self.window).map(|(x, w)| x * w).collect();
+        let spectrum = self.batch_fft.as_mut().unwrap().run_fft(&windowed);
+        for (a, x) in self.accum.iter_mut().zip(&spectrum) {
+            *a += x.norm_sqr();
+        }
+        self.count += 1;
+        if self.count < self.n_averages {
+            return Ok(None);
+        }
+
+        let lines = self.detect();
+        for a in self.accum.iter_mut() {
+            *a = 0.0;
+        }
+        self.count = 0;
+        Ok(Some(lines))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::line_detect_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Buries a pilot 30 dB below the total noise power and checks that after
+    // averaging it's the only line found, at the right frequency and power.
+    fn test_line_detect() {
+        let fft_size = 1024;
+        let n_averages = 1000;
+        let sample_rate = 1e6;
+        let pilot_freq = -123.4e3;
+        let pilot_db = -30.0;
+        let amp = 10.0_f64.powf(pilot_db / 20.0);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 0.5_f64.sqrt());
+        let signal: Vec<Complex<f64>> = (0..fft_size * n_averages)
+            .map(|n| {
+                let phase = 2.0 * PI * pilot_freq * n as f64 / sample_rate;
+                Complex::from_polar(amp, phase)
+                    + Complex::new(rng.sample(dist), rng.sample(dist))
+            })
+            .collect();
+
+        let mut node = SpectralLineDetectNode::new(n_averages, 1.5)
+            .with_sample_rate(sample_rate);
+        let mut lines = None;
+        for frame in signal.chunks_exact(fft_size) {
+            if let Some(l) = node.run(frame).unwrap() {
+                lines = Some(l);
+            }
+        }
+        let lines = lines.unwrap();
+        assert_eq!(lines.len(), 1);
+        let bin_width = sample_rate / fft_size as f64;
+        assert!((lines[0].0 - pilot_freq).abs() < 0.15 * bin_width);
+        assert!((lines[0].1 - pilot_db).abs() < 1.0);
+
+        assert!(node.run(&signal[..100]).is_err());
+
+        // A NaN sample spoils the average but doesn't stop the node.
+        let mut node = SpectralLineDetectNode::new(2, 1.5);
+        let mut frame = signal[..fft_size].to_vec();
+        frame[10] = Complex::new(f64::NAN, 0.0);
+        assert_eq!(node.run(&frame).unwrap(), None);
+        assert!(node.run(&signal[..fft_size]).unwrap().is_some());
+    }
+}
@@ -0,0 +1,283 @@
+//! Nodes for taking measurements from frames of frequency domain data.
+//!
+//! Unless otherwise noted, the nodes here expect frames in the natural order
+//! produced by the FFT nodes, with DC in the first bin and the negative
+//! frequencies in the upper half of the frame.
+use crate::prelude::*;
+
+/// Converts a bin index in natural FFT order to a frequency in Hz.
+fn bin_to_hz(bin: usize, fft_size: usize, sample_rate: f64) -> f64 {
+    let bin = if bin < fft_size - fft_size / 2 {
+        bin as f64
+    } else {
+        bin as f64 - fft_size as f64
+    };
+    bin * sample_rate / fft_size as f64
+}
+
+/// Returns the median of a set of values, or zero if there are none.
+fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    }
+}
+
+/// Returns the given percentile of a set of values, interpolating linearly
+/// between the closest ranks, or zero if there are none.
+fn percentile(values: &[f64], pct: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = pct / 100.0 * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+/// A node that finds the occupied portions of a spectrum.
+///
+/// Takes in a frame of FFT magnitudes and estimates the noise floor as the
+/// median power across all bins, which holds up well as long as less than
+/// half of the band is occupied.  Any run of adjacent bins whose power
+/// exceeds the floor by at least the threshold is reported as an occupied
+/// range, given as the `(low, high)` frequencies in Hz of the first and last
+/// bins in the run.  Ranges are reported in order of increasing frequency,
+/// from `-fs / 2` up to `fs / 2`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::measure_node::OccupancyNode;
+///
+/// // Report anything at least 10 dB above the noise floor.
+/// let node = OccupancyNode::new(10.0, 2.4e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct OccupancyNode {
+    pub input: NodeReceiver<Vec<f64>>,
+    threshold: f64,
+    sample_rate: f64,
+    pub output: NodeSender<Vec<(f64, f64)>>,
+}
+
+impl OccupancyNode {
+    /// Constructs a new `OccupancyNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Power in dB above the noise floor for a bin to be
+    ///   considered occupied.
+    /// * `sample_rate` - Sample rate in Hz of the signal the FFT was taken
+    ///   of, used to convert bins to frequencies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::measure_node::OccupancyNode;
+    ///
+    /// let node = OccupancyNode::new(6.0, 48000.0);
+    /// ```
+    pub fn new(threshold: f64, sample_rate: f64) -> OccupancyNode {
+        OccupancyNode {
+            threshold,
+            sample_rate,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `OccupancyNode`.  Produces the list of occupied frequency
+    /// ranges in the frame.
+    pub fn run(&mut self, frame: &[f64]) -> Result<Vec<(f64, f64)>, NodeError> {
+        let n = frame.len();
+        let power: Vec<f64> = frame.iter().map(|x| x * x).collect();
+        let limit = median(&power) * 10.0_f64.powf(self.threshold / 10.0);
+
+        // Walk the bins from the most negative frequency to the most
+        // positive so that each range is contiguous in frequency.
+        let mut ranges = vec![];
+        let mut start = None;
+        let mut prev = 0;
+        let half = n - n / 2;
+        for bin in (half..n).chain(0..half) {
+            let occupied = power[bin] > limit;
+            match (occupied, start) {
+                (true, None) => start = Some(bin),
+                (false, Some(first)) => {
+                    ranges.push((first, prev));
+                    start = None;
+                }
+                _ => (),
+            }
+            prev = bin;
+        }
+        if let Some(first) = start {
+            ranges.push((first, prev));
+        }
+
+        Ok(ranges
+            .iter()
+            .map(|(lo, hi)| {
+                (
+                    bin_to_hz(*lo, n, self.sample_rate),
+                    bin_to_hz(*hi, n, self.sample_rate),
+                )
+            })
+            .collect())
+    }
+}
+
+/// A node that estimates the SNR of the strongest signal in a spectrum.
+///
+/// Takes in a frame of FFT magnitudes and estimates the noise floor from a
+/// percentile of the power across all bins.  The power of a noise bin is
+/// exponentially distributed, so its `p`th percentile sits at
+/// `-ln(1 - p / 100)` times the mean noise power, and the percentile is
+/// divided by that to give an unbiased floor.  The median is a good choice of
+/// percentile when less than half of the band is occupied; lower percentiles
+/// hold up better in crowded spectra.
+///
+/// The SNR is reported in dB as the ratio of the signal power in the
+/// strongest bin, with the floor taken out, to the noise floor.  Since this is
+/// a per-bin measurement, the SNR is that of the signal in the bandwidth of a
+/// single bin, which depends on the FFT size and window.  Nothing is produced
+/// for a frame that is empty, has no noise floor or has no bin above the
+/// floor.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::measure_node::SnrEstimateNode;
+///
+/// let node = SnrEstimateNode::new(50.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct SnrEstimateNode {
+    pub input: NodeReceiver<Vec<f64>>,
+    percentile: f64,
+    pub output: NodeSender<f64>,
+}
+
+impl SnrEstimateNode {
+    /// Constructs a new `SnrEstimateNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `percentile` - Percentile of the bin powers, on the interval
+    ///   (0.0, 100.0), to estimate the noise floor from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::measure_node::SnrEstimateNode;
+    ///
+    /// // Use the lower quartile in a busy band.
+    /// let node = SnrEstimateNode::new(25.0);
+    /// ```
+    pub fn new(percentile: f64) -> SnrEstimateNode {
+        assert!(
+            percentile > 0.0 && percentile < 100.0,
+            "percentile must be on the interval (0.0, 100.0)"
+        );
+        SnrEstimateNode {
+            percentile,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `SnrEstimateNode`.  Produces the estimated SNR in dB, if
+    /// there is one.
+    pub fn run(&mut self, frame: &[f64]) -> Result<Option<f64>, NodeError> {
+        if frame.is_empty() {
+            return Ok(None);
+        }
+        let power: Vec<f64> = frame.iter().map(|x| x * x).collect();
+        let scale = -(1.0 - self.percentile / 100.0).ln();
+        let floor = percentile(&power, self.percentile) / scale;
+        let peak = power.iter().cloned().fold(0.0, f64::max);
+        if floor <= 0.0 || peak <= floor {
+            return Ok(None);
+        }
+        Ok(Some(10.0 * ((peak - floor) / floor).log10()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::measure_node::*;
+
+    #[test]
+    // Puts two signals over a flat noise floor, one on each side of DC, and
+    // checks that both are found.
+    fn test_occupancy() {
+        let mut frame = vec![1.0; 1024];
+        for bin in &mut frame[100..110] {
+            *bin = 10.0;
+        }
+        for bin in &mut frame[900..920] {
+            *bin = 5.0;
+        }
+
+        // With 1024 bins at 1024 Hz, each bin is 1 Hz wide.
+        let mut node = OccupancyNode::new(10.0, 1024.0);
+        let ranges = node.run(&frame).unwrap();
+        assert_eq!(ranges, vec![(-124.0, -105.0), (100.0, 109.0)]);
+
+        // Neither signal is 30 dB above the floor.
+        let mut node = OccupancyNode::new(30.0, 1024.0);
+        assert!(node.run(&frame).unwrap().is_empty());
+    }
+
+    #[test]
+    // Puts a tone 30 dB above a noise floor of random bin magnitudes and
+    // checks the estimate.
+    fn test_snr_estimate() {
+        use rand::distributions::Normal;
+        use rand::prelude::*;
+        use rand::rngs::SmallRng;
+
+        // Bin magnitudes of complex gaussian noise are Rayleigh distributed,
+        // with unit mean power here.
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, (0.5_f64).sqrt());
+        let mut frame: Vec<f64> = (0..4096)
+            .map(|_| rng.sample(dist).hypot(rng.sample(dist)))
+            .collect();
+        frame[300] = 1000.0_f64.sqrt();
+
+        let mut node = SnrEstimateNode::new(50.0);
+        let snr = node.run(&frame).unwrap().unwrap();
+        assert!((snr - 30.0).abs() < 0.3, "snr {}", snr);
+
+        // The lower quartile gives the same estimate.
+        let mut node = SnrEstimateNode::new(25.0);
+        let snr = node.run(&frame).unwrap().unwrap();
+        assert!((snr - 30.0).abs() < 0.3, "snr {}", snr);
+
+        // Nothing is produced without a noise floor or a signal above it.
+        assert_eq!(node.run(&[]).unwrap(), None);
+        let mut sparse = vec![0.0; 64];
+        sparse[10] = 1.0;
+        assert_eq!(node.run(&sparse).unwrap(), None);
+        assert_eq!(node.run(&[0.0; 64]).unwrap(), None);
+        assert_eq!(node.run(&[1.0; 64]).unwrap(), None);
+        assert_eq!(
+            percentile(&[4.0, 1.0, 3.0, 2.0], 50.0),
+            median(&[4.0, 1.0, 3.0, 2.0])
+        );
+    }
+}
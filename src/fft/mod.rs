@@ -1,6 +1,16 @@
 //! Nodes for performing FFTs and IFFTs.
 
+pub mod acpr_node;
 pub mod fft_node;
+pub mod hold_node;
+pub mod line_detect_node;
+pub mod measure_node;
+pub mod notch_node;
+pub mod obw_node;
+pub mod peak_track_node;
+pub mod psd_node;
+pub mod stft_node;
+pub mod tone_node;
 
 use num::Complex;
 use num::NumCast;
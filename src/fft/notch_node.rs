@@ -0,0 +1,264 @@
+//! Excision of narrowband interference in the frequency domain.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+use crate::util::math::cast_complex;
+
+use num::{Complex, Float, Zero};
+use rustfft::FFTplanner;
+
+/// A node that applies a gain to each FFT bin of a signal, notching out the
+/// bins that hold interference.
+///
+/// The signal is cut into frames of `fft_size` samples overlapping by half,
+/// each multiplied by a Hann window and transformed.  Every bin is scaled by
+/// its entry in the mask, zero to remove it entirely or somewhere between
+/// zero and one to attenuate it, and the frames are transformed back and
+/// overlap added.  With 50% overlap the Hann windows sum to one, so an all
+/// ones mask passes the signal through unchanged, and the window keeps the
+/// leakage of an interferer confined to the bins next to it, so notching a
+/// narrowband interferer removes little of a wideband signal underneath it.
+///
+/// The mask is indexed in the natural order of the FFT, starting from DC.
+/// A new mask on the `mask` control input replaces the current one starting
+/// with the batch it arrives with, so a detector flagging interference can
+/// steer the notches while the node runs.  A mask of the wrong length is
+/// ignored, and the node carries on with the mask it had.
+///
+/// The output lags the input by `fft_size / 2` samples.  The input may be
+/// batched arbitrarily; each run produces the samples for every half frame
+/// the batch completes, or nothing if it doesn't complete one.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::notch_node::SpectralNotchNode;
+///
+/// // Remove the interferer in bin 100 of a 1024 point FFT, along with the
+/// // bins its leakage spreads into.
+/// let mut mask = vec![1.0; 1024];
+/// for gain in &mut mask[99..=101] {
+///     *gain = 0.0;
+/// }
+/// let node: SpectralNotchNode<f32> = SpectralNotchNode::new(1024, mask);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct SpectralNotchNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    pub mask: NodeReceiver<Vec<f64>>,
+    gains: Vec<f64>,
+    window: Vec<f64>,
+    fft: BatchFFT,
+    ifft: BatchFFT,
+    frame: Vec<Complex<f64>>,
+    pending: Vec<Complex<f64>>,
+    overlap: Vec<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> SpectralNotchNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `SpectralNotchNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_size` - Number of samples in each frame.  Must be even.
+    /// * `mask` - Initial gain of each bin, `fft_size` of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::notch_node::SpectralNotchNode;
+    ///
+    /// // Start out passing everything, until a detector says otherwise.
+    /// let node: SpectralNotchNode<f64> =
+    ///     SpectralNotchNode::new(256, vec![1.0; 256]);
+    /// ```
+    pub fn new(fft_size: usize, mask: Vec<f64>) -> Self {
+        assert!(fft_size >= 2 && fft_size & 1 == 0, "FFT size must be even");
+        assert_eq!(mask.len(), fft_size, "mask must have a gain for each bin");
+        SpectralNotchNode {
+            gains: mask,
+            window: Window::Hann.coefficients(fft_size),
+            fft: BatchFFT::new(
+                FFTplanner::new(false).plan_fft(fft_size),
+                fft_size,
+            ),
+            ifft: BatchFFT::new(
+                FFTplanner::new(true).plan_fft(fft_size),
+                fft_size,
+            ),
+            frame: vec![Complex::zero(); fft_size],
+            pending: vec![],
+            overlap: vec![Complex::zero(); fft_size / 2],
+            input: Default::default(),
+            mask: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Replaces the gain of every bin.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - New gain of each bin, one for each bin of the FFT.
+    pub fn set_mask(&mut self, mask: &[f64]) -> Result<(), NodeError> {
+        if mask.len() != self.gains.len() {
+            return Err(NodeError::DataError);
+        }
+        self.gains.copy_from_slice(mask);
+        Ok(())
+    }
+
+    // Masks the current frame and returns the half frame of output it
+    // completes.
+    fn process_frame(&mut self) -> Vec<Complex<f64>> {
+        let half = self.overlap.len();
+        let windowed: Vec<Complex<f64>> = self
+            .frame
+            .iter()
+            .zip(&self.window)
+            .map(|(x, w)| x * w)
+            .collect();
+        let spectrum: Vec<Complex<f64>> = self
+            .fft
+            .run_fft(&windowed)
+            .iter()
+            .zip(&self.gains)
+            .map(|(x, g)| x * g)
+            .collect();
+        let scale = 1.0 / self.frame.len() as f64;
+        let frame = self.ifft.run_fft(&spectrum);
+        let out: Vec<Complex<f64>> = self
+            .overlap
+            .iter()
+            .zip(&frame[..half])
+            .map(|(o, y)| o + y * scale)
+            .collect();
+        for (o, y) in self.overlap.iter_mut().zip(&frame[half..]) {
+            *o = y * scale;
+        }
+        out
+    }
+
+    /// Runs the `SpectralNotchNode<T>`.  Updates the mask if a valid new one
+    /// has arrived, then produces the samples for every half frame
+    /// completed.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<T>>>,
+        mask: Option<Vec<f64>>,
+    ) -> Result<Option<Vec<Complex<T>>>, NodeError> {
+        if let Some(mask) = mask {
+            // Keep the current mask if the new one doesn't fit.
+            let _ = self.set_mask(&mask);
+        }
+        let samples = match input {
+            Some(samples) => samples,
+            None => return Ok(None),
+        };
+        self.pending
+            .extend(samples.iter().map(|x| cast_complex(x).unwrap()));
+
+        let half = self.overlap.len();
+        let n_hops = self.pending.len() / half;
+        if n_hops == 0 {
+            return Ok(None);
+        }
+        let mut output = Vec::with_capacity(n_hops * half);
+        let pending: Vec<Complex<f64>> =
+            self.pending.drain(..n_hops * half).collect();
+        for hop in pending.chunks_exact(half) {
+            self.frame.drain(..half);
+            self.frame.extend_from_slice(hop);
+            output.extend(
+                self.process_frame()
+                    .iter()
+                    .map(|y| cast_complex(y).unwrap()),
+            );
+        }
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::notch_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    // Mean power of the difference between two signals.
+    fn error_power(a: &[Complex<f64>], b: &[Complex<f64>]) -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).norm_sqr())
+            .sum::<f64>()
+            / a.len() as f64
+    }
+
+    #[test]
+    // Buries unit power white noise under an interferer 30 dB stronger and
+    // checks that notching the interferer's bins leaves the noise behind
+    // with little of either the interferer or the noise lost.
+    fn test_spectral_notch() {
+        let fft_size = 256;
+        let len = 64 * fft_size;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 0.5_f64.sqrt());
+        let wideband: Vec<Complex<f64>> = (0..len)
+            .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect();
+        let interferer = |n: usize| {
+            let freq = 50.0 / fft_size as f64;
+            Complex::from_polar(1000.0_f64.sqrt(), 2.0 * PI * freq * n as f64)
+        };
+        let received: Vec<Complex<f64>> = wideband
+            .iter()
+            .enumerate()
+            .map(|(n, x)| x + interferer(n))
+            .collect();
+
+        // An all ones mask passes the signal through, delayed.
+        let delay = fft_size / 2;
+        let mut node = SpectralNotchNode::new(fft_size, vec![1.0; fft_size]);
+        let mut output = vec![];
+        for chunk in received.chunks(1000) {
+            if let Some(out) = node.run(Some(chunk.to_vec()), None).unwrap() {
+                output.extend(out);
+            }
+        }
+        assert!(error_power(&output[delay..], &received) < 1e-20);
+
+        // Switching to a notch over the interferer leaves the noise, and a
+        // mask of the wrong length partway through is ignored.
+        let mut mask = vec![1.0; fft_size];
+        for gain in &mut mask[49..=51] {
+            *gain = 0.0;
+        }
+        let mut node = SpectralNotchNode::new(fft_size, vec![1.0; fft_size]);
+        assert_eq!(node.run(None, Some(mask)).unwrap(), None);
+        let mut output = vec![];
+        for (ix, chunk) in received.chunks(1000).enumerate() {
+            let bad = if ix == 5 { Some(vec![1.0; 10]) } else { None };
+            if let Some(out) = node.run(Some(chunk.to_vec()), bad).unwrap() {
+                output.extend(out);
+            }
+        }
+        // The interferer switching on at the start splatters across the
+        // first frame, so that's left out.
+        assert!(error_power(&received, &wideband) > 999.0);
+        let residual =
+            error_power(&output[delay + fft_size..], &wideband[fft_size..]);
+        assert!(residual < 0.05, "residual {}", residual);
+        assert!(node.set_mask(&[1.0; 10]).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! Following the strongest spectral peaks of a signal from frame to frame.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+
+/// One point on a peak track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackPoint {
+    /// Identifier of the track, unique over the life of the node.
+    pub id: u64,
+    /// Index of the frame the peak was found in, counting from the first
+    /// frame the node received.
+    pub frame: u64,
+    /// Frequency of the peak, in cycles per sample or in Hz if a sample rate
+    /// was given.
+    pub freq: f64,
+    /// Power of the peak in dB relative to a full scale complex exponential.
+    pub power: f64,
+}
+
+// A track that is still being followed.
+struct Track {
+    id: u64,
+    freq: f64,
+}
+
+/// A node that tracks the frequencies of the strongest peaks in the
+/// spectrum of a signal over time.
+///
+/// Each input frame is windowed with a Hann window and transformed, making
+/// up one column of a spectrogram.  The local maxima more than a threshold
+/// above the noise floor, taken as the median of the spectrum, are found and
+/// the `n_peaks` strongest are kept, with their frequencies refined between
+/// bins with a parabola through the peak and its neighbors in dB.
+///
+/// The peaks are then associated with the tracks from the frame before.
+/// Pairs of a track and a peak are matched closest first, as long as they're
+/// no further apart than `max_distance`, so that each track takes at most one
+/// peak and each peak continues at most one track.  A peak that isn't matched
+/// starts a new track, and a track that isn't matched ends.  This follows
+/// drifting and chirping signals as long as they move less than
+/// `max_distance` between frames.
+///
+/// Each frame produces a `TrackPoint` for every peak found, labeled with the
+/// track it belongs to, so that the points with the same `id` trace out the
+/// frequency of one signal against time.  The frequencies are in cycles per
+/// sample on the interval [-0.5, 0.5), or in Hz if a sample rate is given,
+/// and `max_distance` is in the same units.  All of the frames must be the
+/// same length, set by the first frame, or a `NodeError::DataError` is
+/// produced.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::peak_track_node::PeakTrackNode;
+///
+/// // Follow up to three signals that move less than 2 kHz per frame.
+/// let node = PeakTrackNode::new(3, 2e3).with_sample_rate(1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct PeakTrackNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    n_peaks: usize,
+    max_distance: f64,
+    threshold: f64,
+    sample_rate: Option<f64>,
+    window: Vec<f64>,
+    batch_fft: Option<BatchFFT>,
+    tracks: Vec<Track>,
+    next_id: u64,
+    frame: u64,
+    pub output: NodeSender<Vec<TrackPoint>>,
+}
+
+impl PeakTrackNode {
+    /// Constructs a new `PeakTrackNode`, with peaks required to be 10 dB
+    /// above the noise floor.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_peaks` - Largest number of peaks to track at once.
+    /// * `max_distance` - Furthest a peak can move between frames and still
+    ///   continue the same track.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::peak_track_node::PeakTrackNode;
+    ///
+    /// let node = PeakTrackNode::new(1, 0.01);
+    /// ```
+    pub fn new(n_peaks: usize, max_distance: f64) -> PeakTrackNode {
+        assert!(n_peaks > 0, "number of peaks must be nonzero");
+        assert!(max_distance > 0.0, "association distance must be positive");
+        PeakTrackNode {
+            n_peaks,
+            max_distance,
+            threshold: 10.0,
+            sample_rate: None,
+            window: vec![],
+            batch_fft: None,
+            tracks: vec![],
+            next_id: 0,
+            frame: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the height in dB above the noise floor for a peak to be tracked.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the sample rate of the input in Hz, so that frequencies and the
+    /// association distance are in Hz.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    // Finds the strongest peaks in a power spectrum as (frequency, dB).
+    fn find_peaks(&self, spectrum: &[f64]) -> Vec<(f64, f64)> {
+        let n = spectrum.len();
+        let mut sorted = spectrum.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let floor = sorted[n / 2].max(f64::MIN_POSITIVE);
+        let limit = floor * 10.0_f64.powf(self.threshold / 10.0);
+
+        let full_scale = (self.window.iter().sum::<f64>()).powi(2);
+        let db = |p: f64| 10.0 * p.max(f64::MIN_POSITIVE).log10();
+        let bin = |k: isize| spectrum[(k + n as isize) as usize % n];
+
+        let mut peaks = vec![];
+        for k in 0..n as isize {
+            let p = bin(k);
+            if p <= limit || p < bin(k - 1) || p <= bin(k + 1) {
+                continue;
+            }
+            let (below, peak, above) = (db(bin(k - 1)), db(p), db(bin(k + 1)));
+            let denom = below - 2.0 * peak + above;
+            let delta = if denom < 0.0 {
+                0.5 * (below - above) / denom
+            } else {
+                0.0
+            };
+            let mut freq = (k as f64 + delta) / n as f64;
+            if freq >= 0.5 {
+                freq -= 1.0;
+            }
+            if let Some(fs) = self.sample_rate {
+                freq *= fs;
+            }
+            let height = peak - 0.25 * (below - above) * delta;
+            peaks.push((freq, height - db(full_scale)));
+        }
+        peaks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        peaks.truncate(self.n_peaks);
+        peaks
+    }
+
+    /// Runs the `PeakTrackNode`.  Produces the track points for the peaks
+    /// found in the frame.
+    pub fn run(
+        &mut self,
+        frame: &[Complex<f64>],
+    ) -> Result<Vec<TrackPoint>, NodeError> {
+        if self.batch_fft.is_none() {
+            if frame.len() < 3 {
+                return Err(NodeError::DataError);
+            }
+            let mut planner = FFTplanner::new(false);
+            self.batch_fft =
+                Some(BatchFFT::new(planner.plan_fft(frame.len()), frame.len()));
+            self.window = Window::Hann.coefficients(frame.len());
+        }
+        if frame.len() != self.window.len() {
+            return Err(NodeError::DataError);
+        }
+
+        let windowed: Vec<Complex<f64>> =
+            frame.iter().zip(&self.window).map(|(x, w)| x * w).collect();
+        let spectrum: Vec<f64> = self
+            .batch_fft
+            .as_mut()
+            .unwrap()
+            .run_fft(&windowed)
+            .iter()
+            .map(|x| x.norm_sqr())
+            .collect();
+        let peaks = self.find_peaks(&spectrum);
+
+        // Match tracks to peaks, closest pairs first.
+        let mut pairs = vec![];
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (p, peak) in peaks.iter().enumerate() {
+                let distance = (peak.0 - track.freq).abs();
+                if distance <= self.max_distance {
+                    pairs.push((distance, t, p));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut ids = vec![None; peaks.len()];
+        let mut taken = vec![false; self.tracks.len()];
+        for (_, t, p) in pairs {
+            if !taken[t] && ids[p].is_none() {
+                taken[t] = true;
+                ids[p] = Some(self.tracks[t].id);
+            }
+        }
+
+        let mut points = vec![];
+        let mut tracks = vec![];
+        for (id, &(freq, power)) in ids.into_iter().zip(&peaks) {
+            let id = id.unwrap_or_else(|| {
+                self.next_id += 1;
+                self.next_id - 1
+            });
+            tracks.push(Track { id, freq });
+            points.push(TrackPoint {
+                id,
+                frame: self.frame,
+                freq,
+                power,
+            });
+        }
+        self.tracks = tracks;
+        self.frame += 1;
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::peak_track_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Tracks a linear chirp alongside a steady tone in noise and checks that
+    // each stays on its own track for every frame, with the chirp's track
+    // following its instantaneous frequency.
+    fn test_peak_track_chirp() {
+        let fft_size = 256;
+        let n_frames = 200;
+        let len = fft_size * n_frames;
+        let (start, stop) = (-0.2, 0.2);
+        let rate = (stop - start) / len as f64;
+        let tone_freq = 0.3;
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 0.05);
+        let signal: Vec<Complex<f64>> = (0..len)
+            .map(|n| {
+                let t = n as f64;
+                let chirp = 2.0 * PI * (start * t + 0.5 * rate * t * t);
+                Complex::new(0.0, chirp).exp()
+                    + Complex::from_polar(0.5, 2.0 * PI * tone_freq * t)
+                    + Complex::new(rng.sample(dist), rng.sample(dist))
+            })
+            .collect();
+
+        let mut node = PeakTrackNode::new(2, 0.01);
+        let mut chirp_ids = vec![];
+        for (k, frame) in signal.chunks_exact(fft_size).enumerate() {
+            let points = node.run(frame).unwrap();
+            assert_eq!(points.len(), 2);
+            assert!(points.iter().all(|p| p.frame == k as u64));
+
+            // Instantaneous frequency at the middle of the frame.
+            let mid = (k * fft_size + fft_size / 2) as f64;
+            let expected = start + rate * mid;
+            let chirp = points.iter().find(|p| p.freq < 0.25).unwrap();
+            assert!((chirp.freq - expected).abs() < 0.2 / fft_size as f64);
+            assert!(chirp.power.abs() < 1.0);
+            chirp_ids.push(chirp.id);
+
+            let tone = points.iter().find(|p| p.freq >= 0.25).unwrap();
+            assert!((tone.freq - tone_freq).abs() < 0.2 / fft_size as f64);
+        }
+        assert!(chirp_ids.iter().all(|&id| id == chirp_ids[0]));
+
+        assert!(node.run(&signal[..100]).is_err());
+    }
+}
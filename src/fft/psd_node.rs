@@ -0,0 +1,245 @@
+//! Provides a node for calibrated power spectral density estimates.
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+use std::f64::consts::PI;
+
+/// Common window functions for spectral estimation.
+///
+/// The coefficients produced are the periodic form of each window, which is
+/// the usual choice for spectral analysis with the DFT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// Returns the `n` coefficients of the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Length of the window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::psd_node::Window;
+    ///
+    /// let window = Window::Hann.coefficients(1024);
+    /// assert_eq!(window.len(), 1024);
+    /// ```
+    pub fn coefficients(self, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|k| {
+                let x = 2.0 * PI * k as f64 / n as f64;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => {
+                        0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// A node that produces a calibrated power spectral density.
+///
+/// Each input frame is multiplied by the window, transformed, and scaled so
+/// that the output is a true density: each bin is
+///
+/// `|X[k]|^2 / (fs * sum(w^2))`
+///
+/// in dB, offset by the reference level.  The scaling accounts for the FFT
+/// size, the equivalent noise bandwidth of the window, and the sample rate,
+/// so white noise reads the same density regardless of how the spectrum is
+/// computed, and a tone centered on a bin peaks at its power less the
+/// equivalent noise bandwidth in dB-Hz.
+///
+/// The reference level is the power of a full scale signal, a complex
+/// exponential with a magnitude of one.  With a reference level of zero the
+/// output is in dBFS/Hz, and with the calibrated power of a full scale signal
+/// at the ADC in dBm it's in dBm/Hz.
+///
+/// The input frames must be the same length as the window, and the output is
+/// in the natural order produced by the FFT nodes.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::psd_node::{CalibratedPsdNode, Window};
+///
+/// // A full scale signal at the ADC is 4 dBm.
+/// let window = Window::Hann.coefficients(1024);
+/// let node = CalibratedPsdNode::new(window, 2.4e6, 4.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct CalibratedPsdNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    window: Vec<f64>,
+    sample_rate: f64,
+    scale: f64,
+    batch_fft: BatchFFT,
+    pub output: NodeSender<Vec<f64>>,
+}
+
+impl CalibratedPsdNode {
+    /// Constructs a new `CalibratedPsdNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Window coefficients, which also set the FFT size.
+    /// * `sample_rate` - Sample rate of the input in Hz.
+    /// * `ref_level` - Power of a full scale signal, in dBm for an output in
+    ///   dBm/Hz or zero for an output in dBFS/Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::psd_node::{CalibratedPsdNode, Window};
+    ///
+    /// let window = Window::Blackman.coefficients(4096);
+    /// let node = CalibratedPsdNode::new(window, 48000.0, 0.0);
+    /// ```
+    pub fn new(
+        window: Vec<f64>,
+        sample_rate: f64,
+        ref_level: f64,
+    ) -> CalibratedPsdNode {
+        assert!(!window.is_empty(), "window must not be empty");
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let fft_size = window.len();
+        let mut planner = FFTplanner::new(false);
+        let batch_fft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        let power: f64 = window.iter().map(|w| w * w).sum();
+        let scale = ref_level - 10.0 * (sample_rate * power).log10();
+        CalibratedPsdNode {
+            window,
+            sample_rate,
+            scale,
+            batch_fft,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the equivalent noise bandwidth of each bin in Hz.
+    pub fn enbw(&self) -> f64 {
+        let sum: f64 = self.window.iter().sum();
+        let power: f64 = self.window.iter().map(|w| w * w).sum();
+        self.sample_rate * power / (sum * sum)
+    }
+
+    /// Runs the `CalibratedPsdNode`.  Produces the power spectral density of
+    /// the frame, or a `NodeError::DataError` if the frame isn't the same
+    /// length as the window.
+    pub fn run(
+        &mut self,
+        data: &[Complex<f64>],
+    ) -> Result<Vec<f64>, NodeError> {
+        if data.len() != self.window.len() {
+            return Err(NodeError::DataError);
+        }
+        let windowed: Vec<Complex<f64>> =
+            data.iter().zip(&self.window).map(|(x, w)| x * w).collect();
+        Ok(self
+            .batch_fft
+            .run_fft(&windowed)
+            .iter()
+            .map(|x| {
+                10.0 * x.norm_sqr().max(f64::MIN_POSITIVE).log10() + self.scale
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::psd_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Checks the peak of a full scale tone centered on a bin, which should be
+    // the reference level less the window's noise bandwidth in dB-Hz.
+    fn test_psd_tone() {
+        let fft_size = 1024;
+        let sample_rate = 1e6;
+        let bin = 100;
+        let tone: Vec<Complex<f64>> = (0..fft_size)
+            .map(|n| {
+                let phase = 2.0 * PI * (bin * n) as f64 / fft_size as f64;
+                Complex::from_polar(1.0, phase)
+            })
+            .collect();
+
+        // Window and noise bandwidth in bins.
+        let windows = [
+            (Window::Rectangular, 1.0),
+            (Window::Hann, 1.5),
+            (Window::Blackman, 1.7268),
+        ];
+        for &(window, enbw_bins) in &windows {
+            for &ref_level in &[0.0, 10.0] {
+                let mut node = CalibratedPsdNode::new(
+                    window.coefficients(fft_size),
+                    sample_rate,
+                    ref_level,
+                );
+                let enbw = enbw_bins * sample_rate / fft_size as f64;
+                assert!((node.enbw() - enbw).abs() < 1e-3 * enbw);
+
+                let psd = node.run(&tone).unwrap();
+                let expected = ref_level - 10.0 * enbw.log10();
+                assert!((psd[bin] - expected).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    // Checks that white noise reads the same density for different windows
+    // and FFT sizes.
+    fn test_psd_noise() {
+        let sample_rate: f64 = 1e5;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 0.5_f64.sqrt());
+        let noise: Vec<Complex<f64>> = (0..1 << 16)
+            .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect();
+
+        // Unit power spread over the sample rate.
+        let expected = -10.0 * sample_rate.log10();
+        for &(window, fft_size) in
+            &[(Window::Hann, 256), (Window::Hamming, 1024)]
+        {
+            let mut node = CalibratedPsdNode::new(
+                window.coefficients(fft_size),
+                sample_rate,
+                0.0,
+            );
+            let mut total = 0.0;
+            let mut count = 0;
+            for frame in noise.chunks_exact(fft_size) {
+                for p in node.run(frame).unwrap() {
+                    total += 10.0_f64.powf(p / 10.0);
+                    count += 1;
+                }
+            }
+            let density = 10.0 * (total / f64::from(count)).log10();
+            assert!((density - expected).abs() < 0.1);
+        }
+
+        let mut node =
+            CalibratedPsdNode::new(Window::Hann.coefficients(64), 1.0, 0.0);
+        assert!(node.run(&noise[..63]).is_err());
+    }
+}
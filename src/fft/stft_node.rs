@@ -0,0 +1,173 @@
+//! Provides a node for computing short-time Fourier transforms.
+use crate::fft::psd_node::Window;
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+use crate::util::math::cast_complex;
+
+use num::{Complex, Num, NumCast};
+use rustfft::FFTplanner;
+
+/// A node that computes the FFT of a sliding window over a stream.
+///
+/// The node keeps the most recent `win_len` samples and emits the FFT of the
+/// windowed samples every `hop` samples, starting as soon as the first
+/// `win_len` samples have arrived.  A hop shorter than the window gives
+/// overlapping frames, and with it finer time resolution in a spectrogram
+/// than the non-overlapping blocks of `FFTBatchNode`.  A hop longer than the
+/// window skips the samples in between frames.
+///
+/// An input of `len` samples produces `(len - win_len) / hop + 1` frames in
+/// total, in the natural order produced by the FFT nodes.  The input may be
+/// batched arbitrarily; each run emits every frame that the batch completes,
+/// or nothing if it doesn't complete any.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::psd_node::Window;
+/// use comms_rs::fft::stft_node::StftNode;
+///
+/// // 256 point frames overlapping by 75%.
+/// let node: StftNode<f32> = StftNode::new(256, 64, Window::Hann);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct StftNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    hop: usize,
+    window: Vec<f64>,
+    buffer: Vec<Complex<f64>>,
+    skip: usize,
+    batch_fft: BatchFFT,
+    pub output: NodeSender<Vec<Vec<Complex<T>>>>,
+}
+
+impl<T> StftNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    /// Constructs a new `StftNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `win_len` - Number of samples in each frame, and the FFT size.
+    /// * `hop` - Number of samples between the starts of successive frames.
+    /// * `window` - Window applied to each frame before the FFT.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::psd_node::Window;
+    /// use comms_rs::fft::stft_node::StftNode;
+    ///
+    /// let node: StftNode<f64> = StftNode::new(1024, 1024, Window::Rectangular);
+    /// ```
+    pub fn new(win_len: usize, hop: usize, window: Window) -> Self {
+        assert!(win_len > 0, "window length must be nonzero");
+        assert!(hop > 0, "hop must be nonzero");
+        let mut planner = FFTplanner::new(false);
+        let batch_fft = BatchFFT::new(planner.plan_fft(win_len), win_len);
+        StftNode {
+            hop,
+            window: window.coefficients(win_len),
+            buffer: Vec::with_capacity(win_len),
+            skip: 0,
+            batch_fft,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `StftNode<T>`.  Produces the frames completed by the batch
+    /// of samples.
+    #[allow(clippy::type_complexity)]
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<Vec<Complex<T>>>>, NodeError> {
+        let win_len = self.window.len();
+        let mut frames = vec![];
+        for sample in samples {
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            self.buffer
+                .push(cast_complex(sample).ok_or(NodeError::DataError)?);
+            if self.buffer.len() < win_len {
+                continue;
+            }
+
+            let windowed: Vec<Complex<f64>> = self
+                .buffer
+                .iter()
+                .zip(&self.window)
+                .map(|(x, w)| x * w)
+                .collect();
+            let frame = self
+                .batch_fft
+                .run_fft(&windowed)
+                .iter()
+                .map(|x| cast_complex(x).ok_or(NodeError::DataError))
+                .collect::<Result<Vec<_>, _>>()?;
+            frames.push(frame);
+
+            let drop = self.hop.min(win_len);
+            self.buffer.drain(..drop);
+            self.skip = self.hop - drop;
+        }
+        if frames.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(frames))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::stft_node::*;
+
+    #[test]
+    // Checks the number of frames for overlapping, adjacent and gapped hops,
+    // and that each frame is the FFT of the right slice of the input.
+    fn test_stft() {
+        let len = 1000;
+        let win_len = 64;
+        let samples: Vec<Complex<f64>> = (0..len)
+            .map(|n| Complex::new((n as f64 * 0.3).cos(), n as f64 / 100.0))
+            .collect();
+        let window = Window::Hann.coefficients(win_len);
+        let mut planner = FFTplanner::new(false);
+        let mut fft = BatchFFT::new(planner.plan_fft(win_len), win_len);
+
+        for &hop in &[16, 64, 100] {
+            let mut node = StftNode::new(win_len, hop, Window::Hann);
+            let mut frames = vec![];
+            for chunk in samples.chunks(37) {
+                if let Some(f) = node.run(chunk).unwrap() {
+                    frames.extend(f);
+                }
+            }
+            assert_eq!(frames.len(), (len - win_len) / hop + 1);
+
+            for (ix, frame) in frames.iter().enumerate() {
+                let start = ix * hop;
+                let windowed: Vec<Complex<f64>> = samples
+                    [start..start + win_len]
+                    .iter()
+                    .zip(&window)
+                    .map(|(x, w)| x * w)
+                    .collect();
+                let expected = fft.run_fft(&windowed);
+                for (x, y) in frame.iter().zip(&expected) {
+                    assert!((x - y).norm() < 1e-9);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Precise frequency measurement of a single tone.
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::Complex;
+use rustfft::FFTplanner;
+
+/// A node that measures the frequency of the strongest tone in a frame.
+///
+/// Each frame is transformed with an FFT and the bin with the most power is
+/// found.  Picking that bin alone is only accurate to half a bin, so the
+/// peak is refined with Jacobsen's estimator, which fits the complex values
+/// of the peak and its two neighbors:
+///
+/// `delta = -Re((X[k + 1] - X[k - 1]) / (2 * X[k] - X[k - 1] - X[k + 1]))`
+///
+/// For a clean tone this lands within a small fraction of a bin of the true
+/// frequency.  The output is the frequency in Hz, negative for tones below
+/// DC, since the input is complex.  Frames must be exactly the FFT size, or a
+/// `NodeError::DataError` is produced.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::fft::tone_node::ToneFrequencyNode;
+///
+/// let node = ToneFrequencyNode::new(1024, 48000.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ToneFrequencyNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    fft_size: usize,
+    sample_rate: f64,
+    batch_fft: BatchFFT,
+    pub output: NodeSender<f64>,
+}
+
+impl ToneFrequencyNode {
+    /// Constructs a new `ToneFrequencyNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_size` - Number of samples in each frame.
+    /// * `sample_rate` - Sample rate of the input in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::fft::tone_node::ToneFrequencyNode;
+    ///
+    /// let node = ToneFrequencyNode::new(4096, 2.4e6);
+    /// ```
+    pub fn new(fft_size: usize, sample_rate: f64) -> ToneFrequencyNode {
+        assert!(fft_size >= 3, "FFT size must be at least 3");
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let mut planner = FFTplanner::new(false);
+        ToneFrequencyNode {
+            fft_size,
+            sample_rate,
+            batch_fft: BatchFFT::new(planner.plan_fft(fft_size), fft_size),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ToneFrequencyNode`.  Produces the frequency of the strongest
+    /// tone in Hz.
+    pub fn run(&mut self, frame: &[Complex<f64>]) -> Result<f64, NodeError> {
+        let n = self.fft_size;
+        if frame.len() != n {
+            return Err(NodeError::DataError);
+        }
+        let spectrum = self.batch_fft.run_fft(frame);
+        let (peak, _) =
+            spectrum
+                .iter()
+                .enumerate()
+                .fold((0, f64::MIN), |best, (ix, x)| {
+                    let p = x.norm_sqr();
+                    if p > best.1 {
+                        (ix, p)
+                    } else {
+                        best
+                    }
+                });
+
+        // The neighbors wrap around, since the spectrum is periodic.
+        let below = spectrum[(peak + n - 1) % n];
+        let above = spectrum[(peak + 1) % n];
+        let denom = spectrum[peak] * 2.0 - below - above;
+        let delta = if denom.norm() > 0.0 {
+            -((above - below) / denom).re
+        } else {
+            0.0
+        };
+
+        let mut bin = peak as f64 + delta;
+        if bin >= n as f64 / 2.0 {
+            bin -= n as f64;
+        }
+        Ok(bin * self.sample_rate / n as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fft::tone_node::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Places tones between bins, on both sides of DC, and checks that the
+    // measured frequency beats the nearest bin center by a wide margin.
+    fn test_tone_frequency() {
+        let fft_size = 1024;
+        let sample_rate = 48000.0;
+        let bin_width = sample_rate / fft_size as f64;
+        let mut node = ToneFrequencyNode::new(fft_size, sample_rate);
+
+        for &bin in &[100.3, 57.5, -230.8, 411.12] {
+            let freq = bin * bin_width;
+            let tone: Vec<Complex<f64>> = (0..fft_size)
+                .map(|n| {
+                    let t = n as f64 / sample_rate;
+                    Complex::from_polar(0.7, 2.0 * PI * freq * t + 1.0)
+                })
+                .collect();
+            let estimate = node.run(&tone).unwrap();
+            let nearest = bin.round() * bin_width;
+            let error = (estimate - freq).abs();
+            assert!(error < 0.05 * bin_width);
+            assert!(error < 0.25 * (nearest - freq).abs());
+        }
+
+        assert!(node.run(&[Complex::new(1.0, 0.0); 100]).is_err());
+    }
+}
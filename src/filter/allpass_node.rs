@@ -0,0 +1,239 @@
+//! Group delay equalization with cascaded all-pass filters.
+use crate::filter::iir::Biquad;
+use crate::prelude::*;
+use crate::util::MathError;
+
+use num::{Complex, Float, NumCast};
+use std::f64::consts::PI;
+
+/// Designs a second order all-pass section with a group delay peak.
+///
+/// The section has a pair of poles at radius `r` and angle `+/-2 pi center`
+/// and the mirrored zeros at radius `1 / r`, giving
+///
+/// `H(z) = (a2 + a1 z^-1 + z^-2) / (1 + a1 z^-1 + a2 z^-2)`
+///
+/// with `a1 = -2 r cos(2 pi center)` and `a2 = r^2`.  The magnitude response
+/// is exactly one at every frequency, while the group delay rises to a peak
+/// of about `(1 + r) / (1 - r)` samples at the center frequency, falling to
+/// half of that `bandwidth / 2` either side of it.  Cascading sections at
+/// different frequencies builds up a delay profile that fills in the dips
+/// in the group delay of another filter.  Returns the coefficients
+/// `[a1, a2]`.
+///
+/// # Arguments
+///
+/// * `center` - Frequency of the delay peak in cycles per sample, on the
+///   interval (0.0, 0.5).
+/// * `bandwidth` - Width of the delay peak in cycles per sample, on the
+///   interval (0.0, 0.5).  Narrower peaks are taller.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::allpass_node::allpass_design;
+///
+/// // A delay peak at 1 kHz, 200 Hz wide, at a sample rate of 48 kHz.
+/// let section = allpass_design(1000.0 / 48000.0, 200.0 / 48000.0).unwrap();
+/// ```
+pub fn allpass_design(
+    center: f64,
+    bandwidth: f64,
+) -> Result<[f64; 2], MathError> {
+    if center <= 0.0 || center >= 0.5 || bandwidth <= 0.0 || bandwidth >= 0.5 {
+        return Err(MathError::InvalidFrequencyError);
+    }
+    let r = (-PI * bandwidth).exp();
+    Ok([-2.0 * r * (2.0 * PI * center).cos(), r * r])
+}
+
+/// Computes the group delay in samples of a cascade of all-pass sections at
+/// a frequency.
+///
+/// Each section contributes `2 - 2 * tau_a`, where `tau_a` is the group delay
+/// of its denominator polynomial on its own.
+///
+/// # Arguments
+///
+/// * `sections` - Coefficients `[a1, a2]` of each section.
+/// * `freq` - Frequency in cycles per sample.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::allpass_node::{allpass_design, allpass_group_delay};
+///
+/// let section = allpass_design(0.1, 0.01).unwrap();
+/// let peak = allpass_group_delay(&[section], 0.1);
+/// assert!(peak > 10.0 * allpass_group_delay(&[section], 0.3));
+/// ```
+pub fn allpass_group_delay(sections: &[[f64; 2]], freq: f64) -> f64 {
+    let z = Complex::new(0.0, -2.0 * PI * freq).exp();
+    sections
+        .iter()
+        .map(|&[a1, a2]| {
+            let denom = 1.0 + z * a1 + z * z * a2;
+            let weighted = z * a1 + z * z * a2 * 2.0;
+            2.0 - 2.0 * (weighted / denom).re
+        })
+        .sum()
+}
+
+/// A node that corrects the phase response of a signal with a cascade of
+/// second order all-pass sections.
+///
+/// An all-pass filter leaves the magnitude of every frequency untouched and
+/// only delays it, by an amount that varies with frequency.  Filters with
+/// sharp band edges, whether the IIR filters in a receiver or the analog
+/// filters ahead of its ADC, delay the frequencies near their edges more
+/// than the rest, and on a wideband link that smears each symbol into its
+/// neighbours.  Following such a filter with all-pass sections whose delay
+/// peaks where the filter's delay is lowest flattens the total group delay
+/// across the band, restoring the pulse shape at the cost of a little extra
+/// overall delay.
+///
+/// Each section is given by its coefficients `[a1, a2]`, as returned by
+/// `allpass_design`, and the delay the cascade adds at any frequency can be
+/// checked with `allpass_group_delay`.  The samples pass through the
+/// sections in order, and the filter state carries across batches.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::allpass_node::{allpass_design, AllpassNode};
+///
+/// // Equalize the delay around the edges of a +/-0.2 cycles per sample
+/// // band.
+/// let sections = vec![
+///     allpass_design(0.15, 0.05).unwrap(),
+///     allpass_design(0.05, 0.05).unwrap(),
+/// ];
+/// let node: AllpassNode<f32> = AllpassNode::new(sections);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct AllpassNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    biquads: Vec<Biquad>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> AllpassNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `AllpassNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sections` - Coefficients `[a1, a2]` of each section, in the order
+    ///   the samples pass through them.  Every section must be stable, with
+    ///   `|a2| < 1` and `|a1| < 1 + a2`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::allpass_node::AllpassNode;
+    ///
+    /// // A single section with poles at radius 0.9 on the imaginary axis.
+    /// let node: AllpassNode<f64> = AllpassNode::new(vec![[0.0, 0.81]]);
+    /// ```
+    pub fn new(sections: Vec<[f64; 2]>) -> Self {
+        assert!(!sections.is_empty(), "there must be at least one section");
+        assert!(
+            sections
+                .iter()
+                .all(|&[a1, a2]| a2.abs() < 1.0 && a1.abs() < 1.0 + a2),
+            "all-pass sections must be stable"
+        );
+        AllpassNode {
+            biquads: sections
+                .iter()
+                .map(|&[a1, a2]| Biquad::new([a2, a1, 1.0], [1.0, a1, a2]))
+                .collect(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `AllpassNode<T>`.  Produces the filtered batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|x| {
+                let x = Complex::new(
+                    x.re.to_f64().unwrap(),
+                    x.im.to_f64().unwrap(),
+                );
+                let y = self
+                    .biquads
+                    .iter_mut()
+                    .fold(x, |x, biquad| biquad.filter(x));
+                Complex::new(
+                    NumCast::from(y.re).unwrap(),
+                    NumCast::from(y.im).unwrap(),
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::allpass_node::*;
+
+    // Response of the node to a tone, from the ratio of the output to the
+    // input once the filter has settled.
+    fn response(sections: &[[f64; 2]], freq: f64) -> Complex<f64> {
+        let mut node = AllpassNode::new(sections.to_vec());
+        let tone: Vec<Complex<f64>> = (0..4000)
+            .map(|n| Complex::new(0.0, 2.0 * PI * freq * n as f64).exp())
+            .collect();
+        let output = node.run(&tone).unwrap();
+        output[3999] / tone[3999]
+    }
+
+    #[test]
+    // Passes tones across the band through a cascade of two sections and
+    // checks that each comes out at the same level, delayed by the group
+    // delay the sections were designed for.
+    fn test_allpass() {
+        let sections = vec![
+            allpass_design(0.1, 0.02).unwrap(),
+            allpass_design(0.3, 0.05).unwrap(),
+        ];
+        for k in 1..50 {
+            let freq = k as f64 * 0.01 - 0.005;
+            assert!((response(&sections, freq).norm() - 1.0).abs() < 1e-9);
+            assert!((response(&sections, -freq).norm() - 1.0).abs() < 1e-9);
+
+            // The delay is the slope of the phase.
+            let step = 1e-5;
+            let phase = (response(&sections, freq + step)
+                / response(&sections, freq - step))
+            .arg();
+            let delay = -phase / (2.0 * PI * 2.0 * step);
+            let expected = allpass_group_delay(&sections, freq);
+            assert!((delay - expected).abs() < 1e-3 * expected.max(1.0));
+        }
+
+        // The delay peaks at the center of each section, at about the height
+        // and width designed.
+        let r = (-PI * 0.02_f64).exp();
+        let peak = allpass_group_delay(&sections, 0.1);
+        assert!((peak / ((1.0 + r) / (1.0 - r)) - 1.0).abs() < 0.1);
+        let edge = allpass_group_delay(&sections, 0.11);
+        assert!((edge / peak - 0.5).abs() < 0.1);
+        assert!(allpass_group_delay(&sections, 0.3) > 5.0);
+        assert!(allpass_group_delay(&sections, 0.2) < 2.0);
+
+        assert!(allpass_design(0.6, 0.01).is_err());
+        assert!(allpass_design(0.1, 0.0).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Adaptive filtering in the frequency domain.
+use crate::fft::BatchFFT;
+use crate::prelude::*;
+
+use num::{Complex, Zero};
+use rustfft::FFTplanner;
+
+/// A node that implements a frequency-domain adaptive filter.
+///
+/// This is block LMS carried out with FFTs, using overlap-save for both the
+/// filtering and the correlation of the update.  The filter has as many taps
+/// as there are samples in a block, and each block of `N` samples costs a
+/// handful of `2N` point FFTs rather than the `N^2` multiplies of LMS in the
+/// time domain, which makes long filters such as echo cancellers practical.
+///
+/// The node takes two inputs.  The `reference` input is the signal that
+/// passes through the unknown system, such as the far end signal driving a
+/// loudspeaker, and the `desired` input is what was actually received, such
+/// as the microphone picking up the echo.  The filter adapts to make the
+/// reference look like the desired signal, and the output is the error
+/// between them, which is the received signal with the echo cancelled.
+///
+/// The step size of each frequency bin is normalized by a running estimate
+/// of the reference power in that bin, which keeps the convergence rate the
+/// same for colored signals and at any level.  With that normalization, a
+/// step size of around 0.5 or less is stable, and smaller step sizes converge
+/// more slowly but with less misadjustment.  Blocks on the two inputs must
+/// both be the block size, or a `NodeError::DataError` is produced.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::fdaf_node::FdafNode;
+///
+/// // A 1024 tap echo canceller.
+/// let node = FdafNode::new(1024, 0.1);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct FdafNode {
+    pub reference: NodeReceiver<Vec<Complex<f64>>>,
+    pub desired: NodeReceiver<Vec<Complex<f64>>>,
+    block_size: usize,
+    step: f64,
+    taps: Vec<Complex<f64>>,
+    weights: Vec<Complex<f64>>,
+    power: Vec<f64>,
+    last: Vec<Complex<f64>>,
+    fft: BatchFFT,
+    ifft: BatchFFT,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+// Smoothing of the power estimate in each bin.
+const POWER_SMOOTHING: f64 = 0.9;
+
+impl FdafNode {
+    /// Constructs a new `FdafNode`, with all of its taps starting at zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_size` - Number of samples in each block, which is also the
+    ///   number of taps in the filter.
+    /// * `step` - Normalized step size of the adaptation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::fdaf_node::FdafNode;
+    ///
+    /// let node = FdafNode::new(64, 0.5);
+    /// ```
+    pub fn new(block_size: usize, step: f64) -> FdafNode {
+        assert!(block_size > 0, "block size must be nonzero");
+        assert!(step > 0.0, "step size must be positive");
+        let fft_size = 2 * block_size;
+        let mut planner = FFTplanner::new(false);
+        let fft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        let mut planner = FFTplanner::new(true);
+        let ifft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        FdafNode {
+            block_size,
+            step,
+            taps: vec![Complex::zero(); block_size],
+            weights: vec![Complex::zero(); fft_size],
+            power: vec![],
+            last: vec![Complex::zero(); block_size],
+            fft,
+            ifft,
+            reference: Default::default(),
+            desired: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the current taps of the filter in the time domain.
+    pub fn taps(&self) -> &[Complex<f64>] {
+        &self.taps
+    }
+
+    /// Runs the `FdafNode`.  Filters the block of the reference, adapts the
+    /// filter, and produces the error between it and the desired block.
+    pub fn run(
+        &mut self,
+        reference: &[Complex<f64>],
+        desired: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        let n = self.block_size;
+        if reference.len() != n || desired.len() != n {
+            return Err(NodeError::DataError);
+        }
+        let scale = 1.0 / (2 * n) as f64;
+
+        // Overlap-save filtering of the last two blocks of the reference.
+        let mut frame = self.last.clone();
+        frame.extend_from_slice(reference);
+        self.last = reference.to_vec();
+        let x = self.fft.run_fft(&frame);
+        let filtered: Vec<Complex<f64>> =
+            x.iter().zip(&self.weights).map(|(x, w)| x * w).collect();
+        let y = self.ifft.run_fft(&filtered);
+        let error: Vec<Complex<f64>> = desired
+            .iter()
+            .zip(&y[n..])
+            .map(|(d, y)| d - y * scale)
+            .collect();
+
+        // Normalized update, correlating the error against the reference and
+        // keeping only the causal half to stay a linear convolution.
+        if self.power.is_empty() {
+            self.power = x.iter().map(|x| x.norm_sqr()).collect();
+        } else {
+            for (p, x) in self.power.iter_mut().zip(&x) {
+                *p = POWER_SMOOTHING * *p
+                    + (1.0 - POWER_SMOOTHING) * x.norm_sqr();
+            }
+        }
+        let mut padded = vec![Complex::zero(); n];
+        padded.extend_from_slice(&error);
+        let e = self.fft.run_fft(&padded);
+        let gradient: Vec<Complex<f64>> = x
+            .iter()
+            .zip(&e)
+            .zip(&self.power)
+            .map(|((x, e), &p)| {
+                if p > 0.0 {
+                    x.conj() * e / p
+                } else {
+                    Complex::zero()
+                }
+            })
+            .collect();
+        let gradient = self.ifft.run_fft(&gradient);
+        for (w, g) in self.taps.iter_mut().zip(&gradient[..n]) {
+            *w += g * (self.step * scale);
+        }
+        let mut padded = self.taps.clone();
+        padded.extend(vec![Complex::zero(); n]);
+        self.weights = self.fft.run_fft(&padded);
+        Ok(error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::fdaf_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Identifies a long, decaying echo path from white noise, and checks that
+    // the residual echo keeps falling over the blocks and that the taps
+    // match the echo path.
+    fn test_fdaf() {
+        let block_size = 128;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let unit = Normal::new(0.0, 1.0);
+        let noise = Normal::new(0.0, 1e-3);
+        let echo: Vec<Complex<f64>> = (0..100)
+            .map(|k| {
+                Complex::new(rng.sample(unit), rng.sample(unit))
+                    * (-(k as f64) / 30.0).exp()
+            })
+            .collect();
+        let reference: Vec<Complex<f64>> = (0..200 * block_size)
+            .map(|_| Complex::new(rng.sample(unit), rng.sample(unit)))
+            .collect();
+        let desired: Vec<Complex<f64>> = (0..reference.len())
+            .map(|n| {
+                let y: Complex<f64> = echo
+                    .iter()
+                    .enumerate()
+                    .filter(|&(k, _)| k <= n)
+                    .map(|(k, h)| h * reference[n - k])
+                    .sum();
+                y + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect();
+
+        let mut node = FdafNode::new(block_size, 0.5);
+        let residual: Vec<f64> = reference
+            .chunks(block_size)
+            .zip(desired.chunks(block_size))
+            .map(|(x, d)| {
+                let e = node.run(x, d).unwrap();
+                let err: f64 = e.iter().map(|e| e.norm_sqr()).sum();
+                let power: f64 = d.iter().map(|d| d.norm_sqr()).sum();
+                10.0 * (err / power).log10()
+            })
+            .collect();
+
+        // Average residual echo in dB over groups of 10 blocks.
+        let groups: Vec<f64> = residual
+            .chunks(10)
+            .map(|c| c.iter().sum::<f64>() / 10.0)
+            .collect();
+        for pair in groups[..4].windows(2) {
+            assert!(pair[1] < pair[0] - 5.0);
+        }
+        assert!(groups[groups.len() - 1] < -60.0);
+
+        for (k, w) in node.taps().iter().enumerate() {
+            let h = echo.get(k).cloned().unwrap_or_else(Complex::zero);
+            assert!((w - h).norm() < 0.01);
+        }
+        assert!(node.run(&reference[..10], &desired[..10]).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Node based implementation of filters applied in the frequency domain.
+use crate::prelude::*;
+
+use crate::fft::BatchFFT;
+use num::{Complex, Num, NumCast, Zero};
+use rustfft::FFTplanner;
+use std::ops::Range;
+
+/// A node that implements an idealized brick-wall filter in the frequency
+/// domain.
+///
+/// Each block of samples is transformed with an FFT, every bin outside of the
+/// passband is zeroed, and the result is transformed back.  The passband is
+/// given as a range of signed bin indices, so `-10..11` keeps the 21 bins
+/// centered on DC while `100..200` keeps a band of positive frequencies.
+///
+/// The input may arrive in batches of any size.  Samples are gathered into
+/// blocks of the FFT size, each batch produces every whole block it
+/// completes, and the rest is held until the next batch.
+///
+/// Since the blocks are filtered independently with no overlap, this has
+/// time domain artifacts at the block edges and isn't a substitute for a
+/// proper filter.  It's mostly useful for quick experiments and for pulling
+/// out a sub-band to look at.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::fft_filter_node::FreqDomainFilterNode;
+///
+/// // Keep the 64 bins around DC of a 1024 point FFT.
+/// let node: FreqDomainFilterNode<f64> = FreqDomainFilterNode::new(1024, -32..32);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct FreqDomainFilterNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    fft_size: usize,
+    mask: Vec<bool>,
+    pending: Vec<Complex<f64>>,
+    fft: BatchFFT,
+    ifft: BatchFFT,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> FreqDomainFilterNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    /// Constructs a new `FreqDomainFilterNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fft_size` - Size of the FFT, and of the blocks the input is
+    ///   gathered into.
+    /// * `passband` - Range of signed bin indices to keep.  Negative indices
+    ///   refer to negative frequencies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::fft_filter_node::FreqDomainFilterNode;
+    ///
+    /// // Keep only the negative frequencies.
+    /// let node: FreqDomainFilterNode<f32> = FreqDomainFilterNode::new(256, -128..0);
+    /// ```
+    pub fn new(fft_size: usize, passband: Range<isize>) -> Self {
+        assert!(fft_size > 0, "FFT size must be nonzero");
+        let mut mask = vec![false; fft_size];
+        for bin in passband {
+            let ix = bin.rem_euclid(fft_size as isize) as usize;
+            mask[ix] = true;
+        }
+        FreqDomainFilterNode {
+            fft_size,
+            mask,
+            pending: vec![],
+            fft: BatchFFT::new(
+                FFTplanner::new(false).plan_fft(fft_size),
+                fft_size,
+            ),
+            ifft: BatchFFT::new(
+                FFTplanner::new(true).plan_fft(fft_size),
+                fft_size,
+            ),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `FreqDomainFilterNode<T>`.  Produces the filtered samples
+    /// of every block completed by the batch, if there are any.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<Complex<T>>>, NodeError> {
+        self.pending.extend(samples.iter().map(|x| {
+            Complex::new(x.re.to_f64().unwrap(), x.im.to_f64().unwrap())
+        }));
+        let whole = self.pending.len() / self.fft_size * self.fft_size;
+        if whole == 0 {
+            return Ok(None);
+        }
+        let blocks: Vec<Complex<f64>> = self.pending.drain(..whole).collect();
+        let scale = self.fft_size as f64;
+        let mut output = Vec::with_capacity(whole);
+        for block in blocks.chunks(self.fft_size) {
+            let mut spectrum = self.fft.run_fft(block);
+            for (bin, keep) in spectrum.iter_mut().zip(self.mask.iter()) {
+                if !keep {
+                    *bin = Complex::zero();
+                }
+            }
+            output.extend(self.ifft.run_fft(&spectrum).iter().map(|x| {
+                Complex::new(
+                    T::from(x.re / scale).unwrap(),
+                    T::from(x.im / scale).unwrap(),
+                )
+            }));
+        }
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::fft_filter_node::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Filters a block holding two tones and checks that only the one in the
+    // passband survives.
+    fn test_freq_domain_filter() {
+        let n = 512;
+        let tone = |bin: f64, i: usize| {
+            Complex::new(0.0, 2.0 * PI * bin * i as f64 / n as f64).exp()
+        };
+        let block: Vec<Complex<f64>> = (0..n)
+            .map(|i| tone(50.0, i) + tone(-200.0, i) * 0.5)
+            .collect();
+
+        let mut node = FreqDomainFilterNode::new(n, 30..70);
+        let output = node.run(&block).unwrap().unwrap();
+        for (i, y) in output.iter().enumerate() {
+            assert!((y - tone(50.0, i)).norm() < 1e-9);
+        }
+
+        // Batches that don't line up with the blocks are gathered into
+        // whole blocks.
+        let mut node = FreqDomainFilterNode::new(n, -210..-190);
+        let input = [&block[..], &block[..]].concat();
+        let mut output = vec![];
+        for batch in input.chunks(300) {
+            if let Some(out) = node.run(batch).unwrap() {
+                assert_eq!(out.len() % n, 0);
+                output.extend(out);
+            }
+        }
+        assert_eq!(output.len(), 2 * n);
+        for (i, y) in output.iter().enumerate() {
+            assert!((y - tone(-200.0, i % n) * 0.5).norm() < 1e-9);
+        }
+        assert_eq!(node.run(&block[..100]).unwrap(), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Implementation of infinite impulse response (IIR) filters.
+//!
+//! Higher order IIR filters are numerically touchy when implemented directly,
+//! so the building block here is the second order section, or biquad.  Higher
+//! order filters should be built as a cascade of biquads.
+
+use num::{Complex, Zero};
+
+/// A second order IIR filter section in transposed direct form II.
+///
+/// The filter implements the difference equation
+///
+/// `y[n] = b0 x[n] + b1 x[n-1] + b2 x[n-2] - a1 y[n-1] - a2 y[n-2]`
+///
+/// with the coefficients normalized so that `a0 = 1.0`.  The internal state
+/// is carried across calls, so a stream may be filtered in batches of any
+/// size.
+#[derive(Clone, Debug)]
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    state: [Complex<f64>; 2],
+}
+
+impl Biquad {
+    /// Constructs a new `Biquad`.  The coefficients are normalized by `a[0]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - Feedforward coefficients `[b0, b1, b2]`.
+    /// * `a` - Feedback coefficients `[a0, a1, a2]`.  `a0` must be nonzero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::iir::Biquad;
+    /// use comms_rs::util::math::notch_biquad;
+    ///
+    /// let (b, a) = notch_biquad(1000.0, 10.0, 48000.0).unwrap();
+    /// let biquad = Biquad::new(b, a);
+    /// ```
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        assert!(a[0] != 0.0, "a0 must be nonzero");
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [1.0, a[1] / a[0], a[2] / a[0]],
+            state: [Complex::zero(); 2],
+        }
+    }
+
+    /// Replaces the coefficients, normalized by `a[0]`, while keeping the
+    /// internal state.  Following samples carry on from the ones already
+    /// filtered rather than starting over from rest.
+    ///
+    /// # Arguments
+    ///
+    /// * `b` - Feedforward coefficients `[b0, b1, b2]`.
+    /// * `a` - Feedback coefficients `[a0, a1, a2]`.  `a0` must be nonzero.
+    pub fn set_coefficients(&mut self, b: [f64; 3], a: [f64; 3]) {
+        assert!(a[0] != 0.0, "a0 must be nonzero");
+        self.b = [b[0] / a[0], b[1] / a[0], b[2] / a[0]];
+        self.a = [1.0, a[1] / a[0], a[2] / a[0]];
+    }
+
+    /// Filters a single sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Input sample to be filtered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::iir::Biquad;
+    /// use num::Complex;
+    ///
+    /// // A one sample delay.
+    /// let mut biquad = Biquad::new([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+    /// assert_eq!(biquad.filter(Complex::new(1.0, 0.0)), Complex::new(0.0, 0.0));
+    /// assert_eq!(biquad.filter(Complex::new(0.0, 0.0)), Complex::new(1.0, 0.0));
+    /// ```
+    pub fn filter(&mut self, input: Complex<f64>) -> Complex<f64> {
+        let output = input * self.b[0] + self.state[0];
+        self.state[0] = input * self.b[1] - output * self.a[1] + self.state[1];
+        self.state[1] = input * self.b[2] - output * self.a[2];
+        output
+    }
+
+    /// Filters a batch of samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Input samples to be filtered.
+    pub fn batch_filter(
+        &mut self,
+        input: &[Complex<f64>],
+    ) -> Vec<Complex<f64>> {
+        input.iter().map(|x| self.filter(*x)).collect()
+    }
+
+    /// Clears the internal state of the filter.
+    pub fn reset(&mut self) {
+        self.state = [Complex::zero(); 2];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::iir::*;
+
+    #[test]
+    // Checks the impulse response of a simple one pole filter against the
+    // closed form.
+    fn test_biquad_impulse() {
+        let mut biquad = Biquad::new([2.0, 0.0, 0.0], [2.0, -1.0, 0.0]);
+        let mut impulse = vec![Complex::zero(); 20];
+        impulse[0] = Complex::new(1.0, 0.0);
+        let output = biquad.batch_filter(&impulse);
+        for (n, y) in output.iter().enumerate() {
+            assert!((y.re - 0.5_f64.powi(n as i32)).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Node based implementation of IIR filters.
+use crate::prelude::*;
+
+use crate::filter::iir::Biquad;
+use crate::util::math::notch_biquad;
+use crate::util::MathError;
+use num::{Complex, Float, NumCast};
+
+/// A node that removes a single narrowband tone with a second order IIR notch
+/// filter.
+///
+/// This is a cheap way to knock out a CW spur or a pilot tone without the
+/// long FIR filter a comparably narrow notch would need.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::iir_node::NotchNode;
+///
+/// // Remove the 19 kHz pilot tone from an FM broadcast signal.
+/// let node = NotchNode::new(19_000.0, 30.0, 240_000.0).unwrap();
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct NotchNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    biquad: Biquad,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl NotchNode {
+    /// Constructs a new `NotchNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f0` - Center frequency of the notch in Hz.
+    /// * `q` - Quality factor of the notch.  Higher values give a narrower
+    ///   notch.
+    /// * `fs` - Sample rate in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::iir_node::NotchNode;
+    ///
+    /// let node = NotchNode::new(1000.0, 10.0, 48000.0).unwrap();
+    /// ```
+    pub fn new(f0: f64, q: f64, fs: f64) -> Result<NotchNode, MathError> {
+        let (b, a) = notch_biquad(f0, q, fs)?;
+        Ok(NotchNode {
+            biquad: Biquad::new(b, a),
+            input: Default::default(),
+            output: Default::default(),
+        })
+    }
+
+    /// Runs the `NotchNode`.  Produces the filtered batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(self.biquad.batch_filter(samples))
+    }
+}
+
+/// A node that filters a signal with a cascade of biquad sections whose
+/// coefficients can be changed while it runs.
+///
+/// Each section is given as its `(b, a)` coefficients, in the form returned
+/// by the biquad design functions in `comms_rs::util::math`, and the samples
+/// pass through the sections in order.  A new set of sections arriving on the
+/// `sections` control input replaces the coefficients starting with the batch
+/// it arrives with, which makes sweepable and tunable filters such as a
+/// parametric EQ easy to build.
+///
+/// The filter state is kept when the coefficients change, so the output
+/// carries on from the samples already filtered instead of restarting from
+/// rest the way a newly built filter would.  An update must have the same
+/// number of sections as the cascade, each with finite coefficients and a
+/// nonzero `a0`; any other update is ignored, and the node carries on with
+/// the sections it had.  The node is non-blocking, so the control input
+/// may be left unconnected for a fixed filter.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::iir_node::BiquadCascadeNode;
+/// use comms_rs::util::math::notch_biquad;
+///
+/// // Remove a pair of tones.
+/// let sections = vec![
+///     notch_biquad(1000.0, 10.0, 48000.0).unwrap(),
+///     notch_biquad(3000.0, 10.0, 48000.0).unwrap(),
+/// ];
+/// let node: BiquadCascadeNode<f32> = BiquadCascadeNode::new(sections);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct BiquadCascadeNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    pub sections: NodeReceiver<Vec<([f64; 3], [f64; 3])>>,
+    biquads: Vec<Biquad>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> BiquadCascadeNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `BiquadCascadeNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sections` - Initial `(b, a)` coefficients of each section, in the
+    ///   order the samples pass through them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::iir_node::BiquadCascadeNode;
+    ///
+    /// // A single one pole lowpass section.
+    /// let sections = vec![([0.1, 0.0, 0.0], [1.0, -0.9, 0.0])];
+    /// let node: BiquadCascadeNode<f64> = BiquadCascadeNode::new(sections);
+    /// ```
+    pub fn new(sections: Vec<([f64; 3], [f64; 3])>) -> Self {
+        assert!(!sections.is_empty(), "there must be at least one section");
+        BiquadCascadeNode {
+            biquads: sections.iter().map(|&(b, a)| Biquad::new(b, a)).collect(),
+            input: Default::default(),
+            sections: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Replaces the coefficients of every section, keeping the filter state.
+    ///
+    /// # Arguments
+    ///
+    /// * `sections` - New `(b, a)` coefficients of each section, one for
+    ///   each section of the cascade.  Returns a `NodeError::DataError`
+    ///   without changing any section if the number of sections is wrong or
+    ///   any coefficient is invalid.
+    pub fn set_sections(
+        &mut self,
+        sections: &[([f64; 3], [f64; 3])],
+    ) -> Result<(), NodeError> {
+        if sections.len() != self.biquads.len() {
+            return Err(NodeError::DataError);
+        }
+        let valid = sections.iter().all(|(b, a)| {
+            a[0] != 0.0 && b.iter().chain(a.iter()).all(|c| c.is_finite())
+        });
+        if !valid {
+            return Err(NodeError::DataError);
+        }
+        for (biquad, &(b, a)) in self.biquads.iter_mut().zip(sections) {
+            biquad.set_coefficients(b, a);
+        }
+        Ok(())
+    }
+
+    /// Runs the `BiquadCascadeNode<T>`.  Updates the coefficients if valid
+    /// new ones have arrived, then produces the filtered batch if one was
+    /// received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<T>>>,
+        sections: Option<Vec<([f64; 3], [f64; 3])>>,
+    ) -> Result<Option<Vec<Complex<T>>>, NodeError> {
+        if let Some(sections) = sections {
+            // Keep the current sections if the update is invalid.
+            let _ = self.set_sections(&sections);
+        }
+        Ok(input.map(|samples| {
+            samples
+                .iter()
+                .map(|x| {
+                    let x = Complex::new(
+                        x.re.to_f64().unwrap(),
+                        x.im.to_f64().unwrap(),
+                    );
+                    let y = self
+                        .biquads
+                        .iter_mut()
+                        .fold(x, |x, biquad| biquad.filter(x));
+                    Complex::new(
+                        NumCast::from(y.re).unwrap(),
+                        NumCast::from(y.im).unwrap(),
+                    )
+                })
+                .collect()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::iir_node::*;
+    use std::f64::consts::PI;
+
+    // A unit tone at a sample rate of 48 kHz.
+    fn tone(freq: f64, len: usize) -> Vec<Complex<f64>> {
+        (0..len)
+            .map(|n| {
+                Complex::new(0.0, 2.0 * PI * freq * n as f64 / 48000.0).exp()
+            })
+            .collect()
+    }
+
+    // Filters a whole stream with a fixed cascade.
+    fn filter_with(
+        sections: &[([f64; 3], [f64; 3])],
+        input: &[Complex<f64>],
+    ) -> Vec<Complex<f64>> {
+        let mut node = BiquadCascadeNode::new(sections.to_vec());
+        node.run(Some(input.to_vec()), None).unwrap().unwrap()
+    }
+
+    #[test]
+    // Moves a notch onto a tone partway through a stream.  The tone should
+    // pass before the update and be removed after it, while a second tone
+    // well away from the notches carries on without a glitch, matching what
+    // a filter with the new coefficients would have produced all along.
+    fn test_biquad_cascade_update() {
+        let fs = 48000.0;
+        let before = vec![
+            notch_biquad(3000.0, 10.0, fs).unwrap(),
+            notch_biquad(9000.0, 10.0, fs).unwrap(),
+        ];
+        let after = vec![
+            notch_biquad(6000.0, 10.0, fs).unwrap(),
+            notch_biquad(9000.0, 10.0, fs).unwrap(),
+        ];
+        let low = tone(500.0, 20000);
+        let input: Vec<Complex<f64>> = low
+            .iter()
+            .zip(tone(6000.0, 20000))
+            .map(|(a, b)| a + b * 0.1)
+            .collect();
+        let switch = 10000;
+
+        let mut node = BiquadCascadeNode::new(before.clone());
+        let mut output = vec![];
+        for chunk in input[..switch].chunks(1000) {
+            output
+                .extend(node.run(Some(chunk.to_vec()), None).unwrap().unwrap());
+        }
+        let chunk = input[switch..switch + 1000].to_vec();
+        let batch = node.run(Some(chunk), Some(after.clone())).unwrap();
+        output.extend(batch.unwrap());
+        for chunk in input[switch + 1000..].chunks(1000) {
+            output
+                .extend(node.run(Some(chunk.to_vec()), None).unwrap().unwrap());
+        }
+
+        // Before the update the output is that of the old filter, and after
+        // the notch has rung down it is that of the new one.
+        let old = filter_with(&before, &input);
+        let new = filter_with(&after, &input);
+        for (y, x) in output[..switch].iter().zip(&old) {
+            assert!((y - x).norm() < 1e-12);
+        }
+        for (y, x) in output[switch + 2000..].iter().zip(&new[switch + 2000..])
+        {
+            assert!((y - x).norm() < 1e-6);
+        }
+
+        // With the 6 kHz tone gone, only the 500 Hz tone remains.
+        let reference = filter_with(&after, &low);
+        for (y, x) in output[switch + 2000..]
+            .iter()
+            .zip(&reference[switch + 2000..])
+        {
+            assert!((y - x).norm() < 1e-6);
+        }
+
+        // The 500 Hz tone alone passes through the update with only a small
+        // disturbance, smaller than the start up transient of a filter
+        // rebuilt from rest.
+        let mut node = BiquadCascadeNode::new(before.clone());
+        let mut output = node
+            .run(Some(low[..switch].to_vec()), None)
+            .unwrap()
+            .unwrap();
+        let batch = node.run(Some(low[switch..].to_vec()), Some(after.clone()));
+        output.extend(batch.unwrap().unwrap());
+        for (y, x) in output[switch..].iter().zip(&reference[switch..]) {
+            assert!((y - x).norm() < 0.05);
+        }
+        let rebuilt = filter_with(&after, &low[switch..]);
+        let worst = rebuilt
+            .iter()
+            .zip(&reference[switch..])
+            .map(|(y, x)| (y - x).norm())
+            .fold(0.0, f64::max);
+        assert!(worst > 0.1);
+
+        assert!(node.set_sections(&[after[0]]).is_err());
+    }
+
+    #[test]
+    // Invalid coefficients arriving on the control input of a running node
+    // are ignored, and the node carries on filtering with the sections it
+    // had.
+    fn test_biquad_cascade_invalid_update() {
+        let fs = 48000.0;
+        let sections = vec![
+            notch_biquad(3000.0, 10.0, fs).unwrap(),
+            notch_biquad(9000.0, 10.0, fs).unwrap(),
+        ];
+        let input = tone(3000.0, 2000);
+        let expected = filter_with(&sections, &input);
+
+        let mut node: BiquadCascadeNode<f64> =
+            BiquadCascadeNode::new(sections.clone());
+        let (input_send, input_recv) = channel::unbounded();
+        let (sections_send, sections_recv) = channel::unbounded();
+        let (output_send, output_recv) = channel::unbounded();
+        node.input = Some(input_recv);
+        node.sections = Some(sections_recv);
+        node.output.push((output_send, None));
+
+        let zero_a0 = vec![
+            notch_biquad(6000.0, 10.0, fs).unwrap(),
+            ([1.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+        ];
+        let not_finite = vec![
+            notch_biquad(6000.0, 10.0, fs).unwrap(),
+            ([f64::NAN, 0.0, 0.0], [1.0, 0.0, 0.0]),
+        ];
+        sections_send.send(zero_a0).unwrap();
+        input_send.send(input[..1000].to_vec()).unwrap();
+        sections_send.send(not_finite).unwrap();
+        sections_send.send(vec![sections[0]]).unwrap();
+        input_send.send(input[1000..].to_vec()).unwrap();
+        drop(input_send);
+        drop(sections_send);
+
+        thread::spawn(move || node.start()).join().unwrap();
+        let output: Vec<Complex<f64>> = output_recv.iter().flatten().collect();
+        assert_eq!(output.len(), expected.len());
+        for (y, x) in output.iter().zip(&expected) {
+            assert!((y - x).norm() < 1e-12);
+        }
+    }
+
+    fn tone_power(node: &mut NotchNode, freq: f64, fs: f64) -> f64 {
+        let tone: Vec<Complex<f64>> = (0..20000)
+            .map(|n| Complex::new(0.0, 2.0 * PI * freq * n as f64 / fs).exp())
+            .collect();
+        let mut output = vec![];
+        for chunk in tone.chunks(1000) {
+            output.extend(node.run(chunk).unwrap());
+        }
+        // Skip the start up transient.
+        let settled = &output[10000..];
+        settled.iter().map(|x| x.norm_sqr()).sum::<f64>() / settled.len() as f64
+    }
+
+    #[test]
+    // A tone at the notch frequency should be strongly attenuated while a
+    // tone an octave above it passes almost untouched.
+    fn test_notch_node() {
+        let fs = 48000.0;
+        let f0 = 3000.0;
+
+        let mut node = NotchNode::new(f0, 10.0, fs).unwrap();
+        let notched = 10.0 * tone_power(&mut node, f0, fs).log10();
+        assert!(notched < -60.0);
+
+        let mut node = NotchNode::new(f0, 10.0, fs).unwrap();
+        let passed = 10.0 * tone_power(&mut node, 2.0 * f0, fs).log10();
+        assert!(passed.abs() < 0.1);
+    }
+}
@@ -24,5 +24,13 @@
 //! but the most unlikely scenarios, and extremely efficient as well.  Many
 //! times a design that requires an 81 tap FIR filter could only require 9 taps
 //! from a well designed IIR filter alternative.
+pub mod allpass_node;
+pub mod fdaf_node;
+pub mod fft_filter_node;
 pub mod fir;
 pub mod fir_node;
+pub mod iir;
+pub mod iir_node;
+pub mod overlap_save_node;
+pub mod sinc_comp_node;
+pub mod xlating_fir_node;
@@ -0,0 +1,168 @@
+//! FFT based streaming FIR filtering with the overlap-save method.
+use crate::prelude::*;
+
+use crate::fft::BatchFFT;
+use num::{Complex, Num, NumCast, Zero};
+use rustfft::FFTplanner;
+
+/// A node that applies a long FIR filter using fast convolution.
+///
+/// The input is cut into blocks of `block_size` new samples.  Each block is
+/// prepended with the last `taps.len() - 1` samples of the one before it,
+/// transformed with an FFT of `block_size + taps.len() - 1` points,
+/// multiplied by the spectrum of the taps and transformed back.  The circular
+/// convolution wraps around into the first `taps.len() - 1` outputs, which are
+/// discarded, and the rest are exactly the linear convolution of the stream
+/// with the taps, the same as `BatchFirNode` with a zeroed initial state.
+///
+/// This costs far less than direct convolution once the filter is more than a
+/// few dozen taps long, and a block size of a few times the number of taps is
+/// usually a good choice.  Input can arrive in batches of any size, but
+/// output is only produced a whole block at a time, so samples short of a
+/// full block are held until the rest of it arrives.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::overlap_save_node::OverlapSaveFirNode;
+/// use num::Complex;
+///
+/// let taps = vec![Complex::new(0.01, 0.0); 100];
+/// let node = OverlapSaveFirNode::new(taps, 412);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct OverlapSaveFirNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    block_size: usize,
+    overlap: usize,
+    spectrum: Vec<Complex<f64>>,
+    buffer: Vec<Complex<f64>>,
+    fft: BatchFFT,
+    ifft: BatchFFT,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> OverlapSaveFirNode<T>
+where
+    T: NumCast + Copy + Num + Send,
+{
+    /// Constructs a new `OverlapSaveFirNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `taps` - FIR filter taps.
+    /// * `block_size` - Number of new samples filtered with each FFT, and so
+    ///   the number of output samples produced at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::overlap_save_node::OverlapSaveFirNode;
+    /// use num::Complex;
+    ///
+    /// let taps = vec![Complex::new(0.5_f32, 0.0); 2];
+    /// let node = OverlapSaveFirNode::new(taps, 1024);
+    /// ```
+    pub fn new(taps: Vec<Complex<T>>, block_size: usize) -> Self {
+        assert!(!taps.is_empty(), "taps must not be empty");
+        assert!(block_size > 0, "block size must be nonzero");
+        let overlap = taps.len() - 1;
+        let fft_size = block_size + overlap;
+        let mut fft =
+            BatchFFT::new(FFTplanner::new(false).plan_fft(fft_size), fft_size);
+        let ifft =
+            BatchFFT::new(FFTplanner::new(true).plan_fft(fft_size), fft_size);
+
+        // Fold the scaling of the inverse FFT into the spectrum of the taps.
+        let mut padded: Vec<Complex<f64>> = taps
+            .iter()
+            .map(|t| {
+                Complex::new(t.re.to_f64().unwrap(), t.im.to_f64().unwrap())
+                    / fft_size as f64
+            })
+            .collect();
+        padded.resize(fft_size, Complex::zero());
+        let spectrum = fft.run_fft(&padded);
+        OverlapSaveFirNode {
+            block_size,
+            overlap,
+            spectrum,
+            buffer: vec![Complex::zero(); overlap],
+            fft,
+            ifft,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `OverlapSaveFirNode<T>`.  Produces the filtered samples of
+    /// every block completed by the batch.
+    pub fn run(
+        &mut self,
+        input: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        self.buffer.extend(input.iter().map(|x| {
+            Complex::new(x.re.to_f64().unwrap(), x.im.to_f64().unwrap())
+        }));
+        let fft_size = self.block_size + self.overlap;
+        let mut output = vec![];
+        let mut start = 0;
+        while start + fft_size <= self.buffer.len() {
+            let mut spectrum =
+                self.fft.run_fft(&self.buffer[start..start + fft_size]);
+            for (x, h) in spectrum.iter_mut().zip(&self.spectrum) {
+                *x *= h;
+            }
+            let block = self.ifft.run_fft(&spectrum);
+            output.extend(block[self.overlap..].iter().map(|y| {
+                Complex::new(T::from(y.re).unwrap(), T::from(y.im).unwrap())
+            }));
+            start += self.block_size;
+        }
+        self.buffer.drain(..start);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::fir_node::BatchFirNode;
+    use crate::filter::overlap_save_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Filters a long random input fed in uneven batches and checks that the
+    // output matches direct convolution across all of the block boundaries.
+    fn test_overlap_save() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 1.0);
+        let mut random = |len: usize| -> Vec<Complex<f64>> {
+            (0..len)
+                .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+                .collect()
+        };
+        let taps = random(63);
+        let input = random(10000);
+
+        let mut direct = BatchFirNode::new(taps.clone(), None);
+        let expected = direct.run(&input).unwrap();
+
+        let block_size = 200;
+        let mut node = OverlapSaveFirNode::new(taps, block_size);
+        let mut output = vec![];
+        for chunk in input.chunks(333) {
+            output.extend(node.run(chunk).unwrap());
+        }
+        assert_eq!(output.len(), input.len() / block_size * block_size);
+        for (y, x) in output.iter().zip(&expected) {
+            assert!((y - x).norm() < 1e-9);
+        }
+        assert!(node.run(&input[..block_size - 1]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! Compensation of the sinc droop of a zero-order hold DAC.
+use crate::filter::fir::batch_fir;
+use crate::prelude::*;
+use crate::util::math::sinc;
+
+use num::{Complex, Float, NumCast, Zero};
+use std::f64::consts::PI;
+
+/// Number of points used to integrate the desired response.
+const N_POINTS: usize = 4096;
+
+/// Designs the taps of an inverse sinc filter.
+///
+/// A DAC that holds each sample for a whole sample period shapes the
+/// spectrum of its output by `sinc(f / fs)`, which droops by almost 4 dB at
+/// the Nyquist frequency.  The desired response here is the inverse,
+/// `1 / sinc(f / fs)`, and the taps are its inverse Fourier transform,
+/// truncated to the filter length and smoothed with a Hamming window.  The
+/// taps are real and symmetric, so the filter has linear phase with a delay
+/// of `(n_taps - 1) / 2` samples, and the gain at DC is exactly one.
+///
+/// # Arguments
+///
+/// * `n_taps` - Number of taps, which must be odd.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::sinc_comp_node::sinc_comp_taps;
+///
+/// let taps = sinc_comp_taps(15);
+/// assert_eq!(taps.len(), 15);
+/// ```
+pub fn sinc_comp_taps(n_taps: usize) -> Vec<f64> {
+    assert!(n_taps % 2 == 1, "number of taps must be odd");
+    let center = (n_taps / 2) as isize;
+    let mut taps: Vec<f64> = (0..n_taps)
+        .map(|k| {
+            let m = (k as isize - center) as f64;
+            // Midpoint rule over the band from -fs / 2 to fs / 2.
+            let sum: f64 = (0..N_POINTS)
+                .map(|i| {
+                    let f = (i as f64 + 0.5) / N_POINTS as f64 - 0.5;
+                    (2.0 * PI * f * m).cos() / sinc(f)
+                })
+                .sum();
+            let window = if n_taps > 1 {
+                0.54 - 0.46 * (2.0 * PI * k as f64 / (n_taps - 1) as f64).cos()
+            } else {
+                1.0
+            };
+            window * sum / N_POINTS as f64
+        })
+        .collect();
+    let dc: f64 = taps.iter().sum();
+    for t in taps.iter_mut() {
+        *t /= dc;
+    }
+    taps
+}
+
+/// A node that pre-compensates the sinc droop of a zero-order hold DAC.
+///
+/// The signal is filtered with the taps from `sinc_comp_taps`, which boost
+/// the higher frequencies by just enough that after the DAC's zero-order
+/// hold the analog signal has a flat spectrum across most of the band.  The
+/// correction is most accurate up to about 40% of the sample rate, and longer
+/// filters extend it closer to the Nyquist frequency.  Since the gain rises to
+/// about 3.9 dB near the band edge, a signal with energy there may need to be
+/// backed off to avoid clipping.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::sinc_comp_node::SincCompNode;
+///
+/// let node: SincCompNode<f32> = SincCompNode::new(21, 1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SincCompNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    sample_rate: f64,
+    taps: Vec<Complex<T>>,
+    state: Vec<Complex<T>>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> SincCompNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `SincCompNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_taps` - Number of taps in the filter, which must be odd.
+    /// * `sample_rate` - Sample rate of the signal driving the DAC in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::sinc_comp_node::SincCompNode;
+    ///
+    /// let node: SincCompNode<f64> = SincCompNode::new(31, 122.88e6);
+    /// ```
+    pub fn new(n_taps: usize, sample_rate: f64) -> Self {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let taps: Vec<Complex<T>> = sinc_comp_taps(n_taps)
+            .iter()
+            .map(|&t| Complex::new(NumCast::from(t).unwrap(), T::zero()))
+            .collect();
+        SincCompNode {
+            sample_rate,
+            state: vec![Complex::zero(); taps.len()],
+            taps,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the magnitude of the filter's response at a frequency in Hz.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - Frequency in Hz, between zero and the Nyquist frequency.
+    pub fn response(&self, freq: f64) -> f64 {
+        let w = 2.0 * PI * freq / self.sample_rate;
+        self.taps
+            .iter()
+            .enumerate()
+            .map(|(k, t)| {
+                Complex::new(0.0, -w * k as f64).exp() * t.re.to_f64().unwrap()
+            })
+            .sum::<Complex<f64>>()
+            .norm()
+    }
+
+    /// Runs the `SincCompNode<T>`.  Produces the compensated batch of
+    /// samples.
+    pub fn run(
+        &mut self,
+        input: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(batch_fir(input, &self.taps, &mut self.state))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::sinc_comp_node::*;
+
+    #[test]
+    // Checks that the response rises toward the band edge and cancels the
+    // zero-order hold rolloff, and that a tone near the edge comes out
+    // boosted by the expected amount.
+    fn test_sinc_comp() {
+        let sample_rate = 1e6;
+        let mut node: SincCompNode<f64> = SincCompNode::new(21, sample_rate);
+        assert!((node.response(0.0) - 1.0).abs() < 1e-12);
+
+        let mut last = 0.0;
+        for i in 1..=40 {
+            let freq = i as f64 * 0.01 * sample_rate;
+            let x = PI * freq / sample_rate;
+            let zoh = x.sin() / x;
+            let response = node.response(freq);
+            assert!(response > last);
+            // Flat to within 0.05 dB after the hold.
+            assert!((20.0 * (response * zoh).log10()).abs() < 0.05);
+            last = response;
+        }
+
+        let freq = 0.35 * sample_rate;
+        let tone: Vec<Complex<f64>> = (0..500)
+            .map(|n| Complex::new(0.0, 2.0 * PI * 0.35 * n as f64).exp())
+            .collect();
+        let out = node.run(&tone).unwrap();
+        for y in &out[20..] {
+            assert!((y.norm() - node.response(freq)).abs() < 1e-9);
+        }
+        let x = PI * 0.35;
+        assert!((out[100].norm() * x.sin() / x - 1.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,249 @@
+//! Frequency translating FIR filtering for channel selection.
+use crate::prelude::*;
+
+use num::{Complex, Float, NumCast, Zero};
+use std::f64::consts::PI;
+
+/// A node that tunes to a channel, filters it and decimates it in one step.
+///
+/// This is the frequency translating FIR filter found in most SDR toolkits.
+/// The input is mixed down by the center frequency, lowpass filtered by the
+/// given taps and decimated, which selects a channel out of a wideband input
+/// and brings it to baseband at a lower sample rate.  Rather than mixing every
+/// input sample, the taps are shifted up to a bandpass filter at the center
+/// frequency, the filter is only evaluated at the samples kept by the
+/// decimation, and the mixing is applied to those outputs alone.  The result
+/// is the same as mixing first, but costs a fraction of the work.  The filter
+/// starts from a zeroed state and the first output lines up with the first
+/// input sample, so the output matches mixing down with a `Mixer` starting
+/// at zero phase, filtering with a `BatchFirNode` and keeping every
+/// `decimation`th sample, phase included.
+///
+/// The taps should be a lowpass filter designed for the input sample rate,
+/// cutting off below half of the decimated sample rate to avoid aliasing.
+/// The filter state and the phase of the mixing carry over from one batch to
+/// the next, so the input may be batched arbitrarily.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::filter::xlating_fir_node::FreqXlatingFirNode;
+/// use num::Complex;
+///
+/// // Tune 250 kHz up from the center of a 2 MHz input, decimating by 10.
+/// let taps = vec![Complex::new(0.1, 0.0); 10];
+/// let node = FreqXlatingFirNode::new(250e3, taps, 10, 2e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct FreqXlatingFirNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    taps: Vec<Complex<f64>>,
+    decimation: usize,
+    history: Vec<Complex<f64>>,
+    next: usize,
+    phase: f64,
+    dphase: f64,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> FreqXlatingFirNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `FreqXlatingFirNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `center_freq` - Frequency in Hz to bring down to baseband.
+    /// * `taps` - Lowpass filter taps, at the input sample rate.
+    /// * `decimation` - Number of input samples for each output sample.
+    /// * `sample_rate` - Sample rate of the input in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::filter::xlating_fir_node::FreqXlatingFirNode;
+    /// use num::Complex;
+    ///
+    /// let taps = vec![Complex::new(0.25_f32, 0.0); 4];
+    /// let node = FreqXlatingFirNode::new(-12e3, taps, 4, 48e3);
+    /// ```
+    pub fn new(
+        center_freq: f64,
+        taps: Vec<Complex<T>>,
+        decimation: usize,
+        sample_rate: f64,
+    ) -> Self {
+        assert!(!taps.is_empty(), "taps must not be empty");
+        assert!(decimation > 0, "decimation must be nonzero");
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        let w = 2.0 * PI * center_freq / sample_rate;
+        let taps: Vec<Complex<f64>> = taps
+            .iter()
+            .enumerate()
+            .map(|(k, t)| {
+                let t = Complex::new(
+                    t.re.to_f64().unwrap(),
+                    t.im.to_f64().unwrap(),
+                );
+                t * Complex::new(0.0, w * k as f64).exp()
+            })
+            .collect();
+        FreqXlatingFirNode {
+            history: vec![Complex::zero(); taps.len() - 1],
+            next: taps.len() - 1,
+            taps,
+            decimation,
+            phase: 0.0,
+            dphase: -w * decimation as f64,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `FreqXlatingFirNode<T>`.  Produces the channel at baseband
+    /// at the decimated rate.
+    pub fn run(
+        &mut self,
+        input: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        self.history.extend(input.iter().map(|x| {
+            Complex::new(x.re.to_f64().unwrap(), x.im.to_f64().unwrap())
+        }));
+        let n_taps = self.taps.len();
+        let mut output = vec![];
+        while self.next < self.history.len() {
+            let window = &self.history[self.next + 1 - n_taps..=self.next];
+            let y: Complex<f64> = self
+                .taps
+                .iter()
+                .zip(window.iter().rev())
+                .map(|(t, x)| t * x)
+                .sum();
+            let y = y * Complex::new(0.0, self.phase).exp();
+            output.push(Complex::new(
+                NumCast::from(y.re).unwrap(),
+                NumCast::from(y.im).unwrap(),
+            ));
+            self.phase = (self.phase + self.dphase) % (2.0 * PI);
+            self.next += self.decimation;
+        }
+
+        // Keep just enough history for the next output.
+        let used = self.history.len() + 1 - n_taps;
+        self.history.drain(..used);
+        self.next -= used;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::xlating_fir_node::*;
+
+    // Windowed sinc lowpass with a cutoff in cycles per sample.
+    fn lowpass(n_taps: usize, cutoff: f64) -> Vec<Complex<f64>> {
+        let center = (n_taps - 1) as f64 / 2.0;
+        (0..n_taps)
+            .map(|k| {
+                let m = k as f64 - center;
+                let sinc = if m == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * m).sin() / (PI * m)
+                };
+                let window = 0.54
+                    - 0.46 * (2.0 * PI * k as f64 / (n_taps - 1) as f64).cos();
+                Complex::new(sinc * window, 0.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    // Puts a tone 5 kHz above 200 kHz along with a strong interferer, tunes
+    // to 200 kHz and checks that the output is the clean 5 kHz tone at the
+    // decimated rate.
+    fn test_xlating_fir() {
+        let sample_rate = 1e6;
+        let decimation = 8;
+        let out_rate = sample_rate / decimation as f64;
+        let signal: Vec<Complex<f64>> = (0..16000)
+            .map(|n| {
+                let t = n as f64 / sample_rate;
+                Complex::new(0.0, 2.0 * PI * 205e3 * t).exp()
+                    + Complex::new(0.0, -2.0 * PI * 300e3 * t).exp() * 3.0
+            })
+            .collect();
+
+        let mut node = FreqXlatingFirNode::new(
+            200e3,
+            lowpass(101, 40e3 / sample_rate),
+            decimation,
+            sample_rate,
+        );
+        let mut output = vec![];
+        for chunk in signal.chunks(999) {
+            output.extend(node.run(chunk).unwrap());
+        }
+        assert_eq!(output.len(), signal.len() / decimation);
+
+        // Skip the filter's startup, then check each step in phase and the
+        // amplitude of the baseband tone.
+        let expected = Complex::new(0.0, 2.0 * PI * 5e3 / out_rate).exp();
+        for pair in output[20..].windows(2) {
+            assert!((pair[0].norm() - 1.0).abs() < 0.01);
+            assert!((pair[1] - pair[0] * expected).norm() < 0.01);
+        }
+    }
+
+    #[test]
+    // Checks the output against explicitly mixing to baseband, filtering
+    // with a `BatchFirNode` and decimating, sample by sample including the
+    // phase.
+    fn test_xlating_fir_matches_mix_then_filter() {
+        use crate::filter::fir_node::BatchFirNode;
+        use crate::mixer::Mixer;
+
+        let sample_rate = 48e3;
+        let center_freq = 7e3;
+        let decimation = 3;
+        let taps = lowpass(31, 4e3 / sample_rate);
+        let signal: Vec<Complex<f64>> = (0..3000)
+            .map(|n| {
+                let n = n as f64;
+                Complex::new((0.01 * n).cos(), (0.37 * n).sin() + 0.2)
+            })
+            .collect();
+
+        let mut node = FreqXlatingFirNode::new(
+            center_freq,
+            taps.clone(),
+            decimation,
+            sample_rate,
+        );
+        let mut output = vec![];
+        for chunk in signal.chunks(97) {
+            output.extend(node.run(chunk).unwrap());
+        }
+
+        let mut mixer = Mixer::from_frequency(0.0, -center_freq, sample_rate);
+        let mixed: Vec<Complex<f64>> =
+            signal.iter().map(|x| mixer.mix(x)).collect();
+        let mut fir = BatchFirNode::new(taps, None);
+        let expected: Vec<Complex<f64>> = fir
+            .run(&mixed)
+            .unwrap()
+            .into_iter()
+            .step_by(decimation)
+            .collect();
+
+        assert_eq!(output.len(), expected.len());
+        for (y, e) in output.iter().zip(&expected) {
+            assert!((y - e).norm() < 1e-9);
+        }
+    }
+}
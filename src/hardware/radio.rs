@@ -57,6 +57,7 @@ where
     radio: T,
     input_idx: usize,
     num_samples: usize,
+    sample_rate: Option<f64>,
     pub output: NodeSender<Vec<U>>,
 }
 
@@ -70,11 +71,29 @@ where
             radio,
             input_idx,
             num_samples,
+            sample_rate: None,
             output: Default::default(),
         }
     }
 
+    /// Sets the sample rate the radio has been configured for, in Hz, so
+    /// that it can be reported to downstream nodes through `SampleRate`.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
     pub fn run(&mut self) -> Result<Vec<U>, NodeError> {
         Ok(self.radio.recv_samples(self.num_samples, self.input_idx))
     }
 }
+
+impl<T, U> SampleRate for RadioRxNode<T, U>
+where
+    T: RadioRx<U> + Send,
+    U: Clone + Send,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+}
@@ -2,11 +2,19 @@ use crate::io::rodio::buffer;
 use crate::io::rodio::queue::{queue, SourcesQueueInput};
 use crate::io::rodio::{self, Sample, Sink};
 use crate::prelude::*;
+pub use crate::util::compand::{CompandNode, ExpandNode};
+use crate::util::resample_node::RateConverter;
 use std::default::Default;
 use std::sync::Arc;
 
 /// A node that can play received samples out on audio. Currently this only
 /// uses the default output device on the system.
+///
+/// The sample rate of the device is queried when the node is built, and if
+/// it differs from the rate of the samples, they're resampled to the device
+/// rate with a polyphase resampler before they're played.  A pipeline can
+/// then produce audio at whatever rate is convenient without it playing at
+/// the wrong pitch and speed on a device that doesn't run at that rate.
 #[derive(Node)]
 #[pass_by_ref]
 pub struct AudioNode<T>
@@ -17,7 +25,8 @@ where
     _sink: Sink,
     in_queue: Arc<SourcesQueueInput<T>>,
     channels: u16,
-    sample_rate: u32,
+    device_rate: u32,
+    converter: Option<RateConverter>,
 }
 
 impl<T> AudioNode<T>
@@ -25,8 +34,19 @@ where
     T: Sample + Send + 'static,
 {
     /// Creates an AudioNode with the given parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - Number of interleaved channels in the samples.
+    /// * `sample_rate` - Sample rate of the samples in Hz.  If the device
+    ///   rate can't be queried, it's assumed to be this.
+    /// * `volume` - Volume the samples are played at.
     pub fn new(channels: u16, sample_rate: u32, volume: f32) -> Self {
         let device = rodio::default_output_device().unwrap();
+        let device_rate = device
+            .default_output_format()
+            .map(|format| format.sample_rate.0)
+            .unwrap_or(sample_rate);
         let mut sink = Sink::new(&device);
         let (in_queue, out_queue) = queue::<T>(true);
         sink.set_volume(volume);
@@ -35,18 +55,45 @@ where
             _sink: sink,
             in_queue,
             channels,
-            sample_rate,
+            device_rate,
+            converter: if device_rate != sample_rate {
+                Some(RateConverter::new(channels, sample_rate, device_rate))
+            } else {
+                None
+            },
             input: Default::default(),
         }
     }
 
+    /// Returns the sample rate of the output device in Hz, which the
+    /// samples are played at.
+    pub fn device_rate(&self) -> u32 {
+        self.device_rate
+    }
+
     /// Tosses the received samples into the sink for output.
     pub fn run(&mut self, samples: &[T]) -> Result<(), NodeError> {
-        let samplebuffer = buffer::SamplesBuffer::new(
-            self.channels,
-            self.sample_rate,
-            samples,
-        );
+        let samplebuffer = match self.converter {
+            Some(ref mut converter) => {
+                let input: Vec<f32> =
+                    samples.iter().map(|x| x.to_f32()).collect();
+                let output: Vec<T> = converter
+                    .convert(&input)
+                    .iter()
+                    .map(|x| T::from(x))
+                    .collect();
+                buffer::SamplesBuffer::new(
+                    self.channels,
+                    self.device_rate,
+                    output,
+                )
+            }
+            None => buffer::SamplesBuffer::new(
+                self.channels,
+                self.device_rate,
+                samples,
+            ),
+        };
         self.in_queue.append(samplebuffer);
         Ok(())
     }
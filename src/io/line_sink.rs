@@ -0,0 +1,108 @@
+//! Splitting decoded bytes into lines of text.
+use crate::prelude::*;
+
+/// A node that turns a stream of decoded bytes into lines of text.
+///
+/// Bytes are collected until the delimiter arrives, and then everything
+/// before it is emitted as a `String`, without the delimiter.  A line can be
+/// spread across any number of batches, and one batch can complete several
+/// lines, which are emitted together in order.  Bytes after the last
+/// delimiter are held until the rest of their line arrives.  Bytes that
+/// aren't valid UTF-8, as happens when a bit error gets through, are replaced
+/// with U+FFFD rather than dropping the line.
+///
+/// This is the last stage for simple text over RF, such as telemetry sent as
+/// lines of ASCII.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::io::line_sink::LineSinkNode;
+///
+/// let node = LineSinkNode::new(b'\n');
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct LineSinkNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    delimiter: u8,
+    partial: Vec<u8>,
+    pub output: NodeSender<Vec<String>>,
+}
+
+impl LineSinkNode {
+    /// Constructs a new `LineSinkNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter` - Byte that ends each line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::io::line_sink::LineSinkNode;
+    ///
+    /// // Lines ended by a NUL byte.
+    /// let node = LineSinkNode::new(0);
+    /// ```
+    pub fn new(delimiter: u8) -> LineSinkNode {
+        LineSinkNode {
+            delimiter,
+            partial: vec![],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `LineSinkNode`.  Produces the lines completed by the batch of
+    /// bytes.
+    pub fn run(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<Option<Vec<String>>, NodeError> {
+        let mut lines = vec![];
+        for &b in bytes {
+            if b == self.delimiter {
+                lines.push(String::from_utf8_lossy(&self.partial).into_owned());
+                self.partial.clear();
+            } else {
+                self.partial.push(b);
+            }
+        }
+        if lines.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(lines))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::line_sink::*;
+
+    #[test]
+    // Feeds lines split across batches in awkward places and checks that
+    // they come out whole and in order.
+    fn test_line_sink() {
+        let text = b"N0CALL>APRS:temp=21.5\nbatt=12.6V\n\nlast\nunfinished";
+        let mut node = LineSinkNode::new(b'\n');
+        let mut lines = vec![];
+        for chunk in text.chunks(7) {
+            if let Some(l) = node.run(chunk).unwrap() {
+                lines.extend(l);
+            }
+        }
+        assert_eq!(
+            lines,
+            vec!["N0CALL>APRS:temp=21.5", "batt=12.6V", "", "last"]
+        );
+
+        assert_eq!(node.run(b" line").unwrap(), None);
+        assert_eq!(
+            node.run(b"\xff\n").unwrap(),
+            Some(vec!["unfinished line\u{fffd}".to_string()])
+        );
+    }
+}
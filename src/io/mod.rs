@@ -12,4 +12,6 @@ pub mod audio;
 #[cfg(feature = "zmq_node")]
 pub mod zmq_node;
 
+pub mod baseband;
+pub mod line_sink;
 pub mod raw_iq;
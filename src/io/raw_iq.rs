@@ -10,7 +10,8 @@ use num::Complex;
 use crate::prelude::*;
 
 use std::default::Default;
-use std::io::{self, Read, Write};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 use std::{thread, time};
 
 type IQSample = Complex<i16>;
@@ -23,6 +24,7 @@ where
     R: Read + Send,
 {
     reader: R,
+    sample_rate: Option<f64>,
     pub output: NodeSender<IQSample>,
 }
 
@@ -42,10 +44,18 @@ impl<R: Read + Send> IQInput<R> {
     pub fn new(reader: R) -> Self {
         IQInput {
             reader,
+            sample_rate: None,
             output: Default::default(),
         }
     }
 
+    /// Sets the sample rate of the data in the file, in Hz, so that it can
+    /// be reported to downstream nodes through `SampleRate`.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
     pub fn run(&mut self) -> Result<IQSample, NodeError> {
         let re_res = self.reader.read_i16::<NativeEndian>();
         let im_res = self.reader.read_i16::<NativeEndian>();
@@ -81,6 +91,7 @@ where
 {
     reader: R,
     batch_size: usize,
+    sample_rate: Option<f64>,
     pub output: NodeSender<Vec<IQSample>>,
 }
 
@@ -103,10 +114,18 @@ impl<R: Read + Send> IQBatchInput<R> {
         IQBatchInput {
             reader,
             batch_size,
+            sample_rate: None,
             output: Default::default(),
         }
     }
 
+    /// Sets the sample rate of the data in the file, in Hz, so that it can
+    /// be reported to downstream nodes through `SampleRate`.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
     pub fn run(&mut self) -> Result<Vec<IQSample>, NodeError> {
         let mut buf = Vec::with_capacity(self.batch_size);
         for _ in 0..self.batch_size {
@@ -139,6 +158,18 @@ impl<R: Read + Send> IQBatchInput<R> {
     }
 }
 
+impl<R: Read + Send> SampleRate for IQInput<R> {
+    fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+}
+
+impl<R: Read + Send> SampleRate for IQBatchInput<R> {
+    fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+}
+
 /// Will send samples as interleaved 16-bit values in host byte-order to writer.
 #[derive(Node)]
 pub struct IQOutput<W>
@@ -222,6 +253,362 @@ impl<W: Write + Send> IQBatchOutput<W> {
     }
 }
 
+/// Converts an unsigned 8-bit sample, as produced by an rtlsdr, to a float
+/// on the interval [-1, 1].
+fn u8_to_f32(x: u8) -> f32 {
+    (f32::from(x) - 127.5) / 127.5
+}
+
+/// Converts a float on the interval [-1, 1] to an unsigned 8-bit sample,
+/// saturating any value outside of that range.
+fn f32_to_u8(x: f32) -> u8 {
+    (x * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8
+}
+
+/// Reads a single 8-bit complex sample, sleeping forever on end of file.
+fn read_u8_sample<R: Read>(reader: &mut R) -> Complex<f32> {
+    let mut buf = [0u8; 2];
+    if let Err(e) = reader.read_exact(&mut buf) {
+        if let io::ErrorKind::UnexpectedEof = e.kind() {
+            // reached eof, sleep forever
+            // TODO determine what happens if we kill the thread
+            thread::sleep(time::Duration::from_secs(1_000_000));
+        }
+        panic!("Unable to read file with err: {}", e);
+    }
+    Complex::new(u8_to_f32(buf[0]), u8_to_f32(buf[1]))
+}
+
+/// Will retrieve samples as interleaved unsigned 8-bit values from reader,
+/// the format produced by rtlsdr captures, and convert them to floats on the
+/// interval [-1, 1].  Panics upon reaching end of file.
+#[derive(Node)]
+pub struct IQu8Input<R>
+where
+    R: Read + Send,
+{
+    reader: R,
+    sample_rate: Option<f64>,
+    pub output: NodeSender<Complex<f32>>,
+}
+
+impl<R: Read + Send> IQu8Input<R> {
+    /// Make an IQu8Input node reading data from the given file.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use comms_rs::io::raw_iq::IQu8Input;
+    ///
+    /// let reader = BufReader::new(File::open("/tmp/rtlsdr.bin").unwrap());
+    /// let innode = IQu8Input::new(reader).with_sample_rate(2.4e6);
+    /// ```
+    pub fn new(reader: R) -> Self {
+        IQu8Input {
+            reader,
+            sample_rate: None,
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the data in the file, in Hz, so that it can
+    /// be reported to downstream nodes through `SampleRate`.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<Complex<f32>, NodeError> {
+        Ok(read_u8_sample(&mut self.reader))
+    }
+}
+
+/// Will retrieve batches of samples as interleaved unsigned 8-bit values from
+/// reader, converted to floats on the interval [-1, 1].  Will only send
+/// vectors completely filled to size of batch_size.  Panics upon reaching end
+/// of file.
+#[derive(Node)]
+pub struct IQu8BatchInput<R>
+where
+    R: Read + Send,
+{
+    reader: R,
+    batch_size: usize,
+    sample_rate: Option<f64>,
+    pub output: NodeSender<Vec<Complex<f32>>>,
+}
+
+impl<R: Read + Send> IQu8BatchInput<R> {
+    /// Make an IQu8BatchInput node reading data from the given file.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use comms_rs::io::raw_iq::IQu8BatchInput;
+    ///
+    /// let file = File::open("/tmp/rtlsdr.bin").unwrap();
+    /// let innode = IQu8BatchInput::new(file, 1024);
+    /// ```
+    pub fn new(reader: R, batch_size: usize) -> Self {
+        IQu8BatchInput {
+            reader,
+            batch_size,
+            sample_rate: None,
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the data in the file, in Hz, so that it can
+    /// be reported to downstream nodes through `SampleRate`.
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<Vec<Complex<f32>>, NodeError> {
+        Ok((0..self.batch_size)
+            .map(|_| read_u8_sample(&mut self.reader))
+            .collect())
+    }
+}
+
+impl<R: Read + Send> SampleRate for IQu8Input<R> {
+    fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+}
+
+impl<R: Read + Send> SampleRate for IQu8BatchInput<R> {
+    fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+}
+
+/// Will send samples to writer as interleaved unsigned 8-bit values, the
+/// format produced by rtlsdr captures.  Samples are expected on the interval
+/// [-1, 1], and anything outside of that saturates.
+#[derive(Node)]
+pub struct IQu8Output<W>
+where
+    W: Write + Send,
+{
+    pub input: NodeReceiver<Complex<f32>>,
+    writer: W,
+}
+
+impl<W: Write + Send> IQu8Output<W> {
+    /// Make an IQu8Output node sending data to the given file.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::BufWriter;
+    /// use comms_rs::io::raw_iq::IQu8Output;
+    ///
+    /// let writer = BufWriter::new(File::create("/tmp/rtlsdr.bin").unwrap());
+    /// let outnode = IQu8Output::new(writer);
+    /// ```
+    pub fn new(writer: W) -> Self {
+        IQu8Output {
+            writer,
+            input: Default::default(),
+        }
+    }
+
+    pub fn run(&mut self, samp: Complex<f32>) -> Result<(), NodeError> {
+        self.writer
+            .write_all(&[f32_to_u8(samp.re), f32_to_u8(samp.im)])
+            .expect("failed to write sample to writer");
+        Ok(())
+    }
+}
+
+/// Will send batches of samples to writer as interleaved unsigned 8-bit
+/// values.  Samples are expected on the interval [-1, 1], and anything
+/// outside of that saturates.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct IQu8BatchOutput<W>
+where
+    W: Write + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<f32>>>,
+    writer: W,
+}
+
+impl<W: Write + Send> IQu8BatchOutput<W> {
+    /// Make an IQu8BatchOutput node sending data to the given file.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use comms_rs::io::raw_iq::IQu8BatchOutput;
+    ///
+    /// let writer = File::create("/tmp/rtlsdr.bin").unwrap();
+    /// let outnode = IQu8BatchOutput::new(writer);
+    /// ```
+    pub fn new(writer: W) -> Self {
+        IQu8BatchOutput {
+            writer,
+            input: Default::default(),
+        }
+    }
+
+    pub fn run(&mut self, samples: &[Complex<f32>]) -> Result<(), NodeError> {
+        let mut bytes = Vec::with_capacity(2 * samples.len());
+        for samp in samples {
+            bytes.push(f32_to_u8(samp.re));
+            bytes.push(f32_to_u8(samp.im));
+        }
+        self.writer
+            .write_all(&bytes)
+            .expect("failed to write sample to writer");
+        Ok(())
+    }
+}
+
+/// The on-disk format of samples written by `IQRotatingOutput`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IQFormat {
+    /// Interleaved 16-bit integers in host byte-order.
+    I16,
+    /// Interleaved 32-bit floats in host byte-order, holding the same values
+    /// as the 16-bit samples.
+    F32,
+}
+
+impl IQFormat {
+    /// Returns the number of bytes a single complex sample takes up.
+    pub fn sample_size(self) -> usize {
+        match self {
+            IQFormat::I16 => 4,
+            IQFormat::F32 => 8,
+        }
+    }
+}
+
+/// The condition upon which `IQRotatingOutput` rolls over to a new file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RotateLimit {
+    /// Roll over once a file reaches the given size in bytes.  Files are
+    /// split on sample boundaries, so each file holds as many whole samples
+    /// as fit within the limit.
+    Size(u64),
+    /// Roll over once a file has been open for the given amount of time.
+    Duration(time::Duration),
+}
+
+/// Will send samples to a sequence of files, rolling over to a new file when
+/// the current one reaches a size or age limit.
+///
+/// File names are generated from a pattern by replacing `{}` with the index
+/// of the file, starting at zero.  This keeps long captures from ending up
+/// as one enormous file.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct IQRotatingOutput {
+    pub input: NodeReceiver<Vec<IQSample>>,
+    pattern: String,
+    limit: RotateLimit,
+    format: IQFormat,
+    file_ix: usize,
+    writer: Option<BufWriter<File>>,
+    written: u64,
+    opened: time::Instant,
+}
+
+impl IQRotatingOutput {
+    /// Make an IQRotatingOutput node writing to files named after the given
+    /// pattern.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use comms_rs::io::raw_iq::{IQFormat, IQRotatingOutput, RotateLimit};
+    ///
+    /// // Roll over to a new file every 1 GB.
+    /// let outnode = IQRotatingOutput::new(
+    ///     "/tmp/capture_{}.bin",
+    ///     RotateLimit::Size(1 << 30),
+    ///     IQFormat::I16,
+    /// );
+    /// ```
+    pub fn new(pattern: &str, limit: RotateLimit, format: IQFormat) -> Self {
+        assert!(
+            pattern.contains("{}"),
+            "file pattern must contain an index placeholder"
+        );
+        IQRotatingOutput {
+            pattern: pattern.to_string(),
+            limit,
+            format,
+            file_ix: 0,
+            writer: None,
+            written: 0,
+            opened: time::Instant::now(),
+            input: Default::default(),
+        }
+    }
+
+    /// Returns the name of the file with the given index.
+    pub fn file_name(&self, ix: usize) -> String {
+        self.pattern.replace("{}", &ix.to_string())
+    }
+
+    fn needs_rollover(&self) -> bool {
+        match self.limit {
+            RotateLimit::Size(max) => {
+                self.written + self.format.sample_size() as u64 > max
+                    && self.written > 0
+            }
+            RotateLimit::Duration(max) => self.opened.elapsed() >= max,
+        }
+    }
+
+    fn open_next(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+            self.file_ix += 1;
+        }
+        let file = File::create(self.file_name(self.file_ix))?;
+        self.writer = Some(BufWriter::new(file));
+        self.written = 0;
+        self.opened = time::Instant::now();
+        Ok(())
+    }
+
+    fn write_samples(&mut self, samples: &[IQSample]) -> io::Result<()> {
+        for samp in samples {
+            if self.writer.is_none() || self.needs_rollover() {
+                self.open_next()?;
+            }
+            let writer = self.writer.as_mut().unwrap();
+            match self.format {
+                IQFormat::I16 => {
+                    writer.write_i16::<NativeEndian>(samp.re)?;
+                    writer.write_i16::<NativeEndian>(samp.im)?;
+                }
+                IQFormat::F32 => {
+                    writer.write_f32::<NativeEndian>(f32::from(samp.re))?;
+                    writer.write_f32::<NativeEndian>(f32::from(samp.im))?;
+                }
+            }
+            self.written += self.format.sample_size() as u64;
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, samples: &[IQSample]) -> Result<(), NodeError> {
+        self.write_samples(samples)
+            .map_err(|_| NodeError::PermanentError)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::io::raw_iq::*;
@@ -352,5 +739,95 @@ mod test {
         }
     }
 
+    #[test]
+    /// Test that the rotating output rolls over to a new file once the size
+    /// limit is reached.
+    fn test_rotating_out_node() {
+        let dir = std::env::temp_dir();
+        let pattern = dir.join("comms_rs_test_rotate_{}.bin");
+        let pattern = pattern.to_str().unwrap();
+        let samples: Vec<Complex<i16>> =
+            (0..150).map(|i| Complex::new(i, -i)).collect();
+        let mut names = vec![];
+        {
+            let mut node = IQRotatingOutput::new(
+                pattern,
+                RotateLimit::Size(100 * 4),
+                IQFormat::I16,
+            );
+            for chunk in samples.chunks(40) {
+                node.run(chunk).unwrap();
+            }
+            for ix in 0..3 {
+                names.push(node.file_name(ix));
+            }
+        }
+
+        let first = std::fs::read(&names[0]).unwrap();
+        let second = std::fs::read(&names[1]).unwrap();
+        assert!(!std::path::Path::new(&names[2]).exists());
+        std::fs::remove_file(&names[0]).unwrap();
+        std::fs::remove_file(&names[1]).unwrap();
+
+        assert_eq!(first.len(), 400);
+        assert_eq!(second.len(), 200);
+        let mut buf = vec![0u8; 4];
+        complex_into_bytes(&mut buf, samples[100]);
+        assert_eq!(*buf, second[..4]);
+    }
+
+    #[test]
+    /// Test that floats written by the 8-bit output nodes are read back by
+    /// the 8-bit input nodes to within the quantization step.
+    fn test_u8_round_trip() {
+        let samples: Vec<Complex<f32>> = (0..1000)
+            .map(|i| {
+                let phase = i as f32 * 0.05;
+                Complex::new(phase.cos(), 0.7 * phase.sin())
+            })
+            .collect();
+        let mut single: Vec<u8> = Vec::new();
+        let mut batch: Vec<u8> = Vec::new();
+        {
+            let mut node = IQu8Output::new(&mut single);
+            for samp in &samples {
+                node.run(*samp).unwrap();
+            }
+            let mut node = IQu8BatchOutput::new(&mut batch);
+            for chunk in samples.chunks(300) {
+                node.run(chunk).unwrap();
+            }
+        }
+        assert_eq!(single.len(), 2 * samples.len());
+        assert_eq!(single, batch);
+
+        let tol = 0.5 / 127.5 + 1e-6;
+        let mut node = IQu8Input::new(Cursor::new(single));
+        for samp in &samples {
+            assert!(
+                (node.run().unwrap() - samp).norm() <= tol * 2.0_f32.sqrt()
+            );
+        }
+        let mut node = IQu8BatchInput::new(Cursor::new(batch), 100);
+        let read: Vec<Complex<f32>> =
+            (0..10).flat_map(|_| node.run().unwrap()).collect();
+        for (x, y) in read.iter().zip(&samples) {
+            assert!((x.re - y.re).abs() <= tol);
+            assert!((x.im - y.im).abs() <= tol);
+        }
+
+        // Out of range values saturate, and the extremes map to full scale.
+        let mut out: Vec<u8> = Vec::new();
+        IQu8BatchOutput::new(&mut out)
+            .run(&[Complex::new(2.0, -1.5), Complex::new(1.0, -1.0)])
+            .unwrap();
+        assert_eq!(out, vec![255, 0, 255, 0]);
+        let mut out: Vec<u8> = Vec::new();
+        IQu8Output::new(&mut out)
+            .run(Complex::new(0.0, 0.0))
+            .unwrap();
+        assert_eq!(u8_to_f32(out[0]), 0.5 / 127.5);
+    }
+
     // TODO add tests for thread blocking on input exhaustion
 }
@@ -29,6 +29,7 @@ extern crate serde_cbor;
 
 #[macro_use]
 pub mod node;
+pub mod coding;
 pub mod demodulation;
 pub mod fft;
 pub mod filter;
@@ -38,6 +39,7 @@ pub mod mixer;
 pub mod modulation;
 pub mod prns;
 pub mod pulse;
+pub mod sources;
 pub mod util;
 
 #[cfg(test)]
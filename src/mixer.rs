@@ -14,9 +14,18 @@ use crate::util::math;
 ///
 /// This combines an input signal with a complex exponential for modulation or
 /// demodulation of carrier frequencies to passband or baseband signals.
+///
+/// By default the complex exponential is computed exactly for every sample.
+/// A mixer created with `new_table` instead looks it up in a precomputed sine
+/// table indexed by a fixed point phase accumulator, which is much cheaper
+/// per sample at the cost of a small amount of phase quantization.
 pub struct Mixer {
     phase: f64,
     dphase: f64,
+    table: Vec<f64>,
+    acc: u32,
+    dacc: u32,
+    shift: u32,
 }
 
 impl Mixer {
@@ -47,7 +56,87 @@ impl Mixer {
         while dphase < 0.0 {
             dphase += 2.0 * PI;
         }
-        Mixer { phase, dphase }
+        Mixer {
+            phase,
+            dphase,
+            table: vec![],
+            acc: 0,
+            dacc: 0,
+            shift: 0,
+        }
+    }
+
+    /// Creates a new table driven `Mixer` struct.
+    ///
+    /// The phase is tracked in a 32 bit accumulator, and the top `table_bits`
+    /// bits of it (rounded) index a table of one period of a sine wave, with
+    /// the cosine read from a quarter period further along.  The worst case
+    /// phase error is `PI / 2^table_bits` radians, so 12 bits gives errors
+    /// below 1e-3 of the input magnitude.
+    ///
+    /// # Arguments
+    ///
+    /// * `phase` - Intial phase state in radians of complex exponential.
+    /// * `dphase` - Time derivative of phase in radians per sample.
+    /// * `table_bits` - Log base 2 of the number of entries in the table, on
+    ///   the interval [2, 24].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::mixer::Mixer;
+    /// use num::Complex;
+    ///
+    /// let mut mixer = Mixer::new_table(0.0, 0.1, 12);
+    /// let out = mixer.mix(&Complex::new(1.0_f64, 0.0));
+    /// ```
+    pub fn new_table(phase: f64, dphase: f64, table_bits: u32) -> Mixer {
+        assert!(
+            (2..=24).contains(&table_bits),
+            "table bits must be on the interval [2, 24]"
+        );
+        let to_acc = |x: f64| {
+            let cycles = x / (2.0 * PI);
+            ((cycles - cycles.floor()) * 2.0_f64.powi(32)) as u64 as u32
+        };
+        let n = 1 << table_bits;
+        let mut mixer = Mixer::new(phase, dphase);
+        mixer.table = (0..n)
+            .map(|k| (2.0 * PI * k as f64 / n as f64).sin())
+            .collect();
+        mixer.acc = to_acc(phase);
+        mixer.dacc = to_acc(dphase);
+        mixer.shift = 32 - table_bits;
+        mixer
+    }
+
+    /// Looks up the complex exponential for the current accumulator value.
+    fn lookup(&self) -> Complex<f64> {
+        let mask = self.table.len() - 1;
+        let half = 1_u32 << (self.shift - 1);
+        let ix = (self.acc.wrapping_add(half) >> self.shift) as usize & mask;
+        let cos_ix = (ix + self.table.len() / 4) & mask;
+        Complex::new(self.table[cos_ix], self.table[ix])
+    }
+
+    /// Creates a new `Mixer` from a frequency in Hz and a sample rate,
+    /// rather than a phase increment per sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `phase` - Intial phase state in radians of complex exponential.
+    /// * `freq` - Frequency of the complex exponential in Hz.
+    /// * `sample_rate` - Sample rate of the signal being mixed in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::mixer::Mixer;
+    ///
+    /// let mixer = Mixer::from_frequency(0.0, 1000.0, 48000.0);
+    /// ```
+    pub fn from_frequency(phase: f64, freq: f64, sample_rate: f64) -> Mixer {
+        Mixer::new(phase, 2.0 * PI * freq / sample_rate)
     }
 
     /// Runs the input signal through the `Mixer`.
@@ -75,11 +164,18 @@ impl Mixer {
         T: NumCast + Copy + Num,
     {
         let inp: Complex<f64> = math::cast_complex(input).unwrap();
-        let res = inp * Complex::exp(Complex::new(0.0, self.phase));
-        self.phase += self.dphase;
-        if self.phase > 2.0 * PI {
-            self.phase -= 2.0 * PI;
-        }
+        let res = if self.table.is_empty() {
+            let res = inp * Complex::exp(Complex::new(0.0, self.phase));
+            self.phase += self.dphase;
+            if self.phase > 2.0 * PI {
+                self.phase -= 2.0 * PI;
+            }
+            res
+        } else {
+            let res = inp * self.lookup();
+            self.acc = self.acc.wrapping_add(self.dacc);
+            res
+        };
         math::cast_complex(&res).unwrap()
     }
 }
@@ -140,6 +236,32 @@ where
         }
     }
 
+    /// Constructs a new `MixerNode<T>` from a frequency in Hz.
+    ///
+    /// The sample rate will typically come from the `SampleRate` of the node
+    /// feeding the mixer.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq` - The frequency of the oscillator in Hz.
+    /// * `sample_rate` - The sample rate of the input signal in Hz.
+    /// * `phase` - The initial phase of the oscillator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::mixer::*;
+    ///
+    /// let node: MixerNode<f64> = MixerNode::from_frequency(1e3, 48e3, None);
+    /// ```
+    pub fn from_frequency(
+        freq: f64,
+        sample_rate: f64,
+        phase: Option<f64>,
+    ) -> Self {
+        MixerNode::new(2.0 * PI * freq / sample_rate, phase)
+    }
+
     /// Runs the `MixerNode<T>`.  Produces either the mixed `Complex<T>` sample
     /// or a `NodeError`.
     pub fn run(&mut self, input: &Complex<T>) -> Result<Complex<T>, NodeError> {
@@ -147,6 +269,68 @@ where
     }
 }
 
+/// A node that converts a complex baseband signal to a real IF signal.
+///
+/// The signal is mixed up to the intermediate frequency and the real part is
+/// kept, `y[n] = Re{x[n] * exp(j * 2 * pi * f_if * n / fs)}`, which is what
+/// real DACs and any further real signal processing need.  This is the
+/// inverse of forming an analytic signal: the baseband spectrum lands
+/// centered on `f_if`, with its mirror image centered on `-f_if`.  To keep
+/// the two from overlapping, the IF should be larger than the bandwidth of
+/// the baseband signal, and small enough that the signal stays under the
+/// Nyquist frequency.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::mixer::IfDownconvertNode;
+///
+/// // A 12 kHz IF at 48 kHz.
+/// let node: IfDownconvertNode<f32> = IfDownconvertNode::new(12e3, 48e3);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct IfDownconvertNode<T>
+where
+    T: Copy + Num + NumCast + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    mixer: Mixer,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> IfDownconvertNode<T>
+where
+    T: Copy + Num + NumCast + Send,
+{
+    /// Constructs a new `IfDownconvertNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `if_freq` - The intermediate frequency in Hz.
+    /// * `sample_rate` - The sample rate of the input signal in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::mixer::IfDownconvertNode;
+    ///
+    /// let node: IfDownconvertNode<f64> = IfDownconvertNode::new(455e3, 2e6);
+    /// ```
+    pub fn new(if_freq: f64, sample_rate: f64) -> Self {
+        IfDownconvertNode {
+            mixer: Mixer::from_frequency(0.0, if_freq, sample_rate),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `IfDownconvertNode<T>`.  Produces the real IF samples.
+    pub fn run(&mut self, input: &[Complex<T>]) -> Result<Vec<T>, NodeError> {
+        Ok(input.iter().map(|x| self.mixer.mix(x).re).collect())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::mixer::*;
@@ -245,6 +429,36 @@ mod test {
         assert!(check.join().is_ok());
     }
 
+    #[test]
+    // A test to verify a mixer can be configured in Hz from the sample rate
+    // reported by the nodes upstream of it.
+    fn test_mixer_from_frequency() {
+        use crate::io::raw_iq::IQBatchInput;
+        use crate::util::resample_node::DecimateNode;
+        use std::f64::consts::PI;
+        use std::io::Cursor;
+
+        let fs = 48000.0;
+        let freq = 1200.0;
+        let source =
+            IQBatchInput::new(Cursor::new(vec![]), 16).with_sample_rate(fs);
+        assert_eq!(source.sample_rate(), Some(fs));
+
+        let dec: DecimateNode<Complex<f64>> = DecimateNode::new(4)
+            .with_sample_rate(source.sample_rate().unwrap());
+        assert_eq!(dec.sample_rate(), Some(fs / 4.0));
+
+        let mut mixer: MixerNode<f64> =
+            MixerNode::from_frequency(freq, dec.sample_rate().unwrap(), None);
+        let one = Complex::new(1.0, 0.0);
+        for n in 0..100 {
+            let out = mixer.run(&one).unwrap();
+            let phase = 2.0 * PI * freq * n as f64 / (fs / 4.0);
+            assert_approx_eq!(out.re, phase.cos());
+            assert_approx_eq!(out.im, phase.sin());
+        }
+    }
+
     #[test]
     // A test to verify the sample by sample mixer node with initial phase 0.1.
     fn test_mixer_with_phase() {
@@ -334,4 +548,107 @@ mod test {
         });
         assert!(check.join().is_ok());
     }
+
+    #[test]
+    // The table driven mixer should match the exact mixer to within the
+    // phase quantization of the table.
+    fn test_mixer_table() {
+        use std::f64::consts::PI;
+
+        for &bits in &[8, 12, 16] {
+            let mut exact = Mixer::new(0.7, 0.0123);
+            let mut table = Mixer::new_table(0.7, 0.0123, bits);
+            // Quantizing the phase step to the accumulator adds a slow drift
+            // on top of the table error.
+            let n_samples = 20_000;
+            let drift = 2.0 * PI * (n_samples + 1) as f64 / 2.0_f64.powi(32);
+            let bound = PI / (1 << bits) as f64 + drift;
+            for n in 0..n_samples {
+                let input = Complex::new(1.0, -0.5) * (n % 7) as f64;
+                let diff = exact.mix(&input) - table.mix(&input);
+                assert!(diff.norm() <= bound * input.norm());
+            }
+        }
+
+        // A negative frequency wraps the accumulator backwards.
+        let mut exact = Mixer::new(0.0, -0.3);
+        let mut table = Mixer::new_table(0.0, -0.3, 16);
+        let one = Complex::new(1.0, 0.0);
+        for _ in 0..1000 {
+            assert!((exact.mix(&one) - table.mix(&one)).norm() < 1e-4);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    // Compares the speed of the two mixers.  Only meaningful in release
+    // mode, so run it with `cargo test --release -- --ignored`.
+    fn test_mixer_table_timing() {
+        let n = 10_000_000;
+        let input = Complex::new(0.5, 0.25);
+        let mut exact = Mixer::new(0.0, 0.0123);
+        let mut table = Mixer::new_table(0.0, 0.0123, 12);
+
+        let start = Instant::now();
+        let mut acc: Complex<f64> = Complex::zero();
+        for _ in 0..n {
+            acc += exact.mix(&input);
+        }
+        let exact_time = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..n {
+            acc += table.mix(&input);
+        }
+        let table_time = start.elapsed();
+
+        // Using the sum keeps the loops from being optimized away.
+        assert!(acc.re.is_finite() && acc.im.is_finite());
+        if !cfg!(debug_assertions) {
+            assert!(table_time < exact_time);
+        }
+    }
+
+    #[test]
+    // Converts a baseband tone to a real IF and checks that the spectrum of
+    // the output has its peaks at the IF plus the tone frequency.
+    fn test_if_downconvert() {
+        use crate::fft::BatchFFT;
+        use rustfft::FFTplanner;
+
+        let sample_rate = 48000.0;
+        let fft_size = 4800;
+        let tone: Vec<Complex<f64>> = (0..fft_size)
+            .map(|n| {
+                let phase = 2.0 * PI * 1000.0 * n as f64 / sample_rate;
+                Complex::new(0.0, phase).exp()
+            })
+            .collect();
+        let mut node = IfDownconvertNode::new(10000.0, sample_rate);
+        let mut real = vec![];
+        for chunk in tone.chunks(1000) {
+            real.extend(node.run(chunk).unwrap());
+        }
+
+        let mut planner = FFTplanner::new(false);
+        let mut fft = BatchFFT::new(planner.plan_fft(fft_size), fft_size);
+        let input: Vec<Complex<f64>> =
+            real.iter().map(|x| Complex::new(*x, 0.0)).collect();
+        let spectrum: Vec<f64> = fft
+            .run_fft(&input)
+            .iter()
+            .map(|x| x.norm() / fft_size as f64)
+            .collect();
+
+        // 10 Hz bins, so the tone lands at bin 1100 and its mirror image at
+        // the corresponding negative frequency.
+        let bin = 1100;
+        assert!((spectrum[bin] - 0.5).abs() < 1e-6);
+        assert!((spectrum[fft_size - bin] - 0.5).abs() < 1e-6);
+        for (ix, mag) in spectrum.iter().enumerate() {
+            if ix != bin && ix != fft_size - bin {
+                assert!(*mag < 1e-6);
+            }
+        }
+    }
 }
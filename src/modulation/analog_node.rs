@@ -1,4 +1,4 @@
-//! Node based implementation for analog demodulation.
+//! Node based implementation for analog modulation and demodulation.
 //!
 //! Modulation is the general process of taking information and formatting it for going over an RF
 //! channel - in this case, information which varies smoothly, like voice, versus discretely, like
@@ -11,7 +11,12 @@ use crate::modulation::analog;
 use crate::prelude::*;
 use num::Complex;
 use num::Float;
+use num::NumCast;
 use num::Zero;
+use std::f64::consts::PI;
+
+/// Number of taps in the Hilbert transformer used for single sideband.
+const HILBERT_TAPS: usize = 101;
 
 /// This node implements a frequency demodulator node. Upon processing, it takes a batch of complex
 /// samples and converts them to a vector of real, demodulated samples.
@@ -50,3 +55,326 @@ where
         Ok(self.fm.demod(samples))
     }
 }
+
+/// The instantaneous parameters of a batch of complex samples, as produced by
+/// `InstantaneousNode`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Instantaneous<T> {
+    /// Instantaneous amplitude, `|x|`.
+    pub amplitude: Vec<T>,
+    /// Unwrapped instantaneous phase in radians.
+    pub phase: Vec<T>,
+    /// Instantaneous frequency in radians per sample.
+    pub frequency: Vec<T>,
+}
+
+/// This node computes the instantaneous amplitude, unwrapped phase and instantaneous frequency of
+/// a batch of complex samples. Since each sender of a node sends the same value, the three
+/// outputs are bundled together in an `Instantaneous<T>`.
+///
+/// The amplitude is suitable for AM demodulation, the phase for PM demodulation and the frequency
+/// for FM demodulation. The phase unwrapping and the phase derivative both carry state across
+/// batches, so the outputs are continuous from one batch to the next.
+#[derive(Node, Default)]
+#[pass_by_ref]
+pub struct InstantaneousNode<T>
+where
+    T: Float + Zero + Send + Default,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    prev: Option<Complex<T>>,
+    phase: T,
+    pub output: NodeSender<Instantaneous<T>>,
+}
+
+impl<T> InstantaneousNode<T>
+where
+    T: Float + Zero + Send + Default,
+{
+    /// Instantiates a new instantaneous parameter node. Takes no arguments.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use comms_rs::modulation::analog_node::InstantaneousNode;
+    ///
+    /// let node = InstantaneousNode::<f64>::new();
+    /// ```
+    pub fn new() -> Self {
+        InstantaneousNode::default()
+    }
+
+    /// Runs the InstantaneousNode. Produces the instantaneous amplitude, phase and frequency of
+    /// the batch. Cannot actually produce a `NodeError`.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Instantaneous<T>, NodeError> {
+        let mut out = Instantaneous {
+            amplitude: Vec::with_capacity(samples.len()),
+            phase: Vec::with_capacity(samples.len()),
+            frequency: Vec::with_capacity(samples.len()),
+        };
+
+        for samp in samples {
+            let freq = match self.prev {
+                Some(prev) => {
+                    let freq = (samp * prev.conj()).arg();
+                    self.phase = self.phase + freq;
+                    freq
+                }
+                None => {
+                    self.phase = samp.arg();
+                    T::zero()
+                }
+            };
+            self.prev = Some(*samp);
+            out.amplitude.push(samp.norm());
+            out.phase.push(self.phase);
+            out.frequency.push(freq);
+        }
+
+        Ok(out)
+    }
+}
+
+/// The kinds of amplitude modulation supported by `AmModNode`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AmMode {
+    /// Full carrier AM, as used for broadcast, `(1 + m * x) * carrier`.
+    Am,
+    /// Double sideband with a suppressed carrier, `m * x * carrier`.
+    DsbSc,
+    /// Upper sideband, `m * (x + j * H(x)) * carrier`.
+    Usb,
+    /// Lower sideband, `m * (x - j * H(x)) * carrier`.
+    Lsb,
+}
+
+/// This node amplitude modulates a real message onto a complex carrier for a transmitter.
+///
+/// The carrier is a complex exponential at the carrier frequency, which can be zero to produce
+/// the modulated signal at complex baseband. For full carrier AM, the modulation index should be
+/// at most one with the message on [-1, 1] to keep the envelope from going negative. The single
+/// sideband modes form the analytic signal of the message with a windowed Hilbert transformer,
+/// which cancels one sideband from a few hundredths of the sample rate up to just short of the
+/// Nyquist frequency, so the message should have no content near DC, as is the case for voice.
+/// The Hilbert transformer delays the message by `(HILBERT_TAPS - 1) / 2` samples in these modes.
+/// The phase of the carrier and the filter state carry across batches.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct AmModNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    mode: AmMode,
+    mod_index: f64,
+    dphase: f64,
+    phase: f64,
+    hilbert: Vec<f64>,
+    history: Vec<f64>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> AmModNode<T>
+where
+    T: Float + Send,
+{
+    /// Instantiates a new AM modulation node.
+    ///
+    /// Arguments:
+    ///
+    /// * `mode` - Kind of amplitude modulation to apply.
+    /// * `mod_index` - Modulation index, scaling the message before it's applied.
+    /// * `carrier_freq` - Frequency of the carrier in Hz.
+    /// * `sample_rate` - Sample rate of the message and output in Hz.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use comms_rs::modulation::analog_node::{AmModNode, AmMode};
+    ///
+    /// // Upper sideband voice on a 12 kHz carrier at 48 kHz.
+    /// let node = AmModNode::<f32>::new(AmMode::Usb, 1.0, 12e3, 48e3);
+    /// ```
+    pub fn new(
+        mode: AmMode,
+        mod_index: f64,
+        carrier_freq: f64,
+        sample_rate: f64,
+    ) -> Self {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        // Blackman windowed ideal Hilbert transformer, nonzero at odd offsets.
+        let center = (HILBERT_TAPS / 2) as isize;
+        let hilbert = (0..HILBERT_TAPS)
+            .map(|k| {
+                let m = k as isize - center;
+                if m % 2 == 0 {
+                    return 0.0;
+                }
+                let x = 2.0 * PI * k as f64 / (HILBERT_TAPS - 1) as f64;
+                let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+                2.0 / (PI * m as f64) * window
+            })
+            .collect();
+        AmModNode {
+            mode,
+            mod_index,
+            dphase: 2.0 * PI * carrier_freq / sample_rate,
+            phase: 0.0,
+            hilbert,
+            history: vec![0.0; HILBERT_TAPS - 1],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the AmModNode. Produces the modulated batch of complex samples. Cannot actually
+    /// produce a `NodeError`.
+    pub fn run(&mut self, message: &[T]) -> Result<Vec<Complex<T>>, NodeError> {
+        self.history
+            .extend(message.iter().map(|x| x.to_f64().unwrap()));
+        let delay = HILBERT_TAPS / 2;
+        let mut output = Vec::with_capacity(message.len());
+        for (n, window) in self.history.windows(HILBERT_TAPS).enumerate() {
+            let x = self.mod_index * window[HILBERT_TAPS - 1];
+            let envelope = match self.mode {
+                AmMode::Am => Complex::new(1.0 + x, 0.0),
+                AmMode::DsbSc => Complex::new(x, 0.0),
+                AmMode::Usb | AmMode::Lsb => {
+                    let h: f64 = self
+                        .hilbert
+                        .iter()
+                        .zip(window.iter().rev())
+                        .map(|(h, x)| h * x)
+                        .sum();
+                    let h = self.mod_index * h;
+                    let x = self.mod_index * window[HILBERT_TAPS - 1 - delay];
+                    if self.mode == AmMode::Usb {
+                        Complex::new(x, h)
+                    } else {
+                        Complex::new(x, -h)
+                    }
+                }
+            };
+            let phase = self.phase + self.dphase * n as f64;
+            let y = envelope * Complex::new(0.0, phase).exp();
+            output.push(Complex::new(
+                NumCast::from(y.re).unwrap(),
+                NumCast::from(y.im).unwrap(),
+            ));
+        }
+        self.phase =
+            (self.phase + self.dphase * message.len() as f64) % (2.0 * PI);
+        let used = self.history.len() + 1 - HILBERT_TAPS;
+        self.history.drain(..used);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::modulation::analog_node::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Checks the outputs against an FM tone whose phase wraps many times,
+    // split across uneven batches.
+    fn test_instantaneous_fm_tone() {
+        let amp = 0.7;
+        let f_mod = 0.002;
+        let f_dev = 0.05;
+        let mut phase = vec![0.3];
+        for n in 1..5000 {
+            let freq = 2.0 * PI * f_dev * (2.0 * PI * f_mod * n as f64).cos();
+            phase.push(phase[n - 1] + freq);
+        }
+        let signal: Vec<Complex<f64>> =
+            phase.iter().map(|p| Complex::from_polar(amp, *p)).collect();
+
+        let mut node = InstantaneousNode::new();
+        let mut out = Instantaneous::default();
+        for chunk in signal.chunks(333) {
+            let batch = node.run(chunk).unwrap();
+            out.amplitude.extend(batch.amplitude);
+            out.phase.extend(batch.phase);
+            out.frequency.extend(batch.frequency);
+        }
+
+        for (n, truth) in phase.iter().enumerate().skip(1) {
+            let freq = 2.0 * PI * f_dev * (2.0 * PI * f_mod * n as f64).cos();
+            assert!((out.amplitude[n] - amp).abs() < 1e-12);
+            assert!((out.phase[n] - truth).abs() < 1e-9);
+            assert!((out.frequency[n] - freq).abs() < 1e-9);
+        }
+    }
+
+    // Power of the component of a signal at a frequency in cycles per sample.
+    fn tone_power(y: &[Complex<f64>], freq: f64) -> f64 {
+        let c: Complex<f64> = y
+            .iter()
+            .enumerate()
+            .map(|(n, y)| {
+                y * Complex::new(0.0, -2.0 * PI * freq * n as f64).exp()
+            })
+            .sum();
+        (c.norm() / y.len() as f64).powi(2)
+    }
+
+    #[test]
+    // Full carrier AM should have an envelope that follows the message, and DSB-SC should give
+    // back the scaled message when mixed back down with the carrier.
+    fn test_am_mod_envelope() {
+        let (f_msg, f_c) = (0.01, 0.1);
+        let message: Vec<f64> = (0..2000)
+            .map(|n| (2.0 * PI * f_msg * n as f64).sin())
+            .collect();
+
+        let mut node = AmModNode::new(AmMode::Am, 0.5, f_c * 8000.0, 8000.0);
+        let mut out = vec![];
+        for chunk in message.chunks(123) {
+            out.extend(node.run(chunk).unwrap());
+        }
+        for (x, y) in message.iter().zip(&out) {
+            assert!((y.norm() - (1.0 + 0.5 * x)).abs() < 1e-9);
+        }
+
+        let mut node = AmModNode::new(AmMode::DsbSc, 2.0, f_c, 1.0);
+        let mut out = vec![];
+        for chunk in message.chunks(77) {
+            out.extend(node.run(chunk).unwrap());
+        }
+        for (n, (x, y)) in message.iter().zip(&out).enumerate() {
+            let down = y * Complex::new(0.0, -2.0 * PI * f_c * n as f64).exp();
+            assert!((down - Complex::new(2.0 * x, 0.0)).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    // A tone sent on each sideband should land above or below the carrier alone, with the other
+    // sideband and the carrier suppressed.
+    fn test_am_mod_ssb() {
+        let (f_msg, f_c) = (0.05, 0.2);
+        let message: Vec<f64> = (0..4000)
+            .map(|n| (2.0 * PI * f_msg * n as f64).cos())
+            .collect();
+        for &(mode, wanted, unwanted) in &[
+            (AmMode::Usb, f_c + f_msg, f_c - f_msg),
+            (AmMode::Lsb, f_c - f_msg, f_c + f_msg),
+        ] {
+            let mut node = AmModNode::new(mode, 1.0, f_c, 1.0);
+            let mut out = vec![];
+            for chunk in message.chunks(500) {
+                out.extend(node.run(chunk).unwrap());
+            }
+            let out = &out[HILBERT_TAPS..];
+            let wanted = tone_power(out, wanted);
+            assert!((wanted - 1.0).abs() < 0.01);
+            assert!(
+                10.0 * (tone_power(out, unwanted) / wanted).log10() < -50.0
+            );
+            assert!(10.0 * (tone_power(out, f_c) / wanted).log10() < -50.0);
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! Provide tools to do digital modulation
 
+use crate::prelude::*;
+
+use crate::util::math::binary_to_gray;
 use num::Complex;
+use std::f64::consts::PI;
 
 /// Modulates a bit to a complex int16 impulse via BPSK
 pub fn bpsk_bit_mod(bit: u8) -> Option<Complex<i16>> {
@@ -43,11 +47,359 @@ pub fn qpsk_byte_mod(byte: u8) -> Vec<Complex<i16>> {
         .collect()
 }
 
+/// Builds a Gray coded M-PSK constellation table for use with `MapperNode`
+/// and `DemapperNode`.
+///
+/// The points are on the unit circle, with symbol 0 at an angle of zero and
+/// neighboring points differing by a single bit.
+///
+/// # Arguments
+///
+/// * `bits_per_symbol` - Number of bits per symbol, so that there are
+///   `2^bits_per_symbol` points.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::modulation::digital::{psk_table, MapperNode};
+///
+/// // 8-PSK.
+/// let node = MapperNode::new(psk_table(3), 3);
+/// ```
+pub fn psk_table(bits_per_symbol: usize) -> Vec<Complex<f64>> {
+    let m = 1 << bits_per_symbol;
+    let mut table = vec![Complex::new(0.0, 0.0); m];
+    for k in 0..m {
+        let angle = 2.0 * PI * k as f64 / m as f64;
+        table[binary_to_gray(k as u32) as usize] =
+            Complex::from_polar(1.0, angle);
+    }
+    table
+}
+
+/// Builds a Gray coded square QAM constellation table for use with
+/// `MapperNode` and `DemapperNode`.
+///
+/// Half of the bits of each symbol select the in-phase level and the other
+/// half select the quadrature level, each Gray coded so that horizontally or
+/// vertically adjacent points differ by a single bit.  The table is scaled to
+/// unit average power.
+///
+/// # Arguments
+///
+/// * `bits_per_symbol` - Number of bits per symbol.  Must be even.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::modulation::digital::{qam_table, MapperNode};
+///
+/// // 16-QAM.
+/// let node = MapperNode::new(qam_table(4), 4);
+/// ```
+pub fn qam_table(bits_per_symbol: usize) -> Vec<Complex<f64>> {
+    assert!(
+        bits_per_symbol > 0 && bits_per_symbol & 1 == 0,
+        "square QAM needs an even number of bits per symbol"
+    );
+    let half = bits_per_symbol / 2;
+    let side = 1 << half;
+    let level = |k: usize| 2.0 * k as f64 - (side - 1) as f64;
+
+    // The average power of a square QAM with levels +/-1, +/-3, ... is
+    // 2 * (M - 1) / 3.
+    let scale = 1.0 / (2.0 * (side * side - 1) as f64 / 3.0).sqrt();
+    let mut table = vec![Complex::new(0.0, 0.0); side * side];
+    for i in 0..side {
+        for q in 0..side {
+            let ix =
+                (binary_to_gray(i as u32) << half) | binary_to_gray(q as u32);
+            table[ix as usize] = Complex::new(level(i), level(q)) * scale;
+        }
+    }
+    table
+}
+
+/// A node that maps bits onto an arbitrary constellation.
+///
+/// Incoming bits (one bit per `u8`, valued 0 or 1) are packed MSB first into
+/// groups of `bits_per_symbol` to form a symbol index, which is then looked
+/// up in the user-supplied constellation table.  Any bits left over at the
+/// end of a batch are held until enough bits arrive in the next batch to form
+/// a full symbol.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::modulation::digital::MapperNode;
+/// use num::Complex;
+///
+/// // Gray coded QPSK.
+/// let table = vec![
+///     Complex::new(1.0, 1.0),
+///     Complex::new(-1.0, 1.0),
+///     Complex::new(1.0, -1.0),
+///     Complex::new(-1.0, -1.0),
+/// ];
+/// let node = MapperNode::new(table, 2);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct MapperNode {
+    pub input: NodeReceiver<Vec<u8>>,
+    table: Vec<Complex<f64>>,
+    bits_per_symbol: usize,
+    leftover: Vec<u8>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl MapperNode {
+    /// Constructs a new `MapperNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Constellation points, indexed by symbol.  Must contain
+    ///   `2^bits_per_symbol` points.
+    /// * `bits_per_symbol` - Number of bits packed into each symbol.  Must
+    ///   be nonzero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::modulation::digital::MapperNode;
+    /// use num::Complex;
+    ///
+    /// let table = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+    /// let node = MapperNode::new(table, 1);
+    /// ```
+    pub fn new(table: Vec<Complex<f64>>, bits_per_symbol: usize) -> Self {
+        assert!(bits_per_symbol > 0, "bits_per_symbol must be nonzero");
+        assert_eq!(
+            table.len(),
+            1 << bits_per_symbol,
+            "constellation table must have 2^bits_per_symbol points"
+        );
+        MapperNode {
+            table,
+            bits_per_symbol,
+            leftover: vec![],
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Maps a list of symbol indices onto the constellation.
+    ///
+    /// # Arguments
+    ///
+    /// * `indices` - Symbol indices to map.
+    pub fn map(&self, indices: &[usize]) -> Vec<Complex<f64>> {
+        indices.iter().map(|&ix| self.table[ix]).collect()
+    }
+
+    /// Runs the `MapperNode`.  Produces the constellation points for every
+    /// full symbol's worth of bits received so far.
+    pub fn run(&mut self, bits: &[u8]) -> Result<Vec<Complex<f64>>, NodeError> {
+        self.leftover.extend_from_slice(bits);
+        let n_symbols = self.leftover.len() / self.bits_per_symbol;
+        let indices: Vec<usize> = self
+            .leftover
+            .chunks_exact(self.bits_per_symbol)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |acc, &bit| (acc << 1) | (bit & 1) as usize)
+            })
+            .collect();
+        self.leftover.drain(..n_symbols * self.bits_per_symbol);
+        Ok(self.map(&indices))
+    }
+}
+
+/// A node that makes hard decisions on samples against an arbitrary
+/// constellation.
+///
+/// Each sample is assigned to the nearest point in the constellation table
+/// and the index of that point is unpacked MSB first into `bits_per_symbol`
+/// bits, making this the inverse of `MapperNode`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::modulation::digital::DemapperNode;
+/// use num::Complex;
+///
+/// let table = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+/// let node = DemapperNode::new(table, 1);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DemapperNode {
+    pub input: NodeReceiver<Vec<Complex<f64>>>,
+    table: Vec<Complex<f64>>,
+    bits_per_symbol: usize,
+    pub output: NodeSender<Vec<u8>>,
+}
+
+impl DemapperNode {
+    /// Constructs a new `DemapperNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - Constellation points, indexed by symbol.  Must contain
+    ///   `2^bits_per_symbol` points.
+    /// * `bits_per_symbol` - Number of bits unpacked from each symbol.  Must
+    ///   be nonzero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::modulation::digital::DemapperNode;
+    /// use num::Complex;
+    ///
+    /// let table = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+    /// let node = DemapperNode::new(table, 1);
+    /// ```
+    pub fn new(table: Vec<Complex<f64>>, bits_per_symbol: usize) -> Self {
+        assert!(bits_per_symbol > 0, "bits_per_symbol must be nonzero");
+        assert_eq!(
+            table.len(),
+            1 << bits_per_symbol,
+            "constellation table must have 2^bits_per_symbol points"
+        );
+        DemapperNode {
+            table,
+            bits_per_symbol,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Finds the index of the nearest constellation point for each sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Samples to make decisions on.
+    pub fn demap(&self, samples: &[Complex<f64>]) -> Vec<usize> {
+        samples
+            .iter()
+            .map(|samp| {
+                self.table
+                    .iter()
+                    .map(|point| (samp - point).norm_sqr())
+                    .enumerate()
+                    .fold((0, f64::INFINITY), |best, (ix, dist)| {
+                        if dist < best.1 {
+                            (ix, dist)
+                        } else {
+                            best
+                        }
+                    })
+                    .0
+            })
+            .collect()
+    }
+
+    /// Runs the `DemapperNode`.  Produces the decided bits for each sample.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f64>],
+    ) -> Result<Vec<u8>, NodeError> {
+        let bps = self.bits_per_symbol;
+        Ok(self
+            .demap(samples)
+            .iter()
+            .flat_map(|&ix| (0..bps).rev().map(move |b| ((ix >> b) & 1) as u8))
+            .collect())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use num::Complex;
 
+    fn eight_point_table() -> Vec<Complex<f64>> {
+        // An APSK-style table: four inner points and four rotated outer
+        // points.
+        (0..8)
+            .map(|ix| {
+                let radius = if ix < 4 { 1.0 } else { 2.5 };
+                let offset = if ix < 4 { 0.0 } else { 0.25 };
+                let angle =
+                    std::f64::consts::PI / 2.0 * ((ix % 4) as f64 + offset);
+                Complex::from_polar(radius, angle)
+            })
+            .collect()
+    }
+
+    #[test]
+    // Round trips every symbol index through a custom 8-point table, with a
+    // bit of noise added before demapping.
+    fn test_custom_table_round_trip() {
+        let table = eight_point_table();
+        let mapper = MapperNode::new(table.clone(), 3);
+        let demapper = DemapperNode::new(table, 3);
+        let indices: Vec<usize> = (0..64).map(|ix| (ix * 5) % 8).collect();
+        let points: Vec<Complex<f64>> = mapper
+            .map(&indices)
+            .iter()
+            .enumerate()
+            .map(|(ix, p)| p + Complex::from_polar(0.2, ix as f64))
+            .collect();
+        assert_eq!(demapper.demap(&points), indices);
+    }
+
+    #[test]
+    // Checks that bits are packed MSB first and that leftover bits carry
+    // into the next batch.
+    fn test_mapper_bits() {
+        let table = eight_point_table();
+        let mut mapper = MapperNode::new(table.clone(), 3);
+        let mut demapper = DemapperNode::new(table.clone(), 3);
+        let bits = vec![1, 1, 0, 0, 0, 1, 1, 1, 1];
+        let first = mapper.run(&bits[..4]).unwrap();
+        assert_eq!(first, vec![table[6]]);
+        let second = mapper.run(&bits[4..]).unwrap();
+        assert_eq!(second, vec![table[1], table[7]]);
+
+        let mut points = first;
+        points.extend(second);
+        assert_eq!(demapper.run(&points).unwrap(), bits);
+    }
+
+    #[test]
+    // Checks that the nearest neighbors of every point in the generated
+    // tables differ from it by a single bit.
+    fn test_gray_tables() {
+        for bps in 1..6 {
+            let table = psk_table(bps);
+            let m = table.len();
+            for (ix, p) in table.iter().enumerate() {
+                assert!((p.norm() - 1.0).abs() < 1e-12);
+                let next = p * Complex::from_polar(1.0, 2.0 * PI / m as f64);
+                let jx =
+                    DemapperNode::new(table.clone(), bps).demap(&[next])[0];
+                assert_eq!((ix ^ jx).count_ones(), 1);
+            }
+        }
+
+        for &bps in &[2, 4, 6] {
+            let table = qam_table(bps);
+            let power = table.iter().map(|p| p.norm_sqr()).sum::<f64>()
+                / table.len() as f64;
+            assert!((power - 1.0).abs() < 1e-12);
+            let step = (table[0] - table[1]).norm();
+            for (ix, p) in table.iter().enumerate() {
+                for (jx, q) in table.iter().enumerate() {
+                    if ((p - q).norm() - step).abs() < 1e-9 {
+                        assert_eq!((ix ^ jx).count_ones(), 1);
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_bpsk_bit() {
         assert_eq!(bpsk_bit_mod(0_u8).unwrap(), Complex::new(1, 0));
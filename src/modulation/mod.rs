@@ -3,3 +3,4 @@
 pub mod analog;
 pub mod analog_node;
 pub mod digital;
+pub mod dtmf;
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use hashbrown::HashMap;
+use std::fmt::Write;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
@@ -9,9 +10,18 @@ use uuid::Uuid;
 /// after starting the graph. Currently, this does not support connecting the
 /// nodes; nodes need to be connected before passing them to the graph at the
 /// moment.
+///
+/// Nodes can be given names by adding them with `add_named_node`, which can
+/// then be used to look them up with `node_by_name` and to connect them with
+/// `connect_named_nodes`.  `describe` lists the nodes and every connection
+/// made through the graph, which helps when debugging or logging a large
+/// graph.
 #[derive(Default)]
 pub struct Graph {
     nodes: HashMap<Uuid, Arc<Mutex<dyn Node>>>,
+    order: Vec<Uuid>,
+    names: HashMap<String, Uuid>,
+    connections: Mutex<Vec<(Option<String>, Option<String>)>>,
     handles: Vec<JoinHandle<()>>,
     channel_size: Option<usize>,
 }
@@ -20,13 +30,35 @@ impl Graph {
     pub fn new(channel_size: Option<usize>) -> Self {
         Graph {
             nodes: HashMap::new(),
+            order: vec![],
+            names: HashMap::new(),
+            connections: Mutex::new(vec![]),
             handles: vec![],
             channel_size,
         }
     }
 
     pub fn add_node(&mut self, node: Arc<Mutex<dyn Node>>) {
-        self.nodes.insert(Uuid::new_v4(), node);
+        let id = Uuid::new_v4();
+        self.nodes.insert(id, node);
+        self.order.push(id);
+    }
+
+    /// Adds a node to the graph under a name.  Gives a
+    /// `NodeError::DataError` if the name is already in use, leaving the
+    /// graph as it was.
+    pub fn add_named_node(
+        &mut self,
+        node: Arc<Mutex<dyn Node>>,
+        name: &str,
+    ) -> Result<(), NodeError> {
+        if self.names.contains_key(name) {
+            return Err(NodeError::DataError);
+        }
+        self.add_node(node);
+        self.names
+            .insert(name.to_string(), *self.order.last().unwrap());
+        Ok(())
     }
 
     pub fn add_nodes(&mut self, nodes: Vec<Arc<Mutex<dyn Node>>>) {
@@ -35,11 +67,52 @@ impl Graph {
         }
     }
 
+    /// Returns the node added under a name, if there is one.
+    pub fn node_by_name(&self, name: &str) -> Option<Arc<Mutex<dyn Node>>> {
+        self.names.get(name).map(|id| self.nodes[id].clone())
+    }
+
     pub fn connect_nodes<T>(
         &self,
         sender: &mut NodeSender<T>,
         receiver: &mut NodeReceiver<T>,
         default: Option<T>,
+    ) {
+        self.connect(sender, receiver, default, None, None);
+    }
+
+    /// Connects two named nodes, as `connect_nodes` does, so that the
+    /// connection shows up under their names in `describe`.  Gives a
+    /// `NodeError::DataError` without connecting anything if either name
+    /// isn't in the graph.
+    pub fn connect_named_nodes<T>(
+        &self,
+        from: &str,
+        sender: &mut NodeSender<T>,
+        to: &str,
+        receiver: &mut NodeReceiver<T>,
+        default: Option<T>,
+    ) -> Result<(), NodeError> {
+        if !self.names.contains_key(from) || !self.names.contains_key(to) {
+            return Err(NodeError::DataError);
+        }
+        self.connect(
+            sender,
+            receiver,
+            default,
+            Some(from.to_string()),
+            Some(to.to_string()),
+        );
+        Ok(())
+    }
+
+    fn connect<T>(
+        &self,
+        sender: &mut NodeSender<T>,
+        receiver: &mut NodeReceiver<T>,
+        default: Option<T>,
+        from: Option<String>,
+        to: Option<String>,
     ) {
         let (send, recv) = match self.channel_size {
             Some(size) => channel::bounded(size),
@@ -47,6 +120,35 @@ impl Graph {
         };
         sender.push((send, default));
         *receiver = Some(recv);
+        self.connections.lock().unwrap().push((from, to));
+    }
+
+    /// Returns a description of the graph, listing each node by name in the
+    /// order it was added followed by the connections in the order they
+    /// were made.  Nodes and the ends of connections without a name are
+    /// listed as `<unnamed>`.
+    pub fn describe(&self) -> String {
+        let mut names: HashMap<Uuid, &str> = HashMap::new();
+        for (name, id) in self.names.iter() {
+            names.insert(*id, name);
+        }
+        let mut desc = format!("nodes ({}):\n", self.order.len());
+        for id in &self.order {
+            let name = names.get(id).cloned().unwrap_or("<unnamed>");
+            writeln!(desc, "  {}", name).unwrap();
+        }
+        let connections = self.connections.lock().unwrap();
+        writeln!(desc, "connections ({}):", connections.len()).unwrap();
+        for (from, to) in connections.iter() {
+            writeln!(
+                desc,
+                "  {} -> {}",
+                from.as_deref().unwrap_or("<unnamed>"),
+                to.as_deref().unwrap_or("<unnamed>")
+            )
+            .unwrap();
+        }
+        desc
     }
 
     pub fn is_connected(&self) -> bool {
@@ -97,6 +97,33 @@ pub trait Node: Send {
     fn is_connected(&self) -> bool;
 }
 
+/// A trait for nodes that know the sample rate of the data they produce.
+///
+/// Source nodes such as radios and file readers can be configured with the
+/// sample rate of the data they emit, and nodes that change the sample rate
+/// (like decimators) can report their output rate given their input rate.
+/// Downstream nodes can then be constructed with parameters in Hz rather
+/// than in radians per sample.
+///
+/// # Example
+///
+/// ```
+/// use comms_rs::prelude::*;
+/// use comms_rs::io::raw_iq::IQBatchInput;
+/// use comms_rs::mixer::MixerNode;
+/// use std::io::Cursor;
+///
+/// let source = IQBatchInput::new(Cursor::new(vec![]), 1024)
+///     .with_sample_rate(48000.0);
+/// let fs = source.sample_rate().unwrap();
+/// let mixer: MixerNode<f64> = MixerNode::from_frequency(1000.0, fs, None);
+/// ```
+pub trait SampleRate {
+    /// Returns the sample rate of the node's output in Hz, or `None` if the
+    /// node hasn't been told what it is.
+    fn sample_rate(&self) -> Option<f64>;
+}
+
 /// Connects two nodes together with crossbeam channels.
 ///
 /// ```
@@ -477,6 +504,135 @@ mod test {
         }
     }
 
+    #[test]
+    /// Builds a graph of named nodes and checks that they can be looked up
+    /// by name, that duplicate and unknown names are refused, and that every
+    /// connection is described.
+    fn test_named_graph() {
+        #[derive(Node)]
+        struct Source {
+            pub output: NodeSender<u32>,
+        }
+
+        impl Source {
+            pub fn new() -> Self {
+                Source {
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self) -> Result<u32, NodeError> {
+                Ok(1)
+            }
+        }
+
+        #[derive(Node)]
+        struct Double {
+            pub input: NodeReceiver<u32>,
+            pub output: NodeSender<u32>,
+        }
+
+        impl Double {
+            pub fn new() -> Self {
+                Double {
+                    input: Default::default(),
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self, x: u32) -> Result<u32, NodeError> {
+                Ok(2 * x)
+            }
+        }
+
+        #[derive(Node)]
+        struct Sink {
+            pub input: NodeReceiver<u32>,
+        }
+
+        impl Sink {
+            pub fn new() -> Self {
+                Sink {
+                    input: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self, _x: u32) -> Result<(), NodeError> {
+                Ok(())
+            }
+        }
+
+        // Compares the data pointers alone, since the vtables of the same
+        // node may differ.
+        fn same_node(
+            a: &Arc<Mutex<dyn Node>>,
+            b: &Arc<Mutex<dyn Node>>,
+        ) -> bool {
+            Arc::as_ptr(a) as *const u8 == Arc::as_ptr(b) as *const u8
+        }
+
+        let source = Arc::new(Mutex::new(Source::new()));
+        let double = Arc::new(Mutex::new(Double::new()));
+        let sink = Arc::new(Mutex::new(Sink::new()));
+        let spare = Arc::new(Mutex::new(Sink::new()));
+        let mut graph = Graph::new(None);
+        graph.add_named_node(source.clone(), "source").unwrap();
+        graph.add_named_node(double.clone(), "double").unwrap();
+        graph.add_named_node(sink.clone(), "sink").unwrap();
+        graph.add_node(spare.clone());
+        assert!(graph.add_named_node(spare.clone(), "sink").is_err());
+        {
+            let mut source = source.lock().unwrap();
+            let mut double = double.lock().unwrap();
+            let mut sink = sink.lock().unwrap();
+            let mut spare = spare.lock().unwrap();
+            graph
+                .connect_named_nodes(
+                    "source",
+                    &mut source.output,
+                    "double",
+                    &mut double.input,
+                    None,
+                )
+                .unwrap();
+            graph
+                .connect_named_nodes(
+                    "double",
+                    &mut double.output,
+                    "sink",
+                    &mut sink.input,
+                    None,
+                )
+                .unwrap();
+            assert!(graph
+                .connect_named_nodes(
+                    "double",
+                    &mut double.output,
+                    "missing",
+                    &mut spare.input,
+                    None,
+                )
+                .is_err());
+            assert!(spare.input.is_none());
+            graph.connect_nodes(&mut double.output, &mut spare.input, None);
+        }
+
+        let source: Arc<Mutex<dyn Node>> = source;
+        let sink: Arc<Mutex<dyn Node>> = sink;
+        assert!(same_node(&graph.node_by_name("source").unwrap(), &source));
+        assert!(same_node(&graph.node_by_name("sink").unwrap(), &sink));
+        assert!(!same_node(&graph.node_by_name("double").unwrap(), &sink));
+        assert!(graph.node_by_name("missing").is_none());
+
+        assert_eq!(
+            graph.describe(),
+            "nodes (4):\n  source\n  double\n  sink\n  <unnamed>\n\
+             connections (3):\n  source -> double\n  double -> sink\n  \
+             <unnamed> -> <unnamed>\n"
+        );
+        assert!(graph.is_connected());
+    }
+
     #[test]
     /// Constructs a network with three nodes: two aggregating data and one
     /// simple node. Node1 is actually doing aggregation whereas Node2
@@ -1008,4 +1164,164 @@ mod test {
         });
         assert!(check.join().is_ok());
     }
+
+    #[test]
+    /// Feeds a non-blocking node from two inputs running at very different
+    /// rates to make sure it keeps up with the fast input instead of
+    /// stalling on the slow one, that it keeps running on the other input
+    /// when one closes, and that it shuts down once both have closed.
+    fn test_non_blocking() {
+        #[derive(Node)]
+        struct CountNode {
+            count: u32,
+            limit: u32,
+            delay: Duration,
+            output: NodeSender<u32>,
+        }
+
+        impl CountNode {
+            pub fn new(limit: u32, delay: Duration) -> Self {
+                CountNode {
+                    count: 0,
+                    limit,
+                    delay,
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self) -> Result<u32, NodeError> {
+                if self.count == self.limit {
+                    return Err(NodeError::DataEnd);
+                }
+                thread::sleep(self.delay);
+                self.count += 1;
+                Ok(self.count)
+            }
+        }
+
+        #[derive(Node)]
+        #[non_blocking]
+        #[aggregate]
+        struct MergeNode {
+            fast: NodeReceiver<u32>,
+            slow: NodeReceiver<u32>,
+            unused: NodeReceiver<u32>,
+            n_fast: u32,
+            n_slow: u32,
+            output: NodeSender<u32>,
+        }
+
+        impl MergeNode {
+            pub fn new() -> Self {
+                MergeNode {
+                    n_fast: 0,
+                    n_slow: 0,
+                    fast: Default::default(),
+                    slow: Default::default(),
+                    unused: Default::default(),
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(
+                &mut self,
+                fast: Option<u32>,
+                slow: Option<u32>,
+                unused: Option<u32>,
+            ) -> Result<Option<u32>, NodeError> {
+                assert!(unused.is_none());
+                if fast.is_some() {
+                    self.n_fast += 1;
+                }
+                if slow.is_some() {
+                    self.n_slow += 1;
+                }
+                Ok(fast)
+            }
+        }
+
+        // The slow input finishes well before the fast one.
+        let mut fast_node = CountNode::new(100, Duration::from_millis(10));
+        let mut slow_node = CountNode::new(3, Duration::from_millis(100));
+        let mut merge_node = MergeNode::new();
+        connect_nodes!(fast_node, output, merge_node, fast);
+        connect_nodes!(slow_node, output, merge_node, slow);
+        start_nodes!(fast_node, slow_node);
+
+        let check = thread::spawn(move || {
+            merge_node.start();
+            merge_node
+        });
+        let merge_node = check.join().unwrap();
+        assert_eq!(merge_node.n_fast, 100);
+        assert_eq!(merge_node.n_slow, 3);
+    }
+
+    #[test]
+    /// Runs a finite source that drops its sender once it's done to make sure
+    /// the downstream node sees the disconnect as the end of the data and
+    /// shuts down cleanly instead of panicking.
+    fn test_source_disconnect() {
+        #[derive(Node)]
+        struct FiniteNode {
+            count: u32,
+            output: NodeSender<u32>,
+        }
+
+        impl FiniteNode {
+            pub fn new() -> Self {
+                FiniteNode {
+                    count: 0,
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self) -> Result<u32, NodeError> {
+                if self.count == 10 {
+                    return Err(NodeError::DataEnd);
+                }
+                self.count += 1;
+                Ok(self.count)
+            }
+        }
+
+        #[derive(Node)]
+        struct SumNode {
+            input: NodeReceiver<u32>,
+            sum: u32,
+        }
+
+        impl SumNode {
+            pub fn new() -> Self {
+                SumNode {
+                    sum: 0,
+                    input: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self, x: u32) -> Result<(), NodeError> {
+                self.sum += x;
+                Ok(())
+            }
+        }
+
+        let mut source = FiniteNode::new();
+        let mut sum_node = SumNode::new();
+        connect_nodes!(source, output, sum_node, input);
+
+        // Dropping the source drops its sender, disconnecting the channel.
+        thread::spawn(move || {
+            source.start();
+        })
+        .join()
+        .unwrap();
+
+        let check = thread::spawn(move || {
+            sum_node.start();
+            assert!(sum_node.call().is_err());
+            sum_node
+        });
+        let sum_node = check.join().unwrap();
+        assert_eq!(sum_node.sum, 55);
+    }
 }
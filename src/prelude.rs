@@ -2,6 +2,7 @@
 
 pub use crate::node::Node;
 pub use crate::node::NodeError;
+pub use crate::node::SampleRate;
 pub use crossbeam::{channel, Receiver, Sender};
 pub use node_derive::Node;
 pub use std::thread;
@@ -1,8 +1,23 @@
 //! Module to provide pulse shaping features.
 use crate::filter::fir::*;
 use crate::prelude::*;
+use crate::util::math::{gaussian_taps, rc_taps, rect_taps, rrc_taps};
+use crate::util::MathError;
 
-use num::{Complex, Num, Zero};
+use num::{Complex, Num, NumCast, Zero};
+
+/// The pulse shapes that `PulseNode::from_spec` can design taps for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PulseShape {
+    /// Root raised cosine, with the rolloff as the excess bandwidth.
+    RRC,
+    /// Raised cosine, with the rolloff as the excess bandwidth.
+    RC,
+    /// Gaussian, with the rolloff as the shaping parameter `alpha`.
+    Gaussian,
+    /// Rectangular, with the rolloff unused.
+    Rect,
+}
 
 /// A node that implements a pulse shaping filter of a specified sort.
 ///
@@ -78,7 +93,56 @@ where
             output: Default::default(),
         }
     }
+}
+
+impl<T> PulseNode<T>
+where
+    T: Num + NumCast + Copy + Send,
+{
+    /// Constructs a new `PulseNode<T>` from a description of the pulse
+    /// shape, designing the taps with the matching function in `util::math`.
+    /// This keeps the samples per symbol used to design the taps and to
+    /// upsample the symbols the same.
+    ///
+    /// # Arguments
+    ///
+    /// * `shape` - The pulse shape to design
+    /// * `n_taps` - Number of taps in the pulse shaping filter
+    /// * `sam_per_sym` - Number of samples per symbol in the output
+    /// * `rolloff` - Shaping parameter of the pulse, see `PulseShape`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::pulse::{PulseNode, PulseShape};
+    ///
+    /// let node: PulseNode<f32> =
+    ///     PulseNode::from_spec(PulseShape::RRC, 33, 4, 0.35).unwrap();
+    /// ```
+    pub fn from_spec(
+        shape: PulseShape,
+        n_taps: u32,
+        sam_per_sym: usize,
+        rolloff: f64,
+    ) -> Result<Self, MathError> {
+        let sps = sam_per_sym as f64;
+        let taps = match shape {
+            PulseShape::RRC => rrc_taps(n_taps, sps, rolloff)?,
+            PulseShape::RC => rc_taps(n_taps, sps, rolloff)?,
+            PulseShape::Gaussian => gaussian_taps(n_taps, sps, rolloff)
+                .ok_or(MathError::ConvertError)?,
+            PulseShape::Rect => {
+                rect_taps(n_taps as usize).ok_or(MathError::ConvertError)?
+            }
+        };
+        Ok(PulseNode::new(taps, sam_per_sym))
+    }
+}
 
+impl<T> PulseNode<T>
+where
+    T: Num + Copy + Send,
+{
     pub fn run(
         &mut self,
         input: &Complex<T>,
@@ -96,7 +160,6 @@ where
 mod test {
     use crate::prelude::*;
     use crate::pulse::*;
-    use crate::util::math::*;
     use num::Complex;
     use std::thread;
     use std::time::Instant;
@@ -207,4 +270,37 @@ mod test {
         });
         assert!(check.join().is_ok());
     }
+
+    #[test]
+    // Checks that designing the taps from a spec gives the same output as
+    // designing them by hand.
+    fn test_from_spec() {
+        let symbols = vec![
+            Complex::new(1.0, -1.0),
+            Complex::new(-1.0, -1.0),
+            Complex::new(1.0, 1.0),
+            Complex::new(-1.0, 1.0),
+        ];
+        let taps: Vec<Complex<f64>> = rrc_taps(17, 4.0, 0.35).unwrap();
+        let mut manual = PulseNode::new(taps, 4);
+        let mut spec =
+            PulseNode::from_spec(PulseShape::RRC, 17, 4, 0.35).unwrap();
+        for _ in 0..3 {
+            for sym in &symbols {
+                assert_eq!(manual.run(sym).unwrap(), spec.run(sym).unwrap());
+            }
+        }
+
+        let taps: Vec<Complex<f64>> = gaussian_taps(12, 2.0, 0.5).unwrap();
+        let mut manual = PulseNode::new(taps, 2);
+        let mut spec =
+            PulseNode::from_spec(PulseShape::Gaussian, 12, 2, 0.5).unwrap();
+        for sym in &symbols {
+            assert_eq!(manual.run(sym).unwrap(), spec.run(sym).unwrap());
+        }
+
+        let spec: Result<PulseNode<f64>, _> =
+            PulseNode::from_spec(PulseShape::RC, 17, 4, 1.5);
+        assert!(spec.is_err());
+    }
 }
@@ -0,0 +1,4 @@
+//! Nodes that generate signals from nothing, for use at the head of a graph.
+
+pub mod preamble_node;
+pub mod test_vector_node;
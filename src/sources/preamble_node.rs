@@ -0,0 +1,106 @@
+//! A source of standard preamble and synchronization sequences.
+use crate::prelude::*;
+
+use crate::util::math::{barker_code, zadoff_chu};
+use crate::util::MathError;
+use num::Complex;
+
+/// The sequence produced by a `PreambleSourceNode`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Preamble {
+    /// A Barker code of the given length, as generated by `barker_code`.
+    Barker { len: usize },
+    /// A Zadoff-Chu sequence, as generated by `zadoff_chu`.
+    ZadoffChu { root: u32, len: u32 },
+}
+
+/// A node that produces a standard preamble sequence.
+///
+/// Barker codes and Zadoff-Chu sequences both have a sharp autocorrelation
+/// peak, which makes them good preambles to pair with the correlation and
+/// synchronization nodes.  The sequence is generated once, and every run
+/// produces a copy of it, so this can head a chain that frames packets or be
+/// used to build the reference for a correlator.  Barker codes are put on the
+/// real part.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::sources::preamble_node::{Preamble, PreambleSourceNode};
+///
+/// let node = PreambleSourceNode::new(Preamble::Barker { len: 13 }).unwrap();
+/// ```
+#[derive(Node)]
+pub struct PreambleSourceNode {
+    sequence: Vec<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl PreambleSourceNode {
+    /// Constructs a new `PreambleSourceNode`, or returns an error if no
+    /// sequence with the given parameters exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `preamble` - The sequence to produce, along with its parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::sources::preamble_node::{Preamble, PreambleSourceNode};
+    ///
+    /// let zc = Preamble::ZadoffChu { root: 25, len: 63 };
+    /// let node = PreambleSourceNode::new(zc).unwrap();
+    /// assert_eq!(node.sequence().len(), 63);
+    /// ```
+    pub fn new(preamble: Preamble) -> Result<PreambleSourceNode, MathError> {
+        let sequence = match preamble {
+            Preamble::Barker { len } => barker_code(len)?
+                .iter()
+                .map(|&c| Complex::new(c, 0.0))
+                .collect(),
+            Preamble::ZadoffChu { root, len } => zadoff_chu(root, len)?,
+        };
+        Ok(PreambleSourceNode {
+            sequence,
+            output: Default::default(),
+        })
+    }
+
+    /// Returns the sequence the node produces.
+    pub fn sequence(&self) -> &[Complex<f64>] {
+        &self.sequence
+    }
+
+    /// Runs the `PreambleSourceNode`.  Produces a copy of the sequence.
+    pub fn run(&mut self) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok(self.sequence.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sources::preamble_node::*;
+
+    #[test]
+    // Checks that each run produces the requested sequence, and that
+    // sequences that don't exist are refused.
+    fn test_preamble_source() {
+        let mut node =
+            PreambleSourceNode::new(Preamble::Barker { len: 5 }).unwrap();
+        let expected: Vec<Complex<f64>> = [1.0, 1.0, 1.0, -1.0, 1.0]
+            .iter()
+            .map(|&c| Complex::new(c, 0.0))
+            .collect();
+        assert_eq!(node.run().unwrap(), expected);
+        assert_eq!(node.run().unwrap(), expected);
+
+        let zc = Preamble::ZadoffChu { root: 1, len: 139 };
+        let mut node = PreambleSourceNode::new(zc).unwrap();
+        assert_eq!(node.run().unwrap(), zadoff_chu(1, 139).unwrap());
+
+        assert!(PreambleSourceNode::new(Preamble::Barker { len: 8 }).is_err());
+        let zc = Preamble::ZadoffChu { root: 3, len: 63 };
+        assert!(PreambleSourceNode::new(zc).is_err());
+    }
+}
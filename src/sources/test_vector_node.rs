@@ -0,0 +1,230 @@
+//! A source of standard stimulus signals for bring-up and loopback testing.
+use crate::prelude::*;
+
+use crate::prns::PrnsNode;
+use num::{Complex, Zero};
+use rand::distributions::Normal;
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64::consts::PI;
+
+/// The pattern produced by a `TestVectorSourceNode`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestVector {
+    /// Bits from a PRBS generator, mapped to 0.0 and 1.0.  See `PrnsNode` for
+    /// the meaning of the polynomial mask and initial state of the 32 bit
+    /// LFSR.
+    Prbs { poly_mask: u32, state: u32 },
+    /// A complex exponential at the given frequency.
+    Tone {
+        freq: f64,
+        sample_rate: f64,
+        amplitude: f64,
+    },
+    /// A single sample of the given amplitude followed by zeros forever.
+    Impulse { amplitude: f64 },
+    /// Zeros for `delay` samples followed by the given amplitude forever.
+    Step { delay: usize, amplitude: f64 },
+    /// Complex white gaussian noise with the given total power, drawn from a
+    /// generator with a fixed seed so that runs are repeatable.
+    Noise { power: f64, seed: u64 },
+}
+
+/// A node that generates a selectable standard test vector.
+///
+/// This is handy for injecting a known stimulus anywhere in a graph while
+/// bringing up a new chain of nodes, without having to write a one-off source
+/// node each time.  Each run produces the next `batch_size` samples of the
+/// selected pattern as `Complex<f64>`; real valued patterns are put on the
+/// real part.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::sources::test_vector_node::{TestVector, TestVectorSourceNode};
+///
+/// let tone = TestVector::Tone {
+///     freq: 1000.0,
+///     sample_rate: 48000.0,
+///     amplitude: 1.0,
+/// };
+/// let node = TestVectorSourceNode::new(tone, 1024);
+/// ```
+#[derive(Node)]
+pub struct TestVectorSourceNode {
+    vector: TestVector,
+    batch_size: usize,
+    index: usize,
+    prns: Option<PrnsNode<u32>>,
+    rng: SmallRng,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl TestVectorSourceNode {
+    /// Constructs a new `TestVectorSourceNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - The pattern to generate, along with its parameters.
+    /// * `batch_size` - Number of samples to produce on each run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::sources::test_vector_node::{TestVector, TestVectorSourceNode};
+    ///
+    /// let prbs = TestVector::Prbs {
+    ///     poly_mask: 0xA300_0000,
+    ///     state: 0xFFFF_FFFF,
+    /// };
+    /// let node = TestVectorSourceNode::new(prbs, 256);
+    /// ```
+    pub fn new(vector: TestVector, batch_size: usize) -> TestVectorSourceNode {
+        let prns = match vector {
+            TestVector::Prbs { poly_mask, state } => {
+                Some(PrnsNode::new(poly_mask, state))
+            }
+            _ => None,
+        };
+        let seed = match vector {
+            TestVector::Noise { seed, .. } => seed,
+            _ => 0,
+        };
+        TestVectorSourceNode {
+            vector,
+            batch_size,
+            index: 0,
+            prns,
+            rng: SmallRng::seed_from_u64(seed),
+            output: Default::default(),
+        }
+    }
+
+    fn next_sample(&mut self) -> Complex<f64> {
+        let n = self.index;
+        self.index += 1;
+        match self.vector {
+            TestVector::Prbs { .. } => {
+                let bit = self.prns.as_mut().unwrap().run().unwrap();
+                Complex::new(f64::from(bit), 0.0)
+            }
+            TestVector::Tone {
+                freq,
+                sample_rate,
+                amplitude,
+            } => {
+                // Wrap the phase to keep precision on long runs.
+                let cycles = (freq / sample_rate * n as f64).fract();
+                Complex::from_polar(amplitude, 2.0 * PI * cycles)
+            }
+            TestVector::Impulse { amplitude } => {
+                if n == 0 {
+                    Complex::new(amplitude, 0.0)
+                } else {
+                    Complex::zero()
+                }
+            }
+            TestVector::Step { delay, amplitude } => {
+                if n < delay {
+                    Complex::zero()
+                } else {
+                    Complex::new(amplitude, 0.0)
+                }
+            }
+            TestVector::Noise { power, .. } => {
+                let dist = Normal::new(0.0, (power / 2.0).sqrt());
+                Complex::new(self.rng.sample(dist), self.rng.sample(dist))
+            }
+        }
+    }
+
+    /// Runs the `TestVectorSourceNode`.  Produces the next batch of samples
+    /// of the selected pattern.
+    pub fn run(&mut self) -> Result<Vec<Complex<f64>>, NodeError> {
+        Ok((0..self.batch_size).map(|_| self.next_sample()).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prns::PrnGen;
+    use crate::sources::test_vector_node::*;
+
+    fn collect(
+        node: &mut TestVectorSourceNode,
+        n_batches: usize,
+    ) -> Vec<Complex<f64>> {
+        (0..n_batches).flat_map(|_| node.run().unwrap()).collect()
+    }
+
+    #[test]
+    // Checks the PRBS mode against the underlying generator.
+    fn test_prbs() {
+        let vector = TestVector::Prbs {
+            poly_mask: 0xA300_0000,
+            state: 0x1234_5678,
+        };
+        let mut node = TestVectorSourceNode::new(vector, 100);
+        let output = collect(&mut node, 3);
+        let mut prn_gen = PrnGen::new(0xA300_0000_u32, 0x1234_5678);
+        for y in output {
+            assert_eq!(y, Complex::new(f64::from(prn_gen.next_byte()), 0.0));
+        }
+    }
+
+    #[test]
+    // Checks the tone mode is a unit circle rotating at the right rate.
+    fn test_tone() {
+        let vector = TestVector::Tone {
+            freq: 1000.0,
+            sample_rate: 48000.0,
+            amplitude: 2.0,
+        };
+        let mut node = TestVectorSourceNode::new(vector, 100);
+        let output = collect(&mut node, 10);
+        let step = Complex::from_polar(1.0, 2.0 * PI / 48.0);
+        assert_approx_eq!(output[0].re, 2.0);
+        assert_approx_eq!(output[0].im, 0.0);
+        for pair in output.windows(2) {
+            assert_approx_eq!((pair[1] - pair[0] * step).norm(), 0.0);
+        }
+    }
+
+    #[test]
+    // Checks the impulse and step modes across batch boundaries.
+    fn test_impulse_and_step() {
+        let vector = TestVector::Impulse { amplitude: 3.0 };
+        let mut node = TestVectorSourceNode::new(vector, 16);
+        let output = collect(&mut node, 4);
+        assert_eq!(output[0], Complex::new(3.0, 0.0));
+        assert!(output[1..].iter().all(|x| x.is_zero()));
+
+        let vector = TestVector::Step {
+            delay: 20,
+            amplitude: -1.0,
+        };
+        let mut node = TestVectorSourceNode::new(vector, 16);
+        let output = collect(&mut node, 4);
+        assert!(output[..20].iter().all(|x| x.is_zero()));
+        assert!(output[20..].iter().all(|x| *x == Complex::new(-1.0, 0.0)));
+    }
+
+    #[test]
+    // Checks the noise mode has the right statistics and is repeatable.
+    fn test_noise() {
+        let vector = TestVector::Noise {
+            power: 4.0,
+            seed: 7,
+        };
+        let mut node = TestVectorSourceNode::new(vector.clone(), 1000);
+        let output = collect(&mut node, 100);
+        let n = output.len() as f64;
+        let mean = output.iter().sum::<Complex<f64>>() / n;
+        let power = output.iter().map(|x| x.norm_sqr()).sum::<f64>() / n;
+        assert!(mean.norm() < 0.02);
+        assert!((power - 4.0).abs() < 0.05);
+
+        let mut node = TestVectorSourceNode::new(vector, 1000);
+        assert_eq!(node.run().unwrap(), output[..1000].to_vec());
+    }
+}
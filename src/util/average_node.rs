@@ -0,0 +1,506 @@
+//! Nodes for averaging and integrating signals over time.
+use crate::prelude::*;
+
+use num::{Complex, Float, Num, Zero};
+use std::ops::{Add, Mul};
+
+/// A node that coherently integrates successive frames of samples.
+///
+/// The input stream is split into frames of `frame_len` samples, and every
+/// `k` frames are summed sample by sample into a single output frame.  When
+/// the signal of interest repeats from frame to frame while the noise doesn't,
+/// this improves the SNR by up to `10 * log10(k)` dB, which is the usual
+/// trick for pulling weak periodic signals such as GPS spreading codes out of
+/// the noise.
+///
+/// The sum is coherent, so any frequency offset will rotate the signal from
+/// one frame to the next and reduce the gain.  The frames should be frequency
+/// corrected before they get here.  The input may be batched arbitrarily;
+/// frames are formed from the sample stream itself.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::average_node::CoherentIntegrateNode;
+///
+/// // Integrate 20 frames of 1023 samples.
+/// let node: CoherentIntegrateNode<f64> = CoherentIntegrateNode::new(1023, 20);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct CoherentIntegrateNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    frame_len: usize,
+    k: usize,
+    acc: Vec<Complex<T>>,
+    ix: usize,
+    n_frames: usize,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> CoherentIntegrateNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `CoherentIntegrateNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_len` - Number of samples in each frame.
+    /// * `k` - Number of frames to sum into each output frame.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::average_node::CoherentIntegrateNode;
+    ///
+    /// let node: CoherentIntegrateNode<f32> = CoherentIntegrateNode::new(64, 4);
+    /// ```
+    pub fn new(frame_len: usize, k: usize) -> Self {
+        assert!(frame_len > 0, "frame length must be nonzero");
+        assert!(k > 0, "integration count must be nonzero");
+        CoherentIntegrateNode {
+            frame_len,
+            k,
+            acc: vec![Complex::zero(); frame_len],
+            ix: 0,
+            n_frames: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `CoherentIntegrateNode<T>`.  Produces an integrated frame
+    /// each time `k` frames have been received.  If a single batch completes
+    /// more than one integrated frame, they're concatenated.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<Complex<T>>>, NodeError> {
+        let mut output = vec![];
+        for samp in samples {
+            self.acc[self.ix] = self.acc[self.ix] + samp;
+            self.ix += 1;
+            if self.ix == self.frame_len {
+                self.ix = 0;
+                self.n_frames += 1;
+                if self.n_frames == self.k {
+                    self.n_frames = 0;
+                    output.append(&mut self.acc);
+                    self.acc = vec![Complex::zero(); self.frame_len];
+                }
+            }
+        }
+        if output.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(output))
+        }
+    }
+}
+
+/// A node that removes a slowly varying mean from a real signal.
+///
+/// A running estimate of the mean is tracked with a first order exponential
+/// average, `mean += rate * (x - mean)`, and subtracted from each sample.
+/// This removes DC offsets and baseline drift from things like demodulated
+/// audio or FSK discriminator output, which would otherwise bias any decisions
+/// made downstream.  Smaller rates adapt more slowly but disturb the signal
+/// less.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::average_node::MeanRemoveNode;
+///
+/// let node: MeanRemoveNode<f32> = MeanRemoveNode::new(0.001);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct MeanRemoveNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    rate: T,
+    mean: T,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> MeanRemoveNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `MeanRemoveNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - Adaptation rate of the running mean, on the interval
+    ///   (0.0, 1.0].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::average_node::MeanRemoveNode;
+    ///
+    /// let node: MeanRemoveNode<f64> = MeanRemoveNode::new(1e-4);
+    /// ```
+    pub fn new(rate: T) -> Self {
+        assert!(
+            rate > T::zero() && rate <= T::one(),
+            "adaptation rate must be on the interval (0.0, 1.0]"
+        );
+        MeanRemoveNode {
+            rate,
+            mean: T::zero(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `MeanRemoveNode<T>`.  Produces the batch of samples with the
+    /// running mean removed.
+    pub fn run(&mut self, samples: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|&x| {
+                let y = x - self.mean;
+                self.mean = self.mean + self.rate * y;
+                y
+            })
+            .collect())
+    }
+}
+
+/// A node that smooths a signal with a leaky integrator.
+///
+/// Each output sample is `y[n] = alpha * x[n] + (1 - alpha) * y[n - 1]`, a
+/// first order exponential average with a time constant of roughly
+/// `1 / alpha` samples.  This is the usual building block for AGC level
+/// detectors, DC estimates and envelope smoothing.  The input can be real or
+/// complex, and the state is carried across batches, starting from zero.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::average_node::LeakyIntegratorNode;
+/// use num::Complex;
+///
+/// let node: LeakyIntegratorNode<Complex<f64>> = LeakyIntegratorNode::new(0.01);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct LeakyIntegratorNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    alpha: f64,
+    state: T,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> LeakyIntegratorNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    /// Constructs a new `LeakyIntegratorNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `alpha` - Weight of each new sample, on the interval (0.0, 1.0].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::average_node::LeakyIntegratorNode;
+    ///
+    /// let node: LeakyIntegratorNode<f64> = LeakyIntegratorNode::new(0.1);
+    /// ```
+    pub fn new(alpha: f64) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "alpha must be on the interval (0.0, 1.0]"
+        );
+        LeakyIntegratorNode {
+            alpha,
+            state: T::zero(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `LeakyIntegratorNode<T>`.  Produces the smoothed batch of
+    /// samples.
+    pub fn run(&mut self, samples: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|&x| {
+                self.state = x * self.alpha + self.state * (1.0 - self.alpha);
+                self.state
+            })
+            .collect())
+    }
+}
+
+/// A node that takes the first difference of a stream.
+///
+/// Each output sample is `x[n] - x[n - 1]`, with the previous sample carried
+/// across batches and starting from zero.  This is the discrete derivative,
+/// used for edge detection and in frequency discriminators.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::average_node::DiffNode;
+///
+/// let node: DiffNode<f32> = DiffNode::new();
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DiffNode<T>
+where
+    T: Num + Copy + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    prev: T,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> DiffNode<T>
+where
+    T: Num + Copy + Send,
+{
+    /// Constructs a new `DiffNode<T>`.
+    pub fn new() -> Self {
+        DiffNode {
+            prev: T::zero(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `DiffNode<T>`.  Produces the differences of the batch of
+    /// samples.
+    pub fn run(&mut self, samples: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|&x| {
+                let y = x - self.prev;
+                self.prev = x;
+                y
+            })
+            .collect())
+    }
+}
+
+impl<T> Default for DiffNode<T>
+where
+    T: Num + Copy + Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node that takes the running sum of a stream.
+///
+/// Each output sample is the sum of every input sample up to and including
+/// the current one, carried across batches and starting from zero.  This is
+/// the inverse of `DiffNode`, and the usual way to accumulate phase from a
+/// frequency.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::average_node::IntegrateNode;
+/// use num::Complex;
+///
+/// let node: IntegrateNode<Complex<i32>> = IntegrateNode::new();
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct IntegrateNode<T>
+where
+    T: Num + Copy + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    sum: T,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> IntegrateNode<T>
+where
+    T: Num + Copy + Send,
+{
+    /// Constructs a new `IntegrateNode<T>`.
+    pub fn new() -> Self {
+        IntegrateNode {
+            sum: T::zero(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `IntegrateNode<T>`.  Produces the running sum through each
+    /// sample of the batch.
+    pub fn run(&mut self, samples: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|&x| {
+                self.sum = self.sum + x;
+                self.sum
+            })
+            .collect())
+    }
+}
+
+impl<T> Default for IntegrateNode<T>
+where
+    T: Num + Copy + Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::average_node::*;
+    use rand::distributions::Normal;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+    use std::f64::consts::PI;
+
+    #[test]
+    // Integrates K noisy copies of a tone and checks that the SNR improves by
+    // about 10 * log10(K) dB.
+    fn test_coherent_integrate() {
+        let frame_len = 4096;
+        let k = 16;
+        let tone: Vec<Complex<f64>> = (0..frame_len)
+            .map(|n| Complex::new(0.0, 2.0 * PI * 0.01 * n as f64).exp())
+            .collect();
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let noise = Normal::new(0.0, (0.5_f64).sqrt());
+        let noisy: Vec<Complex<f64>> = (0..k)
+            .flat_map(|_| tone.clone())
+            .map(|x| x + Complex::new(rng.sample(noise), rng.sample(noise)))
+            .collect();
+
+        // Input SNR is 0 dB by construction.
+        let mut node = CoherentIntegrateNode::new(frame_len, k);
+        let mut output = vec![];
+        for chunk in noisy.chunks(1000) {
+            if let Some(frame) = node.run(chunk).unwrap() {
+                output.extend(frame);
+            }
+        }
+        assert_eq!(output.len(), frame_len);
+
+        let signal_power = (k * k) as f64;
+        let noise_power = output
+            .iter()
+            .zip(tone.iter())
+            .map(|(y, x)| (y - x * k as f64).norm_sqr())
+            .sum::<f64>()
+            / frame_len as f64;
+        let snr = 10.0 * (signal_power / noise_power).log10();
+        let expected = 10.0 * (k as f64).log10();
+        assert!((snr - expected).abs() < 0.5);
+    }
+
+    #[test]
+    // Feeds in a tone riding on a drifting baseline and checks that the
+    // output is centered on zero with the tone intact.
+    fn test_mean_remove() {
+        let signal: Vec<f64> = (0..40000)
+            .map(|n| {
+                let n = n as f64;
+                (2.0 * PI * 0.05 * n).sin() + 2.0 + 1e-5 * n
+            })
+            .collect();
+
+        let mut node = MeanRemoveNode::new(0.001);
+        let mut output = vec![];
+        for chunk in signal.chunks(512) {
+            output.extend(node.run(chunk).unwrap());
+        }
+
+        // Look well after the initial convergence, over a whole number of
+        // periods of the tone.
+        let settled = &output[20000..];
+        let mean = settled.iter().sum::<f64>() / settled.len() as f64;
+        assert!(mean.abs() < 0.02);
+
+        let amplitude = 2.0
+            * settled
+                .iter()
+                .enumerate()
+                .map(|(n, y)| y * (2.0 * PI * 0.05 * (n + 20000) as f64).sin())
+                .sum::<f64>()
+            / settled.len() as f64;
+        assert!((amplitude - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    // Feeds in a step and checks that the output rises toward it with the
+    // time constant set by alpha.
+    fn test_leaky_integrator() {
+        let alpha = 0.05;
+        let step = vec![2.0; 200];
+        let mut node = LeakyIntegratorNode::new(alpha);
+        let mut output: Vec<f64> = vec![];
+        for chunk in step.chunks(33) {
+            output.extend(node.run(chunk).unwrap());
+        }
+        for (n, y) in output.iter().enumerate() {
+            let expected = 2.0 * (1.0 - (1.0 - alpha).powi(n as i32 + 1));
+            assert_approx_eq!(*y, expected);
+        }
+
+        // After one time constant the output is about 63% of the way there.
+        let tau = (-1.0 / (1.0 - alpha).ln()).round() as usize;
+        assert!((output[tau - 1] / 2.0 - 0.632).abs() < 0.01);
+
+        let mut node = LeakyIntegratorNode::new(0.5);
+        let output = node.run(&[Complex::new(4.0, -4.0); 2]).unwrap();
+        assert_eq!(
+            output,
+            vec![Complex::new(2.0, -2.0), Complex::new(3.0, -3.0)]
+        );
+    }
+
+    #[test]
+    // Checks that the difference of a ramp is constant, and that integrating
+    // the difference gets the original signal back.
+    fn test_diff_integrate() {
+        let ramp: Vec<i32> = (0..100).map(|n| 3 * n + 7).collect();
+        let mut diff = DiffNode::new();
+        let mut output = vec![];
+        for chunk in ramp.chunks(17) {
+            output.extend(diff.run(chunk).unwrap());
+        }
+        // The first difference is against the zero initial condition.
+        assert_eq!(output[0], 7);
+        assert!(output[1..].iter().all(|&d| d == 3));
+
+        let signal: Vec<Complex<f64>> = (0..500)
+            .map(|n| Complex::new((n as f64 * 0.1).sin(), n as f64 * 0.01))
+            .collect();
+        let mut diff = DiffNode::new();
+        let mut integrate = IntegrateNode::new();
+        let mut output = vec![];
+        for chunk in signal.chunks(64) {
+            output.extend(integrate.run(&diff.run(chunk).unwrap()).unwrap());
+        }
+        for (y, x) in output.iter().zip(signal.iter()) {
+            assert!((y - x).norm() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,889 @@
+//! Nodes for simulating the effects of a propagation channel on a signal.
+//!
+//! These nodes are meant to sit between a transmit chain and a receive chain
+//! when testing a system end to end without any hardware in the loop.
+use crate::prelude::*;
+
+use crate::demodulation::farrow_filter::FarrowResampler;
+use num::{Complex, Float, Zero};
+use rand::distributions::Normal;
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// A node that applies a tapped delay line multipath channel to a signal.
+///
+/// Each path in the channel is described by an integer delay in samples and a
+/// complex gain.  The output of the node is the sum of the delayed and scaled
+/// copies of the input, which models a frequency-selective channel.  The
+/// input history is carried across batches, so the output is identical
+/// regardless of how the input stream is split up.
+///
+/// Optionally, a maximum Doppler frequency can be supplied, in which case the
+/// phase of each path rotates over time.  Path `k` of `N` rotates at
+/// `fd * cos(2 * PI * k / N)` cycles per sample, giving a deterministic
+/// spread of Doppler shifts across the paths.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::channel_node::MultipathChannelNode;
+/// use num::Complex;
+///
+/// // A direct path plus an echo 3 samples later at half amplitude.
+/// let paths = vec![(0, Complex::new(1.0, 0.0)), (3, Complex::new(0.5, 0.0))];
+/// let node: MultipathChannelNode<f64> = MultipathChannelNode::new(paths, None);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct MultipathChannelNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    paths: Vec<(usize, Complex<T>)>,
+    doppler: Option<f64>,
+    history: VecDeque<Complex<T>>,
+    sample_ix: u64,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> MultipathChannelNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `MultipathChannelNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - List of `(delay, gain)` pairs, with the delay given in
+    ///   samples.
+    /// * `doppler` - Optional maximum Doppler frequency in cycles per sample.
+    ///   If `None`, the channel is static.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::MultipathChannelNode;
+    /// use num::Complex;
+    ///
+    /// let paths = vec![(0, Complex::new(1.0, 0.0)), (2, Complex::new(0.0, 0.3))];
+    /// let node: MultipathChannelNode<f32> =
+    ///     MultipathChannelNode::new(paths, Some(1e-4));
+    /// ```
+    pub fn new(paths: Vec<(usize, Complex<T>)>, doppler: Option<f64>) -> Self {
+        let max_delay = paths.iter().map(|(d, _)| *d).max().unwrap_or(0);
+        MultipathChannelNode {
+            paths,
+            doppler,
+            history: vec![Complex::zero(); max_delay + 1].into(),
+            sample_ix: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Passes a batch of samples through the channel, updating the internal
+    /// delay line.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Batch of samples to pass through the channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::MultipathChannelNode;
+    /// use num::Complex;
+    ///
+    /// let paths = vec![(0, Complex::new(1.0, 0.0)), (1, Complex::new(0.5, 0.0))];
+    /// let mut node = MultipathChannelNode::new(paths, None);
+    /// let out = node.propagate(&[Complex::new(1.0, 0.0), Complex::new(0.0, 0.0)]);
+    /// assert_eq!(out, vec![Complex::new(1.0, 0.0), Complex::new(0.5, 0.0)]);
+    /// ```
+    pub fn propagate(&mut self, samples: &[Complex<T>]) -> Vec<Complex<T>> {
+        let n_paths = self.paths.len();
+        let mut output = Vec::with_capacity(samples.len());
+        for samp in samples {
+            self.history.pop_back();
+            self.history.push_front(*samp);
+            let mut acc = Complex::zero();
+            for (k, (delay, gain)) in self.paths.iter().enumerate() {
+                let gain = match self.doppler {
+                    Some(fd) => {
+                        let fk =
+                            fd * (2.0 * PI * k as f64 / n_paths as f64).cos();
+                        let phase = 2.0 * PI * fk * self.sample_ix as f64;
+                        let rot = Complex::new(
+                            T::from(phase.cos()).unwrap(),
+                            T::from(phase.sin()).unwrap(),
+                        );
+                        gain * rot
+                    }
+                    None => *gain,
+                };
+                acc = acc + self.history[*delay] * gain;
+            }
+            output.push(acc);
+            self.sample_ix += 1;
+        }
+        output
+    }
+
+    /// Runs the `MultipathChannelNode<T>`.  Produces the batch of samples
+    /// after passing through the channel.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(self.propagate(samples))
+    }
+}
+
+/// A node that applies fixed synchronization impairments and noise to a
+/// signal.
+///
+/// This models the channel between a transmit graph and a receive graph for
+/// testing synchronization algorithms end to end.  The impairments are
+/// applied in the order a real receiver would see them:
+///
+/// * a timing offset, delaying the signal by a possibly fractional number of
+///   samples using a cubic Farrow interpolator,
+/// * a carrier frequency offset,
+/// * a fixed phase offset,
+/// * complex additive white gaussian noise.
+///
+/// The fractional delay is only accurate for signals that are oversampled
+/// relative to their bandwidth.  The interpolator also holds back the last
+/// couple of samples of each batch until the next one arrives, so the output
+/// batches may be slightly shorter than the input batches.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::channel_node::ChannelImpairmentNode;
+///
+/// // 1 kHz of offset at 1 MHz, a quarter sample of delay, 0.5 radians of
+/// // phase and noise 20 dB below a unit power signal.
+/// let node: ChannelImpairmentNode<f64> =
+///     ChannelImpairmentNode::new(1e-3, 0.25, 0.5, 0.01);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ChannelImpairmentNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    cfo: f64,
+    phase: f64,
+    noise: Normal,
+    resampler: FarrowResampler,
+    padding: usize,
+    sample_ix: u64,
+    rng: SmallRng,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> ChannelImpairmentNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `ChannelImpairmentNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cfo` - Carrier frequency offset in cycles per sample.
+    /// * `delay` - Timing offset in samples.  Must not be negative.
+    /// * `phase` - Phase offset in radians.
+    /// * `noise_power` - Power of the complex noise added to each sample.
+    ///   Zero disables the noise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::ChannelImpairmentNode;
+    ///
+    /// // Only a frequency offset.
+    /// let node: ChannelImpairmentNode<f32> =
+    ///     ChannelImpairmentNode::new(0.01, 0.0, 0.0, 0.0);
+    /// ```
+    pub fn new(cfo: f64, delay: f64, phase: f64, noise_power: f64) -> Self {
+        assert!(delay >= 0.0, "timing offset must not be negative");
+        assert!(noise_power >= 0.0, "noise power must not be negative");
+
+        // Zero pad the front of the signal by the delay rounded up, then
+        // skip forward by the difference to get the fractional part.
+        let whole = delay.ceil();
+        let mut resampler = FarrowResampler::new(1.0);
+        resampler.advance(whole - delay);
+
+        ChannelImpairmentNode {
+            cfo,
+            phase,
+            noise: Normal::new(0.0, (noise_power / 2.0).sqrt()),
+            resampler,
+            padding: whole as usize,
+            sample_ix: 0,
+            rng: SmallRng::from_entropy(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Seeds the noise generator so that the noise is repeatable.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Passes a batch of samples through the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Batch of samples to pass through the channel.
+    pub fn impair(&mut self, samples: &[Complex<T>]) -> Vec<Complex<T>> {
+        let mut input = vec![Complex::zero(); self.padding];
+        self.padding = 0;
+        input.extend(samples.iter().map(|x| {
+            Complex::new(x.re.to_f64().unwrap(), x.im.to_f64().unwrap())
+        }));
+        self.resampler
+            .resample(&input)
+            .iter()
+            .map(|x| {
+                let cycles = (self.cfo * self.sample_ix as f64).fract();
+                let rot =
+                    Complex::new(0.0, 2.0 * PI * cycles + self.phase).exp();
+                let noise = Complex::new(
+                    self.rng.sample(self.noise),
+                    self.rng.sample(self.noise),
+                );
+                self.sample_ix += 1;
+                let y = x * rot + noise;
+                Complex::new(T::from(y.re).unwrap(), T::from(y.im).unwrap())
+            })
+            .collect()
+    }
+
+    /// Runs the `ChannelImpairmentNode<T>`.  Produces the batch of samples
+    /// after passing through the channel.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(self.impair(samples))
+    }
+}
+
+/// A node that applies a time varying Doppler shift to a signal.
+///
+/// The signal is shifted in frequency by a profile giving the Doppler shift
+/// in Hz as a function of time in seconds since the first sample, which
+/// models a moving transmitter or receiver such as a satellite pass.  The
+/// phase of the shift is accumulated sample by sample, so the shift changes
+/// smoothly even as the frequency does.  This is handy for stressing
+/// frequency tracking loops with a dynamic offset rather than a fixed one.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::channel_node::DopplerNode;
+///
+/// // Ramp up by 500 Hz every second at 1 MHz.
+/// let node: DopplerNode<f32> = DopplerNode::new(500.0, 1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DopplerNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    profile: Box<dyn Fn(f64) -> f64 + Send>,
+    sample_rate: f64,
+    sample_ix: u64,
+    phase: f64,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> DopplerNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `DopplerNode<T>` with a linear ramp in frequency,
+    /// starting from no shift.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - Rate of change of the Doppler shift in Hz per second.
+    /// * `sample_rate` - Sample rate of the signal in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::DopplerNode;
+    ///
+    /// let node: DopplerNode<f64> = DopplerNode::new(-120.0, 48000.0);
+    /// ```
+    pub fn new(rate: f64, sample_rate: f64) -> Self {
+        DopplerNode::from_fn(move |t| rate * t, sample_rate)
+    }
+
+    /// Constructs a new `DopplerNode<T>` following an arbitrary profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Function giving the Doppler shift in Hz at a time in
+    ///   seconds since the first sample.
+    /// * `sample_rate` - Sample rate of the signal in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::DopplerNode;
+    ///
+    /// // Swing +/- 2 kHz over a ten second period.
+    /// let profile = |t: f64| 2000.0 * (2.0 * std::f64::consts::PI * t / 10.0).sin();
+    /// let node: DopplerNode<f64> = DopplerNode::from_fn(profile, 1e6);
+    /// ```
+    pub fn from_fn<F>(profile: F, sample_rate: f64) -> Self
+    where
+        F: Fn(f64) -> f64 + Send + 'static,
+    {
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        DopplerNode {
+            profile: Box::new(profile),
+            sample_rate,
+            sample_ix: 0,
+            phase: 0.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `DopplerNode<T>`.  Produces the batch of samples with the
+    /// Doppler shift applied.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|x| {
+                let t = self.sample_ix as f64 / self.sample_rate;
+                let freq = (self.profile)(t);
+                let rot = Complex::new(0.0, self.phase).exp();
+                self.phase = (self.phase + 2.0 * PI * freq / self.sample_rate)
+                    % (2.0 * PI);
+                self.sample_ix += 1;
+                let x = Complex::new(
+                    x.re.to_f64().unwrap(),
+                    x.im.to_f64().unwrap(),
+                );
+                let y = x * rot;
+                Complex::new(T::from(y.re).unwrap(), T::from(y.im).unwrap())
+            })
+            .collect())
+    }
+}
+
+/// Number of sinusoids summed for the diffuse fading of each tap.
+const N_SINUSOIDS: usize = 64;
+
+// A complex Gaussian fading process with a Jakes Doppler spectrum, as a sum
+// of sinusoids arriving from random angles with random phases.  Each
+// sinusoid is kept as a unit phasor that is rotated by its Doppler shift
+// once per sample.
+struct FadingProcess {
+    phasors: Vec<Complex<f64>>,
+    steps: Vec<Complex<f64>>,
+}
+
+impl FadingProcess {
+    fn new(doppler: f64, rng: &mut SmallRng) -> Self {
+        let (phasors, steps) = (0..N_SINUSOIDS)
+            .map(|_| {
+                let angle = rng.gen_range(0.0, 2.0 * PI);
+                let freq = doppler * angle.cos();
+                (
+                    Complex::from_polar(1.0, rng.gen_range(0.0, 2.0 * PI)),
+                    Complex::from_polar(1.0, 2.0 * PI * freq),
+                )
+            })
+            .unzip();
+        FadingProcess { phasors, steps }
+    }
+
+    // Unit power gain at the current sample.
+    fn gain(&self) -> Complex<f64> {
+        self.phasors.iter().sum::<Complex<f64>>() / (N_SINUSOIDS as f64).sqrt()
+    }
+
+    // Moves on to the next sample.
+    fn advance(&mut self) {
+        for (phasor, step) in self.phasors.iter_mut().zip(&self.steps) {
+            *phasor *= step;
+        }
+    }
+}
+
+/// A node that applies a tapped delay line channel with Rayleigh or Rician
+/// fading taps.
+///
+/// This is the standard model of a mobile radio channel.  Each tap gathers
+/// the paths arriving at one delay, and as the transmitter or receiver
+/// moves, the paths within a tap add up with changing phases, so the tap
+/// gain wanders as a complex Gaussian process.  Its envelope is Rayleigh
+/// distributed, and with paths arriving evenly from every direction its
+/// Doppler spectrum is the U shaped Jakes spectrum reaching out to the
+/// maximum Doppler frequency.  Each tap's fading is generated as a sum of
+/// sinusoids at the Doppler shifts of random angles of arrival, with random
+/// phases, and the taps fade independently of each other.
+///
+/// When the first tap has a line of sight path, its gain is instead Rician:
+/// the steady line of sight component, arriving broadside with no Doppler
+/// shift, holds `K / (K + 1)` of the tap's power and the fading part holds
+/// the rest.  A K-factor of zero makes the first tap Rayleigh like the rest.
+///
+/// The power-delay profile gives the delay in samples and the average power
+/// of each tap.  The input history is carried across batches, as in
+/// `MultipathChannelNode`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::channel_node::FadingChannelNode;
+///
+/// // Three taps over 4 samples at 1 MHz, with a 6 dB Rician first tap, at
+/// // 100 Hz of Doppler.
+/// let profile = vec![(0, 0.6), (2, 0.3), (4, 0.1)];
+/// let node: FadingChannelNode<f32> =
+///     FadingChannelNode::new(profile, 4.0, 100.0 / 1e6).with_seed(1);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct FadingChannelNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    profile: Vec<(usize, f64)>,
+    k_factor: f64,
+    doppler: f64,
+    fading: Vec<FadingProcess>,
+    scales: Vec<f64>,
+    los: Complex<f64>,
+    history: VecDeque<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> FadingChannelNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `FadingChannelNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - List of `(delay, power)` pairs for the taps, with the
+    ///   delay given in samples, starting with the first tap.
+    /// * `k_factor` - Ratio of the line of sight power to the fading power
+    ///   of the first tap.  Must not be negative.
+    /// * `doppler` - Maximum Doppler frequency in cycles per sample.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::FadingChannelNode;
+    ///
+    /// // A flat Rayleigh fading channel.
+    /// let node: FadingChannelNode<f64> =
+    ///     FadingChannelNode::new(vec![(0, 1.0)], 0.0, 1e-3);
+    /// ```
+    pub fn new(
+        profile: Vec<(usize, f64)>,
+        k_factor: f64,
+        doppler: f64,
+    ) -> Self {
+        assert!(!profile.is_empty(), "there must be at least one tap");
+        assert!(
+            profile.iter().all(|&(_, power)| power >= 0.0),
+            "tap powers must not be negative"
+        );
+        assert!(k_factor >= 0.0, "K-factor must not be negative");
+        let max_delay = profile.iter().map(|(d, _)| *d).max().unwrap();
+        // The first tap splits its power between the line of sight and the
+        // fading, which is all of it when the K-factor is zero.
+        let scales = profile
+            .iter()
+            .enumerate()
+            .map(|(k, &(_, power))| {
+                if k == 0 {
+                    (power / (k_factor + 1.0)).sqrt()
+                } else {
+                    power.sqrt()
+                }
+            })
+            .collect();
+        let mut node = FadingChannelNode {
+            profile,
+            k_factor,
+            doppler,
+            fading: vec![],
+            scales,
+            los: Complex::zero(),
+            history: vec![Complex::zero(); max_delay + 1].into(),
+            input: Default::default(),
+            output: Default::default(),
+        };
+        node.draw(SmallRng::from_entropy());
+        node
+    }
+
+    /// Seeds the fading so that the channel is repeatable.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.draw(SmallRng::seed_from_u64(seed));
+        self
+    }
+
+    // Draws the angles and phases of the fading processes.
+    fn draw(&mut self, mut rng: SmallRng) {
+        let doppler = self.doppler;
+        self.fading = self
+            .profile
+            .iter()
+            .map(|_| FadingProcess::new(doppler, &mut rng))
+            .collect();
+        let los_phase = rng.gen_range(0.0, 2.0 * PI);
+        self.los = Complex::from_polar(self.k_factor.sqrt(), los_phase);
+    }
+
+    // Current gain of the `k`th tap.
+    fn tap_gain(&self, k: usize) -> Complex<f64> {
+        let diffuse = self.fading[k].gain();
+        if k == 0 {
+            (self.los + diffuse) * self.scales[0]
+        } else {
+            diffuse * self.scales[k]
+        }
+    }
+
+    /// Returns the current gain of each tap.
+    pub fn gains(&self) -> Vec<Complex<f64>> {
+        (0..self.profile.len()).map(|k| self.tap_gain(k)).collect()
+    }
+
+    /// Passes a batch of samples through the channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Batch of samples to pass through the channel.
+    pub fn propagate(&mut self, samples: &[Complex<T>]) -> Vec<Complex<T>> {
+        let mut output = Vec::with_capacity(samples.len());
+        for x in samples {
+            self.history.pop_back();
+            self.history.push_front(Complex::new(
+                x.re.to_f64().unwrap(),
+                x.im.to_f64().unwrap(),
+            ));
+            let mut y: Complex<f64> = Complex::zero();
+            for k in 0..self.profile.len() {
+                y += self.history[self.profile[k].0] * self.tap_gain(k);
+                self.fading[k].advance();
+            }
+            output.push(Complex::new(
+                T::from(y.re).unwrap(),
+                T::from(y.im).unwrap(),
+            ));
+        }
+        output
+    }
+
+    /// Runs the `FadingChannelNode<T>`.  Produces the batch of samples
+    /// after passing through the channel.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(self.propagate(samples))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::channel_node::*;
+
+    fn random_signal(len: usize) -> Vec<Complex<f64>> {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Normal::new(0.0, 1.0);
+        (0..len)
+            .map(|_| Complex::new(rng.sample(dist), rng.sample(dist)))
+            .collect()
+    }
+
+    #[test]
+    // Checks that a two path channel sums delayed, scaled copies of the input
+    // and that the output doesn't depend on how the input is batched.
+    fn test_two_path_channel() {
+        let delay = 5;
+        let gain = Complex::new(0.4, -0.3);
+        let paths = vec![(0, Complex::new(1.0, 0.0)), (delay, gain)];
+        let mut node = MultipathChannelNode::new(paths, None);
+
+        let signal = random_signal(1000);
+        let mut out = vec![];
+        for chunk in signal.chunks(37) {
+            out.extend(node.run(chunk).unwrap());
+        }
+
+        for (n, y) in out.iter().enumerate() {
+            let mut truth = signal[n];
+            if n >= delay {
+                truth += gain * signal[n - delay];
+            }
+            assert!((y - truth).norm() < 1e-12);
+        }
+
+        // An ideal (IIR) equalizer for this channel is
+        // x[n] = y[n] - gain * x[n - delay], which should give back the
+        // original signal.
+        let mut equalized: Vec<Complex<f64>> = vec![];
+        for (n, y) in out.iter().enumerate() {
+            let mut x = *y;
+            if n >= delay {
+                x -= gain * equalized[n - delay];
+            }
+            equalized.push(x);
+        }
+        for (x, truth) in equalized.iter().zip(signal.iter()) {
+            assert!((x - truth).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    // With Doppler enabled, a single path channel should just rotate the
+    // signal and leave its magnitude alone.
+    fn test_doppler_path() {
+        let paths = vec![(0, Complex::new(1.0, 0.0))];
+        let fd = 0.01;
+        let mut node = MultipathChannelNode::new(paths, Some(fd));
+        let ones = vec![Complex::new(1.0, 0.0); 100];
+        let out = node.run(&ones).unwrap();
+        for (n, y) in out.iter().enumerate() {
+            let truth = Complex::new(0.0, 2.0 * PI * fd * n as f64).exp();
+            assert!((y - truth).norm() < 1e-12);
+        }
+    }
+
+    fn run_chunked(
+        node: &mut ChannelImpairmentNode<f64>,
+        signal: &[Complex<f64>],
+    ) -> Vec<Complex<f64>> {
+        let mut out = vec![];
+        for chunk in signal.chunks(97) {
+            out.extend(node.run(chunk).unwrap());
+        }
+        out
+    }
+
+    fn qpsk(len: usize) -> Vec<Complex<f64>> {
+        let points = [
+            Complex::new(1.0, 0.0),
+            Complex::new(0.0, 1.0),
+            Complex::new(-1.0, 0.0),
+            Complex::new(0.0, -1.0),
+        ];
+        let mut rng = SmallRng::seed_from_u64(1);
+        (0..len).map(|_| points[rng.gen_range(0, 4)]).collect()
+    }
+
+    #[test]
+    // Applies a frequency offset to a pilot followed by data, estimates the
+    // offset from the pilot and checks the corrected data.
+    fn test_impairment_cfo() {
+        use crate::demodulation::frequency_estimator::frequency_offset_estimate;
+
+        let cfo = 0.013;
+        let mut signal = vec![Complex::new(1.0, 0.0); 200];
+        signal.extend(qpsk(2000));
+        let mut node = ChannelImpairmentNode::new(cfo, 0.0, 0.0, 0.0);
+        let out = run_chunked(&mut node, &signal);
+
+        let est = frequency_offset_estimate(&out[..200]);
+        assert!((est - 2.0 * PI * cfo).abs() < 1e-9);
+        for (n, (y, x)) in out.iter().zip(signal.iter()).enumerate() {
+            let fixed = y * Complex::new(0.0, -est * n as f64).exp();
+            assert!((fixed - x).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    // Applies a phase offset to QPSK symbols and checks the phase estimator
+    // recovers it.
+    fn test_impairment_phase() {
+        use crate::demodulation::phase_estimator::psk_phase_estimate;
+
+        let signal = qpsk(1000);
+        let mut node = ChannelImpairmentNode::new(0.0, 0.0, 0.3, 0.0);
+        let out = run_chunked(&mut node, &signal);
+
+        let est = psk_phase_estimate(&out, 4);
+        assert!((est - 0.3).abs() < 1e-9);
+        for (y, x) in out.iter().zip(signal.iter()) {
+            let fixed = y * Complex::new(0.0, -est).exp();
+            assert!((fixed - x).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    // Delays an oversampled signal by a fractional number of samples and
+    // checks that advancing a Farrow interpolator by the same amount gets it
+    // back.
+    fn test_impairment_delay() {
+        let delay = 3.4;
+        let signal: Vec<Complex<f64>> = (0..2000)
+            .map(|n| {
+                let t = n as f64;
+                Complex::new(0.0, 2.0 * PI * 0.011 * t).exp()
+                    + Complex::new(0.0, -2.0 * PI * 0.017 * t).exp() * 0.5
+            })
+            .collect();
+        let mut node = ChannelImpairmentNode::new(0.0, delay, 0.0, 0.0);
+        let out = run_chunked(&mut node, &signal);
+        assert!(out.len() >= signal.len() - 3);
+
+        // The first few samples are interpolated against the zero padding.
+        let n_skip = 10;
+        let mut corrector = FarrowResampler::new(1.0);
+        corrector.advance(delay);
+        let fixed = corrector.resample(&out);
+        for (y, x) in fixed.iter().zip(signal.iter()).skip(n_skip) {
+            assert!((y - x).norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    // Checks the added noise has the configured power and is repeatable
+    // with a fixed seed.
+    fn test_impairment_noise() {
+        let signal = qpsk(20000);
+        let mut node =
+            ChannelImpairmentNode::new(0.0, 0.0, 0.0, 0.1).with_seed(3);
+        let out = run_chunked(&mut node, &signal);
+        let noise_power = out
+            .iter()
+            .zip(signal.iter())
+            .map(|(y, x)| (y - x).norm_sqr())
+            .sum::<f64>()
+            / out.len() as f64;
+        assert!((noise_power - 0.1).abs() < 0.005);
+
+        let mut node =
+            ChannelImpairmentNode::new(0.0, 0.0, 0.0, 0.1).with_seed(3);
+        assert_eq!(run_chunked(&mut node, &signal), out);
+    }
+
+    #[test]
+    // Applies a Doppler ramp to a constant and checks the instantaneous
+    // frequency of the output follows it.
+    fn test_doppler_ramp() {
+        let sample_rate = 10000.0;
+        let rate = 1000.0;
+        let signal = vec![Complex::new(1.0, 0.0); 10000];
+        let mut node = DopplerNode::new(rate, sample_rate);
+        let mut out: Vec<Complex<f64>> = vec![];
+        for chunk in signal.chunks(333) {
+            out.extend(node.run(chunk).unwrap());
+        }
+
+        // The shift between samples n - 1 and n is set at sample n - 1.
+        for (n, pair) in out.windows(2).enumerate() {
+            let freq =
+                (pair[1] * pair[0].conj()).arg() * sample_rate / (2.0 * PI);
+            let expected = rate * n as f64 / sample_rate;
+            assert!((freq - expected).abs() < 1e-6);
+            assert!((pair[1].norm() - 1.0).abs() < 1e-9);
+        }
+
+        let profile = |t: f64| 50.0 * (2.0 * PI * t).cos();
+        let mut node = DopplerNode::from_fn(profile, sample_rate);
+        let out = node.run(&signal).unwrap();
+        for (n, pair) in out.windows(2).enumerate() {
+            let freq =
+                (pair[1] * pair[0].conj()).arg() * sample_rate / (2.0 * PI);
+            assert!((freq - profile(n as f64 / sample_rate)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    // Runs a constant through a single tap Rayleigh channel for many
+    // fades and checks that the envelope follows the Rayleigh distribution
+    // and that the fading is as fast as the Doppler frequency says.
+    fn test_rayleigh_fading() {
+        let fd = 0.004;
+        let len = 500_000;
+        let mut node =
+            FadingChannelNode::new(vec![(0, 2.0)], 0.0, fd).with_seed(0);
+        let ones = vec![Complex::new(1.0, 0.0); 10000];
+        let mut out: Vec<Complex<f64>> = vec![];
+        while out.len() < len {
+            out.extend(node.run(&ones).unwrap());
+        }
+
+        // The envelope CDF for a mean power of 2 is 1 - exp(-r^2 / 2).
+        let power = out.iter().map(|y| y.norm_sqr()).sum::<f64>() / len as f64;
+        assert!((power - 2.0).abs() < 0.1, "power {}", power);
+        for &r in &[0.3, 0.7, 1.0, 1.5, 2.0, 2.5] {
+            let below = out.iter().filter(|y| y.norm() < r).count();
+            let fraction = below as f64 / len as f64;
+            let expected = 1.0 - (-r * r / 2.0).exp();
+            assert!(
+                (fraction - expected).abs() < 0.03,
+                "r {} fraction {} expected {}",
+                r,
+                fraction,
+                expected
+            );
+        }
+
+        // The level crossing rate at the RMS level is sqrt(2 pi) fd / e
+        // for Jakes fading.
+        let rms = 2.0_f64.sqrt();
+        let crossings = out
+            .windows(2)
+            .filter(|w| w[0].norm() < rms && w[1].norm() >= rms)
+            .count();
+        let expected = (2.0 * PI).sqrt() * fd / 1.0_f64.exp() * len as f64;
+        let ratio = crossings as f64 / expected;
+        assert!((ratio - 1.0).abs() < 0.15, "ratio {}", ratio);
+    }
+
+    #[test]
+    // A Rician tap with a large K-factor barely fades, and carries the
+    // tap power set by the profile.
+    fn test_rician_fading() {
+        let mut node =
+            FadingChannelNode::new(vec![(0, 1.0), (3, 0.5)], 100.0, 0.01)
+                .with_seed(1);
+        let ones = vec![Complex::new(1.0, 0.0); 1000];
+        for _ in 0..20 {
+            node.run(&ones).unwrap();
+            let gains = node.gains();
+            assert!((gains[0].norm() - 1.0).abs() < 0.3);
+        }
+
+        // With only the second tap powered, an impulse comes out 3 samples
+        // late.
+        let mut node =
+            FadingChannelNode::new(vec![(0, 0.0), (3, 1.0)], 0.0, 0.01)
+                .with_seed(2);
+        let mut impulse = vec![Complex::new(0.0, 0.0); 10];
+        impulse[0] = Complex::new(1.0, 0.0);
+        let out = node.run(&impulse).unwrap();
+        for (n, y) in out.iter().enumerate() {
+            assert_eq!(y.norm() > 0.0, n == 3);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Nodes for diagnosing overload of a radio front end.
+use crate::prelude::*;
+
+use num::{Complex, NumCast};
+
+/// A summary of the clipping seen over one window of samples.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClipReport {
+    /// Number of clipped samples in the window.
+    pub clipped: usize,
+    /// Fraction of the samples in the window that were clipped.
+    pub rate: f64,
+    /// Indices in the stream of the clipped samples, counting from the first
+    /// sample the node received.  Only filled in when timestamps are
+    /// enabled with `with_timestamps`.
+    pub timestamps: Vec<u64>,
+}
+
+/// A node that detects clipping of the incoming samples.
+///
+/// A sample counts as clipped when either its real or imaginary part reaches
+/// the threshold in magnitude, which should be set at or just below the full
+/// scale of the ADC.  Overloading the front end is a common and otherwise
+/// silent cause of poor reception, as the distortion spreads the signal and
+/// any strong interferer across the band.
+///
+/// The samples are counted over consecutive windows of `window` samples, and
+/// a `ClipReport` is produced for each window.  The input may be batched
+/// arbitrarily; if a single batch completes more than one window, their
+/// reports are concatenated.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::clip_node::ClipDetectNode;
+///
+/// // Raw samples from an 8 bit ADC centered on zero.
+/// let node: ClipDetectNode<i8> = ClipDetectNode::new(127.0, 100_000);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct ClipDetectNode<T>
+where
+    T: NumCast + Copy + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    threshold: f64,
+    window: usize,
+    timestamps: bool,
+    index: u64,
+    count: usize,
+    report: ClipReport,
+    pub output: NodeSender<Vec<ClipReport>>,
+}
+
+impl<T> ClipDetectNode<T>
+where
+    T: NumCast + Copy + Send,
+{
+    /// Constructs a new `ClipDetectNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Magnitude at which the real or imaginary part of a
+    ///   sample is considered clipped.
+    /// * `window` - Number of samples in each report.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::clip_node::ClipDetectNode;
+    ///
+    /// let node: ClipDetectNode<f32> = ClipDetectNode::new(0.99, 48000);
+    /// ```
+    pub fn new(threshold: f64, window: usize) -> Self {
+        assert!(window > 0, "window must be nonzero");
+        ClipDetectNode {
+            threshold,
+            window,
+            timestamps: false,
+            index: 0,
+            count: 0,
+            report: ClipReport::default(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Records the index of each clipped sample in the reports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::clip_node::ClipDetectNode;
+    ///
+    /// let node: ClipDetectNode<i16> =
+    ///     ClipDetectNode::new(32767.0, 1 << 16).with_timestamps();
+    /// ```
+    pub fn with_timestamps(mut self) -> Self {
+        self.timestamps = true;
+        self
+    }
+
+    fn is_clipped(&self, sample: &Complex<T>) -> bool {
+        let re = sample.re.to_f64().unwrap().abs();
+        let im = sample.im.to_f64().unwrap().abs();
+        re >= self.threshold || im >= self.threshold
+    }
+
+    /// Runs the `ClipDetectNode<T>`.  Produces a report for each window
+    /// completed by the batch of samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<T>],
+    ) -> Result<Option<Vec<ClipReport>>, NodeError> {
+        let mut reports = vec![];
+        for sample in samples {
+            if self.is_clipped(sample) {
+                self.report.clipped += 1;
+                if self.timestamps {
+                    self.report.timestamps.push(self.index);
+                }
+            }
+            self.index += 1;
+            self.count += 1;
+            if self.count == self.window {
+                let mut report = std::mem::take(&mut self.report);
+                report.rate = report.clipped as f64 / self.window as f64;
+                reports.push(report);
+                self.count = 0;
+            }
+        }
+        if reports.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(reports))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::clip_node::*;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Drives a tone into saturation on a random subset of samples and checks
+    // the reported rate and timestamps against what was injected.
+    fn test_clip_detect() {
+        let window = 10_000;
+        let clip_rate = 0.01;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut clipped = vec![];
+        let samples: Vec<Complex<i16>> = (0..5 * window)
+            .map(|n| {
+                let phase = 0.01 * n as f64;
+                let mut re = 20000.0 * phase.cos();
+                if rng.gen::<f64>() < clip_rate {
+                    clipped.push(n as u64);
+                    re = if re < 0.0 { -32768.0 } else { 32767.0 };
+                }
+                Complex::new(re as i16, (20000.0 * phase.sin()) as i16)
+            })
+            .collect();
+
+        let mut node = ClipDetectNode::new(32767.0, window).with_timestamps();
+        let mut reports = vec![];
+        for chunk in samples.chunks(3000) {
+            if let Some(r) = node.run(chunk).unwrap() {
+                reports.extend(r);
+            }
+        }
+
+        assert_eq!(reports.len(), 5);
+        let total: usize = reports.iter().map(|r| r.clipped).sum();
+        assert_eq!(total, clipped.len());
+        for report in &reports {
+            assert!((report.rate - clip_rate).abs() < 0.003);
+            assert_eq!(report.clipped, report.timestamps.len());
+        }
+        let timestamps: Vec<u64> =
+            reports.into_iter().flat_map(|r| r.timestamps).collect();
+        assert_eq!(timestamps, clipped);
+
+        // Without timestamps, only the counts are reported.
+        let mut node = ClipDetectNode::new(32767.0, window);
+        let reports = node.run(&samples).unwrap().unwrap();
+        assert!(reports.iter().all(|r| r.timestamps.is_empty()));
+        assert_eq!(reports.iter().map(|r| r.clipped).sum::<usize>(), total);
+    }
+}
@@ -0,0 +1,305 @@
+//! Companding laws and nodes for telephone audio.
+//!
+//! Companding compresses the dynamic range of audio before it's quantized
+//! and expands it again afterwards, so that quiet samples get a larger share
+//! of the codes than loud ones.
+use crate::prelude::*;
+
+/// The companding laws used for telephone audio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompandLaw {
+    /// The µ-law used in North America and Japan, usually with µ = 255.
+    MuLaw,
+    /// The A-law used in Europe and elsewhere, usually with A = 87.6.
+    ALaw,
+}
+
+impl CompandLaw {
+    /// Compresses a sample on the interval [-1, 1] with the law.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Sample to compress, clamped to [-1, 1].
+    /// * `param` - Value of µ or A for the law.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// let y = CompandLaw::MuLaw.compress(0.01, 255.0);
+    /// assert!(y > 0.2);
+    /// ```
+    pub fn compress(self, x: f64, param: f64) -> f64 {
+        let x = x.clamp(-1.0, 1.0);
+        let mag = x.abs();
+        let y = match self {
+            CompandLaw::MuLaw => (param * mag).ln_1p() / param.ln_1p(),
+            CompandLaw::ALaw => {
+                if mag < 1.0 / param {
+                    param * mag / (1.0 + param.ln())
+                } else {
+                    (1.0 + (param * mag).ln()) / (1.0 + param.ln())
+                }
+            }
+        };
+        y.copysign(x)
+    }
+
+    /// Expands a compressed sample on the interval [-1, 1] with the law,
+    /// inverting `compress`.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - Compressed sample, clamped to [-1, 1].
+    /// * `param` - Value of µ or A for the law.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// let y = CompandLaw::ALaw.compress(-0.3, 87.6);
+    /// assert!((CompandLaw::ALaw.expand(y, 87.6) + 0.3).abs() < 1e-12);
+    /// ```
+    pub fn expand(self, y: f64, param: f64) -> f64 {
+        let y = y.clamp(-1.0, 1.0);
+        let mag = y.abs();
+        let x = match self {
+            CompandLaw::MuLaw => (mag * param.ln_1p()).exp_m1() / param,
+            CompandLaw::ALaw => {
+                let scale = 1.0 + param.ln();
+                if mag < 1.0 / scale {
+                    mag * scale / param
+                } else {
+                    (mag * scale - 1.0).exp() / param
+                }
+            }
+        };
+        x.copysign(y)
+    }
+
+    /// Compresses a sample on the interval [-1, 1] with the law and
+    /// quantizes it uniformly to a signed 8-bit code.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Sample to encode, clamped to [-1, 1].
+    /// * `param` - Value of µ or A for the law.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// assert_eq!(CompandLaw::MuLaw.encode(1.0, 255.0), 127);
+    /// assert_eq!(CompandLaw::MuLaw.encode(0.0, 255.0), 0);
+    /// ```
+    pub fn encode(self, x: f64, param: f64) -> i8 {
+        (self.compress(x, param) * f64::from(i8::MAX)).round() as i8
+    }
+
+    /// Scales a signed 8-bit code back to the interval [-1, 1] and expands
+    /// it with the law, inverting `encode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Code to decode.
+    /// * `param` - Value of µ or A for the law.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// let code = CompandLaw::ALaw.encode(0.5, 87.6);
+    /// assert!((CompandLaw::ALaw.decode(code, 87.6) - 0.5).abs() < 0.02);
+    /// ```
+    pub fn decode(self, code: i8, param: f64) -> f64 {
+        self.expand(f64::from(code) / f64::from(i8::MAX), param)
+    }
+}
+
+/// A node that compands audio into 8-bit codes.
+///
+/// Each sample on the interval [-1, 1] is compressed with the µ-law or A-law
+/// curve and then quantized uniformly to a signed 8-bit code.  The curve
+/// spends more of the codes on quiet samples than loud ones, so the error of
+/// each sample is roughly proportional to its size, and quiet speech keeps a
+/// far better signal to noise ratio than it would with 8-bit linear PCM.
+/// Samples outside of [-1, 1] are clipped.  The codes are turned back into
+/// audio with an `ExpandNode` using the same law.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::compand::CompandNode;
+/// use comms_rs::util::compand::CompandLaw;
+///
+/// let node = CompandNode::new(CompandLaw::MuLaw, 255.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct CompandNode {
+    pub input: NodeReceiver<Vec<f32>>,
+    law: CompandLaw,
+    param: f64,
+    pub output: NodeSender<Vec<i8>>,
+}
+
+impl CompandNode {
+    /// Constructs a new `CompandNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `law` - Companding law to use.
+    /// * `param` - Value of µ for the µ-law or A for the A-law, greater than
+    ///   one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::CompandNode;
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// let node = CompandNode::new(CompandLaw::ALaw, 87.6);
+    /// ```
+    pub fn new(law: CompandLaw, param: f64) -> CompandNode {
+        assert!(param > 1.0, "companding parameter must be greater than one");
+        CompandNode {
+            law,
+            param,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `CompandNode`.  Produces the companded codes.
+    pub fn run(&mut self, samples: &[f32]) -> Result<Vec<i8>, NodeError> {
+        Ok(samples
+            .iter()
+            .map(|&x| self.law.encode(f64::from(x), self.param))
+            .collect())
+    }
+}
+
+/// A node that expands 8-bit companded codes back into audio.
+///
+/// This inverts `CompandNode`, scaling each code back to the interval
+/// [-1, 1] and expanding it with the same law and parameter.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::compand::ExpandNode;
+/// use comms_rs::util::compand::CompandLaw;
+///
+/// let node = ExpandNode::new(CompandLaw::MuLaw, 255.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct ExpandNode {
+    pub input: NodeReceiver<Vec<i8>>,
+    law: CompandLaw,
+    param: f64,
+    pub output: NodeSender<Vec<f32>>,
+}
+
+impl ExpandNode {
+    /// Constructs a new `ExpandNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `law` - Companding law the codes were made with.
+    /// * `param` - Value of µ for the µ-law or A for the A-law, greater than
+    ///   one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::compand::ExpandNode;
+    /// use comms_rs::util::compand::CompandLaw;
+    ///
+    /// let node = ExpandNode::new(CompandLaw::ALaw, 87.6);
+    /// ```
+    pub fn new(law: CompandLaw, param: f64) -> ExpandNode {
+        assert!(param > 1.0, "companding parameter must be greater than one");
+        ExpandNode {
+            law,
+            param,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ExpandNode`.  Produces the expanded audio samples.
+    pub fn run(&mut self, codes: &[i8]) -> Result<Vec<f32>, NodeError> {
+        Ok(codes
+            .iter()
+            .map(|&c| self.law.decode(c, self.param) as f32)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::compand::*;
+    use std::f64::consts::PI;
+
+    // Ratio in dB of the power of a signal to the power of its error.
+    fn snr(x: &[f32], y: &[f32]) -> f64 {
+        let signal: f64 = x.iter().map(|&x| f64::from(x).powi(2)).sum();
+        let noise: f64 = x
+            .iter()
+            .zip(y)
+            .map(|(&x, &y)| f64::from(x - y).powi(2))
+            .sum();
+        10.0 * (signal / noise).log10()
+    }
+
+    #[test]
+    // Companding then expanding a decaying tone through the nodes should
+    // only add the error of one code step at each sample's point on the
+    // curve, and a quiet tone should come through much cleaner than with
+    // 8-bit linear PCM.
+    fn test_compand_round_trip() {
+        let step = 1.0 / f64::from(i8::MAX);
+        for &(law, param) in
+            &[(CompandLaw::MuLaw, 255.0), (CompandLaw::ALaw, 87.6)]
+        {
+            let audio: Vec<f32> = (0..8000)
+                .map(|n| {
+                    let t = n as f64;
+                    ((-t / 1000.0).exp() * (2.0 * PI * 0.0123 * t).sin()) as f32
+                })
+                .collect();
+            let mut compand = CompandNode::new(law, param);
+            let mut expand = ExpandNode::new(law, param);
+            let mut round_trip = |x: &[f32]| -> Vec<f32> {
+                expand.run(&compand.run(x).unwrap()).unwrap()
+            };
+            let out = round_trip(&audio);
+
+            for (&x, &y) in audio.iter().zip(&out) {
+                let x = f64::from(x).abs();
+                // Slope of the expansion at the sample, times half a step.
+                let slope = match law {
+                    CompandLaw::MuLaw => {
+                        param.ln_1p() * (1.0 + param * x) / param
+                    }
+                    CompandLaw::ALaw => (1.0 + param.ln()) * x.max(1.0 / param),
+                };
+                let bound = 0.5 * step * slope * 1.1 + 1e-6;
+                assert!((f64::from(y).abs() - x).abs() < bound);
+            }
+
+            let quiet: Vec<f32> = audio.iter().map(|x| x * 0.01).collect();
+            let companded = round_trip(&quiet);
+            let linear: Vec<f32> = quiet
+                .iter()
+                .map(|&x| (f64::from(x) / step).round() as f32 * step as f32)
+                .collect();
+            assert!(snr(&quiet, &companded) > snr(&quiet, &linear) + 10.0);
+        }
+    }
+}
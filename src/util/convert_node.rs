@@ -0,0 +1,137 @@
+//! Nodes for converting samples between numeric formats.
+use crate::prelude::*;
+
+use num::Complex;
+
+/// A node that scales floating point samples and converts them to `i16`.
+///
+/// Each component is multiplied by the scale factor, rounded to the nearest
+/// integer and saturated to the range of an `i16`, so a signal that's too hot
+/// clips instead of wrapping around.  The node keeps a running count of the
+/// number of samples that clipped, which can be checked to tune the scale
+/// factor.
+///
+/// This is typically placed right before an `IQBatchOutput` so that a float
+/// pipeline scaled to +/-1.0 ends up at full-scale `i16`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::convert_node::FloatToI16Node;
+///
+/// // Map +/-1.0 onto the full range of an i16.
+/// let node = FloatToI16Node::new(32767.0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct FloatToI16Node {
+    pub input: NodeReceiver<Vec<Complex<f32>>>,
+    scale: f32,
+    clip_count: u64,
+    pub output: NodeSender<Vec<Complex<i16>>>,
+}
+
+impl FloatToI16Node {
+    /// Constructs a new `FloatToI16Node`.
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - Factor to multiply each sample by before conversion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::convert_node::FloatToI16Node;
+    ///
+    /// // Leave 6 dB of headroom.
+    /// let node = FloatToI16Node::new(16384.0);
+    /// ```
+    pub fn new(scale: f32) -> FloatToI16Node {
+        FloatToI16Node {
+            scale,
+            clip_count: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the total number of samples that have clipped so far.
+    pub fn clip_count(&self) -> u64 {
+        self.clip_count
+    }
+
+    // Scales and saturates a single component, returning whether it clipped.
+    fn convert(&self, x: f32) -> (i16, bool) {
+        let scaled = (x * self.scale).round();
+        if scaled > f32::from(i16::MAX) {
+            (i16::MAX, true)
+        } else if scaled < f32::from(i16::MIN) {
+            (i16::MIN, true)
+        } else {
+            (scaled as i16, false)
+        }
+    }
+
+    /// Runs the `FloatToI16Node`.  Produces the scaled and saturated
+    /// samples.
+    pub fn run(
+        &mut self,
+        samples: &[Complex<f32>],
+    ) -> Result<Vec<Complex<i16>>, NodeError> {
+        let mut output = Vec::with_capacity(samples.len());
+        for samp in samples {
+            let (re, re_clip) = self.convert(samp.re);
+            let (im, im_clip) = self.convert(samp.im);
+            if re_clip || im_clip {
+                self.clip_count += 1;
+            }
+            output.push(Complex::new(re, im));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::convert_node::*;
+
+    #[test]
+    // In range values should be scaled and rounded, and out of range values
+    // should saturate and bump the clip count.
+    fn test_float_to_i16() {
+        let mut node = FloatToI16Node::new(32767.0);
+        let input = vec![
+            Complex::new(0.0, 1.0),
+            Complex::new(0.5, -0.5),
+            Complex::new(-1.0, 0.25),
+        ];
+        let output = node.run(&input).unwrap();
+        assert_eq!(
+            output,
+            vec![
+                Complex::new(0, 32767),
+                Complex::new(16384, -16384),
+                Complex::new(-32767, 8192),
+            ]
+        );
+        assert_eq!(node.clip_count(), 0);
+
+        let input = vec![
+            Complex::new(1.5, 0.0),
+            Complex::new(-2.0, 3.0),
+            Complex::new(0.1, -1.01),
+            Complex::new(0.1, 0.1),
+        ];
+        let output = node.run(&input).unwrap();
+        assert_eq!(
+            output,
+            vec![
+                Complex::new(32767, 0),
+                Complex::new(-32768, 32767),
+                Complex::new(3277, -32768),
+                Complex::new(3277, 3277),
+            ]
+        );
+        assert_eq!(node.clip_count(), 3);
+    }
+}
@@ -0,0 +1,208 @@
+//! Nodes for building heatmap displays of received symbols.
+use crate::prelude::*;
+
+use num::{Complex, Float};
+
+/// A node that accumulates received symbols into a density grid over the IQ
+/// plane.
+///
+/// A scatter plot of a constellation saturates once the points start to
+/// overlap, hiding how tightly the symbols cluster and what shape the noise
+/// around each point takes.  This node instead counts the symbols falling in
+/// each cell of a square grid, giving a 2D histogram that can be rendered as
+/// a heatmap, where dense clusters, spreading from phase noise and
+/// distortion, and the tails of the noise all stand out.
+///
+/// The grid has `resolution` cells a side and covers `-extent` to `extent`
+/// on both axes.  It's indexed first by the in-phase cell, from the most
+/// negative, and then by the quadrature cell, and each cell holds the
+/// fraction of all the symbols received so far that landed in it, so symbols
+/// outside of the grid leave the total below one.  The grid is produced
+/// after every `interval` symbols, once per batch unless set otherwise with
+/// `with_interval`, so a display can be updated at a steady rate however the
+/// symbols are batched.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::density_node::ConstellationDensityNode;
+///
+/// // A 64 by 64 grid covering +/-1.5, updated every 10000 symbols.
+/// let node: ConstellationDensityNode<f32> =
+///     ConstellationDensityNode::new(64, 1.5).with_interval(10000);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct ConstellationDensityNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    resolution: usize,
+    extent: f64,
+    interval: Option<usize>,
+    counts: Vec<Vec<u64>>,
+    total: u64,
+    since_output: usize,
+    pub output: NodeSender<Vec<Vec<f64>>>,
+}
+
+impl<T> ConstellationDensityNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `ConstellationDensityNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - Number of cells along each side of the grid.
+    /// * `extent` - Largest magnitude of the in-phase and quadrature parts
+    ///   covered by the grid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::density_node::ConstellationDensityNode;
+    ///
+    /// let node: ConstellationDensityNode<f64> =
+    ///     ConstellationDensityNode::new(100, 2.0);
+    /// ```
+    pub fn new(resolution: usize, extent: f64) -> Self {
+        assert!(resolution > 0, "resolution must be nonzero");
+        assert!(extent > 0.0, "extent must be positive");
+        ConstellationDensityNode {
+            resolution,
+            extent,
+            interval: None,
+            counts: vec![vec![0; resolution]; resolution],
+            total: 0,
+            since_output: 0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the number of symbols between each output of the grid.
+    pub fn with_interval(mut self, interval: usize) -> Self {
+        assert!(interval > 0, "interval must be nonzero");
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Returns the center of a cell along either axis.
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - Index of the cell along the axis.
+    pub fn cell_center(&self, cell: usize) -> f64 {
+        let width = 2.0 * self.extent / self.resolution as f64;
+        -self.extent + (cell as f64 + 0.5) * width
+    }
+
+    // Index of the cell holding a value along either axis, if it's on the
+    // grid.
+    fn cell(&self, x: f64) -> Option<usize> {
+        let pos = (x + self.extent) / (2.0 * self.extent);
+        if (0.0..1.0).contains(&pos) {
+            Some((pos * self.resolution as f64) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the density grid of every symbol received so far.
+    pub fn density(&self) -> Vec<Vec<f64>> {
+        let scale = 1.0 / self.total.max(1) as f64;
+        self.counts
+            .iter()
+            .map(|row| row.iter().map(|&c| c as f64 * scale).collect())
+            .collect()
+    }
+
+    /// Runs the `ConstellationDensityNode<T>`.  Produces the density grid if
+    /// it's due.
+    pub fn run(
+        &mut self,
+        symbols: &[Complex<T>],
+    ) -> Result<Option<Vec<Vec<f64>>>, NodeError> {
+        for symbol in symbols {
+            let re = symbol.re.to_f64().ok_or(NodeError::DataError)?;
+            let im = symbol.im.to_f64().ok_or(NodeError::DataError)?;
+            if let (Some(i), Some(q)) = (self.cell(re), self.cell(im)) {
+                self.counts[i][q] += 1;
+            }
+            self.total += 1;
+        }
+        self.since_output += symbols.len();
+        let due = match self.interval {
+            Some(interval) => self.since_output >= interval,
+            None => true,
+        };
+        if !due {
+            return Ok(None);
+        }
+        self.since_output %= self.interval.unwrap_or(1);
+        Ok(Some(self.density()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::modulation::digital::psk_table;
+    use crate::util::density_node::*;
+    use rand::distributions::{Normal, Uniform};
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Feeds noisy QPSK symbols in and checks that the densest cell around
+    // each constellation point is on the point, well above the density
+    // between the points.
+    fn test_constellation_density() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let table = psk_table(2);
+        let pick = Uniform::new(0, 4);
+        let noise = Normal::new(0.0, 0.1);
+        let symbols: Vec<Complex<f64>> = (0..40000)
+            .map(|_| {
+                table[rng.sample(pick)]
+                    + Complex::new(rng.sample(noise), rng.sample(noise))
+            })
+            .collect();
+
+        let resolution = 30;
+        let mut node =
+            ConstellationDensityNode::new(resolution, 1.5).with_interval(10000);
+        let outputs: Vec<Option<Vec<Vec<f64>>>> = symbols
+            .chunks(3000)
+            .map(|chunk| node.run(chunk).unwrap())
+            .collect();
+        assert_eq!(outputs.iter().filter(|x| x.is_some()).count(), 4);
+        let grid = node.density();
+        let total: f64 = grid.iter().flatten().sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        // Search the cells closer to each point than to any other.
+        let width = 3.0 / resolution as f64;
+        for point in &table {
+            let mut best = (0, 0, 0.0);
+            for (i, row) in grid.iter().enumerate() {
+                for (q, &d) in row.iter().enumerate() {
+                    let cell =
+                        Complex::new(node.cell_center(i), node.cell_center(q));
+                    let nearest = table
+                        .iter()
+                        .all(|p| (cell - point).norm() <= (cell - p).norm());
+                    if nearest && d > best.2 {
+                        best = (i, q, d);
+                    }
+                }
+            }
+            assert!((node.cell_center(best.0) - point.re).abs() < width);
+            assert!((node.cell_center(best.1) - point.im).abs() < width);
+            let middle = grid[resolution / 2][resolution / 2];
+            assert!(best.2 > 100.0 * middle.max(1e-6));
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Nodes for exporting data to disk for offline analysis.
+//!
+//! These are handy for headless captures where there's no display available
+//! to plot things live, such as on a server or in CI.
+use crate::prelude::*;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A node that accumulates spectrum frames and writes them out as a CSV.
+///
+/// Each received frame (typically the magnitude of an FFT) becomes one row of
+/// the CSV, with one column per bin.  Once `n_frames` frames have been
+/// accumulated the file is written and accumulation starts over, so the file
+/// on disk always holds the most recent complete set of frames.  Any frames
+/// still pending when the node is dropped are written to a separate file,
+/// `path` with `.partial` appended, so that the last complete set is kept.
+///
+/// # Examples
+///
+/// ```no_run
+/// use comms_rs::util::export_node::SpectrumExportNode;
+///
+/// let node: SpectrumExportNode<f32> =
+///     SpectrumExportNode::new("/tmp/spectrum.csv", 100);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SpectrumExportNode<T>
+where
+    T: Copy + Display + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    path: PathBuf,
+    n_frames: usize,
+    frames: Vec<Vec<T>>,
+}
+
+impl<T> SpectrumExportNode<T>
+where
+    T: Copy + Display + Send,
+{
+    /// Constructs a new `SpectrumExportNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the CSV file to write.
+    /// * `n_frames` - Number of frames to accumulate before writing the file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use comms_rs::util::export_node::SpectrumExportNode;
+    ///
+    /// let node: SpectrumExportNode<f64> =
+    ///     SpectrumExportNode::new("/tmp/spectrum.csv", 10);
+    /// ```
+    pub fn new<P: AsRef<Path>>(path: P, n_frames: usize) -> Self {
+        SpectrumExportNode {
+            path: path.as_ref().to_path_buf(),
+            n_frames,
+            frames: Vec::with_capacity(n_frames),
+            input: Default::default(),
+        }
+    }
+
+    /// Returns the path that any incomplete set of frames is written to when
+    /// the node is dropped.
+    pub fn partial_path(&self) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(".partial");
+        PathBuf::from(name)
+    }
+
+    /// Writes all of the currently accumulated frames to the CSV file and
+    /// clears them.
+    pub fn export(&mut self) -> io::Result<()> {
+        let path = self.path.clone();
+        self.export_to(&path)
+    }
+
+    fn export_to(&mut self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for frame in &self.frames {
+            let row: Vec<String> =
+                frame.iter().map(|x| x.to_string()).collect();
+            writeln!(writer, "{}", row.join(","))?;
+        }
+        writer.flush()?;
+        self.frames.clear();
+        Ok(())
+    }
+
+    /// Runs the `SpectrumExportNode<T>`.  Returns a `NodeError` if the file
+    /// couldn't be written.
+    pub fn run(&mut self, frame: &[T]) -> Result<(), NodeError> {
+        self.frames.push(frame.to_vec());
+        if self.frames.len() >= self.n_frames {
+            self.export().map_err(|_| NodeError::PermanentError)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Drop for SpectrumExportNode<T>
+where
+    T: Copy + Display + Send,
+{
+    fn drop(&mut self) {
+        if !self.frames.is_empty() {
+            let path = self.partial_path();
+            let _ = self.export_to(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::export_node::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    // Writes a few frames out and checks the dimensions and values of the
+    // resulting CSV.
+    fn test_spectrum_export() {
+        let path = env::temp_dir().join("comms_rs_test_spectrum_export.csv");
+        let frames = vec![
+            vec![0.5, 1.0, 1.5, 2.0],
+            vec![2.5, 3.0, 3.5, 4.0],
+            vec![4.5, 5.0, 5.5, 6.0],
+        ];
+        {
+            let mut node = SpectrumExportNode::new(&path, frames.len());
+            for frame in &frames {
+                node.run(frame).unwrap();
+            }
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let rows: Vec<Vec<f64>> = contents
+            .lines()
+            .map(|line| line.split(',').map(|x| x.parse().unwrap()).collect())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows.len(), 3);
+        for (row, frame) in rows.iter().zip(frames.iter()) {
+            assert_eq!(row.len(), 4);
+            assert_eq!(row, frame);
+        }
+    }
+
+    #[test]
+    // Frames pending when the node is dropped go to the partial file and
+    // leave the last complete set untouched.
+    fn test_spectrum_export_on_drop() {
+        let path = env::temp_dir().join("comms_rs_test_spectrum_drop.csv");
+        let partial_path;
+        {
+            let mut node = SpectrumExportNode::new(&path, 2);
+            partial_path = node.partial_path();
+            node.run(&[1u32, 2, 3]).unwrap();
+            node.run(&[4u32, 5, 6]).unwrap();
+            node.run(&[7u32, 8, 9]).unwrap();
+        }
+        let contents = fs::read_to_string(&path).unwrap();
+        let partial = fs::read_to_string(&partial_path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&partial_path).unwrap();
+        assert_eq!(contents, "1,2,3\n4,5,6\n");
+        assert_eq!(partial, "7,8,9\n");
+    }
+}
@@ -0,0 +1,235 @@
+//! Nodes for scaling signals by gains that can change while running.
+use crate::prelude::*;
+use crate::util::math::db_to_linear;
+
+use num::{Complex, Num};
+use std::ops::Mul;
+
+/// A node that scales a signal by a gain given in dB.
+///
+/// Each input sample, real or complex, is multiplied by the amplitude scale
+/// factor `10^(gain / 20)`, so +6 dB roughly doubles the amplitude and -20 dB
+/// divides it by ten.  A new gain in dB arriving on the `gain_db` control
+/// input replaces the old one, starting with the batch it arrives with.
+///
+/// The node is non-blocking, so the control input doesn't need to supply a
+/// gain for every batch and may be left unconnected for a fixed gain.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::gain_node::GainNode;
+/// use num::Complex;
+///
+/// // Attenuate by 10 dB.
+/// let node: GainNode<Complex<f64>> = GainNode::new(-10.0);
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct GainNode<T>
+where
+    T: Copy + Send + Mul<f64, Output = T>,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    pub gain_db: NodeReceiver<f64>,
+    db: f64,
+    scale: f64,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> GainNode<T>
+where
+    T: Copy + Send + Mul<f64, Output = T>,
+{
+    /// Constructs a new `GainNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `gain_db` - Gain in dB to apply until one arrives on the control
+    ///   input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::gain_node::GainNode;
+    ///
+    /// let node: GainNode<f64> = GainNode::new(6.0);
+    /// ```
+    pub fn new(gain_db: f64) -> Self {
+        GainNode {
+            db: gain_db,
+            scale: db_to_linear(gain_db),
+            input: Default::default(),
+            gain_db: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the gain in dB currently being applied.
+    pub fn gain(&self) -> f64 {
+        self.db
+    }
+
+    /// Sets the gain in dB to apply to following samples.
+    pub fn set_gain(&mut self, gain_db: f64) {
+        self.db = gain_db;
+        self.scale = db_to_linear(gain_db);
+    }
+
+    /// Runs the `GainNode<T>`.  Updates the gain if a new one has arrived,
+    /// then produces the scaled batch if one was received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<T>>,
+        gain_db: Option<f64>,
+    ) -> Result<Option<Vec<T>>, NodeError> {
+        if let Some(gain_db) = gain_db {
+            self.set_gain(gain_db);
+        }
+        Ok(input
+            .map(|samples| samples.iter().map(|x| *x * self.scale).collect()))
+    }
+}
+
+/// A node that scales a signal by a complex weight from a control input.
+///
+/// Each input sample is multiplied by the current weight, which rotates and
+/// scales the signal.  Whenever a new weight arrives on the `weight` control
+/// input it replaces the old one, starting with the batch it arrives with.
+/// This is the building block for beamforming style weighting, where an
+/// adaptive algorithm elsewhere in the graph steers the amplitude and phase of
+/// each channel.
+///
+/// The node is non-blocking, so the control input doesn't need to supply a
+/// weight for every batch and may be left unconnected for a fixed gain.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::gain_node::ComplexGainNode;
+/// use num::Complex;
+///
+/// // Start with a 90 degree phase shift.
+/// let node = ComplexGainNode::new(Complex::new(0.0_f64, 1.0));
+/// ```
+#[derive(Node)]
+#[non_blocking]
+#[aggregate]
+pub struct ComplexGainNode<T>
+where
+    T: Num + Copy + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    pub weight: NodeReceiver<Complex<T>>,
+    gain: Complex<T>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> ComplexGainNode<T>
+where
+    T: Num + Copy + Send,
+{
+    /// Constructs a new `ComplexGainNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `weight` - Weight to apply until one arrives on the control input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::gain_node::ComplexGainNode;
+    /// use num::Complex;
+    ///
+    /// let node = ComplexGainNode::new(Complex::new(1.0_f32, 0.0));
+    /// ```
+    pub fn new(weight: Complex<T>) -> Self {
+        ComplexGainNode {
+            gain: weight,
+            input: Default::default(),
+            weight: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Returns the weight currently being applied.
+    pub fn gain(&self) -> Complex<T> {
+        self.gain
+    }
+
+    /// Runs the `ComplexGainNode<T>`.  Updates the weight if a new one has
+    /// arrived, then produces the weighted batch if one was received.
+    pub fn run(
+        &mut self,
+        input: Option<Vec<Complex<T>>>,
+        weight: Option<Complex<T>>,
+    ) -> Result<Option<Vec<Complex<T>>>, NodeError> {
+        if let Some(weight) = weight {
+            self.gain = weight;
+        }
+        Ok(input
+            .map(|samples| samples.iter().map(|x| *x * self.gain).collect()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::gain_node::*;
+
+    #[test]
+    // Checks the output is the input times the current weight, and that a
+    // new weight applies to the following samples.
+    fn test_complex_gain() {
+        let samples: Vec<Complex<f64>> =
+            (0..8).map(|n| Complex::new(n as f64, 1.0)).collect();
+        let w1 = Complex::new(0.0, 2.0);
+        let w2 = Complex::new(-0.5, 0.5);
+
+        let mut node = ComplexGainNode::new(w1);
+        let out = node.run(Some(samples.clone()), None).unwrap().unwrap();
+        for (y, x) in out.iter().zip(samples.iter()) {
+            assert_eq!(*y, x * w1);
+        }
+
+        // A weight on its own doesn't produce any output.
+        assert!(node.run(None, Some(w2)).unwrap().is_none());
+        assert_eq!(node.gain(), w2);
+        let out = node.run(Some(samples.clone()), None).unwrap().unwrap();
+        for (y, x) in out.iter().zip(samples.iter()) {
+            assert_eq!(*y, x * w2);
+        }
+
+        // A weight arriving with a batch applies to that batch.
+        let out = node.run(Some(samples.clone()), Some(w1)).unwrap().unwrap();
+        for (y, x) in out.iter().zip(samples.iter()) {
+            assert_eq!(*y, x * w1);
+        }
+    }
+
+    #[test]
+    // Checks that +6 dB about doubles the amplitude, and that a new gain
+    // applies to the following samples.
+    fn test_gain_db() {
+        let samples: Vec<f64> = (0..8).map(|n| n as f64 - 3.0).collect();
+        let mut node = GainNode::new(6.0);
+        let out = node.run(Some(samples.clone()), None).unwrap().unwrap();
+        for (y, x) in out.iter().zip(samples.iter()) {
+            assert!((y - 2.0 * x).abs() <= 0.005 * x.abs());
+        }
+
+        assert!(node.run(None, Some(-20.0)).unwrap().is_none());
+        assert_eq!(node.gain(), -20.0);
+        let out = node.run(Some(samples.clone()), None).unwrap().unwrap();
+        for (y, x) in out.iter().zip(samples.iter()) {
+            assert_approx_eq!(*y, x / 10.0);
+        }
+
+        let mut node = GainNode::new(0.0);
+        let tone = vec![Complex::new(0.6, -0.8); 4];
+        let out = node.run(Some(tone.clone()), Some(40.0)).unwrap().unwrap();
+        for (y, x) in out.iter().zip(tone.iter()) {
+            assert!((y - x * 100.0).norm() < 1e-9);
+        }
+    }
+}
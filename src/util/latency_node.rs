@@ -0,0 +1,254 @@
+//! Nodes for measuring how long samples take to pass through a pipeline.
+use crate::prelude::*;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Times at which batches passed the start probe, keyed by the index of the
+// first sample of each batch.
+type ArrivalLog = Arc<Mutex<VecDeque<(u64, Instant)>>>;
+
+/// Latency statistics produced by a `LatencyEndNode`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyStats {
+    /// Number of measurements made so far.
+    pub count: u64,
+    /// Latency of the most recent measurement.
+    pub last: Duration,
+    /// Mean latency over every measurement.
+    pub mean: Duration,
+    /// Shortest latency seen.
+    pub min: Duration,
+    /// Longest latency seen.
+    pub max: Duration,
+}
+
+/// A node that marks when samples enter the part of a pipeline whose
+/// latency is being measured.
+///
+/// Batches pass through unchanged.  The time each batch arrives is noted
+/// against the index of its first sample, counted the same way as
+/// `TimestampNode` counts them, in a log shared with the `LatencyEndNode`
+/// built from this node.  Since the log travels alongside the graph rather
+/// than through it, the nodes in between don't need to know they're being
+/// measured.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::latency_node::{LatencyEndNode, LatencyStartNode};
+/// use num::Complex;
+///
+/// let start: LatencyStartNode<Complex<f32>> = LatencyStartNode::new();
+/// let end: LatencyEndNode<f32> = LatencyEndNode::new(&start);
+/// ```
+#[derive(Node)]
+pub struct LatencyStartNode<T>
+where
+    T: Clone + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    index: u64,
+    log: ArrivalLog,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> LatencyStartNode<T>
+where
+    T: Clone + Send,
+{
+    /// Constructs a new `LatencyStartNode<T>`.
+    pub fn new() -> Self {
+        LatencyStartNode {
+            index: 0,
+            log: Default::default(),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `LatencyStartNode<T>`.  Notes the arrival of the batch and
+    /// passes it on.
+    pub fn run(&mut self, data: Vec<T>) -> Result<Vec<T>, NodeError> {
+        if !data.is_empty() {
+            let mut log = self.log.lock().unwrap();
+            log.push_back((self.index, Instant::now()));
+            self.index += data.len() as u64;
+        }
+        Ok(data)
+    }
+}
+
+impl<T> Default for LatencyStartNode<T>
+where
+    T: Clone + Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node that measures how long samples took to arrive from a
+/// `LatencyStartNode`.
+///
+/// The node counts the samples it receives, and when a batch holds a sample
+/// whose arrival the start probe noted, the time between the two is one
+/// latency measurement.  This includes the processing time of every node in
+/// between as well as any time the samples spent queued in channels, which
+/// is usually the larger part in a pipeline like an FM receiver.
+///
+/// Sample indices at the two probes are assumed to line up, so the pipeline
+/// between them mustn't add or drop samples.  A pipeline that changes the
+/// sample rate by a fixed ratio, through decimation or interpolation, can be
+/// measured by giving the ratio to `with_rate_change`.  The node is a sink
+/// for the data, so it's connected alongside whatever consumes the output of
+/// the pipeline.  Each batch that completes at least one measurement
+/// produces the updated statistics.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::latency_node::{LatencyEndNode, LatencyStartNode};
+///
+/// // A chain that decimates by 10 between the probes.
+/// let start: LatencyStartNode<f32> = LatencyStartNode::new();
+/// let end: LatencyEndNode<f32> =
+///     LatencyEndNode::new(&start).with_rate_change(0.1);
+/// ```
+#[derive(Node)]
+#[aggregate]
+pub struct LatencyEndNode<T>
+where
+    T: Clone + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    index: u64,
+    ratio: f64,
+    log: ArrivalLog,
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    pub output: NodeSender<LatencyStats>,
+}
+
+impl<T> LatencyEndNode<T>
+where
+    T: Clone + Send,
+{
+    /// Constructs a new `LatencyEndNode<T>` that measures the latency from
+    /// a start probe.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The probe marking the start of the pipeline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::latency_node::{LatencyEndNode, LatencyStartNode};
+    ///
+    /// let start: LatencyStartNode<u8> = LatencyStartNode::new();
+    /// let end: LatencyEndNode<u8> = LatencyEndNode::new(&start);
+    /// ```
+    pub fn new<U>(start: &LatencyStartNode<U>) -> Self
+    where
+        U: Clone + Send,
+    {
+        LatencyEndNode {
+            index: 0,
+            ratio: 1.0,
+            log: start.log.clone(),
+            count: 0,
+            total: Duration::from_secs(0),
+            min: Duration::from_secs(u64::MAX),
+            max: Duration::from_secs(0),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the number of samples reaching this probe for each sample
+    /// passing the start probe.
+    pub fn with_rate_change(mut self, ratio: f64) -> Self {
+        assert!(ratio > 0.0, "rate change must be positive");
+        self.ratio = ratio;
+        self
+    }
+
+    /// Runs the `LatencyEndNode<T>`.  Produces the latency statistics if
+    /// the batch completed a measurement.
+    pub fn run(
+        &mut self,
+        data: Vec<T>,
+    ) -> Result<Option<LatencyStats>, NodeError> {
+        let now = Instant::now();
+        self.index += data.len() as u64;
+        let mut last = None;
+        let mut log = self.log.lock().unwrap();
+        while let Some(&(index, time)) = log.front() {
+            if (index as f64 * self.ratio).floor() as u64 >= self.index {
+                break;
+            }
+            log.pop_front();
+            let latency = now.duration_since(time);
+            self.count += 1;
+            self.total += latency;
+            self.min = self.min.min(latency);
+            self.max = self.max.max(latency);
+            last = Some(latency);
+        }
+        Ok(last.map(|last| LatencyStats {
+            count: self.count,
+            last,
+            mean: self.total.div_f64(self.count as f64),
+            min: self.min,
+            max: self.max,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::latency_node::*;
+    use std::thread;
+
+    #[test]
+    // Holds batches up between the probes for known times and checks that
+    // the measured latencies match.  The upper bounds are loose, since a
+    // loaded machine may oversleep by a good margin.
+    fn test_latency_probes() {
+        let mut start = LatencyStartNode::new();
+        let mut end = LatencyEndNode::new(&start);
+
+        // Two batches queue up, 40 ms and 20 ms before they're delivered.
+        let a = start.run(vec![0_u8; 100]).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let b = start.run(vec![0_u8; 50]).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(end.run(a[..60].to_vec()).unwrap().unwrap().count, 1);
+        let stats = end.run([&a[60..], &b[..]].concat()).unwrap().unwrap();
+        assert_eq!(stats.count, 2);
+        assert!(stats.max >= Duration::from_millis(40));
+        assert!(stats.max < Duration::from_millis(400));
+        assert!(stats.last >= Duration::from_millis(20));
+        assert!(stats.last < Duration::from_millis(200));
+        assert_eq!(stats.min, stats.last);
+        assert!(stats.mean > stats.min && stats.mean < stats.max);
+
+        // Nothing new is measured until the next batch shows up.
+        assert_eq!(end.run(vec![]).unwrap(), None);
+
+        // With decimation by 4 in between.
+        let mut start = LatencyStartNode::new();
+        let mut end = LatencyEndNode::new(&start).with_rate_change(0.25);
+        start.run(vec![0.0_f32; 400]).unwrap();
+        start.run(vec![0.0_f32; 400]).unwrap();
+        thread::sleep(Duration::from_millis(30));
+        let stats = end.run(vec![0.0_f32; 150]).unwrap().unwrap();
+        assert_eq!(stats.count, 2);
+        assert!(stats.last >= Duration::from_millis(30));
+        assert!(stats.last < Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,284 @@
+//! A harness for testing a complete modem over a simulated channel.
+use crate::prelude::*;
+use crate::util::channel_node::ChannelImpairmentNode;
+
+use num::Complex;
+use std::collections::VecDeque;
+
+/// Link quality statistics produced by a `LoopbackTestNode`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopbackStats {
+    /// Number of bits compared so far.
+    pub bits: u64,
+    /// Number of those bits received in error.
+    pub bit_errors: u64,
+    /// Bit error rate over every bit compared, zero before any have been.
+    pub ber: f64,
+    /// RMS error vector magnitude over every symbol received, as a fraction
+    /// of the RMS reference symbol, zero before any have been.
+    pub evm: f64,
+}
+
+/// A node that runs bits through a transmitter, a channel and a receiver,
+/// and measures how well they come out the other end.
+///
+/// The transmit chain is given as a function from bits to samples and the
+/// receive chain as one from samples to the recovered symbols and bits, so
+/// whatever nodes make up the modem can be run inside them, carrying their
+/// state from one batch to the next.  Between the two, the samples pass
+/// through a `ChannelImpairmentNode` set up with the offsets and noise to
+/// test against.  This turns checking a complete modem into building one
+/// node and feeding it bits, whether that's in a unit test or a graph fed by
+/// a random bit source.
+///
+/// The bits received are compared in order against those sent, after
+/// skipping the number of bits set with `with_bit_delay`, which should be
+/// the latency of the modem in bits.  Bits still making their way through
+/// the modem, or held back by the channel, are compared once they arrive
+/// with a later batch.  Received symbols are compared against the nearest
+/// point of the reference constellation for the EVM, which is QPSK with unit
+/// power unless set otherwise, so the receiver should scale them to match.
+/// Each batch produces the statistics over everything received so far.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::channel_node::ChannelImpairmentNode;
+/// use comms_rs::util::loopback_node::LoopbackTestNode;
+/// use num::Complex;
+///
+/// // A BPSK modem at one sample per symbol in a little noise.
+/// let tx = |bits: &[u8]| -> Vec<Complex<f64>> {
+///     bits.iter().map(|&b| Complex::new(2.0 * b as f64 - 1.0, 0.0)).collect()
+/// };
+/// let rx = |samples: &[Complex<f64>]| -> (Vec<Complex<f64>>, Vec<u8>) {
+///     let bits = samples.iter().map(|x| (x.re > 0.0) as u8).collect();
+///     (samples.to_vec(), bits)
+/// };
+/// let channel = ChannelImpairmentNode::new(0.0, 0.0, 0.0, 0.01);
+/// let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+/// let mut node =
+///     LoopbackTestNode::new(tx, rx, channel).with_constellation(bpsk);
+///
+/// let stats = node.run(&[0, 1, 1, 0, 1, 0, 0, 1]).unwrap();
+/// assert_eq!(stats.bit_errors, 0);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct LoopbackTestNode<Tx, Rx>
+where
+    Tx: FnMut(&[u8]) -> Vec<Complex<f64>> + Send,
+    Rx: FnMut(&[Complex<f64>]) -> (Vec<Complex<f64>>, Vec<u8>) + Send,
+{
+    pub input: NodeReceiver<Vec<u8>>,
+    tx: Tx,
+    rx: Rx,
+    channel: ChannelImpairmentNode<f64>,
+    constellation: Vec<Complex<f64>>,
+    skip: usize,
+    sent: VecDeque<u8>,
+    bits: u64,
+    bit_errors: u64,
+    error_sum: f64,
+    power_sum: f64,
+    pub output: NodeSender<LoopbackStats>,
+}
+
+impl<Tx, Rx> LoopbackTestNode<Tx, Rx>
+where
+    Tx: FnMut(&[u8]) -> Vec<Complex<f64>> + Send,
+    Rx: FnMut(&[Complex<f64>]) -> (Vec<Complex<f64>>, Vec<u8>) + Send,
+{
+    /// Constructs a new `LoopbackTestNode<Tx, Rx>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Transmit chain, from a batch of bits to samples.
+    /// * `rx` - Receive chain, from a batch of samples to the symbols and
+    ///   bits recovered from them.
+    /// * `channel` - Channel between the two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::channel_node::ChannelImpairmentNode;
+    /// use comms_rs::util::loopback_node::LoopbackTestNode;
+    /// use num::Complex;
+    ///
+    /// // An on-off keyed modem, with a quarter sample timing offset.
+    /// let tx = |bits: &[u8]| -> Vec<Complex<f64>> {
+    ///     bits.iter().map(|&b| Complex::new(b as f64, 0.0)).collect()
+    /// };
+    /// let rx = |samples: &[Complex<f64>]| -> (Vec<Complex<f64>>, Vec<u8>) {
+    ///     let bits = samples.iter().map(|x| (x.re > 0.5) as u8).collect();
+    ///     (samples.to_vec(), bits)
+    /// };
+    /// let channel = ChannelImpairmentNode::new(0.0, 0.25, 0.0, 0.0);
+    /// let node = LoopbackTestNode::new(tx, rx, channel);
+    /// ```
+    pub fn new(tx: Tx, rx: Rx, channel: ChannelImpairmentNode<f64>) -> Self {
+        let a = 0.5_f64.sqrt();
+        LoopbackTestNode {
+            tx,
+            rx,
+            channel,
+            constellation: vec![
+                Complex::new(a, a),
+                Complex::new(-a, a),
+                Complex::new(-a, -a),
+                Complex::new(a, -a),
+            ],
+            skip: 0,
+            sent: VecDeque::new(),
+            bits: 0,
+            bit_errors: 0,
+            error_sum: 0.0,
+            power_sum: 0.0,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the number of bits the receiver produces before the first bit
+    /// sent, which are skipped over.
+    pub fn with_bit_delay(mut self, delay: usize) -> Self {
+        self.skip = delay;
+        self
+    }
+
+    /// Sets the reference constellation the symbols are compared against.
+    pub fn with_constellation(mut self, points: Vec<Complex<f64>>) -> Self {
+        assert!(!points.is_empty(), "constellation must not be empty");
+        self.constellation = points;
+        self
+    }
+
+    /// Returns the statistics over everything received so far.
+    pub fn stats(&self) -> LoopbackStats {
+        LoopbackStats {
+            bits: self.bits,
+            bit_errors: self.bit_errors,
+            ber: if self.bits > 0 {
+                self.bit_errors as f64 / self.bits as f64
+            } else {
+                0.0
+            },
+            evm: if self.power_sum > 0.0 {
+                (self.error_sum / self.power_sum).sqrt()
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Runs the `LoopbackTestNode<Tx, Rx>`.  Sends the batch of bits through
+    /// the modem and produces the updated statistics.
+    pub fn run(&mut self, bits: &[u8]) -> Result<LoopbackStats, NodeError> {
+        let samples = (self.tx)(bits);
+        self.sent.extend(bits);
+        let received = self.channel.impair(&samples);
+        let (symbols, rx_bits) = (self.rx)(&received);
+
+        for bit in rx_bits {
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            if let Some(sent) = self.sent.pop_front() {
+                self.bits += 1;
+                if sent != bit {
+                    self.bit_errors += 1;
+                }
+            }
+        }
+        for symbol in symbols {
+            let nearest = self
+                .constellation
+                .iter()
+                .min_by(|a, b| {
+                    (symbol - *a)
+                        .norm_sqr()
+                        .partial_cmp(&(symbol - *b).norm_sqr())
+                        .unwrap()
+                })
+                .unwrap();
+            self.error_sum += (symbol - nearest).norm_sqr();
+            self.power_sum += nearest.norm_sqr();
+        }
+        Ok(self.stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::filter::fir;
+    use crate::util::loopback_node::*;
+    use crate::util::math::rrc_taps;
+    use num::Zero;
+    use rand::distributions::Uniform;
+    use rand::prelude::*;
+    use rand::rngs::SmallRng;
+
+    #[test]
+    // Runs a root raised cosine shaped QPSK modem at four samples per
+    // symbol through a noisy channel with a phase offset the receiver
+    // corrects, and checks that every bit makes it through.
+    fn test_loopback_qpsk() {
+        let sam_per_sym = 4;
+        let taps: Vec<Complex<f64>> =
+            rrc_taps(33, sam_per_sym as f64, 0.35).unwrap();
+        let peak: f64 = taps.iter().map(|t| t.norm_sqr()).sum();
+        let a = 0.5_f64.sqrt();
+
+        let mut tx_state = vec![Complex::zero(); taps.len()];
+        let tx_taps = taps.clone();
+        let tx = move |bits: &[u8]| -> Vec<Complex<f64>> {
+            let mut upsampled = vec![];
+            for pair in bits.chunks(2) {
+                let level = |b: u8| if b == 1 { a } else { -a };
+                upsampled.push(Complex::new(level(pair[0]), level(pair[1])));
+                upsampled.extend(vec![Complex::zero(); sam_per_sym - 1]);
+            }
+            fir::batch_fir(&upsampled, &tx_taps, &mut tx_state)
+        };
+
+        // The matched filter lines the symbols up 32 samples in, and the
+        // receiver knows to take out the phase offset.
+        let mut rx_state = vec![Complex::zero(); taps.len()];
+        let mut sample_ix = 0;
+        let rotation = Complex::new(0.0, -0.3).exp();
+        let rx = move |samples: &[Complex<f64>]| {
+            let filtered = fir::batch_fir(samples, &taps, &mut rx_state);
+            let mut symbols = vec![];
+            let mut bits = vec![];
+            for y in filtered {
+                if sample_ix >= 32 && (sample_ix - 32) % sam_per_sym == 0 {
+                    let symbol = y * rotation / peak;
+                    bits.push((symbol.re > 0.0) as u8);
+                    bits.push((symbol.im > 0.0) as u8);
+                    symbols.push(symbol);
+                }
+                sample_ix += 1;
+            }
+            (symbols, bits)
+        };
+
+        // The shaped signal has unit power, so after the matched filter
+        // gathers up the four samples of each symbol the SNR is 20 dB, for
+        // an EVM of 10%.
+        let channel =
+            ChannelImpairmentNode::new(0.0, 0.0, 0.3, 0.04).with_seed(0);
+        let mut node = LoopbackTestNode::new(tx, rx, channel);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let dist = Uniform::new(0u8, 2u8);
+        let mut stats = node.stats();
+        for _ in 0..20 {
+            let bits: Vec<u8> = (0..1000).map(|_| rng.sample(dist)).collect();
+            stats = node.run(&bits).unwrap();
+        }
+        assert!(stats.bits > 19900);
+        assert_eq!(stats.bit_errors, 0);
+        assert_eq!(stats.ber, 0.0);
+        assert!((stats.evm - 0.1).abs() < 0.01, "EVM {}", stats.evm);
+    }
+}
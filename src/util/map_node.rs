@@ -0,0 +1,140 @@
+//! Nodes for applying user supplied functions to data.
+use crate::prelude::*;
+
+/// A node that applies a function to each batch of data.
+///
+/// Many nodes do nothing more than take a slice of input and produce a new
+/// vector from it.  Rather than defining a new struct for each of these, the
+/// transform can be dropped in as a closure.  The closure may hold state of
+/// its own, since it's called as an `FnMut`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::map_node::BatchMapNode;
+/// use num::Complex;
+///
+/// // Compute the power of each sample.
+/// let node = BatchMapNode::new(|x: &[Complex<f32>]| {
+///     x.iter().map(|s| s.norm_sqr()).collect()
+/// });
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct BatchMapNode<I, O, F>
+where
+    I: Send,
+    O: Send + Clone,
+    F: FnMut(&[I]) -> Vec<O> + Send,
+{
+    pub input: NodeReceiver<Vec<I>>,
+    func: F,
+    pub output: NodeSender<Vec<O>>,
+}
+
+impl<I, O, F> BatchMapNode<I, O, F>
+where
+    I: Send,
+    O: Send + Clone,
+    F: FnMut(&[I]) -> Vec<O> + Send,
+{
+    /// Constructs a new `BatchMapNode<I, O, F>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `func` - Function to apply to each batch of input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::map_node::BatchMapNode;
+    ///
+    /// // Swap the two halves of each FFT frame to put DC in the center.
+    /// let node = BatchMapNode::new(|x: &[f64]| {
+    ///     let mut y = x.to_vec();
+    ///     y.rotate_left(x.len() / 2);
+    ///     y
+    /// });
+    /// ```
+    pub fn new(func: F) -> Self {
+        BatchMapNode {
+            func,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `BatchMapNode<I, O, F>`.  Produces the result of applying
+    /// the function to the batch.
+    pub fn run(&mut self, batch: &[I]) -> Result<Vec<O>, NodeError> {
+        Ok((self.func)(batch))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::map_node::*;
+    use num::Complex;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    // Wires a BatchMapNode computing magnitudes between a source and a
+    // checking node.
+    fn test_batch_map_node() {
+        #[derive(Node)]
+        struct SourceNode {
+            count: u32,
+            output: NodeSender<Vec<Complex<f32>>>,
+        }
+
+        impl SourceNode {
+            pub fn run(&mut self) -> Result<Vec<Complex<f32>>, NodeError> {
+                self.count += 1;
+                let n = self.count as f32;
+                Ok(vec![Complex::new(3.0 * n, 4.0 * n); 8])
+            }
+        }
+
+        #[derive(Node)]
+        struct CheckNode {
+            input: NodeReceiver<Vec<f32>>,
+            count: u32,
+        }
+
+        impl CheckNode {
+            pub fn run(&mut self, mags: Vec<f32>) -> Result<(), NodeError> {
+                self.count += 1;
+                let truth = vec![5.0 * self.count as f32; 8];
+                assert_eq!(mags, truth);
+                Ok(())
+            }
+        }
+
+        let mut source = SourceNode {
+            count: 0,
+            output: Default::default(),
+        };
+        let mut mag = BatchMapNode::new(|x: &[Complex<f32>]| {
+            x.iter().map(|s| s.norm()).collect()
+        });
+        let mut check = CheckNode {
+            count: 0,
+            input: Default::default(),
+        };
+        connect_nodes!(source, output, mag, input);
+        connect_nodes!(mag, output, check, input);
+        start_nodes!(source, mag);
+        let check = thread::spawn(move || {
+            let now = Instant::now();
+            loop {
+                check.call().unwrap();
+                if now.elapsed().as_millis() >= 200 {
+                    break;
+                }
+            }
+            assert!(check.count > 0);
+        });
+        assert!(check.join().is_ok());
+    }
+}
@@ -125,6 +125,57 @@ pub fn sinc(x: f64) -> f64 {
     }
 }
 
+/// Converts a gain in dB to a linear amplitude scale factor, `10^(dB / 20)`.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::db_to_linear;
+///
+/// assert!((db_to_linear(20.0) - 10.0).abs() < 1e-12);
+/// assert!((db_to_linear(-6.0) - 0.501).abs() < 1e-3);
+/// ```
+pub fn db_to_linear(gain_db: f64) -> f64 {
+    10.0_f64.powf(gain_db / 20.0)
+}
+
+/// Converts a binary number to its Gray code.
+///
+/// Consecutive Gray codes differ in exactly one bit, which is why they're
+/// used to label neighboring constellation points: the most likely symbol
+/// error then only causes a single bit error.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::binary_to_gray;
+///
+/// assert_eq!(binary_to_gray(2), 3);
+/// assert_eq!(binary_to_gray(3), 2);
+/// ```
+pub fn binary_to_gray(x: u32) -> u32 {
+    x ^ (x >> 1)
+}
+
+/// Converts a Gray code back to the binary number it represents.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::{binary_to_gray, gray_to_binary};
+///
+/// assert_eq!(gray_to_binary(binary_to_gray(1234)), 1234);
+/// ```
+pub fn gray_to_binary(x: u32) -> u32 {
+    let mut x = x;
+    let mut shift = 1;
+    while shift < 32 {
+        x ^= x >> shift;
+        shift <<= 1;
+    }
+    x
+}
+
 /// Raise Cosine (RC) filter tap calculator.
 ///
 /// Use this to create the taps for a FIR filter node and use that as your
@@ -341,11 +392,292 @@ pub fn qfilt_taps(
     Ok(output)
 }
 
+/// Designs a second order IIR notch filter.
+///
+/// Uses the bilinear transform design from the well known "Audio EQ Cookbook"
+/// by Robert Bristow-Johnson.  The coefficients are normalized so that
+/// `a[0] = 1.0`, and can be passed directly to
+/// `comms_rs::filter::iir::Biquad::new`.  Returns the feedforward and
+/// feedback coefficients as `(b, a)`.
+///
+/// # Arguments
+///
+/// * `f0` - Center frequency of the notch in Hz.  Must be on the interval
+///   (0.0, fs / 2.0).
+/// * `q` - Quality factor of the notch.  Higher values give a narrower
+///   notch.  Must be greater than 0.0.
+/// * `fs` - Sample rate in Hz.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::notch_biquad;
+///
+/// // Knock out the 19 kHz pilot tone in an FM broadcast signal.
+/// let (b, a) = notch_biquad(19_000.0, 30.0, 240_000.0).unwrap();
+/// ```
+pub fn notch_biquad(
+    f0: f64,
+    q: f64,
+    fs: f64,
+) -> Result<([f64; 3], [f64; 3]), MathError> {
+    if f0 <= 0.0 || f0 >= fs / 2.0 {
+        return Err(MathError::InvalidFrequencyError);
+    }
+    if q <= 0.0 {
+        return Err(MathError::InvalidQualityFactorError);
+    }
+
+    let w0 = 2.0 * PI * f0 / fs;
+    let alpha = w0.sin() / (2.0 * q);
+    let a0 = 1.0 + alpha;
+    let b = [1.0 / a0, -2.0 * w0.cos() / a0, 1.0 / a0];
+    let a = [1.0, -2.0 * w0.cos() / a0, (1.0 - alpha) / a0];
+    Ok((b, a))
+}
+
+/// Designs a lowpass FIR filter by windowing a sinc with a Kaiser window.
+///
+/// The Kaiser window trades main lobe width against sidelobe level through
+/// its shape parameter, so the filter can be designed straight from a
+/// specification: the window shape is set by the stopband attenuation, and
+/// the number of taps by the attenuation and the width of the transition
+/// band, using Kaiser's formulas.  The transition band is centered on the
+/// cutoff, the taps are symmetric with an odd count so that the delay is a
+/// whole number of samples, and they're scaled for unity gain at DC.  The
+/// passband ripple is about the same as the stopband level.
+///
+/// # Arguments
+///
+/// * `cutoff` - Center of the transition band in cycles per sample.
+/// * `transition` - Width of the transition band in cycles per sample.  The
+///   band must fit between DC and half the sample rate.
+/// * `attenuation` - Stopband attenuation in dB.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::kaiser_lowpass_taps;
+///
+/// // Pass up to 0.1 and stop from 0.15 cycles per sample, 60 dB down.
+/// let taps = kaiser_lowpass_taps(0.125, 0.05, 60.0).unwrap();
+/// assert_eq!(taps.len() % 2, 1);
+/// assert!((taps.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+/// ```
+pub fn kaiser_lowpass_taps(
+    cutoff: f64,
+    transition: f64,
+    attenuation: f64,
+) -> Result<Vec<f64>, MathError> {
+    if transition <= 0.0
+        || cutoff - transition / 2.0 <= 0.0
+        || cutoff + transition / 2.0 > 0.5
+    {
+        return Err(MathError::InvalidFrequencyError);
+    }
+
+    let beta = if attenuation > 50.0 {
+        0.1102 * (attenuation - 8.7)
+    } else if attenuation >= 21.0 {
+        0.5842 * (attenuation - 21.0).powf(0.4) + 0.07886 * (attenuation - 21.0)
+    } else {
+        0.0
+    };
+    let order = ((attenuation - 8.0) / (2.285 * 2.0 * PI * transition))
+        .ceil()
+        .max(2.0) as usize;
+    let n_taps = order + 1 + order % 2;
+
+    // Zeroth order modified Bessel function of the first kind.
+    let bessel_i0 = |x: f64| {
+        let (mut sum, mut term, mut k) = (1.0, 1.0, 1.0);
+        while term > 1e-12 * sum {
+            term *= (x / (2.0 * k)).powi(2);
+            sum += term;
+            k += 1.0;
+        }
+        sum
+    };
+    let center = (n_taps - 1) as f64 / 2.0;
+    let mut taps: Vec<f64> = (0..n_taps)
+        .map(|k| {
+            let m = k as f64 - center;
+            let r = m / center;
+            let window =
+                bessel_i0(beta * (1.0 - r * r).sqrt()) / bessel_i0(beta);
+            2.0 * cutoff * sinc(2.0 * cutoff * m) * window
+        })
+        .collect();
+    let gain: f64 = taps.iter().sum();
+    for tap in taps.iter_mut() {
+        *tap /= gain;
+    }
+    Ok(taps)
+}
+
+/// Generates a Barker code.
+///
+/// Barker codes are short binary sequences whose aperiodic autocorrelation
+/// sidelobes are never larger than one in magnitude, which makes them
+/// popular preambles for symbol and frame synchronization.  They only exist
+/// for lengths 2, 3, 4, 5, 7, 11 and 13.  The code is returned as +1.0 and
+/// -1.0 chips.
+///
+/// # Arguments
+///
+/// * `len` - Length of the code.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::barker_code;
+///
+/// let code = barker_code(7).unwrap();
+/// assert_eq!(code, vec![1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0]);
+/// ```
+pub fn barker_code(len: usize) -> Result<Vec<f64>, MathError> {
+    let chips: &[i8] = match len {
+        2 => &[1, -1],
+        3 => &[1, 1, -1],
+        4 => &[1, 1, -1, 1],
+        5 => &[1, 1, 1, -1, 1],
+        7 => &[1, 1, 1, -1, -1, 1, -1],
+        11 => &[1, 1, 1, -1, -1, -1, 1, -1, -1, 1, -1],
+        13 => &[1, 1, 1, 1, 1, -1, -1, 1, 1, -1, 1, -1, 1],
+        _ => return Err(MathError::InvalidLengthError),
+    };
+    Ok(chips.iter().map(|&c| c.into()).collect())
+}
+
+/// Generates a Zadoff-Chu sequence.
+///
+/// Zadoff-Chu sequences have a constant magnitude, and their periodic
+/// autocorrelation is zero at every nonzero lag, so they're used as
+/// synchronization and reference signals in LTE and elsewhere.  Element `n`
+/// of the sequence with root `u` and length `N` is
+///
+/// `exp(-j * PI * u * n * (n + N % 2) / N)`
+///
+/// # Arguments
+///
+/// * `root` - Root index, on the interval [1, len) and coprime to `len`.
+/// * `len` - Length of the sequence.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::math::zadoff_chu;
+///
+/// // The length 63 sequence with root 25 used for the LTE primary
+/// // synchronization signal.
+/// let zc = zadoff_chu(25, 63).unwrap();
+/// assert_eq!(zc.len(), 63);
+/// ```
+pub fn zadoff_chu(root: u32, len: u32) -> Result<Vec<Complex<f64>>, MathError> {
+    if len == 0 {
+        return Err(MathError::InvalidLengthError);
+    }
+    let gcd = |mut a: u32, mut b: u32| {
+        while b != 0 {
+            let t = a % b;
+            a = b;
+            b = t;
+        }
+        a
+    };
+    if root == 0 || root >= len || gcd(root, len) != 1 {
+        return Err(MathError::InvalidRootError);
+    }
+    let (u, n_len, cf) = (root as u64, len as u64, (len % 2) as u64);
+    Ok((0..n_len)
+        .map(|n| {
+            // Reduce the exponent modulo 2N to keep precision for long
+            // sequences.
+            let k =
+                (u * n % (2 * n_len)) * ((n + cf) % (2 * n_len)) % (2 * n_len);
+            Complex::from_polar(1.0, -PI * k as f64 / n_len as f64)
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod test {
     use crate::util::math;
     use num::Complex;
 
+    #[test]
+    fn test_gray_code() {
+        for x in (0..70000).chain(u32::MAX - 1000..=u32::MAX) {
+            let gray = math::binary_to_gray(x);
+            assert_eq!(math::gray_to_binary(gray), x);
+            let next = math::binary_to_gray(x.wrapping_add(1));
+            assert_eq!((gray ^ next).count_ones(), 1);
+        }
+    }
+
+    #[test]
+    fn test_notch_biquad() {
+        let (b, a) = math::notch_biquad(1000.0, 10.0, 8000.0).unwrap();
+
+        // The zeros sit right on the unit circle at the notch frequency, so
+        // the response there is exactly zero.
+        let z = Complex::new(0.0, -2.0 * std::f64::consts::PI / 8.0).exp();
+        let num = b[0] + b[1] * z + b[2] * z * z;
+        assert!(num.norm() < 1e-12);
+        assert!((a[0] - 1.0).abs() < f64::EPSILON);
+
+        // Well away from the notch, and at DC, the gain should be unity.
+        let dc = (b[0] + b[1] + b[2]) / (a[0] + a[1] + a[2]);
+        assert!((dc - 1.0).abs() < 1e-12);
+
+        assert!(math::notch_biquad(5000.0, 10.0, 8000.0).is_err());
+        assert!(math::notch_biquad(1000.0, 0.0, 8000.0).is_err());
+    }
+
+    #[test]
+    fn test_barker_code() {
+        let code = math::barker_code(13).unwrap();
+        let known = [1, 1, 1, 1, 1, -1, -1, 1, 1, -1, 1, -1, 1];
+        for (c, k) in code.iter().zip(known.iter()) {
+            assert!((c - f64::from(*k)).abs() < f64::EPSILON);
+        }
+
+        // Every Barker code has aperiodic sidelobes of at most one.
+        for &len in &[2, 3, 4, 5, 7, 11, 13] {
+            let code = math::barker_code(len).unwrap();
+            assert_eq!(code.len(), len);
+            for lag in 1..len {
+                let r: f64 =
+                    code.iter().zip(&code[lag..]).map(|(a, b)| a * b).sum();
+                assert!(r.abs() <= 1.0);
+            }
+        }
+        assert!(math::barker_code(6).is_err());
+    }
+
+    #[test]
+    fn test_zadoff_chu() {
+        for &(root, len) in &[(25, 63), (29, 63), (7, 64), (1, 139)] {
+            let zc = math::zadoff_chu(root, len).unwrap();
+            let n = len as usize;
+            assert_eq!(zc.len(), n);
+            for x in &zc {
+                assert!((x.norm() - 1.0).abs() < 1e-12);
+            }
+
+            // The periodic autocorrelation is zero away from zero lag.
+            for lag in 0..n {
+                let r: Complex<f64> =
+                    (0..n).map(|k| zc[(k + lag) % n] * zc[k].conj()).sum();
+                let expected = if lag == 0 { len as f64 } else { 0.0 };
+                assert!((r.norm() - expected).abs() < 1e-9);
+            }
+        }
+        assert!(math::zadoff_chu(21, 63).is_err());
+        assert!(math::zadoff_chu(0, 63).is_err());
+        assert!(math::zadoff_chu(1, 0).is_err());
+    }
+
     #[test]
     fn test_cast_complex() {
         let val = Complex::new(3.0, 4.0);
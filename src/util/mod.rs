@@ -8,6 +8,10 @@ use std::fmt;
 pub enum MathError {
     ConvertError,
     InvalidRolloffError,
+    InvalidFrequencyError,
+    InvalidQualityFactorError,
+    InvalidLengthError,
+    InvalidRootError,
 }
 
 impl fmt::Display for MathError {
@@ -17,6 +21,18 @@ impl fmt::Display for MathError {
             MathError::InvalidRolloffError => {
                 "Invalid rolloff parameter, must be on interval [0.0, 1.0]"
             }
+            MathError::InvalidFrequencyError => {
+                "Invalid frequency, must be on interval (0.0, fs / 2.0)"
+            }
+            MathError::InvalidQualityFactorError => {
+                "Invalid quality factor, must be greater than 0.0"
+            }
+            MathError::InvalidLengthError => {
+                "Invalid length, no sequence of that length exists"
+            }
+            MathError::InvalidRootError => {
+                "Invalid root, must be on interval [1, len) and coprime to len"
+            }
         };
         write!(f, "Math error: {}", desc)
     }
@@ -24,9 +40,37 @@ impl fmt::Display for MathError {
 
 impl error::Error for MathError {}
 
+/// Some nodes to average and integrate signals over time
+pub mod average_node;
+/// Some nodes to simulate the effects of a propagation channel
+pub mod channel_node;
+/// Some nodes to diagnose overload of a radio front end
+pub mod clip_node;
+/// Some nodes to compand telephone audio
+pub mod compand;
+/// Some nodes to convert samples between numeric formats
+pub mod convert_node;
+/// Some nodes to build heatmap displays of received symbols
+pub mod density_node;
+/// Some nodes to export data to disk for offline analysis
+pub mod export_node;
+/// Some nodes to scale signals by adjustable gains
+pub mod gain_node;
+/// Some nodes to measure latency through a pipeline
+pub mod latency_node;
+/// Some nodes to test a complete modem over a simulated channel
+pub mod loopback_node;
+/// Some nodes to apply user supplied functions to data
+pub mod map_node;
 /// Some basic math functions used elsewhere in the project
 pub mod math;
+/// Some nodes to model and linearize power amplifiers
+pub mod pa_node;
 /// Some nodes to aid in the generation of random numbers
 pub mod rand_node;
 /// Some nodes to aid in resampling signals
 pub mod resample_node;
+/// Some nodes to combine several signals into one
+pub mod signal_mixer_node;
+/// Some nodes to reshape and manage streams of data
+pub mod stream_node;
@@ -0,0 +1,472 @@
+//! Nodes for modeling and linearizing power amplifiers.
+use crate::prelude::*;
+
+use num::{Complex, Float, NumCast, Zero};
+use std::collections::VecDeque;
+
+/// A node that predistorts a signal to compensate for the nonlinearity of a
+/// power amplifier.
+///
+/// The predistorter is a memory polynomial,
+///
+/// `y[n] = sum_m sum_k a[m][k] * x[n - m] * |x[n - m]|^k`
+///
+/// where `m` runs over the delays and `k` over the orders.  With a single
+/// delay this is the memoryless polynomial `sum_k a[k] * x * |x|^k`, which
+/// changes the gain and phase of each sample based on its amplitude alone and
+/// so can undo the AM/AM and AM/PM curves of an amplifier.  The extra delays
+/// also let it undo the memory effects of amplifiers whose distortion depends
+/// on recent samples too.  Passing the output through the amplifier then
+/// gives a nearly linear response overall, reducing spectral regrowth.
+///
+/// The coefficients usually come from a fit of the amplifier's inverse, or,
+/// for a mild nonlinearity given as a polynomial, from inverting its series.
+/// Amplifiers are typically modeled with odd orders only, which here are
+/// the even values of `k`, with coefficient 0 the linear gain.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::pa_node::PredistortNode;
+/// use num::Complex;
+///
+/// // Expand the gain slightly at high amplitudes to offset compression.
+/// let coeffs = vec![
+///     Complex::new(1.0, 0.0),
+///     Complex::new(0.0, 0.0),
+///     Complex::new(0.1, 0.02),
+/// ];
+/// let node: PredistortNode<f32> = PredistortNode::new(coeffs);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct PredistortNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    coeffs: Vec<Vec<Complex<f64>>>,
+    history: VecDeque<Complex<f64>>,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> PredistortNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new memoryless `PredistortNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `coeffs` - Polynomial coefficients, with coefficient `k`
+    ///   multiplying `x * |x|^k`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::pa_node::PredistortNode;
+    /// use num::Complex;
+    ///
+    /// // A plain gain of 2 with no distortion.
+    /// let node: PredistortNode<f64> =
+    ///     PredistortNode::new(vec![Complex::new(2.0, 0.0)]);
+    /// ```
+    pub fn new(coeffs: Vec<Complex<f64>>) -> Self {
+        Self::memory_polynomial(vec![coeffs])
+    }
+
+    /// Constructs a new `PredistortNode<T>` with memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `coeffs` - Polynomial coefficients for each delay, starting with
+    ///   the current sample, with `coeffs[m][k]` multiplying
+    ///   `x[n - m] * |x[n - m]|^k`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::pa_node::PredistortNode;
+    /// use num::Complex;
+    ///
+    /// // A third order term on the current sample, and a small linear
+    /// // term on the one before it.
+    /// let current = vec![1.0, 0.0, 0.1];
+    /// let coeffs = vec![
+    ///     current.iter().map(|&a| Complex::new(a, 0.0)).collect(),
+    ///     vec![Complex::new(-0.05, 0.0)],
+    /// ];
+    /// let node: PredistortNode<f32> =
+    ///     PredistortNode::memory_polynomial(coeffs);
+    /// ```
+    pub fn memory_polynomial(coeffs: Vec<Vec<Complex<f64>>>) -> Self {
+        assert!(!coeffs.is_empty(), "there must be at least one delay");
+        PredistortNode {
+            history: vec![Complex::zero(); coeffs.len()].into(),
+            coeffs,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `PredistortNode<T>`.  Produces the predistorted samples.
+    pub fn run(
+        &mut self,
+        input: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        let mut output = Vec::with_capacity(input.len());
+        for x in input {
+            let x =
+                Complex::new(x.re.to_f64().unwrap(), x.im.to_f64().unwrap());
+            self.history.pop_back();
+            self.history.push_front(x);
+
+            let mut y: Complex<f64> = Complex::zero();
+            for (x, coeffs) in self.history.iter().zip(&self.coeffs) {
+                let r = x.norm();
+                let mut term = *x;
+                for a in coeffs {
+                    y += a * term;
+                    term *= r;
+                }
+            }
+            output.push(Complex::new(
+                NumCast::from(y.re).unwrap(),
+                NumCast::from(y.im).unwrap(),
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// A memoryless power amplifier model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaModel {
+    /// The Saleh model of a traveling wave tube amplifier, with the output
+    /// amplitude `alpha_a * r / (1 + beta_a * r^2)` and the phase shift
+    /// `alpha_p * r^2 / (1 + beta_p * r^2)` in radians for an input
+    /// amplitude `r`.
+    Saleh {
+        alpha_a: f64,
+        beta_a: f64,
+        alpha_p: f64,
+        beta_p: f64,
+    },
+    /// The Rapp model of a solid state amplifier, with the output amplitude
+    ///
+    /// `gain * r / (1 + (gain * r / saturation)^(2p))^(1 / (2p))`
+    ///
+    /// for an input amplitude `r` and a smoothness `p`, and no phase shift.
+    /// Larger smoothness values give a sharper knee, approaching an ideal
+    /// limiter.
+    Rapp {
+        gain: f64,
+        saturation: f64,
+        smoothness: f64,
+    },
+}
+
+impl PaModel {
+    /// The Saleh model with the parameters Saleh fit to a traveling wave
+    /// tube amplifier, which saturates at an input amplitude of about 0.93.
+    pub fn saleh_twt() -> PaModel {
+        PaModel::Saleh {
+            alpha_a: 2.1587,
+            beta_a: 1.1517,
+            alpha_p: 4.0033,
+            beta_p: 9.1040,
+        }
+    }
+
+    /// Returns the output amplitude and phase shift in radians for an input
+    /// amplitude, i.e. the AM/AM and AM/PM curves of the amplifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `r` - Input amplitude.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::pa_node::PaModel;
+    ///
+    /// let model = PaModel::Rapp {
+    ///     gain: 1.0,
+    ///     saturation: 1.0,
+    ///     smoothness: 2.0,
+    /// };
+    /// let (amplitude, phase) = model.response(10.0);
+    /// assert!(amplitude < 1.0);
+    /// assert_eq!(phase, 0.0);
+    /// ```
+    pub fn response(self, r: f64) -> (f64, f64) {
+        match self {
+            PaModel::Saleh {
+                alpha_a,
+                beta_a,
+                alpha_p,
+                beta_p,
+            } => {
+                let r2 = r * r;
+                (
+                    alpha_a * r / (1.0 + beta_a * r2),
+                    alpha_p * r2 / (1.0 + beta_p * r2),
+                )
+            }
+            PaModel::Rapp {
+                gain,
+                saturation,
+                smoothness,
+            } => {
+                let p = 2.0 * smoothness;
+                let amplitude = gain * r
+                    / (1.0 + (gain * r / saturation).powf(p)).powf(1.0 / p);
+                (amplitude, 0.0)
+            }
+        }
+    }
+}
+
+/// A node that models the nonlinearity of a power amplifier.
+///
+/// Each sample's amplitude is mapped through the AM/AM curve of the model
+/// and its phase is shifted by the AM/PM curve, so samples near saturation
+/// are compressed and rotated while small ones see a linear gain.  The model
+/// has no memory, so each sample is distorted independently of the others.
+///
+/// Driving a signal with a varying envelope near saturation spreads its
+/// spectrum, and the resulting spectral regrowth into neighboring channels
+/// grows quickly with drive level, three times as fast in dB as the input
+/// power for the third order products of a mildly driven amplifier.  This
+/// node together with `PredistortNode` allows studying regrowth and how well
+/// linearization removes it.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::pa_node::{PaModel, PowerAmpNode};
+///
+/// let model = PaModel::Rapp {
+///     gain: 10.0,
+///     saturation: 1.0,
+///     smoothness: 3.0,
+/// };
+/// let node: PowerAmpNode<f32> = PowerAmpNode::new(model);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct PowerAmpNode<T>
+where
+    T: Float + Send,
+{
+    pub input: NodeReceiver<Vec<Complex<T>>>,
+    model: PaModel,
+    pub output: NodeSender<Vec<Complex<T>>>,
+}
+
+impl<T> PowerAmpNode<T>
+where
+    T: Float + Send,
+{
+    /// Constructs a new `PowerAmpNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The amplifier model, along with its parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::pa_node::{PaModel, PowerAmpNode};
+    ///
+    /// let node: PowerAmpNode<f64> = PowerAmpNode::new(PaModel::saleh_twt());
+    /// ```
+    pub fn new(model: PaModel) -> Self {
+        PowerAmpNode {
+            model,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `PowerAmpNode<T>`.  Produces the amplified samples.
+    pub fn run(
+        &mut self,
+        input: &[Complex<T>],
+    ) -> Result<Vec<Complex<T>>, NodeError> {
+        Ok(input
+            .iter()
+            .map(|x| {
+                let x = Complex::new(
+                    x.re.to_f64().unwrap(),
+                    x.im.to_f64().unwrap(),
+                );
+                let (r, theta) = x.to_polar();
+                let (amplitude, shift) = self.model.response(r);
+                let y = Complex::from_polar(amplitude, theta + shift);
+                Complex::new(
+                    NumCast::from(y.re).unwrap(),
+                    NumCast::from(y.im).unwrap(),
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::pa_node::*;
+    use std::f64::consts::PI;
+
+    // Power of the component of a signal at a frequency in cycles per
+    // sample.
+    fn tone_power(y: &[Complex<f64>], freq: f64) -> f64 {
+        let c: Complex<f64> = y
+            .iter()
+            .enumerate()
+            .map(|(n, y)| {
+                y * Complex::new(0.0, -2.0 * PI * freq * n as f64).exp()
+            })
+            .sum();
+        (c.norm() / y.len() as f64).powi(2)
+    }
+
+    #[test]
+    // Drives each model with a two tone signal at increasing levels.  The
+    // third order products should rise 3 dB for every 1 dB of input while
+    // the amplifier is mildly driven, and become strong near saturation.
+    fn test_power_amp_regrowth() {
+        let (f1, f2) = (0.05, 0.07);
+        let imd = 2.0 * f1 - f2;
+        let two_tone = |amplitude: f64| -> Vec<Complex<f64>> {
+            (0..1000)
+                .map(|n| {
+                    let t = n as f64;
+                    Complex::new(0.0, 2.0 * PI * f1 * t).exp() * amplitude
+                        + Complex::new(0.0, 2.0 * PI * f2 * t).exp() * amplitude
+                })
+                .collect()
+        };
+        let models = [
+            PaModel::saleh_twt(),
+            PaModel::Rapp {
+                gain: 1.0,
+                saturation: 0.5,
+                smoothness: 1.0,
+            },
+        ];
+        for &model in &models {
+            let mut node = PowerAmpNode::new(model);
+            let imd_db = |node: &mut PowerAmpNode<f64>, amplitude: f64| {
+                let output = node.run(&two_tone(amplitude)).unwrap();
+                10.0 * tone_power(&output, imd).log10()
+            };
+
+            // 3 dB per dB for small signals, measured over a 10 dB step.
+            let low = imd_db(&mut node, 0.01);
+            let high = imd_db(&mut node, 0.01 * 10.0_f64.sqrt());
+            assert!((high - low - 30.0).abs() < 0.5);
+
+            // Near saturation the products are within 20 dB of the tones.
+            let output = node.run(&two_tone(0.4)).unwrap();
+            let carrier = tone_power(&output, f1);
+            let product = tone_power(&output, imd);
+            assert!(10.0 * (product / carrier).log10() > -20.0);
+        }
+    }
+
+    #[test]
+    // Checks the AM/AM and AM/PM curves of the models against their closed
+    // forms at a few points.
+    fn test_power_amp_curves() {
+        let rapp = PaModel::Rapp {
+            gain: 4.0,
+            saturation: 2.0,
+            smoothness: 2.0,
+        };
+        let mut node: PowerAmpNode<f64> = PowerAmpNode::new(rapp);
+        let y = node.run(&[Complex::new(0.0, 0.01), Complex::new(-100.0, 0.0)]);
+        let y = y.unwrap();
+        assert!((y[0] - Complex::new(0.0, 0.04)).norm() < 1e-6);
+        assert!((y[1] - Complex::new(-2.0, 0.0)).norm() < 1e-6);
+
+        // The Saleh model peaks at r = 1 / sqrt(beta_a).
+        let mut node: PowerAmpNode<f64> =
+            PowerAmpNode::new(PaModel::saleh_twt());
+        let r = 1.0 / 1.1517_f64.sqrt();
+        let y = node.run(&[Complex::new(r, 0.0)]).unwrap()[0];
+        assert!((y.norm() - 2.1587 / (2.0 * 1.1517_f64.sqrt())).abs() < 1e-9);
+        let phase = 4.0033 * r * r / (1.0 + 9.1040 * r * r);
+        assert!((y.arg() - phase).abs() < 1e-9);
+    }
+
+    #[test]
+    // Inverts the series of a cubic amplifier with both AM/AM and AM/PM
+    // distortion to fifth order, and checks that the predistorter followed by
+    // the amplifier is nearly linear over its working range.
+    fn test_predistort_cubic() {
+        let c3 = Complex::new(-0.12, 0.05);
+        let pa = |x: Complex<f64>| x + c3 * x * x.norm_sqr();
+        let d3 = -c3;
+        let d5 = c3 * (c3 + 2.0 * c3.re);
+        let coeffs = vec![
+            Complex::new(1.0, 0.0),
+            Complex::zero(),
+            d3,
+            Complex::zero(),
+            d5,
+        ];
+        let mut node = PredistortNode::new(coeffs);
+
+        // Amplitudes sweeping up to 3 dB below full scale, with the phase
+        // rotating.
+        let input: Vec<Complex<f64>> = (0..1000)
+            .map(|n| {
+                Complex::from_polar(
+                    0.7 * n as f64 / 1000.0,
+                    0.1 * PI * n as f64,
+                )
+            })
+            .collect();
+        let mut predistorted = vec![];
+        for chunk in input.chunks(128) {
+            predistorted.extend(node.run(chunk).unwrap());
+        }
+        let worst = |output: Vec<Complex<f64>>| {
+            output
+                .iter()
+                .zip(&input)
+                .map(|(y, x)| (y - x).norm())
+                .fold(0.0, f64::max)
+        };
+        let uncorrected = worst(input.iter().map(|&x| pa(x)).collect());
+        let corrected = worst(predistorted.into_iter().map(pa).collect());
+        assert!(uncorrected > 0.04);
+        assert!(corrected < uncorrected / 10.0);
+    }
+
+    #[test]
+    // Checks that a memory polynomial applies each delay's polynomial to
+    // the right sample, across batch boundaries.
+    fn test_predistort_memory() {
+        let coeffs = vec![
+            vec![Complex::new(1.0, 0.0), Complex::new(0.5, 0.0)],
+            vec![Complex::zero(), Complex::zero(), Complex::new(0.0, 0.25)],
+        ];
+        let mut node: PredistortNode<f32> =
+            PredistortNode::memory_polynomial(coeffs);
+        let input: Vec<Complex<f32>> =
+            (0..10).map(|n| Complex::new(n as f32 * 0.1, 0.2)).collect();
+        let mut output = node.run(&input[..3]).unwrap();
+        output.extend(node.run(&input[3..]).unwrap());
+        for (n, y) in output.iter().enumerate() {
+            let x = input[n];
+            let mut expected = x + x * x.norm() * 0.5;
+            if n > 0 {
+                let prev = input[n - 1];
+                expected += prev * prev.norm_sqr() * Complex::new(0.0, 0.25);
+            }
+            assert!((y - expected).norm() < 1e-6);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::prelude::*;
+use crate::util::math::kaiser_lowpass_taps;
 use num::Zero;
+use std::ops::{Add, Mul};
 
 /// A simple node to decimate the input signal.
 ///
@@ -13,6 +15,7 @@ where
 {
     pub input: NodeReceiver<Vec<T>>,
     dec_rate: usize,
+    input_rate: Option<f64>,
     pub output: NodeSender<Vec<T>>,
 }
 
@@ -23,11 +26,19 @@ where
     pub fn new(dec_rate: usize) -> Self {
         DecimateNode {
             dec_rate,
+            input_rate: None,
             input: Default::default(),
             output: Default::default(),
         }
     }
 
+    /// Sets the sample rate of the input signal in Hz.  The node will then
+    /// report the decimated output rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
     pub fn run(&mut self, signal: &[T]) -> Result<Vec<T>, NodeError> {
         Ok(self.decimate(signal))
     }
@@ -65,6 +76,285 @@ where
     }
 }
 
+impl<T> SampleRate for DecimateNode<T>
+where
+    T: Copy + Send,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let dec_rate = self.dec_rate.max(1) as f64;
+        self.input_rate.map(|fs| fs / dec_rate)
+    }
+}
+
+/// A node to decimate the input signal by averaging.
+///
+/// This node averages each group of `dec_rate` input samples into a single
+/// output sample.  The average acts as a boxcar lowpass filter ahead of the
+/// decimation, so unlike `DecimateNode` it attenuates content that would
+/// otherwise alias into the output band.  It's not a sharp filter, but it's
+/// cheap and a better default than dropping samples.  Groups may span input
+/// batches.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DecimateAverageNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    dec_rate: usize,
+    acc: T,
+    count: usize,
+    input_rate: Option<f64>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> DecimateAverageNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    pub fn new(dec_rate: usize) -> Self {
+        DecimateAverageNode {
+            dec_rate: dec_rate.max(1),
+            acc: T::zero(),
+            count: 0,
+            input_rate: None,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the input signal in Hz.  The node will then
+    /// report the decimated output rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
+    pub fn run(&mut self, signal: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(self.decimate(signal))
+    }
+
+    /// This is the decimation function.
+    ///
+    /// Each group of `dec_rate` samples is averaged into one output sample.
+    /// Any samples left over at the end of `data` are held and combined with
+    /// the start of the next call.
+    ///
+    /// If the decimation rate is equal to zero or one, the original data is
+    /// returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The input data to be reduced down
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::DecimateAverageNode;
+    ///
+    /// let mut node = DecimateAverageNode::new(2);
+    ///
+    /// let data = vec![1.0, 3.0, 5.0, 7.0, 9.0];
+    /// assert_eq!(node.decimate(&data), vec![2.0, 6.0]);
+    /// assert_eq!(node.decimate(&[11.0]), vec![10.0]);
+    /// ```
+    pub fn decimate(&mut self, data: &[T]) -> Vec<T> {
+        let scale = 1.0 / self.dec_rate as f64;
+        let mut data_dec = Vec::with_capacity(data.len() / self.dec_rate + 1);
+        for sample in data {
+            self.acc = self.acc + *sample;
+            self.count += 1;
+            if self.count == self.dec_rate {
+                data_dec.push(self.acc * scale);
+                self.acc = T::zero();
+                self.count = 0;
+            }
+        }
+        data_dec
+    }
+}
+
+impl<T> SampleRate for DecimateAverageNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let dec_rate = self.dec_rate as f64;
+        self.input_rate.map(|fs| fs / dec_rate)
+    }
+}
+
+/// A node to decimate the input signal behind an anti-alias filter designed
+/// from a cutoff.
+///
+/// `DecimateNode` aliases everything above the output Nyquist frequency back
+/// into the output band, and the boxcar of `DecimateAverageNode` only
+/// softens that.  This node instead designs a Kaiser windowed lowpass with
+/// `kaiser_lowpass_taps` that passes up to the cutoff and reaches the
+/// stopband attenuation by the output Nyquist frequency, so anything that
+/// would alias is suppressed by at least that much.  The cutoff is given as
+/// a fraction of the output Nyquist frequency, and the closer it is to one,
+/// the narrower the transition band and the longer the filter.  Only every
+/// `dec_rate`-th filter output is computed.
+///
+/// Output samples line up with those of `DecimateNode`, delayed by the group
+/// delay of the filter, `(len - 1) / 2` input samples.  The filter history
+/// and decimation phase carry across input batches.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::resample_node::DecimateFilterNode;
+/// use num::Complex;
+///
+/// // Decimate 2.4 MHz down to 240 kHz, keeping 100 kHz either side.
+/// let node: DecimateFilterNode<Complex<f64>> =
+///     DecimateFilterNode::new(10, 100e3 / 120e3).with_sample_rate(2.4e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+pub struct DecimateFilterNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    dec_rate: usize,
+    cutoff: f64,
+    taps: Vec<f64>,
+    history: Vec<T>,
+    next: usize,
+    input_rate: Option<f64>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> DecimateFilterNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    /// Constructs a new `DecimateFilterNode<T>` with 60 dB of stopband
+    /// attenuation.
+    ///
+    /// # Arguments
+    ///
+    /// * `dec_rate` - Decimation factor.  Must be nonzero.
+    /// * `cutoff` - Edge of the passband as a fraction of the output Nyquist
+    ///   frequency, on the interval (0, 1).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::DecimateFilterNode;
+    ///
+    /// // Keep the lower 80% of the output band.
+    /// let node: DecimateFilterNode<f64> = DecimateFilterNode::new(4, 0.8);
+    /// ```
+    pub fn new(dec_rate: usize, cutoff: f64) -> Self {
+        assert!(dec_rate > 0, "decimation rate must be nonzero");
+        assert!(cutoff > 0.0 && cutoff < 1.0, "cutoff must be in (0, 1)");
+        let mut node = DecimateFilterNode {
+            dec_rate,
+            cutoff,
+            taps: vec![],
+            history: vec![],
+            next: 0,
+            input_rate: None,
+            input: Default::default(),
+            output: Default::default(),
+        };
+        node.design(60.0);
+        node
+    }
+
+    /// Sets the stopband attenuation in dB, redesigning the filter.
+    pub fn with_attenuation(mut self, attenuation: f64) -> Self {
+        assert!(attenuation > 0.0, "attenuation must be positive");
+        self.design(attenuation);
+        self
+    }
+
+    /// Sets the sample rate of the input signal in Hz.  The node will then
+    /// report the decimated output rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
+    /// Returns the taps of the anti-alias filter.
+    pub fn taps(&self) -> &[f64] {
+        &self.taps
+    }
+
+    // Designs the filter and clears the history to match.
+    fn design(&mut self, attenuation: f64) {
+        let nyquist = 0.5 / self.dec_rate as f64;
+        let passband = self.cutoff * nyquist;
+        self.taps = kaiser_lowpass_taps(
+            (passband + nyquist) / 2.0,
+            nyquist - passband,
+            attenuation,
+        )
+        .unwrap();
+        // Zeros ahead of the first input fill the filter, and the first
+        // output lands on the first input sample.
+        self.history = vec![T::zero(); self.taps.len() - 1];
+        self.next = self.taps.len() - 1;
+    }
+
+    pub fn run(&mut self, signal: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(self.decimate(signal))
+    }
+
+    /// This is the decimation function.
+    ///
+    /// Filters `data` and keeps every `dec_rate`-th sample, holding back
+    /// what's needed to continue with the next call.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The input data to be reduced down
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::DecimateFilterNode;
+    ///
+    /// let mut node = DecimateFilterNode::new(2, 0.5);
+    /// let out = node.decimate(&vec![1.0; 200]);
+    /// assert_eq!(out.len(), 100);
+    /// assert!((out[99] - 1.0).abs() < 1e-3);
+    /// ```
+    pub fn decimate(&mut self, data: &[T]) -> Vec<T> {
+        self.history.extend_from_slice(data);
+        let mut data_dec = Vec::with_capacity(data.len() / self.dec_rate + 1);
+        while self.next < self.history.len() {
+            let ix = self.next;
+            data_dec.push(
+                self.taps.iter().enumerate().fold(T::zero(), |acc, (m, h)| {
+                    acc + self.history[ix - m] * *h
+                }),
+            );
+            self.next += self.dec_rate;
+        }
+
+        // Keep the history the filter still needs.
+        let drop = self.next - (self.taps.len() - 1);
+        let drop = drop.min(self.history.len());
+        self.history.drain(..drop);
+        self.next -= drop;
+        data_dec
+    }
+}
+
+impl<T> SampleRate for DecimateFilterNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let dec_rate = self.dec_rate as f64;
+        self.input_rate.map(|fs| fs / dec_rate)
+    }
+}
+
 /// A simple node to upsample the input signal.
 ///
 /// This node will upsample the input stream by a factor of `ups_rate`, meaning
@@ -77,6 +367,7 @@ where
 {
     pub input: NodeReceiver<Vec<T>>,
     ups_rate: usize,
+    input_rate: Option<f64>,
     pub output: NodeSender<Vec<T>>,
 }
 
@@ -87,11 +378,19 @@ where
     pub fn new(ups_rate: usize) -> Self {
         UpsampleNode {
             ups_rate,
+            input_rate: None,
             input: Default::default(),
             output: Default::default(),
         }
     }
 
+    /// Sets the sample rate of the input signal in Hz.  The node will then
+    /// report the upsampled output rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
     pub fn run(&mut self, signal: &[T]) -> Result<Vec<T>, NodeError> {
         Ok(self.upsample(signal))
     }
@@ -131,6 +430,356 @@ where
     }
 }
 
+impl<T> SampleRate for UpsampleNode<T>
+where
+    T: Copy + Send + Zero,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let ups_rate = self.ups_rate.max(1) as f64;
+        self.input_rate.map(|fs| fs * ups_rate)
+    }
+}
+
+/// A simple node to hold each input symbol for several output samples.
+///
+/// This node repeats each input sample `sam_per_sym` times, which is a zero
+/// order hold.  Unlike `UpsampleNode`, which inserts zeros and relies on a
+/// following pulse shaping filter, this directly produces rectangular pulse or
+/// staircase signals.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct SymbolHoldNode<T>
+where
+    T: Copy + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    sam_per_sym: usize,
+    input_rate: Option<f64>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> SymbolHoldNode<T>
+where
+    T: Copy + Send,
+{
+    pub fn new(sam_per_sym: usize) -> Self {
+        SymbolHoldNode {
+            sam_per_sym,
+            input_rate: None,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the symbol rate of the input signal in Hz.  The node will then
+    /// report the output sample rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
+    pub fn run(&mut self, symbols: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(self.hold(symbols))
+    }
+
+    /// This is the hold function.
+    ///
+    /// Each sample of `data` is repeated `sam_per_sym` times, to have total
+    /// samples equal to sam_per_sym * data.len()
+    ///
+    /// If the hold length is equal to zero or one, the original data is
+    /// returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The input symbols to be held
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::SymbolHoldNode;
+    ///
+    /// let node = SymbolHoldNode::new(3);
+    ///
+    /// let data = vec![1, 2, 3];
+    /// assert_eq!(node.hold(&data), vec![1, 1, 1, 2, 2, 2, 3, 3, 3]);
+    /// ```
+    pub fn hold(&self, data: &[T]) -> Vec<T> {
+        let sam_per_sym = self.sam_per_sym.max(1);
+        data.iter()
+            .flat_map(|sample| vec![*sample; sam_per_sym])
+            .collect()
+    }
+}
+
+impl<T> SampleRate for SymbolHoldNode<T>
+where
+    T: Copy + Send,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let sam_per_sym = self.sam_per_sym.max(1) as f64;
+        self.input_rate.map(|fs| fs * sam_per_sym)
+    }
+}
+
+/// A node to resample the input signal by an arbitrary rate.
+///
+/// This node uses a polyphase filter bank: the prototype lowpass filter is
+/// split into `n_banks` sub-filters, where bank `k` produces the output that
+/// would fall `k / n_banks` of a sample past each input sample if the input
+/// were upsampled by `n_banks` and filtered by the prototype.  For each output
+/// sample the two banks on either side of the wanted time are evaluated and
+/// linearly interpolated between, which supports any rate, including
+/// irrational ones, with an error that shrinks with the square of the number
+/// of banks.
+///
+/// The prototype should be a lowpass filter designed at `n_banks` times the
+/// input rate, cutting off at the lower of the input and output Nyquist
+/// frequencies, with a DC gain of `n_banks`.  The output is delayed by the
+/// group delay of the prototype, `(len - 1) / (2 * n_banks)` input samples.
+/// The filter history and timing carry across input batches.
+#[derive(Node)]
+#[pass_by_ref]
+pub struct PolyphaseArbResampleNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    banks: Vec<Vec<f64>>,
+    rate: f64,
+    time: f64,
+    history: Vec<T>,
+    input_rate: Option<f64>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> PolyphaseArbResampleNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    /// Constructs a new `PolyphaseArbResampleNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prototype` - Taps of the prototype lowpass filter.
+    /// * `n_banks` - Number of filter banks the prototype is split into.
+    /// * `rate` - Ratio of the output sample rate to the input sample rate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::PolyphaseArbResampleNode;
+    ///
+    /// // Resample from 44.1 kHz to 48 kHz with 32 banks.
+    /// let prototype = vec![1.0; 32];
+    /// let node: PolyphaseArbResampleNode<f64> =
+    ///     PolyphaseArbResampleNode::new(prototype, 32, 48000.0 / 44100.0);
+    /// ```
+    pub fn new(prototype: Vec<f64>, n_banks: usize, rate: f64) -> Self {
+        assert!(n_banks > 0, "number of banks must be nonzero");
+        assert!(!prototype.is_empty(), "prototype must not be empty");
+        assert!(rate > 0.0, "resampling rate must be positive");
+        let taps_per_bank = prototype.len().div_ceil(n_banks);
+        let banks: Vec<Vec<f64>> = (0..n_banks)
+            .map(|k| {
+                (0..taps_per_bank)
+                    .map(|m| {
+                        prototype.get(k + m * n_banks).cloned().unwrap_or(0.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        PolyphaseArbResampleNode {
+            banks,
+            rate,
+            // Zeros ahead of the first input fill the filters, and the first
+            // output lands on the first input sample.
+            time: (taps_per_bank - 1) as f64,
+            history: vec![T::zero(); taps_per_bank - 1],
+            input_rate: None,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Sets the sample rate of the input signal in Hz.  The node will then
+    /// report the resampled output rate through `SampleRate`.
+    pub fn with_sample_rate(mut self, input_rate: f64) -> Self {
+        self.input_rate = Some(input_rate);
+        self
+    }
+
+    pub fn run(&mut self, signal: &[T]) -> Result<Vec<T>, NodeError> {
+        Ok(self.resample(signal))
+    }
+
+    // Output of one bank with the newest input sample at `ix`.
+    fn filter(&self, bank: usize, ix: usize) -> T {
+        self.banks[bank]
+            .iter()
+            .enumerate()
+            .fold(T::zero(), |acc, (m, h)| acc + self.history[ix - m] * *h)
+    }
+
+    /// This is the resampling function.
+    ///
+    /// Produces every output sample that the input received so far allows,
+    /// holding back what's needed to continue with the next call.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The input data to be resampled
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::resample_node::PolyphaseArbResampleNode;
+    ///
+    /// // Two banks of a single tap each repeats each input sample.
+    /// let mut node = PolyphaseArbResampleNode::new(vec![1.0, 1.0], 2, 2.0);
+    /// assert_eq!(node.resample(&[1.0, 2.0, 3.0]), vec![1.0, 1.0, 2.0, 2.0]);
+    /// ```
+    pub fn resample(&mut self, data: &[T]) -> Vec<T> {
+        self.history.extend_from_slice(data);
+        let n_banks = self.banks.len();
+        let step = 1.0 / self.rate;
+        let mut output = vec![];
+        loop {
+            let ix = self.time.floor() as usize;
+            // The bank past the last one is the first bank of the next input
+            // sample, which has to have arrived.
+            if ix + 1 >= self.history.len() {
+                break;
+            }
+            let pos = (self.time - ix as f64) * n_banks as f64;
+            let bank = (pos.floor() as usize).min(n_banks - 1);
+            let frac = pos - bank as f64;
+            let lower = self.filter(bank, ix);
+            let upper = if bank + 1 < n_banks {
+                self.filter(bank + 1, ix)
+            } else {
+                self.filter(0, ix + 1)
+            };
+            output.push(lower * (1.0 - frac) + upper * frac);
+            self.time += step;
+        }
+
+        // Keep the history the filters still need.
+        let keep = self.banks[0].len() - 1;
+        let drop = (self.time.floor() as usize).saturating_sub(keep);
+        let drop = drop.min(self.history.len());
+        self.history.drain(..drop);
+        self.time -= drop as f64;
+        output
+    }
+}
+
+impl<T> SampleRate for PolyphaseArbResampleNode<T>
+where
+    T: Copy + Send + Zero + Add<Output = T> + Mul<f64, Output = T>,
+{
+    fn sample_rate(&self) -> Option<f64> {
+        let rate = self.rate;
+        self.input_rate.map(|fs| fs * rate)
+    }
+}
+
+/// Number of banks in the polyphase filters of a `RateConverter`.
+const CONVERTER_BANKS: usize = 32;
+
+/// Converts interleaved audio from one sample rate to another, with a
+/// polyphase resampler for each channel.
+///
+/// The resamplers use a lowpass prototype that passes up to 90% of the lower
+/// of the two Nyquist frequencies and stops by the Nyquist frequency, with
+/// 60 dB of attenuation.  This is what an `AudioNode` uses to play samples on
+/// a device that runs at a different rate.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::resample_node::RateConverter;
+///
+/// // Stereo from 32 kHz to 48 kHz.
+/// let mut converter = RateConverter::new(2, 32000, 48000);
+/// let output = converter.convert(&vec![0.0; 6400]);
+/// ```
+pub struct RateConverter {
+    resamplers: Vec<PolyphaseArbResampleNode<f64>>,
+    partial: Vec<f32>,
+}
+
+impl RateConverter {
+    /// Constructs a new `RateConverter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - Number of interleaved channels.
+    /// * `input_rate` - Sample rate of the input in Hz.
+    /// * `output_rate` - Sample rate of the output in Hz.
+    pub fn new(channels: u16, input_rate: u32, output_rate: u32) -> Self {
+        assert!(channels > 0, "number of channels must be nonzero");
+        // Pass up to 90% of the lower of the two Nyquist frequencies, and
+        // stop by the Nyquist frequency, at the rate of the upsampled input
+        // the prototype works at.
+        let upsampled = f64::from(input_rate) * CONVERTER_BANKS as f64;
+        let nyquist = f64::from(input_rate.min(output_rate)) / 2.0;
+        let mut prototype = kaiser_lowpass_taps(
+            0.95 * nyquist / upsampled,
+            0.1 * nyquist / upsampled,
+            60.0,
+        )
+        .unwrap();
+        for tap in prototype.iter_mut() {
+            *tap *= CONVERTER_BANKS as f64;
+        }
+        let rate = f64::from(output_rate) / f64::from(input_rate);
+        RateConverter {
+            resamplers: (0..channels)
+                .map(|_| {
+                    PolyphaseArbResampleNode::new(
+                        prototype.clone(),
+                        CONVERTER_BANKS,
+                        rate,
+                    )
+                })
+                .collect(),
+            partial: vec![],
+        }
+    }
+
+    /// Resamples a batch of interleaved samples.  A frame split across
+    /// batches is held until the rest of it arrives.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Interleaved samples at the input rate.
+    pub fn convert(&mut self, samples: &[f32]) -> Vec<f32> {
+        let channels = self.resamplers.len();
+        self.partial.extend_from_slice(samples);
+        let whole = self.partial.len() / channels * channels;
+        let frames: Vec<f32> = self.partial.drain(..whole).collect();
+        let outputs: Vec<Vec<f64>> = self
+            .resamplers
+            .iter_mut()
+            .enumerate()
+            .map(|(ch, resampler)| {
+                let channel: Vec<f64> = frames
+                    .iter()
+                    .skip(ch)
+                    .step_by(channels)
+                    .map(|&x| f64::from(x))
+                    .collect();
+                resampler.resample(&channel)
+            })
+            .collect();
+        let len = outputs.iter().map(|x| x.len()).min().unwrap_or(0);
+        (0..len)
+            .flat_map(|n| outputs.iter().map(move |x| x[n] as f32))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,4 +822,216 @@ mod tests {
         let ups_node = UpsampleNode::new(1);
         assert_eq!(ups_node.upsample(&v1), v1);
     }
+
+    #[test]
+    fn test_symbol_hold() {
+        let v1 = vec![1.0, -1.0, 0.5, 2.0, -3.0];
+        for sam_per_sym in 1..6 {
+            let hold_node = SymbolHoldNode::new(sam_per_sym);
+            let held = hold_node.hold(&v1);
+            assert_eq!(held.len(), v1.len() * sam_per_sym);
+            for (ix, chunk) in held.chunks(sam_per_sym).enumerate() {
+                assert!(chunk.iter().all(|x| *x == v1[ix]));
+            }
+        }
+
+        // Check a rate that's zero; the data should be unchanged
+        let hold_node = SymbolHoldNode::new(0);
+        assert_eq!(hold_node.hold(&v1), v1);
+
+        let hold_node = SymbolHoldNode::<f64>::new(8).with_sample_rate(1200.0);
+        assert_eq!(hold_node.sample_rate(), Some(9600.0));
+    }
+
+    #[test]
+    fn test_decimate_average() {
+        use num::Complex;
+        use std::f64::consts::PI;
+
+        // A tone near the input Nyquist rate aliases at full strength under
+        // naive decimation, but is attenuated by averaging.  A tone well
+        // inside the output band passes nearly untouched.
+        let tone = |f: f64| -> Vec<Complex<f64>> {
+            (0..4000)
+                .map(|n| Complex::new(0.0, 2.0 * PI * f * n as f64).exp())
+                .collect()
+        };
+        let power = |x: &[Complex<f64>]| {
+            x.iter().map(|s| s.norm_sqr()).sum::<f64>() / x.len() as f64
+        };
+
+        let high = tone(0.45);
+        let naive = DecimateNode::new(4).decimate(&high);
+        let mut avg_node = DecimateAverageNode::new(4);
+        let mut averaged = vec![];
+        for chunk in high.chunks(333) {
+            averaged.extend(avg_node.decimate(chunk));
+        }
+        assert_eq!(averaged.len(), 1000);
+        assert_approx_eq!(power(&naive), 1.0);
+        assert!(10.0 * power(&averaged).log10() < -15.0);
+
+        let mut avg_node = DecimateAverageNode::new(4);
+        let low = avg_node.decimate(&tone(0.01));
+        assert!(10.0 * power(&low).log10() > -0.1);
+
+        // Check a rate that's zero; the data should be unchanged
+        let mut avg_node = DecimateAverageNode::new(0);
+        assert_eq!(avg_node.decimate(&[1.0, 2.0, 3.0]), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_polyphase_arb_resample() {
+        use num::Complex;
+        use std::f64::consts::PI;
+
+        // Resample a tone by an irrational rate, and compare it and a linear
+        // interpolation against the tone at the exact output times.
+        let freq = 0.1;
+        let tone = |t: f64| Complex::new(0.0, 2.0 * PI * freq * t).exp();
+        let input: Vec<Complex<f64>> =
+            (0..5000).map(|n| tone(n as f64)).collect();
+        let rate = 2.0_f64.sqrt();
+
+        // Blackman windowed sinc prototype at the upsampled rate.
+        let n_banks = 32;
+        let len = 24 * n_banks + 1;
+        let center = (len - 1) as f64 / 2.0;
+        let cutoff = 0.45 / n_banks as f64;
+        let prototype: Vec<f64> = (0..len)
+            .map(|k| {
+                let m = k as f64 - center;
+                let sinc = if m == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * m).sin() / (PI * m)
+                };
+                let x = 2.0 * PI * k as f64 / (len - 1) as f64;
+                let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+                sinc * window * n_banks as f64
+            })
+            .collect();
+        let delay = center / n_banks as f64;
+
+        let mut node = PolyphaseArbResampleNode::new(prototype, n_banks, rate)
+            .with_sample_rate(1000.0);
+        assert!((node.sample_rate().unwrap() - 1000.0 * rate).abs() < 1e-9);
+        let mut output = vec![];
+        for chunk in input.chunks(777) {
+            output.extend(node.resample(chunk));
+        }
+        assert!((output.len() as f64 - 5000.0 * rate).abs() < 3.0);
+
+        let error_db = |err: f64, n: usize| 10.0 * (err / n as f64).log10();
+        let range = 100..output.len() - 100;
+        let mut poly_err = 0.0;
+        let mut linear_err = 0.0;
+        for k in range.clone() {
+            let t = k as f64 / rate;
+            poly_err += (output[k] - tone(t - delay)).norm_sqr();
+            let ix = t.floor() as usize;
+            let frac = t - ix as f64;
+            let linear = input[ix] * (1.0 - frac) + input[ix + 1] * frac;
+            linear_err += (linear - tone(t)).norm_sqr();
+        }
+        let poly_db = error_db(poly_err, range.len());
+        let linear_db = error_db(linear_err, range.len());
+        assert!(poly_db < -60.0);
+        assert!(poly_db < linear_db - 30.0);
+    }
+
+    #[test]
+    fn test_decimate_filter() {
+        use num::Complex;
+        use std::f64::consts::PI;
+
+        // Decimate 48 kHz by 4 with a tone at 1 kHz, inside the output band,
+        // and one at 10 kHz, which lands on -2 kHz at the output rate.
+        let fs = 48000.0;
+        let tone = |f: f64, n: usize| {
+            Complex::new(0.0, 2.0 * PI * f / fs * n as f64).exp()
+        };
+        let input: Vec<Complex<f64>> = (0..48000)
+            .map(|n| tone(1000.0, n) + tone(10000.0, n))
+            .collect();
+        // Amplitude of a tone in the decimated output.
+        let amplitude = |x: &[Complex<f64>], f: f64| {
+            let sum: Complex<f64> = x
+                .iter()
+                .enumerate()
+                .map(|(n, y)| y * tone(f, 4 * n).conj())
+                .sum();
+            sum.norm() / x.len() as f64
+        };
+
+        // Without a filter the alias comes through at full strength.
+        let naive = DecimateNode::new(4).decimate(&input);
+        assert_approx_eq!(amplitude(&naive, -2000.0), 1.0);
+
+        let mut node = DecimateFilterNode::new(4, 0.8).with_sample_rate(fs);
+        assert_eq!(node.sample_rate(), Some(12000.0));
+        let mut output = vec![];
+        for chunk in input.chunks(1001) {
+            output.extend(node.decimate(chunk));
+        }
+        assert_eq!(output.len(), 12000);
+
+        // Leave out the filter starting up, keeping a whole number of
+        // cycles of both tones.
+        let settled = &output[output.len() - 9600..];
+        assert!(amplitude(settled, -2000.0) < 1e-3);
+        assert!((amplitude(settled, 1000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    // Resamples a stereo pair of tones from 32 kHz to a 48 kHz device rate
+    // and checks that there are the right number of samples at the device
+    // rate and that each tone is still at the same frequency.
+    fn test_rate_converter() {
+        use std::f64::consts::PI;
+
+        let input_rate = 32000;
+        let device_rate = 48000;
+        let tone = |freq: f64, rate: u32, n: usize| {
+            (2.0 * PI * freq * n as f64 / f64::from(rate)).sin()
+        };
+        let input: Vec<f32> = (0..input_rate as usize)
+            .flat_map(|n| {
+                vec![
+                    tone(440.0, input_rate, n) as f32,
+                    tone(1000.0, input_rate, n) as f32,
+                ]
+            })
+            .collect();
+
+        let mut converter = RateConverter::new(2, input_rate, device_rate);
+        let mut output = vec![];
+        for chunk in input.chunks(999) {
+            output.extend(converter.convert(chunk));
+        }
+        let frames = output.len() / 2;
+        assert_eq!(output.len() % 2, 0);
+        assert!((frames as i64 - i64::from(device_rate)).abs() < 10);
+
+        // Correlate each channel with the tone it should hold at the device
+        // rate, after the filters have filled.
+        for (ch, &freq) in [440.0, 1000.0].iter().enumerate() {
+            let channel: Vec<f64> = output
+                .iter()
+                .skip(ch)
+                .step_by(2)
+                .map(|&x| f64::from(x))
+                .collect();
+            let (mut re, mut im) = (0.0, 0.0);
+            let range = 1000..frames - 1000;
+            for n in range.clone() {
+                let phase = 2.0 * PI * freq * n as f64 / f64::from(device_rate);
+                re += channel[n] * phase.cos();
+                im += channel[n] * phase.sin();
+            }
+            let amplitude =
+                2.0 * (re * re + im * im).sqrt() / range.len() as f64;
+            assert!((amplitude - 1.0).abs() < 0.01, "amplitude {}", amplitude);
+        }
+    }
 }
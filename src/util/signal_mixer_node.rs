@@ -0,0 +1,191 @@
+//! Nodes for combining several signals into one composite signal.
+use crate::prelude::*;
+
+use crate::mixer::Mixer;
+use crate::util::math::db_to_linear;
+use num::{Complex, Zero};
+
+/// Maximum number of sources a `SignalMixerNode` can combine.
+pub const MAX_SOURCES: usize = 4;
+
+/// A node that sums several sources into one wideband signal.
+///
+/// Each source is scaled by its gain in dB, shifted by its frequency offset,
+/// and added to the others sample by sample.  This is the way to build a
+/// crowded band out of individually generated signals, for exercising
+/// channelizers, detectors and the like.
+///
+/// A node has a fixed set of receivers, so there are `MAX_SOURCES` inputs,
+/// `input0` through `input3`, and source `i` is connected to `input<i>`,
+/// with the gain and offset at index `i`.  Inputs past the number of sources
+/// are left unconnected, and anything arriving on them is ignored.  The node
+/// is non-blocking, so the sources may run at their own pace and deliver
+/// batches of any length.  Sample `n` of every source lands on sample `n` of
+/// the output, so the output runs to the end of the source that is furthest
+/// behind and the rest of each source is held until the others catch up.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::signal_mixer_node::SignalMixerNode;
+///
+/// // One source at full scale in the middle of the band and another 20 dB
+/// // down, 250 kHz above it, on `input0` and `input1`.
+/// let node = SignalMixerNode::new(vec![0.0, -20.0], vec![0.0, 250e3], 1e6);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[non_blocking]
+#[aggregate]
+pub struct SignalMixerNode {
+    pub input0: NodeReceiver<Vec<Complex<f64>>>,
+    pub input1: NodeReceiver<Vec<Complex<f64>>>,
+    pub input2: NodeReceiver<Vec<Complex<f64>>>,
+    pub input3: NodeReceiver<Vec<Complex<f64>>>,
+    scales: Vec<f64>,
+    mixers: Vec<Mixer>,
+    pending: Vec<Vec<Complex<f64>>>,
+    pub output: NodeSender<Vec<Complex<f64>>>,
+}
+
+impl SignalMixerNode {
+    /// Constructs a new `SignalMixerNode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `gains` - Gain in dB applied to each source, for up to
+    ///   `MAX_SOURCES` sources.
+    /// * `offsets` - Frequency offset in Hz applied to each source.
+    /// * `sample_rate` - Sample rate of the sources and output in Hz.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::signal_mixer_node::SignalMixerNode;
+    ///
+    /// let node = SignalMixerNode::new(vec![-3.0; 3], vec![-1e3, 0.0, 1e3], 8e3);
+    /// ```
+    pub fn new(
+        gains: Vec<f64>,
+        offsets: Vec<f64>,
+        sample_rate: f64,
+    ) -> SignalMixerNode {
+        assert!(!gains.is_empty(), "there must be at least one source");
+        assert!(
+            gains.len() <= MAX_SOURCES,
+            "there can be at most MAX_SOURCES sources"
+        );
+        assert_eq!(
+            gains.len(),
+            offsets.len(),
+            "there must be a gain and an offset for each source"
+        );
+        assert!(sample_rate > 0.0, "sample rate must be positive");
+        SignalMixerNode {
+            scales: gains.iter().map(|&g| db_to_linear(g)).collect(),
+            mixers: offsets
+                .iter()
+                .map(|&f| Mixer::from_frequency(0.0, f, sample_rate))
+                .collect(),
+            pending: vec![vec![]; gains.len()],
+            input0: Default::default(),
+            input1: Default::default(),
+            input2: Default::default(),
+            input3: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `SignalMixerNode`.  Produces the sum of the sources for
+    /// every sample that all of them have reached, if there are any new
+    /// ones.
+    pub fn run(
+        &mut self,
+        input0: Option<&Vec<Complex<f64>>>,
+        input1: Option<&Vec<Complex<f64>>>,
+        input2: Option<&Vec<Complex<f64>>>,
+        input3: Option<&Vec<Complex<f64>>>,
+    ) -> Result<Option<Vec<Complex<f64>>>, NodeError> {
+        let batches = [input0, input1, input2, input3];
+        for (pending, batch) in self.pending.iter_mut().zip(&batches) {
+            if let Some(batch) = batch {
+                pending.extend_from_slice(batch);
+            }
+        }
+        let len = self.pending.iter().map(Vec::len).min().unwrap();
+        if len == 0 {
+            return Ok(None);
+        }
+        let mut output = vec![Complex::zero(); len];
+        for ((pending, mixer), scale) in self
+            .pending
+            .iter_mut()
+            .zip(self.mixers.iter_mut())
+            .zip(&self.scales)
+        {
+            for (y, x) in output.iter_mut().zip(pending.drain(..len)) {
+                *y += mixer.mix(&x) * scale;
+            }
+        }
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::signal_mixer_node::*;
+    use std::f64::consts::PI;
+
+    // Power of the component of a signal at a frequency in Hz.
+    fn tone_power(y: &[Complex<f64>], freq: f64, sample_rate: f64) -> f64 {
+        let c: Complex<f64> = y
+            .iter()
+            .enumerate()
+            .map(|(n, y)| {
+                let phase = -2.0 * PI * freq * n as f64 / sample_rate;
+                y * Complex::new(0.0, phase).exp()
+            })
+            .sum();
+        (c.norm() / y.len() as f64).powi(2)
+    }
+
+    #[test]
+    // Mixes a tone and a carrier, batched differently, to their offsets and
+    // checks that each lands at its frequency at the right relative power.
+    fn test_signal_mixer() {
+        let sample_rate = 1e4;
+        let tone: Vec<Complex<f64>> = (0..4000)
+            .map(|n| {
+                let phase = 2.0 * PI * 500.0 * n as f64 / sample_rate;
+                Complex::new(0.0, phase).exp()
+            })
+            .collect();
+        let carrier = vec![Complex::new(1.0, 0.0); 4000];
+
+        let mut node =
+            SignalMixerNode::new(vec![0.0, -12.0], vec![1000.0, -2000.0], 1e4);
+        let mut output = vec![];
+        for (a, b) in tone.chunks(400).zip(carrier.chunks(350)) {
+            let (a, b) = (a.to_vec(), b.to_vec());
+            let out = node.run(Some(&a), Some(&b), None, None).unwrap();
+            output.extend(out.unwrap());
+        }
+        let rest = carrier[3500..].to_vec();
+        let out = node.run(None, Some(&rest), None, None).unwrap();
+        output.extend(out.unwrap());
+        assert_eq!(output.len(), 4000);
+
+        // Nothing comes out until every source has moved on, and an input
+        // past the number of sources is ignored.
+        let extra = tone[..100].to_vec();
+        let out = node.run(Some(&extra), None, Some(&extra), None).unwrap();
+        assert_eq!(out, None);
+
+        let first = tone_power(&output, 1500.0, sample_rate);
+        let second = tone_power(&output, -2000.0, sample_rate);
+        assert!((10.0 * first.log10()).abs() < 0.01);
+        assert!((10.0 * (second / first).log10() + 12.0).abs() < 0.01);
+        assert!(tone_power(&output, 500.0, sample_rate) < 1e-6);
+        assert!(tone_power(&output, 0.0, sample_rate) < 1e-6);
+    }
+}
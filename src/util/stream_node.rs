@@ -0,0 +1,487 @@
+//! Nodes for reshaping and managing streams of data independent of what the
+//! data actually is.
+use crate::prelude::*;
+
+use std::collections::VecDeque;
+
+/// A node that maintains a window of the most recent samples it has seen.
+///
+/// On each sample received, the node emits the last `len` samples, oldest
+/// first and ending with the current sample.  This gives downstream nodes a
+/// consistent look-back window without having to carry their own state
+/// across batches.  Nothing is emitted until the window has filled up.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::stream_node::HistoryNode;
+///
+/// let node: HistoryNode<f64> = HistoryNode::new(16);
+/// ```
+#[derive(Node)]
+#[aggregate]
+pub struct HistoryNode<T>
+where
+    T: Clone + Send,
+{
+    pub input: NodeReceiver<T>,
+    len: usize,
+    history: VecDeque<T>,
+    pub output: NodeSender<Vec<T>>,
+}
+
+impl<T> HistoryNode<T>
+where
+    T: Clone + Send,
+{
+    /// Constructs a new `HistoryNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - Number of samples in the window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::stream_node::HistoryNode;
+    /// use num::Complex;
+    ///
+    /// let node: HistoryNode<Complex<f32>> = HistoryNode::new(4);
+    /// ```
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "history length must be nonzero");
+        HistoryNode {
+            len,
+            history: VecDeque::with_capacity(len),
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `HistoryNode<T>`.  Produces the current window once it has
+    /// filled up.
+    pub fn run(&mut self, sample: T) -> Result<Option<Vec<T>>, NodeError> {
+        if self.history.len() == self.len {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+        if self.history.len() == self.len {
+            Ok(Some(self.history.iter().cloned().collect()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// A node that time aligns two streams offset by a known integer delay.
+///
+/// The node takes in two streams, `a` and `b`, where `b` lags `a` by `delay`
+/// samples (or leads it, for a negative delay).  The leading stream is
+/// buffered, and the first `|delay|` samples of the lagging stream are
+/// dropped, so that sample `n` of one output lines up in time with sample
+/// `n` of the other.  Both outputs are always the same length, so any samples
+/// of one stream that the other hasn't caught up to yet are held until it
+/// does.  This is handy for comparing a reference signal to the output of a
+/// system that delays it, once the delay has been measured.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::stream_node::AlignNode;
+///
+/// // The second stream arrives 12 samples after the first.
+/// let node: AlignNode<f64> = AlignNode::new(12);
+/// ```
+#[derive(Node)]
+#[pass_by_ref]
+#[aggregate]
+pub struct AlignNode<T>
+where
+    T: Clone + Send,
+{
+    pub a: NodeReceiver<Vec<T>>,
+    pub b: NodeReceiver<Vec<T>>,
+    skip_a: usize,
+    skip_b: usize,
+    buf_a: VecDeque<T>,
+    buf_b: VecDeque<T>,
+    pub output: NodeSender<(Vec<T>, Vec<T>)>,
+}
+
+impl<T> AlignNode<T>
+where
+    T: Clone + Send,
+{
+    /// Constructs a new `AlignNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` - Number of samples that stream `b` lags stream `a` by.
+    ///   Negative if `b` leads `a`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::stream_node::AlignNode;
+    /// use num::Complex;
+    ///
+    /// // The first stream arrives 3 samples after the second.
+    /// let node: AlignNode<Complex<f32>> = AlignNode::new(-3);
+    /// ```
+    pub fn new(delay: isize) -> Self {
+        let (skip_a, skip_b) = if delay < 0 {
+            (delay.unsigned_abs(), 0)
+        } else {
+            (0, delay as usize)
+        };
+        AlignNode {
+            skip_a,
+            skip_b,
+            buf_a: VecDeque::new(),
+            buf_b: VecDeque::new(),
+            a: Default::default(),
+            b: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `AlignNode<T>`.  Produces the aligned samples from both
+    /// streams, once there are any.
+    #[allow(clippy::type_complexity)]
+    pub fn run(
+        &mut self,
+        a: &[T],
+        b: &[T],
+    ) -> Result<Option<(Vec<T>, Vec<T>)>, NodeError> {
+        let drop_a = self.skip_a.min(a.len());
+        self.skip_a -= drop_a;
+        self.buf_a.extend(a[drop_a..].iter().cloned());
+        let drop_b = self.skip_b.min(b.len());
+        self.skip_b -= drop_b;
+        self.buf_b.extend(b[drop_b..].iter().cloned());
+
+        let n = self.buf_a.len().min(self.buf_b.len());
+        if n == 0 {
+            return Ok(None);
+        }
+        Ok(Some((
+            self.buf_a.drain(..n).collect(),
+            self.buf_b.drain(..n).collect(),
+        )))
+    }
+}
+
+/// A node that pairs up the items of two streams.
+///
+/// Each run takes one item from each input and emits them together as a
+/// tuple, blocking until both are available.  This is the common pairwise
+/// case of fanning two streams into one node, for when the streams are
+/// synchronized and downstream processing needs both at once.
+///
+/// The pairing is purely by arrival order, so the two inputs should produce
+/// items at the same rate.  If one runs faster than the other, its extra
+/// items queue up in the channel waiting for a partner: with unbounded
+/// channels the backlog, and the latency of that stream, grows without bound,
+/// and with bounded channels the faster source ends up throttled to the rate
+/// of the slower one.  Either way each item is still paired with the item of
+/// the same index from the other stream.  Streams that are offset in time
+/// should be lined up with `AlignNode` instead.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::stream_node::ZipNode;
+/// use num::Complex;
+///
+/// // Pair up batches of samples with the gain they were measured at.
+/// let node: ZipNode<Vec<Complex<f32>>, f64> = ZipNode::new();
+/// ```
+#[derive(Node)]
+pub struct ZipNode<A, B>
+where
+    A: Clone + Send,
+    B: Clone + Send,
+{
+    pub a: NodeReceiver<A>,
+    pub b: NodeReceiver<B>,
+    pub output: NodeSender<(A, B)>,
+}
+
+impl<A, B> ZipNode<A, B>
+where
+    A: Clone + Send,
+    B: Clone + Send,
+{
+    /// Constructs a new `ZipNode<A, B>`.
+    pub fn new() -> Self {
+        ZipNode {
+            a: Default::default(),
+            b: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `ZipNode<A, B>`.  Produces the pair of items.
+    pub fn run(&mut self, a: A, b: B) -> Result<(A, B), NodeError> {
+        Ok((a, b))
+    }
+}
+
+impl<A, B> Default for ZipNode<A, B>
+where
+    A: Clone + Send,
+    B: Clone + Send,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node that tags each batch with the index of its first sample.
+///
+/// Each batch passes through unchanged, paired with the running count of
+/// samples seen before it, starting from the given index.  Downstream nodes
+/// can then line up, reorder, or log data from different streams by sample
+/// index instead of by arrival order.
+///
+/// # Examples
+///
+/// ```
+/// use comms_rs::util::stream_node::TimestampNode;
+/// use num::Complex;
+///
+/// let node: TimestampNode<Complex<f32>> = TimestampNode::new(0);
+/// ```
+#[derive(Node)]
+pub struct TimestampNode<T>
+where
+    T: Clone + Send,
+{
+    pub input: NodeReceiver<Vec<T>>,
+    index: u64,
+    pub output: NodeSender<(u64, Vec<T>)>,
+}
+
+impl<T> TimestampNode<T>
+where
+    T: Clone + Send,
+{
+    /// Constructs a new `TimestampNode<T>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Index of the first sample received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use comms_rs::util::stream_node::TimestampNode;
+    ///
+    /// // Pick up the count where an earlier capture left off.
+    /// let node: TimestampNode<i16> = TimestampNode::new(1 << 20);
+    /// ```
+    pub fn new(start: u64) -> Self {
+        TimestampNode {
+            index: start,
+            input: Default::default(),
+            output: Default::default(),
+        }
+    }
+
+    /// Runs the `TimestampNode<T>`.  Produces the batch tagged with the
+    /// index of its first sample.
+    pub fn run(&mut self, data: Vec<T>) -> Result<(u64, Vec<T>), NodeError> {
+        let index = self.index;
+        self.index += data.len() as u64;
+        Ok((index, data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::stream_node::*;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    // Checks that every window emitted holds the most recent samples in
+    // order.
+    fn test_history_node() {
+        #[derive(Node)]
+        struct CountNode {
+            count: u32,
+            output: NodeSender<u32>,
+        }
+
+        impl CountNode {
+            pub fn new() -> Self {
+                CountNode {
+                    count: 0,
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self) -> Result<u32, NodeError> {
+                self.count += 1;
+                Ok(self.count)
+            }
+        }
+
+        #[derive(Node)]
+        struct CheckNode {
+            input: NodeReceiver<Vec<u32>>,
+            n_windows: u32,
+        }
+
+        impl CheckNode {
+            pub fn new() -> Self {
+                CheckNode {
+                    n_windows: 0,
+                    input: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self, window: Vec<u32>) -> Result<(), NodeError> {
+                // The first full window ends on the fifth sample.
+                let last = self.n_windows + 5;
+                let truth: Vec<u32> = (last - 4..=last).collect();
+                assert_eq!(window, truth);
+                self.n_windows += 1;
+                Ok(())
+            }
+        }
+
+        let mut count = CountNode::new();
+        let mut history = HistoryNode::new(5);
+        let mut check = CheckNode::new();
+        connect_nodes!(count, output, history, input);
+        connect_nodes!(history, output, check, input);
+        start_nodes!(count, history);
+        let check = thread::spawn(move || {
+            let now = Instant::now();
+            loop {
+                check.call().unwrap();
+                if now.elapsed().as_millis() >= 500 {
+                    break;
+                }
+            }
+            assert!(check.n_windows > 0);
+        });
+        assert!(check.join().is_ok());
+    }
+
+    #[test]
+    // Feeds in a signal and a delayed copy, in batches of different sizes,
+    // and checks the outputs are sample aligned.
+    fn test_align_node() {
+        let delay = 7;
+        let signal: Vec<i32> = (0..500).collect();
+        let delayed: Vec<i32> = vec![-1; delay]
+            .into_iter()
+            .chain(signal.iter().cloned())
+            .collect();
+
+        for &(x, y, d) in &[(&signal, &delayed, 7), (&delayed, &signal, -7)] {
+            let mut node = AlignNode::new(d);
+            let mut out_a = vec![];
+            let mut out_b = vec![];
+            for (a, b) in x.chunks(13).zip(y.chunks(11)) {
+                if let Some((a, b)) = node.run(a, b).unwrap() {
+                    assert_eq!(a.len(), b.len());
+                    out_a.extend(a);
+                    out_b.extend(b);
+                }
+            }
+            assert!(out_a.len() > 300);
+            assert_eq!(out_a, out_b);
+            assert_eq!(out_a[0], 0);
+        }
+    }
+
+    #[test]
+    // Zips a stream of counts with a stream of their squares and checks that
+    // every pair matches up.
+    fn test_zip_node() {
+        #[derive(Node)]
+        struct CountNode {
+            count: u32,
+            square: bool,
+            output: NodeSender<u32>,
+        }
+
+        impl CountNode {
+            pub fn new(square: bool) -> Self {
+                CountNode {
+                    count: 0,
+                    square,
+                    output: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self) -> Result<u32, NodeError> {
+                self.count += 1;
+                if self.square {
+                    Ok(self.count * self.count)
+                } else {
+                    Ok(self.count)
+                }
+            }
+        }
+
+        #[derive(Node)]
+        struct CheckNode {
+            input: NodeReceiver<(u32, u32)>,
+            n_pairs: u32,
+        }
+
+        impl CheckNode {
+            pub fn new() -> Self {
+                CheckNode {
+                    n_pairs: 0,
+                    input: Default::default(),
+                }
+            }
+
+            pub fn run(&mut self, pair: (u32, u32)) -> Result<(), NodeError> {
+                self.n_pairs += 1;
+                assert_eq!(pair, (self.n_pairs, self.n_pairs * self.n_pairs));
+                Ok(())
+            }
+        }
+
+        let mut count = CountNode::new(false);
+        let mut square = CountNode::new(true);
+        let mut zip = ZipNode::new();
+        let mut check = CheckNode::new();
+        connect_nodes!(count, output, zip, a);
+        connect_nodes!(square, output, zip, b);
+        connect_nodes!(zip, output, check, input);
+        start_nodes!(count, square, zip);
+        let check = thread::spawn(move || {
+            let now = Instant::now();
+            loop {
+                check.call().unwrap();
+                if now.elapsed().as_millis() >= 500 || check.n_pairs >= 1000 {
+                    break;
+                }
+            }
+            assert!(check.n_pairs > 0);
+        });
+        assert!(check.join().is_ok());
+    }
+
+    #[test]
+    // Checks that the index advances by the batch size and the batches pass
+    // through unchanged.
+    fn test_timestamp_node() {
+        let mut node = TimestampNode::new(100);
+        let mut expected = 100;
+        for len in &[5, 0, 12, 1, 7] {
+            let data: Vec<u32> = (0..*len).collect();
+            let (index, out) = node.run(data.clone()).unwrap();
+            assert_eq!(index, expected);
+            assert_eq!(out, data);
+            expected += u64::from(*len);
+        }
+    }
+}